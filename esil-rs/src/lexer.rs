@@ -45,6 +45,15 @@ pub enum Token {
     EClear,
     EDup,
     ETrap,
+    // Floating point Opcodes
+    EFAdd,
+    EFSub,
+    EFMul,
+    EFDiv,
+    EFCmp,
+    EFLt,
+    EI2F,
+    EF2I,
     // Invalid
     EInvalid,
     // Parser Instructions.
@@ -99,6 +108,12 @@ impl Token {
             Token::ESub |
             Token::EDiv |
             Token::EMod |
+            Token::EFAdd |
+            Token::EFSub |
+            Token::EFMul |
+            Token::EFDiv |
+            Token::EFCmp |
+            Token::EFLt |
             Token::EPoke(_) => true,
             _ => false,
         }
@@ -106,7 +121,12 @@ impl Token {
 
     pub fn is_unary(&self) -> bool {
         match *self {
-            Token::EPop | Token::ENeg | Token::EIf | Token::EPeek(_) => true,
+            Token::EPop |
+            Token::ENeg |
+            Token::EIf |
+            Token::EI2F |
+            Token::EF2I |
+            Token::EPeek(_) => true,
             _ => false,
         }
     }
@@ -141,9 +161,30 @@ impl Token {
         }
     }
 
+    pub fn is_internal(&self) -> bool {
+        match *self {
+            Token::IZero(_) |
+            Token::ICarry(_) |
+            Token::IParity(_) |
+            Token::IOverflow(_) |
+            Token::ISign(_) |
+            Token::IBorrow(_) |
+            Token::ISize(_) |
+            Token::IAddress(_) |
+            Token::IConstant(_) => true,
+            _ => false,
+        }
+    }
+
     pub fn should_set_vars(&self) -> bool {
         match *self {
-            Token::ECmp | Token::EEq | Token::EPoke(_) | Token::EGt | Token::ELt => true,
+            Token::ECmp |
+            Token::EEq |
+            Token::EPoke(_) |
+            Token::EGt |
+            Token::ELt |
+            Token::EFCmp |
+            Token::EFLt => true,
             _ => false,
         }
     }
@@ -389,6 +430,17 @@ impl Tokenize for Tokenizer {
                     "CLEAR" => vec![Token::EClear],
                     "DUP" => vec![Token::EDup],
                     "TRAP" => vec![Token::ETrap],
+
+                    "F+" => vec![Token::EFAdd],
+                    "F-" => vec![Token::EFSub],
+                    "F*" => vec![Token::EFMul],
+                    "F/" => vec![Token::EFDiv],
+                    "F==" => vec![Token::EFCmp],
+                    "F<" => vec![Token::EFLt],
+                    "F<=" => vec![Token::PCopy(2), Token::EFLt, Token::PPop(2),
+                    Token::EFCmp, Token::EOr],
+                    "I2D" | "S2D" | "I2F" => vec![Token::EI2F],
+                    "D2I" | "F2I" => vec![Token::EF2I],
                     _   => {
             // Handle internal vars
                         if Some(ESIL_INTERNAL_PREFIX) == t.chars().nth(0) {
@@ -480,4 +532,13 @@ mod test {
     fn utf8_internal_prefix() {
         Tokenizer::tokenize(str::from_utf8(&vec![0x24,0xda,0x91]).unwrap());
     }
+
+    #[test]
+    fn float_ops() {
+        let tokens = Tokenizer::tokenize("xmm0l,D2I,xmm1l,xmm0l,F+");
+        assert_eq!(tokens[1], Token::EF2I);
+        assert_eq!(tokens[4], Token::EFAdd);
+        assert!(Token::EFAdd.is_binary());
+        assert!(Token::EF2I.is_unary());
+    }
 }
@@ -54,6 +54,9 @@ pub struct Parser {
     last_op: Option<Token>,
     // Last two pop operations performed by the consumer.
     last_pop: (Option<Token>, Option<Token>),
+    // Evaluate internal vars in terms of old, cur and lastsz, instead of returning them to the
+    // consumer.
+    expand_internal: bool,
     // Allow access for the consumer to set these. If these are set, then the parser automatically
     // returns the value of old, cur and lastsz when required rather than returning the tokens to
     // indicate the same.
@@ -116,6 +119,9 @@ impl Parse for Parser {
                 Token::ISize(_) |
                 Token::IConstant(_) |
                 Token::IAddress(_) => {
+                    if !self.expand_internal {
+                        return Ok(Some(token));
+                    }
                     let mut internal_q = self.evaluate_internal(&token)?;
                     //self.skip_esil_set += internal_q.len() + 1;
                     while let Some(i) = internal_q.pop_back() {
@@ -201,7 +207,7 @@ impl Parse for Parser {
                 Ok(op) => (op, None),
                 Err(e) => return Err(e),
             }
-        } else if t.is_arity_zero() || t.is_meta() {
+        } else if t.is_arity_zero() || t.is_meta() || t.is_internal() {
             (None, None)
         } else if !t.is_implemented() {
             return Err(ParserError::Unimplemented);
@@ -253,6 +259,7 @@ impl Parser {
             default_size: default_size.unwrap_or(64),
             last_op: None,
             last_pop: (None, None),
+            expand_internal: true,
             tokens: None,
             eold: None,
             eold_: None,
@@ -261,6 +268,13 @@ impl Parser {
         }
     }
 
+    /// Whether internal vars (`$z`, `$c`, ...) are evaluated by the parser, which is the default.
+    /// If not, they are returned to the consumer like opcodes without operands, so that it can
+    /// compute them from its own knowledge of the last operation.
+    pub fn set_expand_internal(&mut self, expand: bool) {
+        self.expand_internal = expand;
+    }
+
     fn get_meta(&self, t: Token) -> Token {
         match t {
             Token::EOld => self.eold.as_ref().unwrap_or(&t),
@@ -721,6 +735,15 @@ mod test {
         assert_eq!(parser.skip_esil_set, 1);
     }
 
+    #[test]
+    fn parser_keep_internal_vars() {
+        let mut parser = Parser::init(None, None);
+        parser.set_expand_internal(false);
+        let expr = ExpressionConstructor::run("$z,zf,=,$c31,cf,=", Some(&mut parser)).unwrap();
+        let expected = "(EEq  zf, (IZero(64)  -, -))(EEq  cf, (ICarry(31)  -, -))";
+        assert_eq!(expected, &expr);
+    }
+
     #[test]
     fn parser_follow_false() {
         // TODO
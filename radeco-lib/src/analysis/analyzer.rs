@@ -1,5 +1,7 @@
 use std::any::Any;
 use std::convert::From;
+use std::error;
+use std::fmt;
 use std::fmt::Debug;
 
 use petgraph::graph::NodeIndex;
@@ -7,8 +9,13 @@ use petgraph::graph::NodeIndex;
 use crate::analysis::cse::cse;
 use crate::analysis::functions::{fix_ssa_opcalls, infer_regusage};
 use crate::analysis::interproc::interproc;
-use crate::analysis::{arithmetic, copy_propagation, dce, inst_combine, sccp};
+use crate::analysis::{
+    arithmetic, bounds_check, canonicalize, copy_propagation, dce, dse, fold_extends, inst_combine,
+    load_forwarding, magic_div, regstate_cleanup, sccp, shift_mul, simplify_selectors,
+    string_recovery,
+};
 use crate::frontend::radeco_containers::{RadecoFunction, RadecoModule};
+use crate::middle::ssa::memoryssa;
 
 /// This trait provides access to extra informations generated during the analysis pass.
 pub trait AnalyzerResult: Any + Debug {
@@ -19,14 +26,25 @@ pub trait AnalyzerResult: Any + Debug {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AnalyzerKind {
     Arithmetic,
+    BoundsCheck,
     CallSiteFixer,
+    Canonicalizer,
     Combiner,
     CopyPropagation,
     CSE,
     DCE,
+    DSE,
+    FoldExtends,
     Inferer,
     InterProc,
+    LoadForwarding,
+    MagicDiv,
+    MemorySSA,
+    RegStateCleanup,
     SCCP,
+    ShiftMul,
+    SimplifySelectors,
+    StringRecovery,
 }
 
 /// A struct providing information about an analyzer.
@@ -63,18 +81,53 @@ impl From<AnalyzerKind> for &'static AnalyzerInfo {
     fn from(kind: AnalyzerKind) -> &'static AnalyzerInfo {
         match kind {
             AnalyzerKind::Arithmetic => &arithmetic::INFO,
+            AnalyzerKind::BoundsCheck => &bounds_check::INFO,
             AnalyzerKind::CallSiteFixer => &fix_ssa_opcalls::INFO,
+            AnalyzerKind::Canonicalizer => &canonicalize::INFO,
             AnalyzerKind::Combiner => &inst_combine::INFO,
             AnalyzerKind::CopyPropagation => &copy_propagation::INFO,
             AnalyzerKind::CSE => &cse::INFO,
             AnalyzerKind::DCE => &dce::INFO,
+            AnalyzerKind::DSE => &dse::INFO,
+            AnalyzerKind::FoldExtends => &fold_extends::INFO,
             AnalyzerKind::Inferer => &infer_regusage::INFO,
             AnalyzerKind::InterProc => &interproc::INFO,
+            AnalyzerKind::LoadForwarding => &load_forwarding::INFO,
+            AnalyzerKind::MagicDiv => &magic_div::INFO,
+            AnalyzerKind::MemorySSA => &memoryssa::INFO,
+            AnalyzerKind::RegStateCleanup => &regstate_cleanup::INFO,
             AnalyzerKind::SCCP => &sccp::INFO,
+            AnalyzerKind::ShiftMul => &shift_mul::INFO,
+            AnalyzerKind::SimplifySelectors => &simplify_selectors::INFO,
+            AnalyzerKind::StringRecovery => &string_recovery::INFO,
         }
     }
 }
 
+/// Error returned when an analyzer is run on a function before the ones it relies on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrerequisiteError {
+    /// The analyzers which were not run yet.
+    pub missing: Vec<AnalyzerKind>,
+}
+
+impl error::Error for PrerequisiteError {}
+
+impl fmt::Display for PrerequisiteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names = self
+            .missing
+            .iter()
+            .map(|&kind| <&'static AnalyzerInfo>::from(kind).name)
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "prerequisite not met: {} must be run first",
+            names.join(", ")
+        )
+    }
+}
+
 /// Basic trait for all the analyzers.
 pub trait Analyzer: Any + Debug {
     fn info(&self) -> &'static AnalyzerInfo;
@@ -166,11 +219,26 @@ pub fn all_func_analyzers() -> Vec<AnalyzerKind> {
     ]
 }
 
+/// Get the `FuncAnalyzer`s which are only run when enabled, see `RadecoEngine::enable`
+pub fn optional_func_analyzers() -> Vec<AnalyzerKind> {
+    vec![
+        AnalyzerKind::DSE,
+        AnalyzerKind::FoldExtends,
+        AnalyzerKind::LoadForwarding,
+        AnalyzerKind::MagicDiv,
+        AnalyzerKind::RegStateCleanup,
+        AnalyzerKind::ShiftMul,
+        AnalyzerKind::SimplifySelectors,
+    ]
+}
+
 /// Get all the available `ModuleAnalyzer`s
 pub fn all_module_analyzers() -> Vec<AnalyzerKind> {
     vec![
         AnalyzerKind::CallSiteFixer,
         AnalyzerKind::Inferer,
         AnalyzerKind::InterProc,
+        AnalyzerKind::StringRecovery,
+        AnalyzerKind::BoundsCheck,
     ]
 }
@@ -0,0 +1,229 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Bounds check detection
+//!
+//! Safe languages check every index against the length of what it indexes, and panic when it is
+//! out of bounds: `v[i]` is compiled into `if i >= len { panic_bounds_check(i, len) }` before the
+//! access. This pass finds the branches on an unsigned comparison one side of which calls a
+//! function which never returns, and annotates their selector with a `bounds check` comment, so
+//! that they can be told apart from the control flow of the program itself.
+//!
+//! The functions which never return are the ones found so when the module was loaded, see
+//! [`RadecoFunction::is_noreturn`](::frontend::radeco_containers::RadecoFunction::is_noreturn).
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, ModuleAnalyzer,
+};
+use crate::frontend::radeco_containers::{RadecoFunction, RadecoModule};
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::{SSAExtra, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use std::any::Any;
+use std::collections::HashSet;
+
+const NAME: &str = "bounds_check";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::BoundsCheck,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+/// Comment of the selectors of bounds checks.
+pub const BOUNDS_CHECK: &str = "bounds check";
+
+type SSABlock = <SSAStorage as CFG>::ActionRef;
+type SSAValue = <SSAStorage as SSA>::ValueRef;
+
+#[derive(Debug)]
+pub struct BoundsCheckDetector;
+
+impl BoundsCheckDetector {
+    pub fn new() -> Self {
+        BoundsCheckDetector
+    }
+}
+
+impl Analyzer for BoundsCheckDetector {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl ModuleAnalyzer for BoundsCheckDetector {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rmod: &mut RadecoModule,
+        _policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let noreturn = rmod.noreturn_addresses();
+        if noreturn.is_empty() {
+            return None;
+        }
+        for rfn in rmod.functions.values_mut() {
+            annotate_bounds_checks(rfn, &noreturn);
+        }
+        None
+    }
+}
+
+/// Annotates the bounds checks of `rfn`, which panic by calling one of the functions at
+/// `noreturn`. Returns the number of annotated branches.
+pub fn annotate_bounds_checks(rfn: &mut RadecoFunction, noreturn: &HashSet<u64>) -> usize {
+    let panics = rfn
+        .direct_calls()
+        .into_iter()
+        .filter(|&(_, target)| noreturn.contains(&target))
+        .map(|(site, _)| site)
+        .collect::<HashSet<_>>();
+    if panics.is_empty() {
+        return 0;
+    }
+
+    let ssa = rfn.ssa_mut();
+    let mut annotated = 0;
+    for block in ssa.blocks() {
+        let ci = match ssa.conditional_blocks(block) {
+            Some(ci) => ci,
+            None => continue,
+        };
+        let selector = match ssa.selector_in(block) {
+            Some(selector) if is_unsigned_compare(ssa, selector) => selector,
+            _ => continue,
+        };
+        if panics_in(ssa, ci.true_side, &panics) || panics_in(ssa, ci.false_side, &panics) {
+            radeco_trace!("bounds_check|{:?}|{:?}", block, selector);
+            ssa.set_comment(&selector, BOUNDS_CHECK.to_owned());
+            annotated += 1;
+        }
+    }
+    annotated
+}
+
+/// Returns `true` if `block` ends with a bounds check.
+pub fn is_bounds_check(ssa: &SSAStorage, block: SSABlock) -> bool {
+    ssa.selector_in(block)
+        .and_then(|selector| ssa.comments(&selector))
+        .map_or(false, |comment| comment == BOUNDS_CHECK)
+}
+
+// Whether `node` is an unsigned comparison, the negation of one or a copy of either.
+fn is_unsigned_compare(ssa: &SSAStorage, node: SSAValue) -> bool {
+    let operands = ssa.operands_of(node);
+    match ssa.opcode(node) {
+        Some(MOpcode::OpLt) | Some(MOpcode::OpGt) => true,
+        Some(MOpcode::OpNot) | Some(MOpcode::OpMov) => {
+            operands.len() == 1 && is_unsigned_compare(ssa, operands[0])
+        }
+        // `cmp == 0`, which is how some architectures negate a flag.
+        Some(MOpcode::OpEq) if operands.len() == 2 => {
+            let negates = |cmp: SSAValue, zero: SSAValue| {
+                ssa.constant_value(zero) == Some(0) && is_unsigned_compare(ssa, cmp)
+            };
+            negates(operands[0], operands[1]) || negates(operands[1], operands[0])
+        }
+        _ => false,
+    }
+}
+
+// Whether `block` calls one of the functions which never return, at one of the call sites in
+// `panics`. The empty blocks the constructor leaves on the side of a branch are looked through.
+fn panics_in(ssa: &SSAStorage, mut block: SSABlock, panics: &HashSet<u64>) -> bool {
+    let mut seen = HashSet::new();
+    while ssa.exprs_in(block).is_empty() && seen.insert(block) {
+        match ssa.unconditional_block(block) {
+            Some(next) => block = next,
+            None => return false,
+        }
+    }
+    ssa.exprs_in(block).into_iter().any(|expr| {
+        ssa.opcode(expr) == Some(MOpcode::OpCall)
+            && ssa
+                .address(expr)
+                .map_or(false, |addr| panics.contains(&addr.address))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frontend::ssaconstructor::SSAConstruct;
+    use crate::middle::ssa::verifier;
+    use r2api::structs::LRegInfo;
+    use serde_json;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    // fn get(v: &[u64], i: usize) -> u64 { v[i] }, with `core::panicking::panic_bounds_check`
+    // at 0x2000:
+    //   cmp rsi, rdx; jae 0x1009; mov rax, rsi; ret;
+    //   mov rdi, rsi; call panic_bounds_check
+    const GET_OPS: &str = r#"[
+        {"offset":4096,"size":3,"esil":"rdx,rsi,==,$b64,cf,=","type":"cmp"},
+        {"offset":4099,"size":2,"esil":"cf,!,?{,4105,rip,=,}","type":"cjmp"},
+        {"offset":4101,"size":3,"esil":"rsi,rax,=","type":"mov"},
+        {"offset":4104,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"},
+        {"offset":4105,"size":3,"esil":"rsi,rdi,=","type":"mov"},
+        {"offset":4108,"size":5,"esil":"8192,rip,8,rsp,-=,rsp,=[],rip,=","type":"call"}
+    ]"#;
+
+    fn construct(ops: &str, noreturn: &HashSet<u64>) -> RadecoFunction {
+        let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+        let reg_profile: LRegInfo = serde_json::from_str(&*s).unwrap();
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = serde_json::from_str(ops).unwrap();
+        SSAConstruct::<SSAStorage>::construct_noreturn(
+            &mut rfn,
+            &reg_profile,
+            false,
+            true,
+            noreturn,
+        );
+        rfn
+    }
+
+    fn bounds_checks(rfn: &RadecoFunction) -> Vec<SSABlock> {
+        let ssa = rfn.ssa();
+        ssa.blocks()
+            .into_iter()
+            .filter(|&b| is_bounds_check(ssa, b))
+            .collect()
+    }
+
+    #[test]
+    fn bounds_check_tagged() {
+        let noreturn = vec![0x2000].into_iter().collect();
+        let mut rfn = construct(GET_OPS, &noreturn);
+        assert_eq!(annotate_bounds_checks(&mut rfn, &noreturn), 1);
+        assert!(verifier::verify(rfn.ssa()).is_ok());
+
+        let checks = bounds_checks(&rfn);
+        assert_eq!(checks.len(), 1);
+        let addr = rfn.ssa().starting_address(checks[0]).unwrap();
+        assert_eq!(addr.address, 0x1000);
+    }
+
+    #[test]
+    fn equality_not_tagged() {
+        // The same, but panicking when `i == len`.
+        let ops = GET_OPS
+            .replace("$b64,cf,=", "$z,zf,=")
+            .replace("cf,!,?{", "zf,?{");
+        let noreturn = vec![0x2000].into_iter().collect();
+        let mut rfn = construct(&ops, &noreturn);
+        assert_eq!(annotate_bounds_checks(&mut rfn, &noreturn), 0);
+        assert!(bounds_checks(&rfn).is_empty());
+    }
+}
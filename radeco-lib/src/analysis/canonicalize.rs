@@ -0,0 +1,456 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Rewrites equivalent forms of an expression into the most readable one.
+//!
+//! Which form is the most readable is decided by a `CostModel`. The rewrites handled are:
+//!   * `x << n` and `x * 2^n`, folding chains like `(x << 1) << 1` on the way;
+//!   * `x & (2^n - 1)` and `ZeroExt(Narrow_n(x))`.
+//!
+//! The new constants are computed with `MOpcode::eval_binop`, so that they wrap exactly as the
+//! rest of the analyses expect.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
+};
+use crate::frontend::radeco_containers::RadecoFunction;
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::ssa_traits::{SSAMod, SSAWalk, ValueInfo, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use std::any::Any;
+
+type SSAValue = <SSAStorage as SSA>::ValueRef;
+
+/// Costs of the forms an expression can be rewritten to. A rewrite is only done if the new form
+/// is strictly cheaper than the current one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CostModel {
+    /// `x << n`
+    pub shift: u32,
+    /// `x * 2^n`
+    pub mul: u32,
+    /// `x & (2^n - 1)`
+    pub mask: u32,
+    /// `ZeroExt(Narrow_n(x))`
+    pub truncate: u32,
+}
+
+impl Default for CostModel {
+    /// Prefers multiplications and explicit truncations.
+    fn default() -> CostModel {
+        CostModel {
+            shift: 2,
+            mul: 1,
+            mask: 2,
+            truncate: 1,
+        }
+    }
+}
+
+impl CostModel {
+    pub fn cost(&self, form: Form) -> u32 {
+        match form {
+            Form::Shift(_) => self.shift,
+            Form::Mul(_) => self.mul,
+            Form::Mask(_) => self.mask,
+            Form::Truncate(_) => self.truncate,
+        }
+    }
+}
+
+/// A form of an expression of a single non-constant operand.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Form {
+    /// Shift left by the given amount.
+    Shift(u64),
+    /// Multiplication by the given power of two.
+    Mul(u64),
+    /// Bitwise and with the given low bits mask.
+    Mask(u64),
+    /// Narrowing to the given width, then extension back to the original one.
+    Truncate(u16),
+}
+
+impl Form {
+    /// Returns the form with the same meaning in the other representation of its family.
+    fn alternative(self) -> Option<Form> {
+        match self {
+            Form::Shift(n) => MOpcode::OpLsl.eval_binop(1, n).map(Form::Mul),
+            Form::Mul(c) => Some(Form::Shift(u64::from(c.trailing_zeros()))),
+            Form::Mask(m) => Some(Form::Truncate(m.wrapping_add(1).trailing_zeros() as u16)),
+            Form::Truncate(n) => MOpcode::OpLsl
+                .eval_binop(1, u64::from(n))
+                .and_then(|c| MOpcode::OpSub.eval_binop(c, 1))
+                .map(Form::Mask),
+        }
+    }
+
+    /// Returns the amount `x` is shifted left by, if this is a shift or a multiplication.
+    fn shift_amount(self) -> Option<u64> {
+        match self {
+            Form::Shift(n) => Some(n),
+            Form::Mul(c) => Some(u64::from(c.trailing_zeros())),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RewriteChange {
+    /// Index of the node to rewrite.
+    pub node: SSAValue,
+
+    /// Current form of the node.
+    pub old: Form,
+
+    /// Form of the replacement.
+    pub new: Form,
+}
+
+impl Change for RewriteChange {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+const NAME: &str = "canonicalize";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::Canonicalizer,
+    requires: REQUIRES,
+    uses_policy: true,
+};
+
+#[derive(Debug)]
+pub struct Canonicalizer {
+    costs: CostModel,
+}
+
+impl Canonicalizer {
+    pub fn new() -> Self {
+        Canonicalizer::with_costs(CostModel::default())
+    }
+
+    /// Creates a `Canonicalizer` which rewrites expressions toward the forms cheapest in `costs`.
+    pub fn with_costs(costs: CostModel) -> Self {
+        Canonicalizer { costs: costs }
+    }
+
+    /// Returns the rewrite of `node`, along with the non-constant operand of the new form.
+    fn visit_node(&self, ssa: &SSAStorage, node: SSAValue) -> Option<(RewriteChange, SSAValue)> {
+        let (x, old) = match_form(ssa, node)?;
+        let width = u64::from(ssa.node_data(node).ok()?.vt.width().get_width()?);
+
+        // Fold the shifts by a constant of `x` into this one, keeping the form of this one
+        let (x, current) = match (old.shift_amount(), match_form(ssa, x)) {
+            (Some(n), Some((inner_x, inner))) => {
+                match inner
+                    .shift_amount()
+                    .and_then(|m| MOpcode::OpAdd.eval_binop(n, m))
+                {
+                    Some(sum) if sum < width => {
+                        if let Form::Mul(_) = old {
+                            (inner_x, Form::Shift(sum).alternative()?)
+                        } else {
+                            (inner_x, Form::Shift(sum))
+                        }
+                    }
+                    _ => (x, old),
+                }
+            }
+            _ => (x, old),
+        };
+
+        let new = match current.alternative() {
+            Some(alt) if self.costs.cost(alt) < self.costs.cost(current) => alt,
+            _ if current != old => current,
+            _ => return None,
+        };
+
+        Some((
+            RewriteChange {
+                node: node,
+                old: old,
+                new: new,
+            },
+            x,
+        ))
+    }
+}
+
+impl Analyzer for Canonicalizer {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl FuncAnalyzer for Canonicalizer {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rfn: &mut RadecoFunction,
+        policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let mut policy = policy.expect("A policy function must be provided");
+        let ssa = rfn.ssa_mut();
+        for node in ssa.inorder_walk() {
+            let (change, x) = match self.visit_node(ssa, node) {
+                Some(rewrite) => rewrite,
+                None => continue,
+            };
+            let new = change.new;
+            match policy(Box::new(change)) {
+                Action::Apply => {
+                    radeco_trace!(target: NAME, "{:?} ==> {:?}", node, new);
+                    rewrite(ssa, node, x, new);
+                }
+                Action::Skip => {}
+                Action::Abort => return None,
+            }
+        }
+
+        None
+    }
+}
+
+/// Returns the non-constant operand of `node` and the form of `node`, if it has one.
+fn match_form(ssa: &SSAStorage, node: SSAValue) -> Option<(SSAValue, Form)> {
+    let opcode = ssa.opcode(node)?;
+    let width = u64::from(ssa.node_data(node).ok()?.vt.width().get_width()?);
+    let operands = ssa.operands_of(node);
+    // Constant operand of a commutative binary operation
+    let commutative = || match operands.as_slice() {
+        &[x, c] | &[c, x] if ssa.constant(c).is_some() && ssa.constant(x).is_none() => {
+            Some((x, ssa.constant(c)?))
+        }
+        _ => None,
+    };
+
+    match opcode {
+        MOpcode::OpLsl => match operands.as_slice() {
+            &[x, n] if ssa.constant(x).is_none() => {
+                let n = ssa.constant(n)?;
+                if n > 0 && n < width {
+                    Some((x, Form::Shift(n)))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        },
+        MOpcode::OpMul => {
+            let (x, c) = commutative()?;
+            if c > 1 && c.is_power_of_two() && u64::from(c.trailing_zeros()) < width {
+                Some((x, Form::Mul(c)))
+            } else {
+                None
+            }
+        }
+        MOpcode::OpAnd => {
+            let (x, m) = commutative()?;
+            let n = u64::from(m.wrapping_add(1).trailing_zeros());
+            if m != 0 && m.wrapping_add(1).is_power_of_two() && n < width {
+                Some((x, Form::Mask(m)))
+            } else {
+                None
+            }
+        }
+        MOpcode::OpZeroExt(w) => {
+            let narrow = *operands.get(0)?;
+            let x = *ssa.operands_of(narrow).get(0)?;
+            match ssa.opcode(narrow)? {
+                MOpcode::OpNarrow(n)
+                    if n > 0
+                        && n < w
+                        && u64::from(w) == width
+                        && ssa.node_data(x).ok()?.vt.width().get_width() == Some(w) =>
+                {
+                    Some((x, Form::Truncate(n)))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Replaces `node` with the expression of form `form` over `x`.
+fn rewrite(ssa: &mut SSAStorage, node: SSAValue, x: SSAValue, form: Form) -> Option<()> {
+    let vt = ssa.node_data(node).ok()?.vt;
+    let addr = ssa.address(node)?;
+    let blk = ssa.block_for(node)?;
+    let new_node = match form {
+        Form::Shift(c) | Form::Mul(c) | Form::Mask(c) => {
+            let opcode = match form {
+                Form::Shift(_) => MOpcode::OpLsl,
+                Form::Mul(_) => MOpcode::OpMul,
+                _ => MOpcode::OpAnd,
+            };
+            let op = ssa.insert_op(opcode, vt, Some(addr.address)).ok()?;
+            let c = ssa.insert_const(c, vt.width().get_width()).ok()?;
+            ssa.op_use(op, 0, x);
+            ssa.op_use(op, 1, c);
+            op
+        }
+        Form::Truncate(n) => {
+            let narrowed = {
+                let mut nvt: ValueInfo = scalar!(n);
+                nvt.vty = vt.vty;
+                ssa.insert_op(MOpcode::OpNarrow(n), nvt, Some(addr.address))
+                    .ok()
+            }?;
+            let w = vt.width().get_width()?;
+            let extended = ssa
+                .insert_op(MOpcode::OpZeroExt(w), vt, Some(addr.address))
+                .ok()?;
+            ssa.op_use(narrowed, 0, x);
+            ssa.op_use(extended, 0, narrowed);
+            ssa.insert_into_block(narrowed, blk, addr);
+            extended
+        }
+    };
+    ssa.replace_value(node, new_node);
+    ssa.insert_into_block(new_node, blk, addr);
+    Some(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::analysis::dce::DCE;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use crate::middle::ssa::cfg_traits::CFG;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    const PREFER_SHIFT: CostModel = CostModel {
+        shift: 1,
+        mul: 2,
+        mask: 1,
+        truncate: 2,
+    };
+
+    // Canonicalizes a function computing `$rdi` from `$rdi` with `body`, and returns the
+    // remaining expressions as their opcode and constant operands.
+    fn canonicalize(body: &str, costs: CostModel) -> Vec<(MOpcode, Vec<Option<u64>>)> {
+        let il = format!(
+            "; radeco-ir v1
+define-fun sym.f(unknown) -> unknown {{
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x8):
+{}
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %4;
+        $mem = %2;
+}}
+",
+            body
+        );
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ir_reader::parse_il(&il, REGISTER_FILE.clone());
+
+        Canonicalizer::with_costs(costs).analyze(&mut rfn, Some(all));
+        DCE::new().analyze(&mut rfn, Some(all));
+
+        let ssa = rfn.ssa();
+        ssa.blocks()
+            .into_iter()
+            .flat_map(|b| ssa.exprs_in(b))
+            .filter(|&e| !ssa.is_constant(e))
+            .map(|e| {
+                let consts = ssa
+                    .operands_of(e)
+                    .into_iter()
+                    .map(|op| ssa.constant(op))
+                    .collect();
+                (ssa.opcode(e).unwrap(), consts)
+            })
+            .collect()
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const SHIFTS: &str = "\
+        [@0x001000.0001] %3: $Unknown64 = %1 << #x1;
+        [@0x001004.0001] %4: $Unknown64 = %3 << #x1;";
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const MULS: &str = "\
+        [@0x001000.0001] %3: $Unknown64 = %1 * #x2;
+        [@0x001004.0001] %4: $Unknown64 = #x4 * %3;";
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const MASK: &str = "\
+        [@0x001000.0001] %3: $Unknown64 = %1 + #x1;
+        [@0x001004.0001] %4: $Unknown64 = %3 & #xff;";
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const TRUNCATE: &str = "\
+        [@0x001000.0001] %3: $Unknown8 = Narrow8(%1);
+        [@0x001004.0001] %4: $Unknown64 = ZeroExt64(%3);";
+
+    #[test]
+    fn shift_to_mul() {
+        let exprs = canonicalize(SHIFTS, CostModel::default());
+        assert_eq!(exprs, vec![(MOpcode::OpMul, vec![None, Some(4)])]);
+    }
+
+    #[test]
+    fn shift_chain_folds() {
+        let exprs = canonicalize(SHIFTS, PREFER_SHIFT);
+        assert_eq!(exprs, vec![(MOpcode::OpLsl, vec![None, Some(2)])]);
+    }
+
+    #[test]
+    fn mul_to_shift() {
+        let exprs = canonicalize(MULS, PREFER_SHIFT);
+        assert_eq!(exprs, vec![(MOpcode::OpLsl, vec![None, Some(3)])]);
+
+        let exprs = canonicalize(MULS, CostModel::default());
+        assert_eq!(exprs, vec![(MOpcode::OpMul, vec![None, Some(8)])]);
+    }
+
+    #[test]
+    fn mask_to_truncate() {
+        let exprs = canonicalize(MASK, CostModel::default());
+        assert_eq!(exprs.len(), 3);
+        assert!(exprs.contains(&(MOpcode::OpNarrow(8), vec![None])));
+        assert!(exprs.contains(&(MOpcode::OpZeroExt(64), vec![None])));
+
+        let exprs = canonicalize(MASK, PREFER_SHIFT);
+        assert!(exprs.contains(&(MOpcode::OpAnd, vec![None, Some(0xff)])));
+    }
+
+    #[test]
+    fn truncate_to_mask() {
+        let exprs = canonicalize(TRUNCATE, PREFER_SHIFT);
+        assert_eq!(exprs, vec![(MOpcode::OpAnd, vec![None, Some(0xff)])]);
+
+        let exprs = canonicalize(TRUNCATE, CostModel::default());
+        assert_eq!(exprs.len(), 2);
+    }
+}
@@ -1,3 +1,10 @@
+//! Copy propagation
+//!
+//! Replaces the uses of a copy with the value it copies. A copy is an `OpMov`, e.g. a register
+//! shuffle, or a resize which does not change the value: an `OpNarrow`, `OpZeroExt` or
+//! `OpSignExt` to the width of its operand. The uses of a chain of copies, like `a = b; c = a;
+//! d = c`, all go to the value at its start, `b`, and the copies are left dead.
+
 use crate::analysis::analyzer::{
     Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
     ReplaceValue,
@@ -11,6 +18,8 @@ use crate::middle::ssa::ssastorage::SSAStorage;
 use std::any::Any;
 use std::collections::HashSet;
 
+type SSAValue = <SSAStorage as SSA>::ValueRef;
+
 #[derive(Debug)]
 pub struct CopyPropagation {
     skip: Vec<ReplaceValue>,
@@ -21,25 +30,53 @@ impl CopyPropagation {
         CopyPropagation { skip: Vec::new() }
     }
 
+    // Pairs every copy with the value at the start of its chain.
     fn gather_copies(ssa: &SSAStorage) -> Vec<ReplaceValue> {
         ssa.blocks()
             .into_iter()
             .flat_map(|b| ssa.exprs_in(b))
-            .filter_map(|e| match ssa.opcode(e) {
-                Some(MOpcode::OpMov) => {
-                    if let Some(&o) = ssa.operands_of(e).iter().nth(0) {
-                        Some(ReplaceValue(o, e))
-                    } else {
-                        radeco_err!("No operand of `OpMov` found");
-                        None
+            .filter_map(|e| {
+                let mut source = copied_value(ssa, e)?;
+                let mut chain = HashSet::new();
+                chain.insert(e);
+                while let Some(next) = copied_value(ssa, source) {
+                    if !chain.insert(source) {
+                        break;
                     }
+                    source = next;
                 }
-                _ => None,
+                Some(ReplaceValue(source, e))
             })
             .collect::<Vec<_>>()
     }
 }
 
+// Returns the value `node` copies, if it is a copy.
+fn copied_value(ssa: &SSAStorage, node: SSAValue) -> Option<SSAValue> {
+    let resized_to = match ssa.opcode(node)? {
+        MOpcode::OpMov => None,
+        MOpcode::OpNarrow(w) | MOpcode::OpZeroExt(w) | MOpcode::OpSignExt(w) => Some(w),
+        _ => return None,
+    };
+    let operand = match ssa.operands_of(node).first() {
+        Some(&operand) => operand,
+        None => {
+            radeco_err!("No operand of {:?} found", node);
+            return None;
+        }
+    };
+    match resized_to {
+        Some(w) if width_of(ssa, operand) != Some(w) => None,
+        _ => Some(operand),
+    }
+}
+
+fn width_of(ssa: &SSAStorage, node: SSAValue) -> Option<u16> {
+    ssa.node_data(node)
+        .ok()
+        .and_then(|nd| nd.vt.width().get_width())
+}
+
 const NAME: &str = "copy_propagation";
 const REQUIRES: &[AnalyzerKind] = &[];
 
@@ -77,18 +114,13 @@ impl FuncAnalyzer for CopyPropagation {
                 break;
             }
 
-            let mut replaced = HashSet::new();
+            // The sources are not copies, so none of them is replaced in this round.
             for change in copies {
                 let from = change.0;
                 let to = change.1;
 
-                if replaced.contains(&from) {
-                    continue;
-                }
-
                 match policy(Box::new(change)) {
                     Action::Apply => {
-                        replaced.insert(to);
                         ssa.replace_value(to, from);
                         self.skip.clear();
                     }
@@ -105,3 +137,82 @@ impl FuncAnalyzer for CopyPropagation {
         None
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    // rcx = rdi; rdx = rcx; rax = (rdx zero extended to its own width) + 1, and eax is a real
+    // narrowing of rdi.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CHAIN_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.chain(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x10):
+        [@0x001000.0001] %3: $Unknown64 = %1;
+        [@0x001004.0001] %4: $Unknown64 = %3;
+        [@0x001008.0001] %5: $Unknown64 = ZeroExt64(%4);
+        [@0x00100C.0001] %6: $Unknown64 = %5 + #x1;
+        [@0x00100C.0002] %7: $Unknown32 = Narrow32(%1);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %6;
+        $rcx = %3;
+        $rdx = %4;
+        $rsi = %7;
+        $mem = %2;
+}
+";
+
+    #[test]
+    fn copy_chain_collapsed() {
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ir_reader::parse_il(CHAIN_TXT, REGISTER_FILE.clone());
+        CopyPropagation::new().analyze(&mut rfn, Some(all));
+        let ssa = rfn.ssa();
+
+        let values = ssa.values();
+        let find = |opcode: &MOpcode| {
+            values
+                .iter()
+                .cloned()
+                .find(|&v| ssa.opcode(v).as_ref() == Some(opcode))
+        };
+        let rdi = values
+            .iter()
+            .cloned()
+            .find(|&v| ssa.comment(v) == Some("rdi".to_owned()))
+            .unwrap();
+        assert_eq!(find(&MOpcode::OpMov), None);
+        assert_eq!(find(&MOpcode::OpZeroExt(64)), None);
+        assert_eq!(ssa.operands_of(find(&MOpcode::OpAdd).unwrap())[0], rdi);
+        assert_eq!(
+            ssa.operands_of(find(&MOpcode::OpNarrow(32)).unwrap()),
+            vec![rdi]
+        );
+
+        // The registers the copies were left in now hold the source.
+        let regstate = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        let uses = ssa.operands_of(regstate);
+        assert_eq!(uses.iter().filter(|&&v| v == rdi).count(), 2);
+    }
+}
@@ -5,9 +5,14 @@
 //! opcodes. On the other hand, considering too much will cause
 //! a huge memory consume. Thus, a balanced solution should be
 //! improved.
+//!
+//! The hashes of the expressions are kept in a `ValueNumberTable`. A table passed to
+//! `CSE::run_with` outlives the run, so that running CSE again after other passes only hashes
+//! the expressions they changed.
 
 use std::any::Any;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::mem;
 
 use crate::analysis::analyzer::{
     Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
@@ -20,10 +25,56 @@ use crate::middle::ssa::ssa_traits::SSA;
 use crate::middle::ssa::ssa_traits::{NodeType, SSAMod, SSAWalk};
 use crate::middle::ssa::ssastorage::SSAStorage;
 
+type SSARef = <SSAStorage as SSA>::ValueRef;
+
+/// Hashes of the expressions of a function, shared by the runs of CSE on it.
+#[derive(Debug, Default)]
+pub struct ValueNumberTable {
+    exprs: HashMap<String, Vec<SSARef>>,
+    hashed: HashMap<SSARef, String>,
+    // Opcode and operands of every hashed expression, when they are left unchanged along with the
+    // hashes of the operands, so is the hash of the expression.
+    keys: HashMap<SSARef, (MOpcode, Vec<SSARef>)>,
+    hashes: usize,
+}
+
+impl ValueNumberTable {
+    pub fn new() -> ValueNumberTable {
+        Default::default()
+    }
+
+    /// Number of hashes computed by the runs of CSE using this table so far. An expression is
+    /// only hashed again when it, or the hash of one of its operands, changed since the previous
+    /// run.
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    fn insert(&mut self, expr: SSARef, key: (MOpcode, Vec<SSARef>), hs: String) {
+        if self.hashed.get(&expr) != Some(&hs) {
+            self.forget(expr);
+            self.exprs
+                .entry(hs.clone())
+                .or_insert_with(Vec::new)
+                .push(expr);
+            self.hashed.insert(expr, hs);
+        }
+        self.keys.insert(expr, key);
+    }
+
+    fn forget(&mut self, expr: SSARef) {
+        self.keys.remove(&expr);
+        if let Some(hs) = self.hashed.remove(&expr) {
+            if let Some(idxs) = self.exprs.get_mut(&hs) {
+                idxs.retain(|&e| e != expr);
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct CSE {
-    exprs: HashMap<String, Vec<<SSAStorage as SSA>::ValueRef>>,
-    hashed: HashMap<<SSAStorage as SSA>::ValueRef, String>,
+    table: ValueNumberTable,
 }
 
 const NAME: &str = "cse";
@@ -39,12 +90,25 @@ pub const INFO: AnalyzerInfo = AnalyzerInfo {
 impl CSE {
     pub fn new() -> CSE {
         CSE {
-            exprs: HashMap::new(),
-            hashed: HashMap::new(),
+            table: ValueNumberTable::new(),
         }
     }
 
-    fn hash_args(&self, ssa: &SSAStorage, args: &[<SSAStorage as SSA>::ValueRef]) -> String {
+    /// Runs CSE on `func` with the hashes of `table`, which is updated to the hashes of the
+    /// expressions left in `func`.
+    pub fn run_with<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        func: &mut RadecoFunction,
+        table: &mut ValueNumberTable,
+        policy: Option<T>,
+    ) {
+        mem::swap(&mut self.table, table);
+        self.analyze(func, policy);
+        mem::swap(&mut self.table, table);
+    }
+
+    // Only the hashes of `visited` are up to date, the other ones may be left from a previous run.
+    fn hash_args(&self, ssa: &SSAStorage, args: &[SSARef], visited: &HashSet<SSARef>) -> String {
         let mut result = String::new();
         for arg in args {
             if let Ok(node_data) = ssa.node_data(*arg) {
@@ -52,7 +116,8 @@ impl CSE {
                     NodeType::Op(opc) => match opc {
                         MOpcode::OpConst(val) => result.push_str(&format!("{}", val)),
                         _ => {
-                            if let Some(hash) = self.hashed.get(arg) {
+                            let hash = self.table.hashed.get(arg).filter(|_| visited.contains(arg));
+                            if let Some(hash) = hash {
                                 result.push_str(hash);
                             } else {
                                 radeco_err!("Hash value not found!");
@@ -77,11 +142,16 @@ impl CSE {
     }
 
     // NOTE: Because we have sorted the operands, it's unnecessary to consider commutative opcodes.
-    fn hash_string(&self, ssa: &SSAStorage, idx: &<SSAStorage as SSA>::ValueRef) -> Option<String> {
+    fn hash_string(
+        &self,
+        ssa: &SSAStorage,
+        idx: &SSARef,
+        visited: &HashSet<SSARef>,
+    ) -> Option<String> {
         if let Ok(node_data) = ssa.node_data(*idx) {
             if let NodeType::Op(opc) = node_data.nt {
                 let args = ssa.operands_of(*idx);
-                let hashed_args = self.hash_args(ssa, &args);
+                let hashed_args = self.hash_args(ssa, &args, visited);
                 let hs = format!("{}{}", opc, hashed_args);
                 return Some(hs);
             }
@@ -109,14 +179,37 @@ impl FuncAnalyzer for CSE {
 
         {
             let ssa = func.ssa_mut();
+            // Expressions hashed during this run, the others recorded in the table were removed
+            // since it was filled.
+            let mut visited = HashSet::new();
+
+            // Expressions whose hash changed during this run, their users are hashed again.
+            let mut changed = HashSet::new();
 
             for expr in ssa.inorder_walk() {
-                let hs = match self.hash_string(ssa, &expr) {
-                    Some(hs) => hs,
+                let key = match ssa.opcode(expr) {
+                    Some(opc) => (opc, ssa.operands_of(expr)),
                     None => continue,
                 };
+                let old = self
+                    .table
+                    .hashed
+                    .get(&expr)
+                    .filter(|_| self.table.keys.get(&expr) == Some(&key))
+                    .filter(|_| !key.1.iter().any(|op| changed.contains(op)))
+                    .cloned();
+                let hs = match old {
+                    Some(hs) => hs,
+                    None => {
+                        self.table.hashes += 1;
+                        match self.hash_string(ssa, &expr, &visited) {
+                            Some(hs) => hs,
+                            None => continue,
+                        }
+                    }
+                };
                 let mut replaced = false;
-                if let Some(ex_idxs) = self.exprs.get(&hs).cloned() {
+                if let Some(ex_idxs) = self.table.exprs.get(&hs).cloned() {
                     // NOTE: Though we can eliminate expression even if the two aren't in the same
                     // block, we do not do so cause it requires that block b1 (block of the
                     // replacer) must dominate block b2 (the replacee). Since we are currently not
@@ -124,6 +217,9 @@ impl FuncAnalyzer for CSE {
                     // restrict outselves to the case where both the expressions belong to the same
                     // block.
                     for ex_idx in &ex_idxs {
+                        if !visited.contains(ex_idx) {
+                            continue;
+                        }
                         if ssa.block_for(*ex_idx) == ssa.block_for(expr) {
                             match policy(Box::new(ReplaceValue(expr, *ex_idx))) {
                                 Action::Apply => {
@@ -138,16 +234,138 @@ impl FuncAnalyzer for CSE {
                     }
                 }
 
-                if !replaced {
-                    self.exprs
-                        .entry(hs.clone())
-                        .or_insert_with(Vec::new)
-                        .push(expr);
-                    self.hashed.insert(expr, hs.clone());
+                if replaced {
+                    self.table.forget(expr);
+                } else {
+                    if self.table.hashed.get(&expr) != Some(&hs) {
+                        changed.insert(expr);
+                    }
+                    self.table.insert(expr, key, hs);
+                    visited.insert(expr);
                 }
             }
+
+            let removed = self
+                .table
+                .keys
+                .keys()
+                .filter(|e| !visited.contains(e))
+                .cloned()
+                .collect::<Vec<_>>();
+            for expr in removed {
+                self.table.forget(expr);
+            }
         }
 
         None
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::middle::ir_reader;
+    use crate::middle::ir_writer;
+    use crate::middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    // `%4` is the same as `%3`.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const COMMON_TXT: &str = "\
+; radeco-ir v1
+define-fun main(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %7: $Mem = $mem;
+    bb_0x001000.0000(sz 0xc):
+        [@0x001000.0001] %3: $Unknown64 = %1 + %2;
+        [@0x001004.0001] %4: $Unknown64 = %1 + %2;
+        [@0x001008.0001] %5: $Unknown64 = %3 * %4;
+        [@0x001008.0002] %6: $Unknown64 = %5 - #x1;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %6;
+        $mem = %7;
+}
+";
+
+    fn emit(rfn: &RadecoFunction) -> String {
+        let mut il = String::new();
+        ir_writer::emit_il(&mut il, Some("main".to_owned()), rfn.ssa()).unwrap();
+        il
+    }
+
+    #[test]
+    fn incremental_run_reuses_table() {
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ir_reader::parse_il(COMMON_TXT, REGISTER_FILE.clone());
+        let mut table = ValueNumberTable::new();
+
+        CSE::new().run_with(&mut rfn, &mut table, Some(all));
+        let first = table.hashes();
+        let first_il = emit(&rfn);
+        assert!(first > 0);
+        let adds = rfn
+            .ssa()
+            .values()
+            .into_iter()
+            .filter(|&v| rfn.ssa().opcode(v) == Some(MOpcode::OpAdd))
+            .count();
+        assert_eq!(adds, 1);
+
+        // Nothing changed since, no expression is hashed again.
+        CSE::new().run_with(&mut rfn, &mut table, Some(all));
+        assert_eq!(table.hashes(), first);
+        assert_eq!(emit(&rfn), first_il);
+
+        // A table filled from scratch leads to the same function.
+        let mut fresh = RadecoFunction::new();
+        *fresh.ssa_mut() = ir_reader::parse_il(COMMON_TXT, REGISTER_FILE.clone());
+        CSE::new().analyze(&mut fresh, Some(all));
+        CSE::new().analyze(&mut fresh, Some(all));
+        assert_eq!(emit(&fresh), first_il);
+    }
+
+    #[test]
+    fn incremental_run_hashes_changed_exprs() {
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ir_reader::parse_il(COMMON_TXT, REGISTER_FILE.clone());
+        let mut table = ValueNumberTable::new();
+        CSE::new().run_with(&mut rfn, &mut table, Some(all));
+        let first = table.hashes();
+
+        // `%5` becomes `%3 * %1`, only it and `%6` using it are hashed again.
+        {
+            let ssa = rfn.ssa_mut();
+            let exprs = ssa.inorder_walk().collect::<Vec<_>>();
+            let by_opcode = |opc: MOpcode| {
+                exprs
+                    .iter()
+                    .cloned()
+                    .find(|&e| ssa.opcode(e) == Some(opc.clone()))
+                    .unwrap()
+            };
+            let (add, mul) = (by_opcode(MOpcode::OpAdd), by_opcode(MOpcode::OpMul));
+            let rdi = ssa.operands_of(add)[0];
+            ssa.op_unuse(mul, add);
+            ssa.op_use(mul, 0, add);
+            ssa.op_use(mul, 1, rdi);
+        }
+        CSE::new().run_with(&mut rfn, &mut table, Some(all));
+        assert_eq!(table.hashes(), first + 2);
+    }
+}
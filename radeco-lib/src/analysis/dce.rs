@@ -11,11 +11,15 @@
 //! The algorithm will not consider whether the uses keeping a node alive
 //! are in code that is actually executed or not. For a better analysis
 //! look at `analysis::constant_propagation`.
+//!
+//! An assumption (`OpAssume`) is never used, it is kept as long as the value
+//! it is on is live.
 
 use crate::analysis::analyzer::{
     Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer, RemoveValue,
 };
 use crate::frontend::radeco_containers::RadecoFunction;
+use crate::middle::ir::MOpcode;
 use crate::middle::ssa::cfg_traits::{CFGMod, CFG};
 use crate::middle::ssa::graph_traits::Graph;
 use crate::middle::ssa::ssa_traits::{NodeType, SSAExtra, SSAMod, SSA};
@@ -60,12 +64,26 @@ impl DCE {
         }
         ssa.clear_mark(&roots);
         queue.extend(&[roots]);
-        while let Some(ni) = queue.pop_front() {
-            if ssa.is_marked(&ni) {
-                continue;
+        loop {
+            while let Some(ni) = queue.pop_front() {
+                if ssa.is_marked(&ni) {
+                    continue;
+                }
+                ssa.mark(&ni);
+                queue.extend(ssa.operands_of(ni));
+            }
+            // The predicates of the assumptions kept may make more values live.
+            queue.extend(nodes.iter().filter(|&node| {
+                !ssa.is_marked(node)
+                    && ssa.opcode(*node) == Some(MOpcode::OpAssume)
+                    && ssa
+                        .operands_of(*node)
+                        .get(0)
+                        .map_or(false, |target| ssa.is_marked(target))
+            }));
+            if queue.is_empty() {
+                break;
             }
-            ssa.mark(&ni);
-            queue.extend(ssa.operands_of(ni));
         }
     }
 
@@ -152,3 +170,76 @@ impl FuncAnalyzer for DCE {
         None
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use crate::middle::ssa::verifier;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    // rdi + 1 is returned, rdi - 1 is dead.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const ASSUME_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.assume(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %3: $Unknown64 = %1 + #x1;
+        [@0x001000.0002] %4: $Unknown1 = %3 > #x0;
+        [@0x001000.0003] %5: $Unknown1 = Assume(%3, %4);
+        [@0x001004.0001] %6: $Unknown64 = %1 - #x1;
+        [@0x001004.0002] %7: $Unknown1 = %6 > #x0;
+        [@0x001004.0003] %8: $Unknown1 = Assume(%6, %7);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %3;
+        $rdi = %1;
+        $mem = %2;
+}
+";
+
+    #[test]
+    fn assume_kept_while_target_live() {
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ir_reader::parse_il(ASSUME_TXT, REGISTER_FILE.clone());
+        DCE::new().analyze(&mut rfn, Some(all));
+
+        let ssa = rfn.ssa();
+        let opcodes = ssa
+            .values()
+            .into_iter()
+            .filter_map(|v| ssa.opcode(v))
+            .collect::<Vec<_>>();
+        // The assumption on `%3`, and its predicate, are kept.
+        let assumes = ssa
+            .values()
+            .into_iter()
+            .filter(|&v| ssa.opcode(v) == Some(MOpcode::OpAssume))
+            .collect::<Vec<_>>();
+        assert_eq!(assumes.len(), 1);
+        let operands = ssa.operands_of(assumes[0]);
+        assert_eq!(ssa.opcode(operands[0]), Some(MOpcode::OpAdd));
+        assert_eq!(ssa.opcode(operands[1]), Some(MOpcode::OpGt));
+        // The one on `%6` went away with it.
+        assert!(!opcodes.contains(&MOpcode::OpSub));
+        assert_eq!(opcodes.iter().filter(|&op| *op == MOpcode::OpGt).count(), 1);
+        assert!(verifier::verify(ssa).is_ok());
+    }
+}
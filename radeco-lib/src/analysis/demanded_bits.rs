@@ -0,0 +1,200 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Demanded bits analysis
+//!
+//! Computes, for every value, the bits of its result which may be observed by some consumer. This
+//! is a backward dataflow analysis over the data edges: the bits a user demands of its operands
+//! follow from the bits demanded of the user itself and from its opcode. E.g. `x & 0xff` only
+//! demands the low byte of `x`, and the low bits of a sum only depend on the low bits of its
+//! operands.
+//!
+//! Register states, memory operations, comparisons and any operation not modelled here demand all
+//! the bits of their operands. The bits of a value which are not demanded can be changed freely,
+//! which allows removing masks and extensions or narrowing operations.
+
+use std::collections::HashMap;
+
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::ssa_traits::SSA;
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+type SSARef = <SSAStorage as SSA>::ValueRef;
+
+/// Set of bits of a value, the least significant bit first.
+pub type BitMask = u128;
+
+/// Returns the bits demanded of each value of `ssa`. Values whose result is never used are mapped
+/// to an empty mask.
+pub fn demanded_bits(ssa: &SSAStorage) -> HashMap<SSARef, BitMask> {
+    let values = ssa.values();
+    let mut demanded = HashMap::new();
+    for &v in &values {
+        // Selectors are used by the branch of their block.
+        let bits = if ssa.is_selector(v) {
+            all_bits(ssa, v)
+        } else {
+            0
+        };
+        demanded.insert(v, bits);
+    }
+    let mut worklist = values;
+    while let Some(user) = worklist.pop() {
+        let user_demanded = demanded.get(&user).cloned().unwrap_or(0);
+        let operands = ssa.sparse_operands_of(user);
+        for &(idx, operand) in &operands {
+            let bits =
+                operand_demanded(ssa, user, &operands, idx, user_demanded) & all_bits(ssa, operand);
+            let old = demanded.entry(operand).or_insert(0);
+            if *old | bits != *old {
+                *old |= bits;
+                worklist.push(operand);
+            }
+        }
+    }
+    demanded
+}
+
+fn mask(width: u16) -> BitMask {
+    if width >= 128 {
+        !0
+    } else {
+        (1 << width) - 1
+    }
+}
+
+fn all_bits(ssa: &SSAStorage, node: SSARef) -> BitMask {
+    ssa.node_data(node)
+        .ok()
+        .and_then(|nd| nd.vt.width().get_width())
+        .filter(|&w| w > 0)
+        .map_or(!0, mask)
+}
+
+// Bits demanded of the operand at `idx` of `user`, given that `demanded` bits of `user` are.
+fn operand_demanded(
+    ssa: &SSAStorage,
+    user: SSARef,
+    operands: &[(u8, SSARef)],
+    idx: u8,
+    demanded: BitMask,
+) -> BitMask {
+    if ssa.is_phi(user) {
+        return demanded;
+    }
+    let other = operands
+        .iter()
+        .find(|&&(i, _)| i != idx)
+        .and_then(|&(_, v)| ssa.constant_value(v))
+        .map(BitMask::from);
+    match ssa.opcode(user) {
+        Some(MOpcode::OpAnd) => demanded & other.unwrap_or(!0),
+        Some(MOpcode::OpOr) => demanded & !other.unwrap_or(0),
+        Some(MOpcode::OpXor) | Some(MOpcode::OpNot) | Some(MOpcode::OpMov) => demanded,
+        Some(MOpcode::OpNarrow(_)) | Some(MOpcode::OpZeroExt(_)) => demanded,
+        Some(MOpcode::OpSignExt(_)) => {
+            let width = ssa
+                .node_data(operands[0].1)
+                .ok()
+                .and_then(|nd| nd.vt.width().get_width());
+            match width {
+                // The extended bits are copies of the sign bit.
+                Some(w) if w > 0 && demanded & !mask(w) != 0 => demanded | 1 << (w - 1),
+                _ => demanded,
+            }
+        }
+        // Carries only propagate towards the most significant bits.
+        Some(MOpcode::OpAdd) | Some(MOpcode::OpSub) | Some(MOpcode::OpMul) => {
+            if demanded == 0 {
+                0
+            } else {
+                mask(128 - demanded.leading_zeros() as u16)
+            }
+        }
+        Some(MOpcode::OpLsl) if idx == 0 => match other {
+            Some(s) if s < 128 => demanded >> s,
+            _ => !0,
+        },
+        Some(MOpcode::OpLsr) if idx == 0 => match other {
+            Some(s) if s < 128 => demanded << s,
+            _ => !0,
+        },
+        Some(MOpcode::OpITE) if idx != 0 => demanded,
+        _ => !0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::regfile::SubRegisterFile;
+    use crate::middle::ssa::ssa_builder::SSABuilder;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    #[test]
+    fn masked_high_bits() {
+        // mov rax, [rdi]; add rax, [rsi]; and rax, 0xff
+        let mut b = SSABuilder::new(REGISTER_FILE.clone());
+        let rdi = b.register("rdi");
+        let rsi = b.register("rsi");
+        let mem = b.register("mem");
+        let bb = b.block(0x1000, 0x8);
+        let x = b.op(bb, MOpcode::OpLoad, 64, &[mem, rdi]);
+        let y = b.op(bb, MOpcode::OpLoad, 64, &[mem, rsi]);
+        let sum = b.op(bb, MOpcode::OpAdd, 64, &[x, y]);
+        let k = b.constant(0xff);
+        let rax = b.op(bb, MOpcode::OpAnd, 64, &[sum, k]);
+        b.ret(bb)
+            .exit_register("rax", rax)
+            .exit_register("mem", mem);
+        let ssa = b.finish();
+
+        let demanded = demanded_bits(&ssa);
+        assert_eq!(demanded[&rax], !0u64 as BitMask);
+        assert_eq!(demanded[&sum], 0xff);
+        assert_eq!(demanded[&x], 0xff);
+        assert_eq!(demanded[&y], 0xff);
+        // The addresses are used as they are, and `rdi` is also left as it is at the exit.
+        assert_eq!(demanded[&rdi], !0u64 as BitMask);
+    }
+
+    #[test]
+    fn shifted_and_sign_extended() {
+        // movsx eax, byte [rdi]; shr eax, 8
+        let mut b = SSABuilder::new(REGISTER_FILE.clone());
+        let rdi = b.register("rdi");
+        let mem = b.register("mem");
+        let bb = b.block(0x1000, 0x8);
+        let byte = b.op(bb, MOpcode::OpLoad, 8, &[mem, rdi]);
+        let ext = b.op(bb, MOpcode::OpSignExt(32), 32, &[byte]);
+        let k = b.sized_constant(8, 32);
+        let shr = b.op(bb, MOpcode::OpLsr, 32, &[ext, k]);
+        let rax = b.op(bb, MOpcode::OpZeroExt(64), 64, &[shr]);
+        b.ret(bb)
+            .exit_register("rax", rax)
+            .exit_register("mem", mem);
+        let ssa = b.finish();
+
+        let demanded = demanded_bits(&ssa);
+        assert_eq!(demanded[&shr], 0xffff_ffff);
+        assert_eq!(demanded[&ext], 0xffff_ff00);
+        // Only the sign bit of the byte is observed.
+        assert_eq!(demanded[&byte], 0x80);
+        assert_eq!(demanded[&k], 0xffff_ffff);
+    }
+}
@@ -0,0 +1,239 @@
+// Copyright (c) 2015, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Dead store elimination
+//!
+//! Removes `OpStore`s whose memory is only consumed by a later `OpStore` to the same address,
+//! i.e. stores that are overwritten before anything can observe them.
+//!
+//! Accesses that may fall in a volatile (memory-mapped I/O) range are treated as having side
+//! effects and are never removed. An access whose address is not a constant is assumed to possibly
+//! hit a volatile range whenever any such range is configured.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
+    ReplaceValue,
+};
+use crate::frontend::radeco_containers::RadecoFunction;
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::{SSAMod, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use std::any::Any;
+use std::ops::Range;
+
+#[derive(Debug)]
+pub struct DSE {
+    volatile: Vec<Range<u64>>,
+    skip: Vec<ReplaceValue>,
+}
+
+const NAME: &str = "dse";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::DSE,
+    requires: REQUIRES,
+    uses_policy: true,
+};
+
+impl DSE {
+    pub fn new() -> Self {
+        DSE::with_volatile(Vec::new())
+    }
+
+    /// Creates a `DSE` which preserves every access that may fall in one of `volatile`.
+    pub fn with_volatile(volatile: Vec<Range<u64>>) -> Self {
+        DSE {
+            volatile: volatile,
+            skip: Vec::new(),
+        }
+    }
+
+    fn may_be_volatile(&self, ssa: &SSAStorage, addr: <SSAStorage as SSA>::ValueRef) -> bool {
+        if self.volatile.is_empty() {
+            return false;
+        }
+        match ssa.constant_value(addr) {
+            Some(a) => self.volatile.iter().any(|r| r.contains(&a)),
+            None => true,
+        }
+    }
+
+    fn same_address(
+        ssa: &SSAStorage,
+        a: <SSAStorage as SSA>::ValueRef,
+        b: <SSAStorage as SSA>::ValueRef,
+    ) -> bool {
+        if a == b {
+            return true;
+        }
+        match (ssa.constant_value(a), ssa.constant_value(b)) {
+            (Some(x), Some(y)) => x == y,
+            _ => false,
+        }
+    }
+
+    fn store_width(ssa: &SSAStorage, store: <SSAStorage as SSA>::ValueRef) -> Option<u16> {
+        let value = *ssa.operands_of(store).get(2)?;
+        ssa.node_data(value).ok()?.vt.width().get_width()
+    }
+
+    // Returns `ReplaceValue(mem, store)` for every store that is only used as the memory operand
+    // of a later store which completely overwrites it.
+    fn gather_dead_stores(&self, ssa: &SSAStorage) -> Vec<ReplaceValue> {
+        ssa.blocks()
+            .into_iter()
+            .flat_map(|b| ssa.exprs_in(b))
+            .filter(|&e| ssa.opcode(e) == Some(MOpcode::OpStore))
+            .filter_map(|store| {
+                let ops = ssa.operands_of(store);
+                if ops.len() != 3 || self.may_be_volatile(ssa, ops[1]) {
+                    return None;
+                }
+                let uses = ssa.uses_of(store);
+                if uses.is_empty() {
+                    return None;
+                }
+                let width = DSE::store_width(ssa, store)?;
+                let overwritten = uses.iter().all(|&u| {
+                    if ssa.opcode(u) != Some(MOpcode::OpStore) {
+                        return false;
+                    }
+                    let u_ops = ssa.operands_of(u);
+                    u_ops.len() == 3
+                        && u_ops[0] == store
+                        && DSE::same_address(ssa, ops[1], u_ops[1])
+                        && DSE::store_width(ssa, u).map_or(false, |w| w >= width)
+                });
+                if overwritten {
+                    Some(ReplaceValue(ops[0], store))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+impl Analyzer for DSE {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl FuncAnalyzer for DSE {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rfn: &mut RadecoFunction,
+        policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let mut policy = policy.expect("A policy function must be provided");
+        let ssa = rfn.ssa_mut();
+        loop {
+            let dead = self
+                .gather_dead_stores(ssa)
+                .into_iter()
+                .filter(|change| !self.skip.contains(change))
+                .collect::<Vec<_>>();
+
+            if dead.is_empty() {
+                break;
+            }
+
+            for change in dead {
+                match policy(Box::new(change)) {
+                    Action::Apply => {
+                        radeco_trace!("dse_rm_store|{:?}", change.1);
+                        ssa.replace_value(change.1, change.0);
+                        self.skip.clear();
+                    }
+                    Action::Skip => {
+                        self.skip.push(change);
+                    }
+                    Action::Abort => {
+                        return None;
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::frontend::radeco_containers::RadecoModule;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const STORES_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.stores(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64(*?) = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x0):
+        [@0x001000.0001] %3: $Mem = Store(%2, #x40000000, %1);
+        [@0x001000.0002] %4: $Mem = Store(%3, #x40000000, %1);
+        [@0x001000.0003] %5: $Mem = Store(%4, #x2000, %1);
+        [@0x001000.0004] %6: $Mem = Store(%5, #x2000, %1);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %1;
+        $mem = %6;
+}
+";
+
+    fn stores_to(ssa: &SSAStorage, addr: u64) -> usize {
+        ssa.blocks()
+            .into_iter()
+            .flat_map(|b| ssa.exprs_in(b))
+            .filter(|&e| ssa.opcode(e) == Some(MOpcode::OpStore))
+            .filter(|&e| ssa.constant_value(ssa.operands_of(e)[1]) == Some(addr))
+            .count()
+    }
+
+    #[test]
+    fn volatile_store_preserved() {
+        let mut rmod = RadecoModule::new("test".to_owned());
+        rmod.add_volatile_range(0x4000_0000..0x4000_1000);
+
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ir_reader::parse_il(STORES_TXT, REGISTER_FILE.clone());
+        assert_eq!(stores_to(rfn.ssa(), 0x4000_0000), 2);
+        assert_eq!(stores_to(rfn.ssa(), 0x2000), 2);
+
+        let mut dse = DSE::with_volatile(rmod.volatile_ranges().to_vec());
+        dse.analyze(&mut rfn, Some(all));
+
+        assert_eq!(stores_to(rfn.ssa(), 0x4000_0000), 2);
+        assert_eq!(stores_to(rfn.ssa(), 0x2000), 1);
+    }
+}
@@ -8,23 +8,44 @@ use rayon::prelude::*;
 use std::any::Any;
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::PathBuf;
 
 use crate::analysis::analyzer;
 use crate::analysis::analyzer::{
     Action, AnalyzerInfo, AnalyzerKind, Change, FuncAnalyzer, ModuleAnalyzer,
 };
-use crate::analysis::arithmetic::{ArithChange, Arithmetic};
+use crate::analysis::arithmetic::{self, ArithChange, Arithmetic};
+use crate::analysis::bounds_check::BoundsCheckDetector;
 use crate::analysis::copy_propagation::CopyPropagation;
-use crate::analysis::cse::cse::CSE;
+use crate::analysis::cse::cse::{ValueNumberTable, CSE};
 use crate::analysis::cse::ssasort::Sorter;
 use crate::analysis::dce::DCE;
+use crate::analysis::dse::DSE;
+use crate::analysis::fold_extends;
+use crate::analysis::functions::callee_saved;
 use crate::analysis::functions::fix_ssa_opcalls::CallSiteFixer;
 use crate::analysis::functions::infer_regusage::Inferer;
 use crate::analysis::inst_combine::Combiner;
+use crate::analysis::interproc::constprop::ConstPropagation;
 use crate::analysis::interproc::fixcall::CallFixer;
+use crate::analysis::interproc::interproc::InterProcAnalyzer;
+use crate::analysis::load_forwarding::LoadForwarding;
+use crate::analysis::magic_div;
+use crate::analysis::regstate_cleanup::RegStateCleanup;
 use crate::analysis::sccp::SCCP;
+use crate::analysis::shift_mul;
+use crate::analysis::signatures::SignatureDb;
+use crate::analysis::simplify_selectors;
+use crate::analysis::string_recovery::StringRecovery;
 use crate::frontend::radeco_containers::{FunctionKind, RadecoFunction, RadecoModule};
 use crate::middle::regfile::SubRegisterFile;
+use crate::middle::ssa::memoryssa;
+use crate::middle::ssa::ssadiff::{self, SsaDiff};
+use crate::middle::ssa::ssastorage::SSAStorage;
+use crate::middle::{dot, ir_writer};
 
 fn sort_by_requires(analyzers: &Vec<AnalyzerKind>) -> impl Iterator<Item = AnalyzerKind> {
     // Build the dependency graph.
@@ -66,54 +87,94 @@ pub trait Engine: Any + Debug {
     fn run_func(&self, rfn: &mut RadecoFunction) -> Option<Box<dyn EngineResult>>;
 }
 
+/// Where to write the IR of every function after each pass of the engine.
+///
+/// The IR after the `n`-th pass, `stage`, is written in `<dir>/<n>-<stage>/<function>.ir`, the
+/// IR before the first pass being the `construct` stage.
+#[derive(Clone, Debug)]
+pub struct Snapshots {
+    pub dir: PathBuf,
+    /// Also write the IR as a Graphviz graph, in `<function>.dot`.
+    pub dot: bool,
+}
+
+impl Snapshots {
+    fn write(&self, step: usize, stage: &str, rfn: &RadecoFunction) -> io::Result<()> {
+        let dir = self.dir.join(format!("{:02}-{}", step, stage));
+        fs::create_dir_all(&dir)?;
+        let name = rfn.name.replace('/', "_");
+
+        let mut il = String::new();
+        ir_writer::emit_il(&mut il, Some(rfn.name.to_string()), rfn.ssa())
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        fs::write(dir.join(format!("{}.ir", name)), il)?;
+        if self.dot {
+            fs::write(dir.join(format!("{}.dot", name)), dot::emit_dot(rfn.ssa()))?;
+        }
+        Ok(())
+    }
+}
+
 /// Radeco's default engine.
 #[derive(Debug)]
 pub struct RadecoEngine {
     max_iteration: u32,
+    snapshots: Option<Snapshots>,
+    signatures: Option<SignatureDb>,
+    enabled: Vec<AnalyzerKind>,
 }
 
 impl RadecoEngine {
     pub fn new(max_iteration: u32) -> Self {
         RadecoEngine {
             max_iteration: max_iteration,
+            snapshots: None,
+            signatures: None,
+            enabled: Vec::new(),
         }
     }
-}
-
-impl Engine for RadecoEngine {
-    fn run_module(
-        &self,
-        rmod: &mut RadecoModule,
-        regfile: &SubRegisterFile,
-    ) -> Option<Box<dyn EngineResult>> {
-        radeco_trace!("run_module");
 
-        // Analyze preserved for all functions.
-        {
-            let bp_name = regfile.get_name_by_alias(&"BP".to_string());
-            let bp_name = bp_name.map(|s| s.to_owned());
-            let sp_name = regfile.get_name_by_alias(&"SP".to_string());
-            let sp_name = sp_name.map(|s| s.to_owned());
-            let mut callfixer = CallFixer::new(rmod, bp_name, sp_name);
-            callfixer.rounded_analysis();
+    /// Also runs the function analyzer `kind`, one of `analyzer::optional_func_analyzers`, along
+    /// with the default ones.
+    pub fn enable(mut self, kind: AnalyzerKind) -> Self {
+        if !self.enabled.contains(&kind) {
+            self.enabled.push(kind);
         }
+        self
+    }
 
-        // Fix call sites
-        let mut call_site_fixer = CallSiteFixer::new();
-        call_site_fixer.analyze(rmod, None::<fn(_) -> _>);
-
-        // Infer calling conventions
-        let mut inferer = Inferer::new((*regfile).clone());
-        inferer.analyze(rmod, None::<fn(_) -> _>);
+    /// Writes the IR of every function after each pass as described by `snapshots`.
+    pub fn snapshots(mut self, snapshots: Snapshots) -> Self {
+        self.snapshots = Some(snapshots);
+        self
+    }
 
-        rmod.functions.par_iter_mut().for_each(|(_, rfn)| {
-            self.run_func(rfn);
-        });
+    /// Types the functions called from a module with the signatures of `db`.
+    pub fn signatures(mut self, db: SignatureDb) -> Self {
+        self.signatures = Some(db);
+        self
+    }
 
-        None
+    fn snapshot(&self, step: &mut usize, stage: &str, rfn: &RadecoFunction) {
+        if let Some(ref snapshots) = self.snapshots {
+            if let Err(_e) = snapshots.write(*step, stage, rfn) {
+                radeco_err!(
+                    "Cannot write the {} snapshot of {}: {}",
+                    stage,
+                    rfn.name,
+                    _e
+                );
+            }
+            *step += 1;
+        }
     }
 
-    fn run_func(&self, rfn: &mut RadecoFunction) -> Option<Box<dyn EngineResult>> {
+    // Same as `run_func`, but never removes accesses that may fall in one of `volatile`.
+    fn run_func_volatile(
+        &self,
+        rfn: &mut RadecoFunction,
+        volatile: &[Range<u64>],
+    ) -> Option<Box<dyn EngineResult>> {
         radeco_trace!("run_func: {}", rfn.name);
 
         // There is no code for imported/relocated functions.
@@ -121,6 +182,9 @@ impl Engine for RadecoEngine {
             return None;
         }
 
+        let mut step = 0;
+        self.snapshot(&mut step, "construct", rfn);
+
         // Try to convert the condition codes to relational operators. This should be done before
         // all the other passes.
         let mut arithmetic = Arithmetic::new();
@@ -138,6 +202,8 @@ impl Engine for RadecoEngine {
                 }
             }),
         );
+        rfn.mark_analyzed(AnalyzerKind::Arithmetic);
+        self.snapshot(&mut step, arithmetic::INFO.name, rfn);
 
         {
             // Sort the IR.
@@ -145,7 +211,16 @@ impl Engine for RadecoEngine {
             sorter.run();
         }
 
-        let mut analyzers = sort_by_requires(&analyzer::all_func_analyzers());
+        let mut kinds = analyzer::all_func_analyzers();
+        for &kind in &self.enabled {
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+        // Collected, as every iteration runs them all again.
+        let analyzers = sort_by_requires(&kinds).collect::<Vec<_>>();
+        // Shared by the runs of CSE, which only hash again the values changed in between.
+        let mut value_numbers = ValueNumberTable::new();
 
         // Run iteratively all the available analyzers until a stable point or the maximum
         // number of iterations is reached.
@@ -153,42 +228,44 @@ impl Engine for RadecoEngine {
             let mut stable = true;
 
             // Build and run the analyzers.
-            while let Some(analyzer) = analyzers.next() {
+            for &analyzer in &analyzers {
                 radeco_trace!("running analyzer: {:?}", analyzer);
+                let info: &'static AnalyzerInfo = From::from(analyzer);
+                if let Err(e) = rfn.requires(info.requires) {
+                    // Run again the prerequisites whose results are out of date.
+                    for kind in e.missing {
+                        if run_func_analyzer(kind, rfn, volatile, &mut value_numbers, analyzer::all)
+                            .is_some()
+                        {
+                            rfn.mark_analyzed(kind);
+                        }
+                    }
+                    if let Err(_e) = rfn.requires(info.requires) {
+                        radeco_err!("Cannot run {} on {}: {}", info.name, rfn.name, _e);
+                        return None;
+                    }
+                }
                 // If the policy is called then there is still something to change, thus this is
                 // not a stable point.
+                let mut changed = false;
                 let policy = |_| {
-                    stable = false;
+                    changed = true;
                     Action::Apply
                 };
 
-                match analyzer {
-                    AnalyzerKind::Arithmetic => {
-                        let mut arithmetic = Arithmetic::new();
-                        arithmetic.analyze(rfn, Some(policy));
-                    }
-                    AnalyzerKind::Combiner => {
-                        let mut combiner = Combiner::new();
-                        combiner.analyze(rfn, Some(policy));
-                    }
-                    AnalyzerKind::CopyPropagation => {
-                        let mut copy_propagation = CopyPropagation::new();
-                        copy_propagation.analyze(rfn, Some(policy));
-                    }
-                    AnalyzerKind::CSE => {
-                        let mut cse = CSE::new();
-                        cse.analyze(rfn, Some(policy));
-                    }
-                    AnalyzerKind::DCE => {
-                        let mut dce = DCE::new();
-                        dce.analyze(rfn, Some(policy));
-                    }
-                    AnalyzerKind::SCCP => {
-                        let mut sccp = SCCP::new();
-                        sccp.analyze(rfn, Some(policy));
-                    }
-                    _ => (),
+                // The analyzers which do not use a policy report their changes instead.
+                let rewritten =
+                    match run_func_analyzer(analyzer, rfn, volatile, &mut value_numbers, policy) {
+                        Some(rewritten) => rewritten,
+                        None => continue,
+                    };
+                rfn.mark_analyzed(analyzer);
+                if changed || rewritten {
+                    stable = false;
+                    // The stores the loads read from may have changed.
+                    rfn.invalidate(AnalyzerKind::MemorySSA);
                 }
+                self.snapshot(&mut step, info.name, rfn);
             }
 
             if stable {
@@ -199,3 +276,532 @@ impl Engine for RadecoEngine {
         None
     }
 }
+
+// Runs the function analyzer `analyzer` on `rfn`, never removing accesses that may fall in one of
+// `volatile`. Returns `None` if `analyzer` is not a function analyzer, otherwise whether it
+// changed `rfn` without calling `policy`, as the analyzers which do not use one do.
+fn run_func_analyzer<T: FnMut(Box<dyn Change>) -> Action>(
+    analyzer: AnalyzerKind,
+    rfn: &mut RadecoFunction,
+    volatile: &[Range<u64>],
+    value_numbers: &mut ValueNumberTable,
+    policy: T,
+) -> Option<bool> {
+    match analyzer {
+        AnalyzerKind::Arithmetic => {
+            let mut arithmetic = Arithmetic::new();
+            arithmetic.analyze(rfn, Some(policy));
+        }
+        AnalyzerKind::Combiner => {
+            let mut combiner = Combiner::new();
+            combiner.analyze(rfn, Some(policy));
+        }
+        AnalyzerKind::CopyPropagation => {
+            let mut copy_propagation = CopyPropagation::new();
+            copy_propagation.analyze(rfn, Some(policy));
+        }
+        AnalyzerKind::CSE => {
+            let mut cse = CSE::new();
+            cse.run_with(rfn, value_numbers, Some(policy));
+        }
+        AnalyzerKind::DCE => {
+            let mut dce = DCE::new();
+            dce.analyze(rfn, Some(policy));
+        }
+        AnalyzerKind::DSE => {
+            let mut dse = DSE::with_volatile(volatile.to_vec());
+            dse.analyze(rfn, Some(policy));
+        }
+        AnalyzerKind::LoadForwarding => {
+            let mut load_forwarding = LoadForwarding::with_volatile(volatile.to_vec());
+            load_forwarding.analyze(rfn, Some(policy));
+        }
+        AnalyzerKind::MemorySSA => memoryssa::record_clobbering_stores(rfn),
+        AnalyzerKind::RegStateCleanup => {
+            let mut cleanup = RegStateCleanup::new();
+            cleanup.analyze(rfn, Some(policy));
+        }
+        AnalyzerKind::SCCP => {
+            let mut sccp = SCCP::new();
+            sccp.analyze(rfn, Some(policy));
+        }
+        AnalyzerKind::FoldExtends => {
+            return Some(fold_extends::fold_extends(rfn.ssa_mut()) > 0);
+        }
+        AnalyzerKind::MagicDiv => {
+            return Some(magic_div::recover_divisions(rfn.ssa_mut()) > 0);
+        }
+        AnalyzerKind::ShiftMul => {
+            return Some(shift_mul::recover_multiplications(rfn.ssa_mut()) > 0);
+        }
+        AnalyzerKind::SimplifySelectors => {
+            return Some(simplify_selectors::simplify_selectors(rfn.ssa_mut()) > 0);
+        }
+        _ => return None,
+    }
+    Some(false)
+}
+
+// Runs the module analyzer `analyzer` on `rmod`. Returns `false` if `analyzer` is not one of the
+// module analyzers run once the functions are simplified, the others are run by `run_module`
+// before.
+fn run_module_analyzer(analyzer: AnalyzerKind, rmod: &mut RadecoModule) -> bool {
+    match analyzer {
+        // Annotate the references to string literals which survived the analysis
+        AnalyzerKind::StringRecovery => {
+            let mut string_recovery = StringRecovery::new();
+            string_recovery.analyze(rmod, None::<fn(_) -> _>);
+        }
+        // Tell the bounds checks apart from the rest of the control flow
+        AnalyzerKind::BoundsCheck => {
+            let mut bounds_check = BoundsCheckDetector::new();
+            bounds_check.analyze(rmod, None::<fn(_) -> _>);
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Runs each of `pipeline_a` and `pipeline_b` on a copy of `ssa`, their analyzers once each in
+/// order, and returns the values computed differently by the results. This tells whether the
+/// result of the analyzers depends on their order.
+///
+/// The analyzers which are not function analyzers are skipped, and the ones an analyzer
+/// `requires` are not run unless they are part of the pipeline.
+pub fn compare_pipelines(
+    ssa: &SSAStorage,
+    pipeline_a: &[AnalyzerKind],
+    pipeline_b: &[AnalyzerKind],
+) -> Vec<SsaDiff> {
+    let run = |pipeline: &[AnalyzerKind]| {
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ssa.clone();
+        let mut value_numbers = ValueNumberTable::new();
+        for &kind in pipeline {
+            radeco_trace!("compare_pipelines: {:?}", kind);
+            if run_func_analyzer(kind, &mut rfn, &[], &mut value_numbers, analyzer::all).is_some() {
+                rfn.mark_analyzed(kind);
+            }
+        }
+        rfn
+    };
+    let (a, b) = (run(pipeline_a), run(pipeline_b));
+    ssadiff::diff(a.ssa(), b.ssa())
+}
+
+impl Engine for RadecoEngine {
+    fn run_module(
+        &self,
+        rmod: &mut RadecoModule,
+        regfile: &SubRegisterFile,
+    ) -> Option<Box<dyn EngineResult>> {
+        radeco_trace!("run_module");
+
+        // Analyze preserved for all functions.
+        {
+            let bp_name = regfile.get_name_by_alias(&"BP".to_string());
+            let bp_name = bp_name.map(|s| s.to_owned());
+            let sp_name = regfile.get_name_by_alias(&"SP".to_string());
+            let sp_name = sp_name.map(|s| s.to_owned());
+            let mut callfixer = CallFixer::new(rmod, bp_name, sp_name);
+            callfixer.rounded_analysis();
+        }
+
+        // Fix call sites
+        let mut call_site_fixer = CallSiteFixer::new();
+        call_site_fixer.analyze(rmod, None::<fn(_) -> _>);
+
+        // Pair the restores of callee-saved registers with their saves
+        for rfn in rmod.functions.values_mut() {
+            callee_saved::mark_callee_saved(rfn);
+        }
+
+        // Infer calling conventions
+        let mut inferer = Inferer::new((*regfile).clone());
+        inferer.analyze(rmod, None::<fn(_) -> _>);
+
+        for rfn in rmod.functions.values_mut() {
+            rfn.mark_analyzed(AnalyzerKind::CallSiteFixer);
+            rfn.mark_analyzed(AnalyzerKind::Inferer);
+        }
+
+        // Type the callees which have a user supplied signature
+        if let Some(ref db) = self.signatures {
+            db.apply(rmod);
+        }
+
+        let volatile = rmod.volatile_ranges().to_vec();
+        rmod.functions.par_iter_mut().for_each(|(_, rfn)| {
+            self.run_func_volatile(rfn, &volatile);
+        });
+
+        // Propagate the constants across calls, then fold what they made constant
+        let mut const_propagation: InterProcAnalyzer<ConstPropagation> = InterProcAnalyzer::new();
+        const_propagation.analyze(rmod, None::<fn(_) -> _>);
+        rmod.functions.par_iter_mut().for_each(|(_, rfn)| {
+            if rfn.kind != FunctionKind::Local {
+                return;
+            }
+            let mut value_numbers = ValueNumberTable::new();
+            for &kind in &[AnalyzerKind::SCCP, AnalyzerKind::DCE] {
+                run_func_analyzer(kind, rfn, &volatile, &mut value_numbers, analyzer::all);
+            }
+        });
+
+        // Annotate the functions once they are simplified
+        for analyzer in analyzer::all_module_analyzers() {
+            radeco_trace!("running analyzer: {:?}", analyzer);
+            run_module_analyzer(analyzer, rmod);
+        }
+
+        None
+    }
+
+    fn run_func(&self, rfn: &mut RadecoFunction) -> Option<Box<dyn EngineResult>> {
+        self.run_func_volatile(rfn, &[])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frontend::radeco_containers::ProjectLoader;
+    use crate::frontend::radeco_source::FileSource;
+    use crate::middle::ir_reader;
+    use serde_json;
+    use std::env;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const FOO_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.foo(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x0):
+        [@0x001000.0001] %3: $Unknown64 = #x2 + #x3;
+        [@0x001000.0002] %4: $Unknown64 = %1 + %3;
+        [@0x001000.0003] %5: $Unknown64 = %1 * %1;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %4;
+        $mem = %2;
+}
+";
+
+    // rax = rdx = rdi + (2 + 3), computed twice.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const TWICE_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.twice(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %3: $Unknown64 = #x2 + #x3;
+        [@0x001000.0002] %4: $Unknown64 = %1 + %3;
+        [@0x001004.0001] %5: $Unknown64 = %1 + %3;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %4;
+        $rdx = %5;
+        $mem = %2;
+}
+";
+
+    #[test]
+    fn snapshot_every_pass() {
+        let mut rfn = RadecoFunction::new();
+        rfn.name = "sym.foo".into();
+        *rfn.ssa_mut() = ir_reader::parse_il(FOO_TXT, REGISTER_FILE.clone());
+
+        let dir = env::temp_dir().join("radeco_snapshot_every_pass");
+        let _ = fs::remove_dir_all(&dir);
+        let engine = RadecoEngine::new(10).snapshots(Snapshots {
+            dir: dir.clone(),
+            dot: true,
+        });
+        engine.run_func(&mut rfn);
+
+        let stages = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        for stage in &["construct", "arithmetic", "dce", "sccp", "cse"] {
+            let stage_dir = stages
+                .iter()
+                .find(|s| s.ends_with(&format!("-{}", stage)))
+                .unwrap_or_else(|| panic!("no {} snapshot in {:?}", stage, stages));
+            assert!(dir.join(stage_dir).join("sym.foo.ir").is_file());
+            assert!(dir.join(stage_dir).join("sym.foo.dot").is_file());
+        }
+
+        // The multiplication is dead, so it is gone after DCE.
+        let construct = fs::read_to_string(dir.join("00-construct/sym.foo.ir")).unwrap();
+        assert!(construct.contains(" * "));
+        let dce_dir = stages.iter().find(|s| s.ends_with("-dce")).unwrap();
+        let dce = fs::read_to_string(dir.join(dce_dir).join("sym.foo.ir")).unwrap();
+        assert!(!dce.contains(" * "));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn analyzers_recorded() {
+        let mut rfn = RadecoFunction::new();
+        rfn.name = "sym.foo".into();
+        *rfn.ssa_mut() = ir_reader::parse_il(FOO_TXT, REGISTER_FILE.clone());
+
+        let err = rfn
+            .requires(&[AnalyzerKind::SCCP, AnalyzerKind::DCE])
+            .unwrap_err();
+        assert_eq!(err.missing, vec![AnalyzerKind::SCCP, AnalyzerKind::DCE]);
+        assert_eq!(
+            err.to_string(),
+            "prerequisite not met: sccp, dce must be run first"
+        );
+
+        RadecoEngine::new(10).run_func(&mut rfn);
+        assert!(rfn.analyzed(AnalyzerKind::SCCP));
+        assert!(rfn
+            .requires(&[AnalyzerKind::SCCP, AnalyzerKind::DCE])
+            .is_ok());
+        // Module analyzers are not run on a lone function.
+        assert!(!rfn.analyzed(AnalyzerKind::Inferer));
+    }
+
+    #[test]
+    fn pipeline_order_converges() {
+        let ssa = ir_reader::parse_il(TWICE_TXT, REGISTER_FILE.clone());
+        let sccp_cse = [AnalyzerKind::SCCP, AnalyzerKind::CSE];
+        let cse_sccp = [AnalyzerKind::CSE, AnalyzerKind::SCCP];
+        assert_eq!(compare_pipelines(&ssa, &sccp_cse, &cse_sccp), vec![]);
+
+        // Without SCCP, `2 + 3` is left as it is.
+        let diffs = compare_pipelines(&ssa, &[], &[AnalyzerKind::SCCP]);
+        let roots = diffs.iter().map(|d| &*d.root).collect::<Vec<_>>();
+        assert_eq!(roots, vec!["$rax", "$rdx"]);
+        assert!(diffs[0].left_expr.contains("#x3") && diffs[0].right_expr.contains("#x5"));
+    }
+
+    // Stores twice to the same address, and multiplies by 10 with shifts.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const OPTIONAL_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.optional(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x10):
+        [@0x001000.0001] %3: $Mem = Store(%2, #x2000, %1);
+        [@0x001004.0001] %4: $Mem = Store(%3, #x2000, #x0);
+        [@0x001008.0001] %5: $Unknown64 = %1 << #x3;
+        [@0x001008.0002] %6: $Unknown64 = %1 << #x1;
+        [@0x00100c.0001] %7: $Unknown64 = %5 + %6;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %7;
+        $rdi = %1;
+        $mem = %4;
+}
+";
+
+    #[test]
+    fn optional_analyzers_disabled_by_default() {
+        let run = |engine: RadecoEngine| {
+            let mut rfn = RadecoFunction::new();
+            rfn.name = "sym.optional".into();
+            *rfn.ssa_mut() = ir_reader::parse_il(OPTIONAL_TXT, REGISTER_FILE.clone());
+            engine.run_func(&mut rfn);
+            emit(&rfn)
+        };
+
+        let default = run(RadecoEngine::new(10));
+        assert_eq!(default.matches("Store(").count(), 2);
+        assert!(!default.contains(" * "));
+
+        let enabled = run(RadecoEngine::new(10)
+            .enable(AnalyzerKind::DSE)
+            .enable(AnalyzerKind::ShiftMul));
+        assert_eq!(enabled.matches("Store(").count(), 1);
+        assert!(enabled.contains(" * #xa"));
+    }
+
+    // rax = dil * 3 >> 9, i.e. dil / 171, with the multiplication made of shifts. The shift is
+    // also returned in rdx, so that no other pass changes anything once the multiplication is
+    // recovered.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const DIV171_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.div171(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x10):
+        [@0x001000.0001] %3: $Unknown8 = Narrow8(%1);
+        [@0x001000.0002] %4: $Unknown64 = ZeroExt64(%3);
+        [@0x001004.0001] %5: $Unknown64 = %4 << #x1;
+        [@0x001008.0001] %6: $Unknown64 = %5 + %4;
+        [@0x00100c.0001] %7: $Unknown64 = %6 >> #x9;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %7;
+        $rdx = %5;
+        $mem = %2;
+}
+";
+
+    #[test]
+    fn rewrite_without_policy_is_a_change() {
+        let mut rfn = RadecoFunction::new();
+        rfn.name = "sym.div171".into();
+        *rfn.ssa_mut() = ir_reader::parse_il(DIV171_TXT, REGISTER_FILE.clone());
+        // The division is only found once the multiplication is recovered, by the pass run after.
+        let engine = RadecoEngine::new(10)
+            .enable(AnalyzerKind::ShiftMul)
+            .enable(AnalyzerKind::MagicDiv);
+        engine.run_func(&mut rfn);
+        let il = emit(&rfn);
+        assert!(il.contains(" / #xab"), "{}", il);
+    }
+
+    // rax = rdi == 0 || rsi == 0 ? 1 : 2
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const EITHER_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.either(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Mem = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %4: $Unknown1 = %1 == #x0;
+        JMP IF %4 0x001008.0000 ELSE 0x001004.0000
+    bb_0x001004.0000(sz 0x4):
+        [@0x001004.0001] %5: $Unknown1 = %2 == #x0;
+        JMP IF %5 0x001008.0000 ELSE 0x00100C.0000
+    bb_0x001008.0000(sz 0x4):
+        JMP 0x001010.0000
+    bb_0x00100C.0000(sz 0x4):
+        JMP 0x001010.0000
+    bb_0x001010.0000(sz 0x1):
+        %6: $Unknown64 = Phi(#x1 from 0x001008.0000, #x2 from 0x00100C.0000);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %6;
+        $mem = %3;
+}
+";
+
+    // movzx eax, dil; mov dl, al
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const EXTEND_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.extend(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x6):
+        [@0x001000.0001] %3: $Unknown8 = Narrow8(%1);
+        [@0x001000.0002] %4: $Unknown32 = ZeroExt32(%3);
+        [@0x001004.0001] %5: $Unknown8 = Narrow8(%4);
+        RETURN
+    exit-node:
+    final-register-state:
+        $dl = %5;
+        $mem = %2;
+}
+";
+
+    // Runs each of `kinds` in turn on the function of `il`, and returns whether they changed it
+    // without calling the policy.
+    fn rewritten(il: &str, kinds: &[AnalyzerKind]) -> Vec<Option<bool>> {
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ir_reader::parse_il(il, REGISTER_FILE.clone());
+        let mut value_numbers = ValueNumberTable::new();
+        kinds
+            .iter()
+            .map(|&kind| run_func_analyzer(kind, &mut rfn, &[], &mut value_numbers, analyzer::all))
+            .collect()
+    }
+
+    #[test]
+    fn recovered_divisions_are_changes() {
+        let kinds = [
+            AnalyzerKind::MagicDiv,
+            AnalyzerKind::ShiftMul,
+            AnalyzerKind::MagicDiv,
+            AnalyzerKind::MagicDiv,
+        ];
+        assert_eq!(
+            rewritten(DIV171_TXT, &kinds),
+            vec![Some(false), Some(true), Some(true), Some(false)]
+        );
+    }
+
+    #[test]
+    fn folded_selectors_are_changes() {
+        let kinds = [
+            AnalyzerKind::SimplifySelectors,
+            AnalyzerKind::SimplifySelectors,
+        ];
+        assert_eq!(rewritten(EITHER_TXT, &kinds), vec![Some(true), Some(false)]);
+    }
+
+    #[test]
+    fn folded_extends_are_changes() {
+        let kinds = [AnalyzerKind::FoldExtends, AnalyzerKind::FoldExtends];
+        assert_eq!(rewritten(EXTEND_TXT, &kinds), vec![Some(true), Some(false)]);
+    }
+
+    fn emit(rfn: &RadecoFunction) -> String {
+        let mut il = String::new();
+        ir_writer::emit_il(&mut il, Some(rfn.name.to_string()), rfn.ssa()).unwrap();
+        il
+    }
+
+    #[test]
+    fn analyze_taken_function() {
+        let fsource = Rc::new(FileSource::open("./test_files/bin1_filesource/bin1"));
+        let mut rproj = ProjectLoader::new().source(fsource).load();
+        let rmod = rproj.iter_mut().next().unwrap().module;
+        let offset = rmod
+            .functions
+            .values()
+            .find(|f| f.name == "main")
+            .unwrap()
+            .offset;
+        let count = rmod.functions.len();
+        let engine = RadecoEngine::new(10);
+
+        let mut cloned = rmod.function(offset).unwrap().clone();
+        engine.run_func(&mut cloned);
+
+        // The module does not keep a copy of the function while it is analyzed.
+        let mut rfn = rmod.take_function(offset).unwrap();
+        assert!(rmod.function(offset).is_none());
+        assert_eq!(rmod.functions.len(), count - 1);
+        engine.run_func(&mut rfn);
+        assert!(rmod.restore_function(rfn).is_none());
+
+        assert_eq!(rmod.functions.len(), count);
+        assert_eq!(emit(&cloned), emit(rmod.function(offset).unwrap()));
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Redundant extension elimination
+//!
+//! A load is often followed by an extension to the width of a register, as for `movzx` or
+//! `movsx`, although only the low bits of the register are used afterwards, e.g. by `cmp al, 0x2a`.
+//! Construction translates this into an `OpZeroExt` or an `OpSignExt` of the load, narrowed again
+//! by every use of it.
+//!
+//! This pass looks up the bits of each extension which are demanded by its uses, see
+//! `analysis::demanded_bits`. An extension whose demanded bits all come from the value it extends,
+//! and which is only narrowed, is redundant: its uses are made to narrow that value instead, or to
+//! use it as it is, and the extension is removed.
+
+use crate::analysis::analyzer::{AnalyzerInfo, AnalyzerKind};
+use crate::analysis::demanded_bits::{self, BitMask};
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::ssa_traits::{SSAMod, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+type SSARef = <SSAStorage as SSA>::ValueRef;
+
+const NAME: &str = "fold_extends";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::FoldExtends,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+/// Removes the extensions of `ssa` whose extended bits are never used. Returns the number of
+/// removed extensions.
+pub fn fold_extends(ssa: &mut SSAStorage) -> usize {
+    // Folding an extension does not change the bits demanded of the other values.
+    let demanded = demanded_bits::demanded_bits(ssa);
+    let extends = ssa
+        .values()
+        .into_iter()
+        .filter(|&v| match ssa.opcode(v) {
+            Some(MOpcode::OpZeroExt(_)) | Some(MOpcode::OpSignExt(_)) => true,
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+    let mut folded = 0;
+    for extend in extends {
+        let bits = demanded.get(&extend).cloned().unwrap_or(!0);
+        if fold(ssa, extend, bits).is_some() {
+            folded += 1;
+        }
+    }
+    folded
+}
+
+// Makes the uses of `extend` narrow its operand rather than it, if only the `demanded` bits of it
+// are used and they all come from its operand.
+fn fold(ssa: &mut SSAStorage, extend: SSARef, demanded: BitMask) -> Option<()> {
+    let value = *ssa.operands_of(extend).get(0)?;
+    let width = ssa.node_data(value).ok()?.vt.width().get_width()?;
+    if width >= 128 || demanded >> width != 0 {
+        return None;
+    }
+    // Any other use would have to be given a narrower operand.
+    let uses = ssa.uses_of(extend);
+    let narrowed = uses.iter().all(|&user| match ssa.opcode(user) {
+        Some(MOpcode::OpNarrow(w)) => w <= width,
+        _ => false,
+    });
+    if uses.is_empty() || !narrowed {
+        return None;
+    }
+    radeco_trace!("fold_extends|{:?} of {:?}", extend, value);
+    for narrow in ssa.uses_of(extend) {
+        match ssa.opcode(narrow) {
+            Some(MOpcode::OpNarrow(w)) if w == width => ssa.replace_value(narrow, value),
+            _ => {
+                ssa.op_unuse(narrow, extend);
+                ssa.op_use(narrow, 0, value);
+            }
+        }
+    }
+    ssa.remove_value(extend);
+    Some(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::regfile::SubRegisterFile;
+    use crate::middle::ssa::ssa_builder::SSABuilder;
+    use crate::middle::ssa::verifier;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    #[test]
+    fn byte_load_compared() {
+        // movzx eax, byte [rdi]; cmp al, 0x2a; movzx rdx, byte [rsi]
+        let mut b = SSABuilder::new(REGISTER_FILE.clone());
+        let rdi = b.register("rdi");
+        let rsi = b.register("rsi");
+        let mem = b.register("mem");
+        let bb = b.block(0x1000, 0x10);
+        let byte = b.op(bb, MOpcode::OpLoad, 8, &[mem, rdi]);
+        let eax = b.op(bb, MOpcode::OpZeroExt(32), 32, &[byte]);
+        let al = b.op(bb, MOpcode::OpNarrow(8), 8, &[eax]);
+        let k = b.sized_constant(0x2a, 8);
+        let eq = b.op(bb, MOpcode::OpEq, 1, &[al, k]);
+        let other = b.op(bb, MOpcode::OpLoad, 8, &[mem, rsi]);
+        let rdx = b.op(bb, MOpcode::OpZeroExt(64), 64, &[other]);
+        b.ret(bb)
+            .exit_register("zf", eq)
+            .exit_register("rdx", rdx)
+            .exit_register("mem", mem);
+        let mut ssa = b.finish();
+
+        assert_eq!(fold_extends(&mut ssa), 1);
+        assert!(verifier::verify(&ssa).is_ok());
+        // The comparison is on the loaded byte.
+        assert_eq!(ssa.operands_of(eq)[0], byte);
+        assert!(ssa.node_data(eax).is_err());
+        assert!(ssa
+            .values()
+            .into_iter()
+            .all(|v| ssa.opcode(v) != Some(MOpcode::OpNarrow(8))));
+        // `rdx` is the whole zero-extended byte.
+        assert_eq!(ssa.opcode(rdx), Some(MOpcode::OpZeroExt(64)));
+    }
+}
@@ -0,0 +1,331 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Callee-saved register restoration
+//!
+//! A function which uses a callee-saved register saves it in its prologue, `push rbx`, and
+//! restores it in its epilogue, `pop rbx`, right before returning. In the SSA the register is then
+//! returned as a load from the stack rather than as the value it was entered with, so the function
+//! looks like it clobbers it.
+//!
+//! This pass pairs the loads the registers are returned as with the stores of their entry values
+//! to the same stack slot, and returns the entry values instead. The registers are then marked as
+//! preserved on the function, which lets its callers keep their values across the call.
+//!
+//! The stack slots are told apart by their offset from the stack pointer at the entry of the
+//! function. Stores to other addresses, including those of callees, are assumed not to write to
+//! the slots where registers are saved, since no well-behaved program takes their address.
+
+use crate::frontend::radeco_containers::RadecoFunction;
+use crate::middle::ir::MOpcode;
+use crate::middle::regfile::RegisterId;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::{SSAMod, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+use crate::middle::ssa::utils;
+
+use std::collections::HashSet;
+
+type SSAValue = <SSAStorage as SSA>::ValueRef;
+
+// What a stack slot holds when it is read.
+enum Slot {
+    // The value stored to it.
+    Saved(SSAValue),
+    // Nothing more than on the other paths, as the read is reached again through a loop.
+    Cycle,
+    Unknown,
+}
+
+/// Makes `rfn` return the registers it restores from the stack slot they were saved to with the
+/// values they were entered with, and marks them as preserved. Returns the names of the
+/// registers.
+pub fn mark_callee_saved(rfn: &mut RadecoFunction) -> Vec<String> {
+    let restored = restored_registers(rfn.ssa());
+    if restored.is_empty() {
+        return Vec::new();
+    }
+
+    let ssa = rfn.ssa_mut();
+    let exit_state = match ssa.exit_node().and_then(|exit| ssa.registers_in(exit)) {
+        Some(exit_state) => exit_state,
+        None => return Vec::new(),
+    };
+    let exit_regs = utils::register_state_info(exit_state, ssa);
+    let restores = restored
+        .iter()
+        .filter_map(|&(id, _)| exit_regs.get(id).map(|&(value, _)| value))
+        .collect::<HashSet<_>>();
+    for &restore in &restores {
+        ssa.op_unuse(exit_state, restore);
+    }
+    for id in ssa.regfile.iter_register_ids() {
+        let entry = restored
+            .iter()
+            .find(|&&(r, _)| r == id)
+            .map(|&(_, entry)| entry);
+        match (entry, exit_regs.get(id)) {
+            (Some(entry), _) => ssa.op_use(exit_state, id.to_u8(), entry),
+            // Keep the other registers returned as one of the restored values.
+            (None, Some(&(value, _))) if restores.contains(&value) => {
+                ssa.op_use(exit_state, id.to_u8(), value)
+            }
+            _ => {}
+        }
+    }
+
+    let names = restored
+        .iter()
+        .filter_map(|&(id, _)| ssa.regfile.get_name(id).map(str::to_owned))
+        .collect::<Vec<_>>();
+    radeco_trace!("callee_saved|{}|{:?}", rfn.name, names);
+    for &(id, _) in &restored {
+        // The default register usage cannot be modified, the inferer will tell from the SSA.
+        if rfn.regusage.is_mutable() {
+            rfn.regusage.set_preserved(id);
+        }
+    }
+    for binding in rfn.bindings_mut().iter_mut() {
+        if names.iter().any(|name| name == binding.name()) {
+            binding.mark_preserved();
+        }
+    }
+    names
+}
+
+/// Registers `ssa` returns restored from the stack slot their entry value was saved to, along
+/// with that entry value.
+pub fn restored_registers(ssa: &SSAStorage) -> Vec<(RegisterId, SSAValue)> {
+    let (entry_state, exit_state) = match (ssa.entry_node(), ssa.exit_node()) {
+        (Some(entry), Some(exit)) => match (ssa.registers_in(entry), ssa.registers_in(exit)) {
+            (Some(entry_state), Some(exit_state)) => (entry_state, exit_state),
+            _ => return Vec::new(),
+        },
+        _ => return Vec::new(),
+    };
+    let entry_regs = utils::register_state_info(entry_state, ssa);
+    let exit_regs = utils::register_state_info(exit_state, ssa);
+    let sp_id = match ssa.regfile.register_id_by_alias("SP") {
+        Some(sp_id) => sp_id,
+        None => return Vec::new(),
+    };
+    let sp = match entry_regs.get(sp_id) {
+        Some(&(sp, _)) => sp,
+        None => return Vec::new(),
+    };
+
+    let mut restored = Vec::new();
+    for id in ssa.regfile.iter_register_ids() {
+        if id == sp_id || id == ssa.regfile.mem_id() {
+            continue;
+        }
+        let (entry, exit) = match (entry_regs.get(id), exit_regs.get(id)) {
+            (Some(&(entry, _)), Some(&(exit, _))) => (entry, exit),
+            _ => continue,
+        };
+        if entry != exit && is_restored(ssa, sp, entry, exit, &mut HashSet::new()) {
+            restored.push((id, entry));
+        }
+    }
+    restored
+}
+
+// Whether `value` is `entry` on every path, and restored from the stack on at least one.
+fn is_restored(
+    ssa: &SSAStorage,
+    sp: SSAValue,
+    entry: SSAValue,
+    value: SSAValue,
+    visited: &mut HashSet<SSAValue>,
+) -> bool {
+    if value == entry || !visited.insert(value) {
+        return true;
+    }
+    if ssa.is_phi(value) {
+        return ssa
+            .operands_of(value)
+            .into_iter()
+            .all(|operand| is_restored(ssa, sp, entry, operand, visited));
+    }
+    if ssa.opcode(value) != Some(MOpcode::OpLoad) {
+        return false;
+    }
+    let operands = ssa.operands_of(value);
+    if operands.len() != 2 {
+        return false;
+    }
+    let (offset, size) = match (sp_offset(ssa, sp, operands[1]), size_of(ssa, value)) {
+        (Some(offset), Some(size)) => (offset, size),
+        _ => return false,
+    };
+    match saved_in(ssa, sp, operands[0], offset, size, &mut HashSet::new()) {
+        Slot::Saved(saved) => saved == entry && size_of(ssa, entry) == Some(size),
+        Slot::Cycle | Slot::Unknown => false,
+    }
+}
+
+// What the `size` bytes wide slot at `offset` from the stack pointer holds in the memory `mem`.
+fn saved_in(
+    ssa: &SSAStorage,
+    sp: SSAValue,
+    mem: SSAValue,
+    offset: i64,
+    size: i64,
+    visited: &mut HashSet<SSAValue>,
+) -> Slot {
+    if !visited.insert(mem) {
+        return Slot::Cycle;
+    }
+    if ssa.is_phi(mem) {
+        let mut saved = None;
+        for operand in ssa.operands_of(mem) {
+            match (saved_in(ssa, sp, operand, offset, size, visited), saved) {
+                (Slot::Unknown, _) => return Slot::Unknown,
+                (Slot::Cycle, _) => {}
+                (Slot::Saved(value), None) => saved = Some(value),
+                (Slot::Saved(value), Some(other)) if value == other => {}
+                (Slot::Saved(_), Some(_)) => return Slot::Unknown,
+            }
+        }
+        return saved.map_or(Slot::Cycle, Slot::Saved);
+    }
+
+    let operands = ssa.operands_of(mem);
+    match ssa.opcode(mem) {
+        Some(MOpcode::OpStore) if operands.len() == 3 => {
+            let (prev, addr, value) = (operands[0], operands[1], operands[2]);
+            match (sp_offset(ssa, sp, addr), size_of(ssa, value)) {
+                (Some(o), Some(s)) if o == offset && s == size => Slot::Saved(value),
+                (Some(o), Some(s)) if o + s <= offset || offset + size <= o => {
+                    saved_in(ssa, sp, prev, offset, size, visited)
+                }
+                (Some(_), _) => Slot::Unknown,
+                (None, _) => saved_in(ssa, sp, prev, offset, size, visited),
+            }
+        }
+        None => {
+            // The memory after a call, which is a comment on the call.
+            let call = operands
+                .into_iter()
+                .find(|&o| ssa.opcode(o) == Some(MOpcode::OpCall));
+            let prev = call
+                .and_then(|call| utils::call_info(call, ssa))
+                .and_then(|ci| ci.register_args.get(ssa.regfile.mem_id()).cloned());
+            match prev {
+                Some(prev) => saved_in(ssa, sp, prev, offset, size, visited),
+                None => Slot::Unknown,
+            }
+        }
+        _ => Slot::Unknown,
+    }
+}
+
+// Offset of `addr` from the stack pointer `sp` at the entry of the function, if it is one.
+fn sp_offset(ssa: &SSAStorage, sp: SSAValue, addr: SSAValue) -> Option<i64> {
+    if addr == sp {
+        return Some(0);
+    }
+    let operands = ssa.operands_of(addr);
+    if operands.len() != 2 {
+        return None;
+    }
+    let constant = |i: usize| ssa.constant_value(operands[i]).map(|c| c as i64);
+    match ssa.opcode(addr)? {
+        MOpcode::OpAdd => match (constant(0), constant(1)) {
+            (None, Some(c)) => Some(sp_offset(ssa, sp, operands[0])?.wrapping_add(c)),
+            (Some(c), None) => Some(sp_offset(ssa, sp, operands[1])?.wrapping_add(c)),
+            _ => None,
+        },
+        MOpcode::OpSub => Some(sp_offset(ssa, sp, operands[0])?.wrapping_sub(constant(1)?)),
+        _ => None,
+    }
+}
+
+// Size of `value` in bytes.
+fn size_of(ssa: &SSAStorage, value: SSAValue) -> Option<i64> {
+    let width = ssa.node_data(value).ok()?.vt.width().get_width()?;
+    if width == 0 || width % 8 != 0 {
+        return None;
+    }
+    Some(i64::from(width / 8))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use crate::middle::ssa::verifier;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    // push rbx; push r12; mov [rdi], 1; mov [rsp], rdi; pop r12; pop rbx; ret
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const SAVE_RBX_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.save_rbx(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rsp;
+        %2: $Unknown64 = $rbx;
+        %3: $Unknown64 = $r12;
+        %4: $Unknown64 = $rdi;
+        %5: $Mem = $mem;
+    bb_0x001000.0000(sz 0x10):
+        [@0x001000.0001] %6: $Unknown64 = %1 - #x8;
+        [@0x001000.0002] %7: $Mem = Store(%5, %6, %2);
+        [@0x001001.0001] %8: $Unknown64 = %6 - #x8;
+        [@0x001001.0002] %9: $Mem = Store(%7, %8, %3);
+        [@0x001003.0001] %10: $Mem = Store(%9, %4, #x1);
+        [@0x001009.0001] %11: $Mem = Store(%10, %8, %4);
+        [@0x00100D.0001] %12: $Unknown64 = Load(%11, %8);
+        [@0x00100D.0002] %13: $Unknown64 = %8 + #x8;
+        [@0x00100E.0001] %14: $Unknown64 = Load(%11, %13);
+        [@0x00100E.0002] %15: $Unknown64 = %13 + #x8;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rsp = %15;
+        $rbx = %14;
+        $r12 = %12;
+        $rdi = %4;
+        $mem = %11;
+}
+";
+
+    #[test]
+    fn rbx_preserved() {
+        let mut rfn = RadecoFunction::default();
+        *rfn.ssa_mut() = ir_reader::parse_il(SAVE_RBX_TXT, REGISTER_FILE.clone());
+        rfn.regusage = REGISTER_FILE.new_register_usage();
+
+        // `r12` is restored from a slot which was overwritten.
+        assert_eq!(mark_callee_saved(&mut rfn), vec!["rbx".to_owned()]);
+        let rbx = REGISTER_FILE.register_id_by_name("rbx").unwrap();
+        let r12 = REGISTER_FILE.register_id_by_name("r12").unwrap();
+        assert!(rfn.regusage.is_preserved(rbx));
+        assert!(!rfn.regusage.is_preserved(r12));
+
+        let ssa = rfn.ssa();
+        assert!(verifier::verify(ssa).is_ok());
+        let state = |node| utils::register_state_info(ssa.registers_in(node).unwrap(), ssa);
+        let entry = state(ssa.entry_node().unwrap());
+        let exit = state(ssa.exit_node().unwrap());
+        assert_eq!(exit[rbx].0, entry[rbx].0);
+        assert_ne!(exit[r12].0, entry[r12].0);
+        assert!(restored_registers(ssa).is_empty());
+    }
+}
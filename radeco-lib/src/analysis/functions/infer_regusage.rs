@@ -9,8 +9,9 @@
 //! This analysis is super conservative; for example, if a function preserves a
 //! register by pushing it onto the stack and popping it back right before
 //! returning, it is considered to be read and not preserved because we can't
-//! guarantee that that stack location is never subsequently read or modified.
-//! See #147 for further discussion
+//! guarantee that that stack location is never subsequently read or modified,
+//! unless [`callee_saved`](super::callee_saved) paired the pop with the push
+//! beforehand. See #147 for further discussion
 
 use crate::analysis::analyzer::{
     all, Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
@@ -1,2 +1,3 @@
+pub mod callee_saved;
 pub mod fix_ssa_opcalls;
 pub mod infer_regusage;
@@ -151,7 +151,7 @@ impl Combiner {
                     Action::Apply => {
                         // combined to constant
                         radeco_trace!("{:?} = {:#x}", cur_node, c_val);
-                        let c_node = ssa.insert_const(c_val, None).ok_or(CombErr::NoComb)?;
+                        let c_node = ssa.insert_const(c_val, None).map_err(|_| CombErr::NoComb)?;
                         Ok(c_node)
                     }
                     Action::Skip => Err(CombErr::Skip),
@@ -316,18 +316,18 @@ fn make_opinfo_node(
 ) -> Option<SSAValue> {
     use self::CombinableOpConstInfo as COCI;
 
-    let ret = ssa.insert_op(opinfo.0, vt, None)?;
+    let ret = ssa.insert_op(opinfo.0, vt, None).ok()?;
     match opinfo.1 {
         COCI::Unary => {
             ssa.op_use(ret, 0, sub_node);
         }
         COCI::Left(new_c) => {
-            let new_cnode = ssa.insert_const(new_c, None)?;
+            let new_cnode = ssa.insert_const(new_c, None).ok()?;
             ssa.op_use(ret, 0, new_cnode);
             ssa.op_use(ret, 1, sub_node);
         }
         COCI::Right(new_c) => {
-            let new_cnode = ssa.insert_const(new_c, None)?;
+            let new_cnode = ssa.insert_const(new_c, None).ok()?;
             ssa.op_use(ret, 0, sub_node);
             ssa.op_use(ret, 1, new_cnode);
         }
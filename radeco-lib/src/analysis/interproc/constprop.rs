@@ -0,0 +1,287 @@
+//! Propagates constants across calls.
+//!
+//! When every call site of a function sets an argument register to the same constant, the value
+//! of this register at the entry of the function is replaced by the constant, so that SCCP can
+//! specialize the function. Conversely, when a function always leaves a constant in a register,
+//! the value of this register after each call to the function is replaced by the constant.
+//!
+//! Only the calls recorded in the call graph are accounted for, so only the local functions whose
+//! callers are all known (see `RadecoModule::callers_known`) are specialized.
+
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+
+use crate::analysis::interproc::transfer::InterProcAnalysis;
+use crate::frontend::radeco_containers::{RadecoFunction, RadecoModule};
+use crate::middle::ir::MOpcode;
+use crate::middle::regfile::RegisterId;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::{SSAMod, ValueInfo, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+use crate::middle::ssa::utils;
+
+type LValueRef = <SSAStorage as SSA>::ValueRef;
+
+#[derive(Clone, Debug, Default)]
+pub struct ConstPropagation {}
+
+impl InterProcAnalysis for ConstPropagation {
+    fn new() -> ConstPropagation {
+        Default::default()
+    }
+
+    // Replace the value at the entry of the registers set to the same constant by every caller.
+    fn transfer(&mut self, rmod: &mut RadecoModule, fn_ref: u64) {
+        let constants = constant_args(rmod, fn_ref);
+        let rfn = match rmod.function_mut(fn_ref) {
+            Some(rfn) => rfn,
+            None => return,
+        };
+        let ssa = rfn.ssa_mut();
+        let entry_state = match ssa.entry_node().and_then(|n| ssa.registers_in(n)) {
+            Some(state) => state,
+            None => return,
+        };
+        let entry_values = utils::register_state_info(entry_state, ssa);
+        for (id, value) in constants {
+            if let Some(&(node, vt)) = entry_values.get(id) {
+                radeco_trace!(
+                    "ConstPropagation|{:#x}: {:?} = {:#x}",
+                    fn_ref,
+                    ssa.regfile.get_name(id),
+                    value
+                );
+                // The entry register state still holds the value of the register at the entry.
+                if let Some(const_node) = replace_with_const(ssa, node, vt, value) {
+                    ssa.op_unuse(entry_state, const_node);
+                    ssa.op_use(entry_state, id.to_u8(), node);
+                }
+            }
+        }
+    }
+
+    // Replace the values of the registers a callee always sets to a constant after the calls.
+    fn propagate(&mut self, rmod: &mut RadecoModule, fn_ref: u64) {
+        let calls = match rmod.callgraph_node(fn_ref) {
+            Some(node) => rmod
+                .callgraph
+                .edges_directed(node, Direction::Outgoing)
+                .filter_map(|e| {
+                    let callee = rmod.function(rmod.callgraph[e.target()])?;
+                    Some((e.weight().csite_node, constant_returns(callee)))
+                })
+                .collect::<Vec<_>>(),
+            None => return,
+        };
+        let rfn = match rmod.function_mut(fn_ref) {
+            Some(rfn) => rfn,
+            None => return,
+        };
+        let ssa = rfn.ssa_mut();
+        for (call, constants) in calls {
+            if ssa.opcode(call) != Some(MOpcode::OpCall) {
+                continue;
+            }
+            let rets = utils::call_rets(call, ssa);
+            for (id, value) in constants {
+                if let Some(&(node, vt)) = rets.get(id) {
+                    replace_with_const(ssa, node, vt, value);
+                }
+            }
+        }
+    }
+}
+
+// Returns the registers set to the same constant by every call to the function at `fn_ref`, none
+// for a function that is never called or which may be called from elsewhere.
+fn constant_args(rmod: &RadecoModule, fn_ref: u64) -> Vec<(RegisterId, u64)> {
+    let node = match rmod.callgraph_node(fn_ref) {
+        Some(node) if rmod.callers_known(fn_ref) => node,
+        _ => return Vec::new(),
+    };
+    let mut common: Option<Vec<(RegisterId, u64)>> = None;
+    for edge in rmod.callgraph.edges_directed(node, Direction::Incoming) {
+        let caller = match rmod.function(rmod.callgraph[edge.source()]) {
+            Some(caller) => caller,
+            None => return Vec::new(),
+        };
+        let ssa = caller.ssa();
+        let csite_node = edge.weight().csite_node;
+        if ssa.opcode(csite_node) != Some(MOpcode::OpCall) {
+            return Vec::new();
+        }
+        let info = match utils::call_info(csite_node, ssa) {
+            Some(info) => info,
+            None => return Vec::new(),
+        };
+        let args = info
+            .register_args
+            .iter()
+            .filter_map(|(id, &arg)| Some((id, ssa.constant(arg)?)))
+            .collect::<Vec<_>>();
+        common = Some(match common {
+            Some(common) => common.into_iter().filter(|a| args.contains(a)).collect(),
+            None => args,
+        });
+    }
+    common.unwrap_or_default()
+}
+
+// Returns the registers `rfn` always leaves set to a constant.
+fn constant_returns(rfn: &RadecoFunction) -> Vec<(RegisterId, u64)> {
+    let ssa = rfn.ssa();
+    match ssa.exit_node().and_then(|n| ssa.registers_in(n)) {
+        Some(exit_state) => utils::register_state_info(exit_state, ssa)
+            .iter()
+            .filter_map(|(id, &(ret, _))| Some((id, ssa.constant(ret)?)))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+// Replaces the uses of `node` by the constant `value`, returns the node of the constant.
+fn replace_with_const(
+    ssa: &mut SSAStorage,
+    node: LValueRef,
+    vt: ValueInfo,
+    value: u64,
+) -> Option<LValueRef> {
+    match ssa.insert_const(value, vt.width().get_width()) {
+        Ok(const_node) => {
+            ssa.replace_uses(node, const_node);
+            Some(const_node)
+        }
+        Err(_e) => {
+            radeco_err!("Cannot insert new constants: {}", _e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::{all, FuncAnalyzer, ModuleAnalyzer};
+    use crate::analysis::interproc::interproc::InterProcAnalyzer;
+    use crate::analysis::sccp::SCCP;
+    use crate::frontend::radeco_containers::CallContextInfo;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::borrow::Cow;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CALLER_TXT: &str = "\
+; radeco-ir v1
+define-fun main(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rax;
+        %2: $Unknown64 = $rdi;
+        %3: $Mem = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] (%4: $Unknown64 = $rax, %5: $Mem = $mem) = CALL #x2000($rdi=#x0, $mem=%3);
+        [@0x001004.0001] (%6: $Unknown64 = $rax, %7: $Mem = $mem) = CALL #x2000($rdi=#x0, $mem=%5);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %6;
+        $rdi = %2;
+        $mem = %7;
+}
+";
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CALLEE_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.check(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rax;
+        %2: $Unknown64 = $rdi;
+        %3: $Mem = $mem;
+    bb_0x002000.0000(sz 0x4):
+        [@0x002000.0001] %4: $Unknown1 = %2 == #x0;
+        JMP IF %4 0x002004.0000 ELSE 0x002008.0000
+    bb_0x002004.0000(sz 0x4):
+        JMP 0x00200C.0000
+    bb_0x002008.0000(sz 0x4):
+        [@0x002008.0001] %5: $Unknown64 = %2 + #x1;
+        JMP 0x00200C.0000
+    bb_0x00200C.0000(sz 0x1):
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = #x1;
+        $rdi = %2;
+        $mem = %3;
+}
+";
+
+    // Adds the function `name` at `addr`, along with its node in the call graph.
+    fn add_function(rmod: &mut RadecoModule, addr: u64, name: &str, il: &str) {
+        let mut rfn = RadecoFunction::new();
+        rfn.name = Cow::from(name.to_owned());
+        rfn.offset = addr;
+        *rfn.ssa_mut() = ir_reader::parse_il(il, REGISTER_FILE.clone());
+        rmod.callgraph.add_node(addr);
+        rmod.functions.insert(addr, rfn);
+    }
+
+    fn calls(ssa: &SSAStorage) -> Vec<LValueRef> {
+        ssa.values()
+            .into_iter()
+            .filter(|&v| ssa.opcode(v) == Some(MOpcode::OpCall))
+            .collect()
+    }
+
+    #[test]
+    fn constant_argument_folds_branch() {
+        let mut rmod = RadecoModule::new("consts".to_owned());
+        add_function(&mut rmod, 0x1000, "main", CALLER_TXT);
+        add_function(&mut rmod, 0x2000, "sym.check", CALLEE_TXT);
+        let caller = rmod.callgraph_node(0x1000).unwrap();
+        let callee = rmod.callgraph_node(0x2000).unwrap();
+        let call_nodes = calls(rmod.functions[&0x1000].ssa());
+        for call in call_nodes {
+            let mut cctx = CallContextInfo::default();
+            cctx.csite_node = call;
+            rmod.callgraph.add_edge(caller, callee, cctx);
+        }
+
+        let mut analyzer: InterProcAnalyzer<ConstPropagation> = InterProcAnalyzer::new();
+        analyzer.analyze(&mut rmod, Some(all));
+
+        // rdi is 0 in `sym.check`, the branch to 0x2008 is never taken.
+        let check = rmod.functions.get_mut(&0x2000).unwrap();
+        SCCP::new().analyze(check, Some(all));
+        let ssa = check.ssa();
+        let blocks = ssa
+            .blocks()
+            .into_iter()
+            .filter_map(|b| ssa.starting_address(b))
+            .map(|a| a.address)
+            .collect::<Vec<_>>();
+        assert!(blocks.contains(&0x2004) && !blocks.contains(&0x2008));
+
+        // `sym.check` always returns 1 in rax.
+        let ssa = rmod.functions[&0x1000].ssa();
+        let rax = ssa.regfile.register_id_by_name("rax").unwrap();
+        for call in calls(ssa) {
+            let rets = utils::call_rets(call, ssa);
+            assert!(ssa.uses_of(rets[rax].0).is_empty());
+        }
+        let exit_state = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        let rax_at_exit = utils::register_state_info(exit_state, ssa)[rax].0;
+        assert_eq!(ssa.constant(rax_at_exit), Some(1));
+    }
+}
@@ -51,11 +51,7 @@ impl<'a> CallFixer<'a> {
 
     // Make a ROUNDED analyze for the RadecoModule.
     pub fn rounded_analysis(&mut self) {
-        let functions = self.rmod.functions.clone();
-        let matched_func_vec: Vec<u64> = functions
-            .iter()
-            .map(|(fn_addr, _)| fn_addr.clone())
-            .collect();
+        let matched_func_vec = self.rmod.function_offsets();
         // Do the first analysis.
         radeco_trace!("CallFixer|Do the first analysis.");
         for fn_addr in &matched_func_vec {
@@ -506,11 +502,7 @@ mod test {
     #[ignore]
     fn analysis_test() {
         let mut rmod = RadecoModule::new("./test_files/ct1_sccp_ex/ct1_sccp_ex".to_string());
-        let functions = rmod.functions.clone();
-        let matched_func_vec: Vec<u64> = functions
-            .iter()
-            .map(|(fn_addr, _)| fn_addr.clone())
-            .collect();
+        let matched_func_vec = rmod.function_offsets();
 
         // Analyze preserved for all functions.
         {
@@ -525,11 +517,7 @@ mod test {
     #[ignore]
     fn fix_test() {
         let mut rmod = RadecoModule::new("./test_files/ct1_sccp_ex/ct1_sccp_ex".to_string());
-        let functions = rmod.functions.clone();
-        let matched_func_vec: Vec<u64> = functions
-            .iter()
-            .map(|(fn_addr, _)| fn_addr.clone())
-            .collect();
+        let matched_func_vec = rmod.function_offsets();
 
         // Analyze preserved for all functions.
         {
@@ -547,11 +535,7 @@ mod test {
     #[ignore]
     fn reanalysis_test() {
         let mut rmod = RadecoModule::new("./test_files/ct1_sccp_ex/ct1_sccp_ex".to_string());
-        let functions = rmod.functions.clone();
-        let matched_func_vec: Vec<u64> = functions
-            .iter()
-            .map(|(fn_addr, _)| fn_addr.clone())
-            .collect();
+        let matched_func_vec = rmod.function_offsets();
 
         // Analyze preserved for all functions.
         {
@@ -6,6 +6,8 @@ use crate::analysis::analyzer::{
 use crate::analysis::interproc::transfer::InterProcAnalysis;
 use crate::frontend::radeco_containers::RadecoModule;
 
+use petgraph::Direction;
+
 use std::any::Any;
 use std::collections::HashSet;
 use std::fmt::Debug;
@@ -41,18 +43,24 @@ where
     }
 
     fn analyze_function(&mut self, rmod: &mut RadecoModule, func_addr: u64) {
-        // If the current function has already been analyzed, return.
-        if self.analyzed.contains(&func_addr) {
+        // If the current function has already been analyzed, return. It is inserted into the
+        // analyzed set right away, so that recursive calls do not analyze it again.
+        if !self.analyzed.insert(func_addr) {
             return;
         }
         // Analyze all children of the present node in call graph.
         let callees = rmod
-            .function(func_addr)
-            .map(|rfn| rmod.callees_of(rfn))
+            .callgraph_node(func_addr)
+            .map(|node| {
+                rmod.callgraph
+                    .neighbors_directed(node, Direction::Outgoing)
+                    .map(|callee| rmod.callgraph[callee])
+                    .collect::<Vec<_>>()
+            })
             .unwrap_or(Vec::new());
 
-        for (call, _) in callees {
-            self.analyze_function(rmod, call);
+        for callee in callees {
+            self.analyze_function(rmod, callee);
         }
 
         // Propagate changes and remove deadcode based on the analysis information from
@@ -64,9 +72,6 @@ where
             // Analyze transfer function for the current function.
             self.analyzer.transfer(rmod, func_addr);
         }
-
-        // Insert the current function into analyzed set.
-        self.analyzed.insert(func_addr);
     }
 }
 
@@ -91,9 +96,8 @@ where
         rmod: &mut RadecoModule,
         _policy: Option<F>,
     ) -> Option<Box<dyn AnalyzerResult>> {
-        let fs = rmod.functions.clone();
-        for (_, f) in fs {
-            self.analyze_function(rmod, f.offset);
+        for offset in rmod.function_offsets() {
+            self.analyze_function(rmod, offset);
         }
 
         None
@@ -106,11 +110,58 @@ mod test {
     use crate::analysis::analyzer::{all, FuncAnalyzer};
     use crate::analysis::dce::DCE;
     use crate::analysis::interproc::summary;
-    use crate::frontend::radeco_containers::ProjectLoader;
+    use crate::frontend::radeco_containers::{CallContextInfo, ProjectLoader, RadecoFunction};
     use crate::frontend::radeco_source::FileSource;
     use crate::middle::ir_writer;
     use std::rc::Rc;
 
+    // Records the order in which the functions are transferred.
+    #[derive(Debug, Default)]
+    struct Order {
+        transferred: Vec<u64>,
+    }
+
+    impl InterProcAnalysis for Order {
+        fn new() -> Order {
+            Default::default()
+        }
+
+        fn transfer(&mut self, _: &mut RadecoModule, fn_ref: u64) {
+            self.transferred.push(fn_ref);
+        }
+
+        fn propagate(&mut self, _: &mut RadecoModule, _: u64) {}
+    }
+
+    #[test]
+    fn callees_analyzed_first_and_once() {
+        let mut rmod = RadecoModule::new("order".to_owned());
+        for &addr in &[0x1000, 0x2000, 0x3000] {
+            let mut rfn = RadecoFunction::new();
+            rfn.offset = addr;
+            rmod.functions.insert(addr, rfn);
+        }
+        // main calls a, a and b call each other, b calls itself and the import at 0x4000.
+        let nodes = [0x1000, 0x2000, 0x3000, 0x4000]
+            .iter()
+            .map(|&addr| rmod.callgraph.add_node(addr))
+            .collect::<Vec<_>>();
+        for &(caller, callee) in &[(0, 1), (1, 2), (2, 1), (2, 2), (2, 3)] {
+            let cctx = CallContextInfo::default();
+            rmod.callgraph.add_edge(nodes[caller], nodes[callee], cctx);
+        }
+
+        let mut analyzer: InterProcAnalyzer<Order> = InterProcAnalyzer::new();
+        analyzer.analyze(&mut rmod, Some(all));
+
+        let order = &analyzer.analyzer.transferred;
+        let position = |addr| order.iter().position(|&a| a == addr).unwrap();
+        assert_eq!(order.len(), 4);
+        assert!(position(0x4000) < position(0x3000));
+        assert!(position(0x3000) < position(0x2000));
+        assert!(position(0x2000) < position(0x1000));
+    }
+
     #[test]
     #[ignore]
     fn ipa_t1() {
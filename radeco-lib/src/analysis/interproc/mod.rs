@@ -5,8 +5,10 @@
 // This file may not be copied, modified, or distributed
 // except according to those terms.
 
+pub mod constprop;
 mod digstack;
 pub mod fixcall;
 pub mod interproc;
+pub mod noreturn;
 pub mod summary;
 pub mod transfer;
@@ -0,0 +1,213 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Propagates no-return through the call graph.
+//!
+//! A function every path of which ends in a call to a function which never returns does not
+//! return either. Starting from the imports known to never return, like `exit` or `abort` (see
+//! `imports::NORETURN_FUNCTIONS`), the functions found to never return are propagated to their
+//! callers until no more are found. The SSA of a caller is constructed again once one of its
+//! callees is found to never return, so that the code following the calls to it is pruned.
+//!
+//! Only direct calls are accounted for. The callers are constructed again from their
+//! instructions with the register file of their SSA, without assuming calling conventions.
+
+use petgraph::visit::EdgeRef;
+use petgraph::Direction;
+
+use crate::analysis::interproc::transfer::InterProcAnalysis;
+use crate::frontend::llanalyzer;
+use crate::frontend::radeco_containers::{RadecoFunction, RadecoModule};
+use crate::frontend::ssaconstructor::{self, SSAConstruct};
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::SSA;
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use std::collections::HashSet;
+
+#[derive(Clone, Debug, Default)]
+pub struct NoReturn {}
+
+impl InterProcAnalysis for NoReturn {
+    fn new() -> NoReturn {
+        Default::default()
+    }
+
+    // Mark the function as no-return if it only exits through calls to no-return functions, and
+    // go back to its callers if so.
+    fn transfer(&mut self, rmod: &mut RadecoModule, fn_ref: u64) {
+        let noreturn = rmod.noreturn_addresses();
+        let rfn = match rmod.function_mut(fn_ref) {
+            Some(rfn) if !rfn.is_noreturn() => rfn,
+            _ => return,
+        };
+        let sites = noreturn_calls(rfn, &noreturn);
+        if sites.is_empty() || !ssaconstructor::only_exits_through(rfn.ssa(), &sites) {
+            return;
+        }
+        radeco_trace!("NoReturn|{:#x}", fn_ref);
+        rfn.set_noreturn(true);
+
+        // The callers were possibly analyzed before, e.g. when the function is part of a cycle
+        // of the call graph.
+        let callers = match rmod.callgraph_node(fn_ref) {
+            Some(node) => rmod
+                .callgraph
+                .edges_directed(node, Direction::Incoming)
+                .map(|e| rmod.callgraph[e.source()])
+                .collect::<HashSet<_>>(),
+            None => return,
+        };
+        for caller in callers {
+            self.propagate(rmod, caller);
+            self.transfer(rmod, caller);
+        }
+    }
+
+    // Prune the code following the calls to no-return functions.
+    fn propagate(&mut self, rmod: &mut RadecoModule, fn_ref: u64) {
+        let noreturn = rmod.noreturn_addresses();
+        let node = rmod.callgraph_node(fn_ref);
+        let rfn = match rmod.functions.get_mut(&fn_ref) {
+            Some(rfn) => rfn,
+            None => return,
+        };
+        let sites = noreturn_calls(rfn, &noreturn);
+        if !sites.iter().any(|&site| falls_through(rfn.ssa(), site)) {
+            return;
+        }
+        radeco_trace!("NoReturn|construct {:#x}", fn_ref);
+        let regfile = rfn.ssa().regfile.clone();
+        *rfn.ssa_mut() = SSAStorage::new();
+        SSAConstruct::<SSAStorage>::construct_with_regfile(rfn, regfile, false, true, &noreturn);
+        if let Some(node) = node {
+            llanalyzer::update_call_sites(&mut rmod.callgraph, node, rfn);
+        }
+    }
+}
+
+// Returns the call sites of `rfn` calling one of the functions at `noreturn`.
+fn noreturn_calls(rfn: &RadecoFunction, noreturn: &HashSet<u64>) -> Vec<u64> {
+    rfn.direct_calls()
+        .into_iter()
+        .filter(|&(_, target)| noreturn.contains(&target))
+        .map(|(site, _)| site)
+        .collect()
+}
+
+// Whether the code following the call at `site` is still part of `ssa`, i.e. the block of the
+// call goes on after it or does not flow to the exit.
+fn falls_through(ssa: &SSAStorage, site: u64) -> bool {
+    let call = ssa.values().into_iter().find(|&v| {
+        ssa.opcode(v) == Some(MOpcode::OpCall)
+            && ssa.address(v).map_or(false, |a| a.address == site)
+    });
+    let block = match call.and_then(|call| ssa.block_for(call)) {
+        Some(block) => block,
+        None => return false,
+    };
+    let after_call = ssa
+        .exprs_in(block)
+        .into_iter()
+        .any(|e| ssa.address(e).map_or(false, |a| a.address > site));
+    let exit = ssa.exit_node();
+    after_call || ssa.succs_of(block).into_iter().any(|s| Some(s) != exit)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::{all, ModuleAnalyzer};
+    use crate::analysis::interproc::interproc::InterProcAnalyzer;
+    use crate::frontend::imports::ImportInfo;
+    use crate::frontend::radeco_containers::CallContextInfo;
+    use crate::middle::ssa::verifier;
+    use r2api::structs::LRegInfo;
+    use serde_json;
+    use std::borrow::Cow;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    // main: call wrapper; add ebx, eax; ret
+    const MAIN_OPS: &str = r#"[
+        {"offset":4096,"size":5,"esil":"8192,rip,8,rsp,-=,rsp,=[],rip,=","type":"call"},
+        {"offset":4101,"size":2,"esil":"eax,ebx,+=","type":"add"},
+        {"offset":4103,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"}
+    ]"#;
+
+    // wrapper: test edi, edi; je 0x200b; mov edi, 1; call exit; mov edi, 2; call exit; ret
+    const WRAPPER_OPS: &str = r#"[
+        {"offset":8192,"size":2,"esil":"edi,edi,&,$z,zf,=","type":"test"},
+        {"offset":8194,"size":2,"esil":"zf,?{,8203,rip,=,}","type":"cjmp"},
+        {"offset":8196,"size":5,"esil":"1,edi,=","type":"mov"},
+        {"offset":8201,"size":2,"esil":"12288,rip,8,rsp,-=,rsp,=[],rip,=","type":"call"},
+        {"offset":8203,"size":5,"esil":"2,edi,=","type":"mov"},
+        {"offset":8208,"size":5,"esil":"12288,rip,8,rsp,-=,rsp,=[],rip,=","type":"call"},
+        {"offset":8213,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"}
+    ]"#;
+
+    // Adds the function `name` at `addr`, constructed without knowing any no-return function.
+    fn add_function(rmod: &mut RadecoModule, ri: &LRegInfo, addr: u64, name: &str, ops: &str) {
+        let mut rfn = RadecoFunction::new();
+        rfn.name = Cow::from(name.to_owned());
+        rfn.offset = addr;
+        rfn.instructions = serde_json::from_str(ops).unwrap();
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, ri, false, true);
+        rmod.callgraph.add_node(addr);
+        rmod.functions.insert(addr, rfn);
+    }
+
+    fn add_call(rmod: &mut RadecoModule, caller: u64, callee: u64, csite: u64) {
+        let mut cctx = CallContextInfo::default();
+        cctx.csite = csite;
+        let caller_node = rmod.callgraph_node(caller).unwrap();
+        let callee_node = rmod.callgraph_node(callee).unwrap();
+        rmod.callgraph.add_edge(caller_node, callee_node, cctx);
+        llanalyzer::update_call_sites(&mut rmod.callgraph, caller_node, &rmod.functions[&caller]);
+    }
+
+    #[test]
+    fn wrapper_of_exit_never_returns() {
+        let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+        let reg_profile: LRegInfo = serde_json::from_str(&*s).unwrap();
+        let mut rmod = RadecoModule::new("noreturn".to_owned());
+        add_function(&mut rmod, &reg_profile, 0x1000, "main", MAIN_OPS);
+        add_function(&mut rmod, &reg_profile, 0x2000, "sym.wrapper", WRAPPER_OPS);
+        rmod.imports.insert(
+            0x3000,
+            ImportInfo::new_stub(0x3000, Cow::from("sym.imp.exit")),
+        );
+        rmod.callgraph.add_node(0x3000);
+        add_call(&mut rmod, 0x1000, 0x2000, 0x1000);
+        add_call(&mut rmod, 0x2000, 0x3000, 0x2009);
+        add_call(&mut rmod, 0x2000, 0x3000, 0x2010);
+        assert!(falls_through(rmod.functions[&0x1000].ssa(), 0x1000));
+
+        let mut analyzer: InterProcAnalyzer<NoReturn> = InterProcAnalyzer::new();
+        analyzer.analyze(&mut rmod, Some(all));
+
+        // Both paths of `sym.wrapper` call `exit`.
+        assert!(rmod.functions[&0x2000].is_noreturn());
+        // So nothing after the call to it in `main` is reachable, and `main` never returns.
+        let main = &rmod.functions[&0x1000];
+        let ssa = main.ssa();
+        assert!(main.is_noreturn());
+        assert!(!falls_through(ssa, 0x1000));
+        assert!(ssa
+            .blocks()
+            .into_iter()
+            .flat_map(|b| ssa.exprs_in(b))
+            .all(|v| ssa.address(v).map_or(true, |a| a.address < 0x1005)));
+        assert!(verifier::verify(ssa).is_ok());
+
+        // The call graph refers to the call in the SSA constructed again.
+        let node = rmod.callgraph_node(0x1000).unwrap();
+        let edge = rmod.callgraph.edges(node).next().unwrap();
+        assert_eq!(ssa.opcode(edge.weight().csite_node), Some(MOpcode::OpCall));
+    }
+}
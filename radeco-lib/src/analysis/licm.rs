@@ -0,0 +1,435 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Loop-invariant code motion
+//!
+//! Moves the computations of a natural loop whose operands are all defined outside of it to the
+//! preheader of the loop, i.e. the single block the loop is entered from. When this block has
+//! other successors, an empty preheader is inserted by splitting the edge entering the loop.
+//!
+//! Stores and calls are never moved. Loads are only moved when the Memory SSA proves that none of
+//! the memory they may read is defined within the loop. As they may trap, loads, divisions and
+//! remainders are only moved out of the blocks executed at every iteration, i.e. those dominating
+//! all the exits of the loop.
+
+use crate::middle::cfg_cleanup;
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::graph_traits::Graph;
+use crate::middle::ssa::memoryssa::MemorySSA;
+use crate::middle::ssa::ssa_traits::{SSAMod, SSA};
+use crate::middle::ssa::ssastorage::{SSAStorage, Walker};
+
+use petgraph::graph::NodeIndex;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Default)]
+pub struct LICM {
+    // Blocks of the memory definitions every load may read, according to the Memory SSA.
+    reaching_blocks: HashMap<NodeIndex, Vec<NodeIndex>>,
+}
+
+impl LICM {
+    /// Creates a `LICM` which does not move any memory access.
+    pub fn new() -> LICM {
+        LICM::default()
+    }
+
+    /// Creates a `LICM` which also moves the loads `mssa` proves invariant. `mssa` must have been
+    /// run on the function to be transformed.
+    pub fn with_memory_ssa(mssa: &MemorySSA<Walker, SSAStorage>) -> LICM {
+        let reaching_blocks = mssa
+            .associated_nodes
+            .values()
+            .filter_map(|&access| mssa.reaching_blocks(access).map(|blocks| (access, blocks)))
+            .collect();
+        LICM { reaching_blocks }
+    }
+
+    /// Moves the loop-invariant computations of `ssa` to the preheaders of their loops, inner
+    /// loops first. Returns the number of moved values.
+    pub fn run(&self, ssa: &mut SSAStorage) -> usize {
+        let mut loops = natural_loops(ssa);
+        let mut hoisted = 0;
+        for i in 0..loops.len() {
+            let header = loops[i].0;
+            let (preheader, inserted) = match preheader_of(ssa, header, &loops[i].1) {
+                Some(preheader) => preheader,
+                None => {
+                    radeco_trace!("licm|no preheader for the loop at {:?}", header);
+                    continue;
+                }
+            };
+            if inserted {
+                // The inserted block belongs to the outer loops the loop is entered from.
+                let pred = ssa.preds_of(preheader)[0];
+                for (_, body) in loops[i + 1..].iter_mut() {
+                    if body.contains(&pred) {
+                        body.insert(preheader);
+                    }
+                }
+            }
+            hoisted += self.hoist(ssa, header, &loops[i].1, preheader);
+        }
+        hoisted
+    }
+
+    // Moves the invariant computations of the loop `body` to the end of `preheader`. Returns the
+    // number of moved values.
+    fn hoist(
+        &self,
+        ssa: &mut SSAStorage,
+        header: NodeIndex,
+        body: &HashSet<NodeIndex>,
+        preheader: NodeIndex,
+    ) -> usize {
+        // Calls are not part of the Memory SSA, any of them may clobber what a load reads.
+        let calls = body
+            .iter()
+            .flat_map(|&b| ssa.exprs_in(b))
+            .any(|e| match ssa.opcode(e) {
+                Some(MOpcode::OpCall) => true,
+                _ => false,
+            });
+        let mut blocks = body.iter().cloned().collect::<Vec<_>>();
+        blocks.sort_by_key(|&b| ssa.starting_address(b));
+        // Blocks of the loop branching out of it. A loop without any is only executed as a whole
+        // up to its header.
+        let exits = blocks
+            .iter()
+            .cloned()
+            .filter(|&b| ssa.succs_of(b).into_iter().any(|s| !body.contains(&s)))
+            .collect::<Vec<_>>();
+        let idom = ssa.immediate_dominators();
+
+        let mut hoisted = 0;
+        loop {
+            let mut changed = false;
+            for &block in &blocks {
+                let executed = if exits.is_empty() {
+                    block == header
+                } else {
+                    exits
+                        .iter()
+                        .all(|&exit| cfg_cleanup::dominates(&idom, block, exit))
+                };
+                for expr in ssa.exprs_in(block) {
+                    let outside = |v| ssa.block_for(v).map_or(true, |b| !body.contains(&b));
+                    let memory = match ssa.opcode(expr) {
+                        Some(MOpcode::OpLoad)
+                            if executed && !calls && self.is_invariant_load(body, expr) =>
+                        {
+                            let mut operands = ssa.sparse_operands_of(expr);
+                            operands.sort();
+                            let memory = match operands.first() {
+                                Some(&(0, memory)) => memory,
+                                _ => continue,
+                            };
+                            if !operands[1..].iter().all(|&(_, op)| outside(op)) {
+                                continue;
+                            }
+                            if outside(memory) {
+                                None
+                            } else {
+                                match entry_value(ssa, header, body, memory) {
+                                    Some(entry) => Some((memory, entry)),
+                                    None => continue,
+                                }
+                            }
+                        }
+                        Some(MOpcode::OpLoad)
+                        | Some(MOpcode::OpStore)
+                        | Some(MOpcode::OpCall)
+                        | None => continue,
+                        Some(MOpcode::OpDiv) | Some(MOpcode::OpMod) if !executed => continue,
+                        Some(_) => {
+                            let is_memory = ssa
+                                .node_data(expr)
+                                .ok()
+                                .map_or(true, |nd| nd.vt.is_memory());
+                            if is_memory || !ssa.operands_of(expr).into_iter().all(outside) {
+                                continue;
+                            }
+                            None
+                        }
+                    };
+
+                    // The memory entering the loop is the one the load reads in the preheader.
+                    if let Some((memory, entry)) = memory {
+                        ssa.op_unuse(expr, memory);
+                        ssa.op_use(expr, 0, entry);
+                    }
+                    let addr = ssa
+                        .exprs_in(preheader)
+                        .last()
+                        .and_then(|&e| ssa.address(e))
+                        .or_else(|| ssa.starting_address(preheader))
+                        .unwrap_or_default()
+                        .next();
+                    radeco_trace!("licm_hoist|{:?} to {:?} @ {}", expr, preheader, addr);
+                    ssa.remove_edges_between(expr, block);
+                    ssa.insert_into_block(expr, preheader, addr);
+                    hoisted += 1;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        hoisted
+    }
+
+    // Returns `true` if every memory definition `load` may read is outside of the loop `body`.
+    fn is_invariant_load(&self, body: &HashSet<NodeIndex>, load: NodeIndex) -> bool {
+        match self.reaching_blocks.get(&load) {
+            Some(blocks) => !blocks.is_empty() && blocks.iter().all(|b| !body.contains(b)),
+            None => false,
+        }
+    }
+}
+
+// Returns the natural loops of `ssa` as their header along with the blocks of their body, the
+// smaller ones first. Loops sharing the same header are merged together.
+fn natural_loops(ssa: &SSAStorage) -> Vec<(NodeIndex, HashSet<NodeIndex>)> {
+    let idom = ssa.immediate_dominators();
+    let mut loops: HashMap<NodeIndex, HashSet<NodeIndex>> = HashMap::new();
+    for &latch in idom.keys() {
+        for header in ssa.succs_of(latch) {
+            if !cfg_cleanup::dominates(&idom, header, latch) {
+                continue;
+            }
+            let body = loops.entry(header).or_insert_with(HashSet::new);
+            body.insert(header);
+            let mut worklist = vec![latch];
+            while let Some(block) = worklist.pop() {
+                if body.insert(block) {
+                    worklist.extend(
+                        ssa.preds_of(block)
+                            .into_iter()
+                            .filter(|b| idom.contains_key(b)),
+                    );
+                }
+            }
+        }
+    }
+
+    let mut loops = loops.into_iter().collect::<Vec<_>>();
+    loops.sort_by_key(|&(header, ref body)| (body.len(), ssa.starting_address(header)));
+    loops
+}
+
+// Returns the preheader of the loop `body` starting at `header`, and whether it was inserted.
+//
+// A loop entered from several blocks gets none, as the operands of the phis of its header are not
+// tied to the blocks they come from.
+fn preheader_of(
+    ssa: &mut SSAStorage,
+    header: NodeIndex,
+    body: &HashSet<NodeIndex>,
+) -> Option<(NodeIndex, bool)> {
+    let entering = ssa
+        .incoming_edges(header)
+        .into_iter()
+        .filter_map(|(edge, _)| ssa.edge_info(edge).map(|info| (edge, info.source)))
+        .filter(|&(_, pred)| !body.contains(&pred))
+        .collect::<Vec<_>>();
+    if entering.len() != 1 {
+        return None;
+    }
+    let (edge, pred) = entering[0];
+    if Some(pred) != ssa.entry_node() && ssa.succs_of(pred).len() == 1 {
+        Some((pred, false))
+    } else {
+        cfg_cleanup::split_edge(ssa, edge).map(|split| (split, true))
+    }
+}
+
+// Returns the value `phi` of the loop `header` takes when entering the loop `body`, i.e. its only
+// operand defined outside of the loop.
+fn entry_value(
+    ssa: &SSAStorage,
+    header: NodeIndex,
+    body: &HashSet<NodeIndex>,
+    phi: NodeIndex,
+) -> Option<NodeIndex> {
+    if !ssa.is_phi(phi) || ssa.block_for(phi) != Some(header) {
+        return None;
+    }
+    let entering = ssa
+        .operands_of(phi)
+        .into_iter()
+        .filter(|&op| ssa.block_for(op).map_or(true, |b| !body.contains(&b)))
+        .collect::<Vec<_>>();
+    if entering.len() == 1 {
+        Some(entering[0])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ir::MAddress;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use crate::middle::ssa::verifier;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    // `rsi * 4` does not depend on the counter of the loop at 0x1004, which is skipped when `rdi`
+    // is zero.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const LOOP_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.loop(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Mem = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %4: $Unknown1 = %1 == #x0;
+        JMP IF %4 0x001010.0000 ELSE 0x001004.0000
+    bb_0x001004.0000(sz 0xc):
+        %5: $Unknown64 = Phi(%1, %8);
+        [@0x001004.0001] %6: $Unknown64 = %2 * #x4;
+        [@0x001004.0002] %7: $Unknown64 = Load(%3, %6);
+        [@0x001008.0001] %8: $Unknown64 = %5 - %6;
+        [@0x00100c.0001] %9: $Unknown1 = %8 == %7;
+        JMP IF %9 0x001010.0000 ELSE 0x001004.0000
+    bb_0x001010.0000(sz 0x1):
+        %10: $Unknown64 = Phi(%1, %8);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %10;
+        $rsi = %2;
+        $mem = %3;
+}
+";
+
+    // `rsi / rdx` is only computed at the iterations of the loop at 0x1004 where the counter is
+    // equal to `rdx`, and may trap when `rdx` is zero.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const GUARDED_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.guarded(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown64 = $rdx;
+        %4: $Mem = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %5: $Unknown1 = %1 == #x0;
+        JMP IF %5 0x001018.0000 ELSE 0x001004.0000
+    bb_0x001004.0000(sz 0x4):
+        %6: $Unknown64 = Phi(%1, %13);
+        %7: $Unknown64 = Phi(%2, %12);
+        [@0x001004.0001] %8: $Unknown64 = %2 * #x4;
+        [@0x001004.0002] %9: $Unknown1 = %6 == %8;
+        JMP IF %9 0x001018.0000 ELSE 0x001008.0000
+    bb_0x001008.0000(sz 0x4):
+        [@0x001008.0001] %10: $Unknown1 = %6 == %3;
+        JMP IF %10 0x00100c.0000 ELSE 0x001010.0000
+    bb_0x00100c.0000(sz 0x4):
+        [@0x00100c.0001] %11: $Unknown64 = %2 / %3;
+        JMP 0x001010.0000
+    bb_0x001010.0000(sz 0x8):
+        %12: $Unknown64 = Phi(%7, %11);
+        [@0x001010.0001] %13: $Unknown64 = %6 - #x1;
+        JMP 0x001004.0000
+    bb_0x001018.0000(sz 0x1):
+        %14: $Unknown64 = Phi(%1, %7);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %14;
+        $rsi = %2;
+        $rdx = %3;
+        $mem = %4;
+}
+";
+
+    fn block_at(ssa: &SSAStorage, addr: u64) -> NodeIndex {
+        ssa.blocks()
+            .into_iter()
+            .find(|&b| ssa.starting_address(b) == Some(MAddress::new(addr, 0)))
+            .unwrap()
+    }
+
+    #[test]
+    fn hoist_invariant_arithmetic() {
+        let mut ssa = ir_reader::parse_il(LOOP_TXT, REGISTER_FILE.clone());
+        let (head, body) = (block_at(&ssa, 0x1000), block_at(&ssa, 0x1004));
+        let exprs = ssa.exprs_in(body);
+        let (mul, load) = (exprs[0], exprs[1]);
+        let blocks = ssa.blocks().len();
+
+        assert_eq!(LICM::new().run(&mut ssa), 1);
+
+        // The loop is entered through a conditional branch, a preheader is inserted.
+        assert_eq!(ssa.blocks().len(), blocks + 1);
+        let preheader = ssa.preds_of(body).into_iter().find(|&b| b != body).unwrap();
+        assert_eq!(ssa.preds_of(preheader), vec![head]);
+        assert_eq!(ssa.exprs_in(preheader), vec![mul]);
+        assert_eq!(ssa.address(mul), Some(MAddress::new(0x1000, 3)));
+        // Without Memory SSA, the load stays in the loop.
+        assert_eq!(ssa.block_for(load), Some(body));
+        assert_eq!(ssa.exprs_in(body).len(), 3);
+        assert!(verifier::verify(&ssa).is_ok());
+
+        assert_eq!(LICM::new().run(&mut ssa), 0);
+        assert_eq!(ssa.blocks().len(), blocks + 1);
+    }
+
+    #[test]
+    fn hoist_invariant_load() {
+        let mut ssa = ir_reader::parse_il(LOOP_TXT, REGISTER_FILE.clone());
+        let body = block_at(&ssa, 0x1004);
+        let exprs = ssa.exprs_in(body);
+        let (mul, load) = (exprs[0], exprs[1]);
+        let licm = {
+            let mut mssa = MemorySSA::new(&ssa);
+            mssa.gather_variables(&vec![0x2000], &Vec::new(), &Vec::new());
+            mssa.run();
+            LICM::with_memory_ssa(&mssa)
+        };
+
+        // Nothing is stored in the loop, the load reads the same memory at every iteration.
+        assert_eq!(licm.run(&mut ssa), 2);
+        let preheader = ssa.block_for(mul).unwrap();
+        assert_ne!(preheader, body);
+        assert_eq!(ssa.exprs_in(preheader), vec![mul, load]);
+        assert!(verifier::verify(&ssa).is_ok());
+    }
+
+    #[test]
+    fn keep_conditional_division() {
+        let mut ssa = ir_reader::parse_il(GUARDED_TXT, REGISTER_FILE.clone());
+        let (header, guarded) = (block_at(&ssa, 0x1004), block_at(&ssa, 0x100c));
+        let mul = ssa.exprs_in(header)[0];
+        let div = ssa.exprs_in(guarded)[0];
+
+        // The division does not dominate the exit at the header, moving it could make it trap
+        // when the loop would not have.
+        assert_eq!(LICM::new().run(&mut ssa), 1);
+        assert_ne!(ssa.block_for(mul), Some(header));
+        assert_eq!(ssa.block_for(div), Some(guarded));
+        assert!(verifier::verify(&ssa).is_ok());
+    }
+}
@@ -0,0 +1,251 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Load forwarding
+//!
+//! Replaces an `OpLoad` with the value stored by the `OpStore` it reads from, when the Memory SSA
+//! of the function finds a single such store and both access the same address with the same
+//! width. The Memory SSA must have been generated on the function beforehand, see
+//! `memoryssa::record_clobbering_stores`.
+//!
+//! A load that may fall in a volatile (memory-mapped I/O) range may read something else than what
+//! was stored, so it is never forwarded. As in DSE, a load whose address is not a constant is
+//! assumed to possibly hit a volatile range whenever any such range is configured.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
+    PrerequisiteError, ReplaceValue,
+};
+use crate::frontend::radeco_containers::RadecoFunction;
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::{SSAMod, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use std::any::Any;
+use std::ops::Range;
+
+#[derive(Debug)]
+pub struct LoadForwarding {
+    volatile: Vec<Range<u64>>,
+}
+
+const NAME: &str = "load_forwarding";
+const REQUIRES: &[AnalyzerKind] = &[AnalyzerKind::MemorySSA];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::LoadForwarding,
+    requires: REQUIRES,
+    uses_policy: true,
+};
+
+impl LoadForwarding {
+    pub fn new() -> Self {
+        LoadForwarding::with_volatile(Vec::new())
+    }
+
+    /// Creates a `LoadForwarding` which never forwards a load that may fall in one of `volatile`.
+    pub fn with_volatile(volatile: Vec<Range<u64>>) -> Self {
+        LoadForwarding { volatile: volatile }
+    }
+
+    fn may_be_volatile(&self, ssa: &SSAStorage, addr: <SSAStorage as SSA>::ValueRef) -> bool {
+        if self.volatile.is_empty() {
+            return false;
+        }
+        match ssa.constant_value(addr) {
+            Some(a) => self.volatile.iter().any(|r| r.contains(&a)),
+            None => true,
+        }
+    }
+
+    /// Forwards the stored values to the loads of `rfn`. Fails if the Memory SSA of `rfn` was not
+    /// generated since it was last changed.
+    pub fn run<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rfn: &mut RadecoFunction,
+        mut policy: T,
+    ) -> Result<(), PrerequisiteError> {
+        rfn.requires(INFO.requires)?;
+        let forwarded = self.gather_forwarded_loads(rfn);
+        for change in forwarded {
+            match policy(Box::new(change)) {
+                Action::Apply => {
+                    radeco_trace!("load_forwarding|{:?} to {:?}", change.0, change.1);
+                    rfn.ssa_mut().replace_value(change.0, change.1);
+                }
+                Action::Skip => (),
+                Action::Abort => break,
+            }
+        }
+        // The loads are gone, the stores they read from are out of date.
+        rfn.invalidate(AnalyzerKind::MemorySSA);
+        Ok(())
+    }
+
+    // Returns `ReplaceValue(load, value)` for every load which reads `value` from a store.
+    fn gather_forwarded_loads(&self, rfn: &RadecoFunction) -> Vec<ReplaceValue> {
+        let ssa = rfn.ssa();
+        ssa.blocks()
+            .into_iter()
+            .flat_map(|b| ssa.exprs_in(b))
+            .filter(|&e| ssa.opcode(e) == Some(MOpcode::OpLoad))
+            .filter_map(|load| {
+                let store = rfn.clobbering_store(load)?;
+                if ssa.opcode(store) != Some(MOpcode::OpStore) {
+                    return None;
+                }
+                let (l_ops, s_ops) = (ssa.operands_of(load), ssa.operands_of(store));
+                if l_ops.len() != 2 || s_ops.len() != 3 || self.may_be_volatile(ssa, l_ops[1]) {
+                    return None;
+                }
+                let value = s_ops[2];
+                let same_width = width(ssa, load) == width(ssa, value);
+                if same_width && same_address(ssa, l_ops[1], s_ops[1]) {
+                    Some(ReplaceValue(load, value))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+fn same_address(
+    ssa: &SSAStorage,
+    a: <SSAStorage as SSA>::ValueRef,
+    b: <SSAStorage as SSA>::ValueRef,
+) -> bool {
+    if a == b {
+        return true;
+    }
+    match (ssa.constant_value(a), ssa.constant_value(b)) {
+        (Some(x), Some(y)) => x == y,
+        _ => false,
+    }
+}
+
+fn width(ssa: &SSAStorage, value: <SSAStorage as SSA>::ValueRef) -> Option<u16> {
+    ssa.node_data(value).ok()?.vt.width().get_width()
+}
+
+impl Analyzer for LoadForwarding {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl FuncAnalyzer for LoadForwarding {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rfn: &mut RadecoFunction,
+        policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let policy = policy.expect("A policy function must be provided");
+        if let Err(_e) = self.run(rfn, policy) {
+            radeco_err!("Cannot run {} on {}: {}", NAME, rfn.name, _e);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use crate::middle::ssa::memoryssa;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    // `%4` reads back `%1`, stored by `%3`.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const STORE_LOAD_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.store_load(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %3: $Mem = Store(%2, #x2000, %1);
+        [@0x001004.0001] %4: $Unknown64 = Load(%3, #x2000);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %4;
+        $rdi = %1;
+        $mem = %3;
+}
+";
+
+    fn loads(rfn: &RadecoFunction) -> usize {
+        let ssa = rfn.ssa();
+        ssa.blocks()
+            .into_iter()
+            .flat_map(|b| ssa.exprs_in(b))
+            .filter(|&e| ssa.opcode(e) == Some(MOpcode::OpLoad))
+            .count()
+    }
+
+    #[test]
+    fn forward_stored_value() {
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ir_reader::parse_il(STORE_LOAD_TXT, REGISTER_FILE.clone());
+        memoryssa::record_clobbering_stores(&mut rfn);
+
+        assert!(LoadForwarding::new().run(&mut rfn, all).is_ok());
+        assert_eq!(loads(&rfn), 0);
+        // The function changed, the Memory SSA has to be generated again.
+        assert!(!rfn.analyzed(AnalyzerKind::MemorySSA));
+    }
+
+    #[test]
+    fn keep_volatile_load() {
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ir_reader::parse_il(STORE_LOAD_TXT, REGISTER_FILE.clone());
+        memoryssa::record_clobbering_stores(&mut rfn);
+
+        // A device register may not read back what was written to it.
+        let mut load_forwarding = LoadForwarding::with_volatile(vec![0x2000..0x2100]);
+        assert!(load_forwarding.run(&mut rfn, all).is_ok());
+        assert_eq!(loads(&rfn), 1);
+
+        memoryssa::record_clobbering_stores(&mut rfn);
+        let mut load_forwarding = LoadForwarding::with_volatile(vec![0x3000..0x3100]);
+        assert!(load_forwarding.run(&mut rfn, all).is_ok());
+        assert_eq!(loads(&rfn), 0);
+    }
+
+    #[test]
+    fn memory_ssa_required() {
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ir_reader::parse_il(STORE_LOAD_TXT, REGISTER_FILE.clone());
+
+        let err = LoadForwarding::new().run(&mut rfn, all).unwrap_err();
+        assert_eq!(err.missing, vec![AnalyzerKind::MemorySSA]);
+        assert_eq!(
+            err.to_string(),
+            "prerequisite not met: memory_ssa must be run first"
+        );
+        assert_eq!(loads(&rfn), 1);
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Division by a constant recovery
+//!
+//! Compilers avoid dividing by a constant `d`: `x / d` is instead compiled into a multiplication
+//! by a magic number `m`, close to `2^s / d`, of which only the high bits are kept by shifting it
+//! right by `s` bits. For instance, a 32 bits `x / 3` becomes `(x * 0xaaaaaaab) >> 33`. This pass
+//! finds such sequences and turns them back into an `OpDiv` by `d`.
+//!
+//! A sequence is only recovered if it gives the quotient for every value the dividend may take,
+//! which is bounded by its width, or the one it was zero extended from.
+//!
+//! Only unsigned divisions are recovered. The signed ones correct the sign of the quotient with an
+//! arithmetic shift, which the IR has no opcode for, any more than it has for signed divisions.
+
+use crate::analysis::analyzer::{AnalyzerInfo, AnalyzerKind};
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::{SSAMod, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+type SSARef = <SSAStorage as SSA>::ValueRef;
+
+const NAME: &str = "magic_div";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::MagicDiv,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+/// Replaces the multiplications by a magic number of `ssa` with the divisions they compute.
+/// Returns the number of recovered divisions.
+pub fn recover_divisions(ssa: &mut SSAStorage) -> usize {
+    let shifts = ssa
+        .blocks()
+        .into_iter()
+        .flat_map(|b| ssa.exprs_in(b))
+        .filter(|&e| ssa.opcode(e) == Some(MOpcode::OpLsr))
+        .collect::<Vec<_>>();
+    let mut recovered = 0;
+    for shift in shifts {
+        if let Some((dividend, divisor)) = magic_division(ssa, shift) {
+            radeco_trace!("magic_div|{:?} / {}", dividend, divisor);
+            if divide(ssa, shift, dividend, divisor).is_some() {
+                recovered += 1;
+            }
+        }
+    }
+    recovered
+}
+
+// Returns the dividend and the divisor of `shift`, if it keeps the high bits of a
+// multiplication by a magic number.
+fn magic_division(ssa: &SSAStorage, shift: SSARef) -> Option<(SSARef, u64)> {
+    // Compilers may split the shift in two, e.g. to keep the high half of the product first.
+    let mut total = 0;
+    let mut node = shift;
+    while ssa.opcode(node) == Some(MOpcode::OpLsr) {
+        let operands = ssa.operands_of(node);
+        total += ssa.constant_value(*operands.get(1)?)?;
+        if total >= 64 {
+            return None;
+        }
+        node = operands[0];
+    }
+    if ssa.opcode(node) != Some(MOpcode::OpMul) {
+        return None;
+    }
+    let operands = ssa.operands_of(node);
+    if operands.len() != 2 {
+        return None;
+    }
+    let (dividend, magic) = match (
+        ssa.constant_value(operands[0]),
+        ssa.constant_value(operands[1]),
+    ) {
+        (None, Some(magic)) => (operands[0], magic),
+        (Some(magic), None) => (operands[1], magic),
+        _ => return None,
+    };
+    let bits = dividend_bits(ssa, dividend)?;
+    // The product must not wrap around, or its high bits are lost.
+    let magic_bits = 64 - magic.leading_zeros();
+    if bits + magic_bits > u32::from(width_of(ssa, node)?) {
+        return None;
+    }
+    divisor(magic, total as u32, bits).map(|d| (dividend, d))
+}
+
+// Returns `d` if `(x * magic) >> shift` is `x / d` for every `x` of `bits` bits, that is if
+// `2^shift <= magic * d <= 2^shift + 2^(shift - bits)`.
+fn divisor(magic: u64, shift: u32, bits: u32) -> Option<u64> {
+    if magic == 0 {
+        return None;
+    }
+    let magic = u128::from(magic);
+    let pow = 1u128 << shift;
+    let d = (pow + magic - 1) / magic;
+    if d < 2 {
+        return None;
+    }
+    let error = magic * d - pow;
+    if error << bits <= pow {
+        Some(d as u64)
+    } else {
+        None
+    }
+}
+
+// Number of significant bits of `node`, i.e. the width it was zero extended from, if it was.
+fn dividend_bits(ssa: &SSAStorage, node: SSARef) -> Option<u32> {
+    let node = match ssa.opcode(node) {
+        Some(MOpcode::OpZeroExt(_)) => *ssa.operands_of(node).first()?,
+        _ => node,
+    };
+    width_of(ssa, node).map(u32::from)
+}
+
+fn width_of(ssa: &SSAStorage, node: SSARef) -> Option<u16> {
+    ssa.node_data(node)
+        .ok()
+        .and_then(|nd| nd.vt.width().get_width())
+}
+
+// Replaces `shift` with the division of `dividend` by `divisor`.
+fn divide(ssa: &mut SSAStorage, shift: SSARef, dividend: SSARef, divisor: u64) -> Option<()> {
+    let vt = ssa.node_data(shift).ok()?.vt;
+    let addr = ssa.address(shift)?;
+    let blk = ssa.block_for(shift)?;
+    let divisor = ssa.insert_const(divisor, None).ok()?;
+    let div = ssa.insert_op(MOpcode::OpDiv, vt, Some(addr.address)).ok()?;
+    ssa.op_use(div, 0, dividend);
+    ssa.op_use(div, 1, divisor);
+    ssa.insert_into_block(div, blk, addr);
+    ssa.replace_value(shift, div);
+    Some(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use crate::middle::ssa::verifier;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    // mov eax, edi; mov edx, 0xaaaaaaab; imul rax, rdx; shr rax, 33
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const DIV3_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.div3(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x10):
+        [@0x001000.0001] %3: $Unknown32 = Narrow32(%1);
+        [@0x001000.0002] %4: $Unknown64 = ZeroExt64(%3);
+        [@0x001007.0001] %5: $Unknown64 = %4 * #xaaaaaaab;
+        [@0x00100B.0001] %6: $Unknown64 = %5 >> #x21;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %6;
+        $mem = %2;
+}
+";
+
+    fn division(ssa: &SSAStorage) -> SSARef {
+        ssa.values()
+            .into_iter()
+            .find(|&v| ssa.opcode(v) == Some(MOpcode::OpDiv))
+            .unwrap()
+    }
+
+    #[test]
+    fn unsigned_div_by_3() {
+        let mut ssa = ir_reader::parse_il(DIV3_TXT, REGISTER_FILE.clone());
+        assert_eq!(recover_divisions(&mut ssa), 1);
+        assert!(verifier::verify(&ssa).is_ok());
+
+        let div = division(&ssa);
+        let operands = ssa.operands_of(div);
+        assert_eq!(ssa.opcode(operands[0]), Some(MOpcode::OpZeroExt(64)));
+        assert_eq!(ssa.constant_value(operands[1]), Some(3));
+        assert!(ssa
+            .values()
+            .into_iter()
+            .all(|v| ssa.opcode(v) != Some(MOpcode::OpLsr)));
+    }
+
+    #[test]
+    fn magic_numbers() {
+        // x / 3 on 32 bits, x / 7 on 31 bits and x / 10 on 16 bits.
+        assert_eq!(divisor(0xaaaaaaab, 33, 32), Some(3));
+        assert_eq!(divisor(0x92492493, 34, 31), Some(7));
+        assert_eq!(divisor(0xcccd, 19, 16), Some(10));
+        // Too coarse to divide every 32 bits value.
+        assert_eq!(divisor(0xaaab, 17, 32), None);
+    }
+}
@@ -55,7 +55,7 @@ fn visit_expr(ssa: &mut SSAStorage, expr: NodeIndex, n: NodeIndex, mask: NodeInd
     let blk = ssa.block_for(expr)?;
     match op {
         MOpcode::OpMov => {
-            let new_op = ssa.insert_op(op, vt, Some(addr.address))?;
+            let new_op = ssa.insert_op(op, vt, Some(addr.address)).ok()?;
             ssa.op_use(new_op, 0, n);
             ssa.replace_value(expr, new_op);
             ssa.insert_into_block(new_op, blk, addr);
@@ -64,7 +64,7 @@ fn visit_expr(ssa: &mut SSAStorage, expr: NodeIndex, n: NodeIndex, mask: NodeInd
             let narrowed_op = {
                 let mut x = scalar!(w);
                 x.vty = vt.vty;
-                ssa.insert_op(op, x, Some(addr.address))
+                ssa.insert_op(op, x, Some(addr.address)).ok()
             }?;
             let extended_op = {
                 let www = vt
@@ -72,6 +72,7 @@ fn visit_expr(ssa: &mut SSAStorage, expr: NodeIndex, n: NodeIndex, mask: NodeInd
                     .get_width()
                     .expect("vt.width() should not be `None`");
                 ssa.insert_op(MOpcode::OpZeroExt(www), vt, Some(addr.address))
+                    .ok()
             }?;
             ssa.op_use(narrowed_op, 0, n);
             ssa.op_use(extended_op, 0, narrowed_op);
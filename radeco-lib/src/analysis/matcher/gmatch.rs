@@ -224,6 +224,16 @@ where
                     MOpcode::OpNarrow(w) => format!("OpNarrow{}", w),
                     MOpcode::OpZeroExt(w) => format!("OpZeroExt{}", w),
                     MOpcode::OpCall => "OpCall".to_owned(),
+                    MOpcode::OpIndirectJump => "OpIndirectJump".to_owned(),
+                    MOpcode::OpAssume => "OpAssume".to_owned(),
+                    MOpcode::OpFAdd => "OpFAdd".to_owned(),
+                    MOpcode::OpFSub => "OpFSub".to_owned(),
+                    MOpcode::OpFMul => "OpFMul".to_owned(),
+                    MOpcode::OpFDiv => "OpFDiv".to_owned(),
+                    MOpcode::OpFEq => "OpFEq".to_owned(),
+                    MOpcode::OpFLt => "OpFLt".to_owned(),
+                    MOpcode::OpFToI => "OpFToI".to_owned(),
+                    MOpcode::OpIToF => "OpIToF".to_owned(),
                     _ => unreachable!(),
                 });
             } else if let NodeType::Comment(s) = node_data.nt {
@@ -376,6 +386,16 @@ where
                 "OpLoad" => Some(MOpcode::OpLoad),
                 "OpStore" => Some(MOpcode::OpStore),
                 "OpCall" => Some(MOpcode::OpCall),
+                "OpIndirectJump" => Some(MOpcode::OpIndirectJump),
+                "OpAssume" => Some(MOpcode::OpAssume),
+                "OpFAdd" => Some(MOpcode::OpFAdd),
+                "OpFSub" => Some(MOpcode::OpFSub),
+                "OpFMul" => Some(MOpcode::OpFMul),
+                "OpFDiv" => Some(MOpcode::OpFDiv),
+                "OpFEq" => Some(MOpcode::OpFEq),
+                "OpFLt" => Some(MOpcode::OpFLt),
+                "OpFToI" => Some(MOpcode::OpFToI),
+                "OpIToF" => Some(MOpcode::OpIToF),
                 _ => None,
             }
         };
@@ -391,7 +411,7 @@ where
             match *op {
                 MOpcode::OpConst(_) => {}
                 _ => {
-                    addr.offset += 1;
+                    addr.inc_offset();
                     self.ssa.insert_into_block(node, *block, *addr);
                 }
             }
@@ -401,7 +421,7 @@ where
                 .ssa
                 .insert_comment(ValueInfo::new_unresolved(WidthSpec::from(64)), t.to_owned())
                 .expect("Cannot insert new comments");
-            addr.offset += 1;
+            addr.inc_offset();
             self.ssa.insert_into_block(node, *block, *addr);
             node
         }
@@ -492,7 +512,7 @@ where
             }
         }
 
-        address.offset += 1;
+        address.inc_offset();
         self.ssa.set_address(replace_root, address);
         replace_root
     }
@@ -15,6 +15,7 @@ pub mod engine;
 // pub mod propagate;
 pub mod dce;
 pub mod dom;
+pub mod dse;
 pub mod sccp;
 pub mod cse {
     pub mod cse;
@@ -28,12 +29,24 @@ pub mod matcher {
 }
 
 pub mod arithmetic;
+pub mod bounds_check;
+pub mod canonicalize;
 pub mod constraint_set;
 pub mod copy_propagation;
+pub mod demanded_bits;
+pub mod fold_extends;
 pub mod functions;
 pub mod inst_combine;
 pub mod interproc;
+pub mod licm;
+pub mod load_forwarding;
+pub mod magic_div;
 pub mod mask2narrow;
 pub mod reference_marking;
+pub mod regstate_cleanup;
+pub mod shift_mul;
+pub mod signatures;
+pub mod simplify_selectors;
+pub mod string_recovery;
 pub mod tie;
 pub mod vsa;
@@ -199,7 +199,8 @@ impl ReferenceMarker {
         for (ni, vt) in self.cs.iter_bindings() {
             let ssa = rfn.ssa_mut();
             if let Some(ref mut nd) = ssa.g.node_weight_mut(*ni) {
-                if let Some(vi) = nd.valueinfo_mut() {
+                // Memory states are neither scalars nor references.
+                if let Some(vi) = nd.valueinfo_mut().filter(|vi| !vi.is_memory()) {
                     vi.set_value_type(*vt);
                 }
             }
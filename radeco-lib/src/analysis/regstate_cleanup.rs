@@ -0,0 +1,172 @@
+// Copyright (c) 2015, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Register state cleanup
+//!
+//! Every call is given a `reg@address` comment for each register it may clobber, standing for
+//! the value the callee leaves in it. This pass removes those which are never read, i.e. whose
+//! register is overwritten, or never looked at again, after the call. Unlike `DCE`, only this
+//! bookkeeping is removed, the computations of the function are left alone.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer, RemoveValue,
+};
+use crate::frontend::radeco_containers::RadecoFunction;
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::ssa_traits::{SSAMod, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use std::any::Any;
+
+#[derive(Debug)]
+pub struct RegStateCleanup {}
+
+const NAME: &str = "regstate_cleanup";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::RegStateCleanup,
+    requires: REQUIRES,
+    uses_policy: true,
+};
+
+impl RegStateCleanup {
+    pub fn new() -> Self {
+        RegStateCleanup {}
+    }
+
+    // Returns `true` if `node` stands for the value of a register, or of memory, after a call.
+    fn is_call_def(ssa: &SSAStorage, node: <SSAStorage as SSA>::ValueRef) -> bool {
+        ssa.is_comment(node)
+            && ssa
+                .operands_of(node)
+                .into_iter()
+                .any(|op| ssa.opcode(op) == Some(MOpcode::OpCall))
+    }
+}
+
+impl Analyzer for RegStateCleanup {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl FuncAnalyzer for RegStateCleanup {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rfn: &mut RadecoFunction,
+        policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let mut policy = policy.expect("A policy function must be provided");
+        let ssa = rfn.ssa_mut();
+        let unread = ssa
+            .values()
+            .into_iter()
+            .filter(|&v| Self::is_call_def(ssa, v) && ssa.uses_of(v).is_empty())
+            .collect::<Vec<_>>();
+        for node in unread {
+            match policy(Box::new(RemoveValue(node))) {
+                Action::Apply => {
+                    radeco_trace!("regstate_cleanup_rm|{:?}", ssa.comment(node));
+                    ssa.remove_value(node);
+                }
+                Action::Skip => (),
+                Action::Abort => break,
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use crate::middle::ssa::cfg_traits::CFG;
+    use crate::middle::ssa::utils;
+    use crate::middle::ssa::verifier;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    // `rax` is returned and `rdx` is read after the call, `rcx` is overwritten and `rsi` is
+    // never looked at again.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CALL_TXT: &str = "\
+; radeco-ir v1
+define-fun main(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] (%3: $Unknown64 = $rax, %4: $Unknown64 = $rcx, %5: $Unknown64 = $rdx, %6: $Unknown64 = $rsi, %7: $Mem = $mem) = CALL #x2000($rdi=%1, $mem=%2);
+        [@0x001004.0001] %8: $Unknown64 = %5 + #x1;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %3;
+        $rcx = #x0;
+        $rdx = %8;
+        $mem = %7;
+}
+";
+
+    #[test]
+    fn unread_call_defs_removed() {
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ir_reader::parse_il(CALL_TXT, REGISTER_FILE.clone());
+        let call_rets = |rfn: &RadecoFunction| {
+            let ssa = rfn.ssa();
+            let call = ssa
+                .values()
+                .into_iter()
+                .find(|&v| ssa.opcode(v) == Some(MOpcode::OpCall))
+                .unwrap();
+            let mut rets = utils::call_rets(call, ssa)
+                .iter()
+                .map(|(id, _)| ssa.regfile.get_name(id).unwrap_or("mem").to_owned())
+                .collect::<Vec<_>>();
+            rets.sort();
+            rets
+        };
+        assert_eq!(call_rets(&rfn), vec!["mem", "rax", "rcx", "rdx", "rsi"]);
+
+        RegStateCleanup::new().analyze(&mut rfn, Some(all));
+
+        assert_eq!(call_rets(&rfn), vec!["mem", "rax", "rdx"]);
+        let ssa = rfn.ssa();
+        assert!(verifier::verify(ssa).is_ok());
+        // The computations are left alone, as are the values flowing to the exit.
+        assert_eq!(
+            ssa.values()
+                .into_iter()
+                .filter(|&v| ssa.opcode(v) == Some(MOpcode::OpAdd))
+                .count(),
+            1
+        );
+        let exit_state = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        let rax = ssa.regfile.register_id_by_name("rax").unwrap();
+        let rax_at_exit = utils::register_state_info(exit_state, ssa)[rax].0;
+        assert!(RegStateCleanup::is_call_def(ssa, rax_at_exit));
+    }
+}
@@ -16,7 +16,7 @@ use crate::analysis::analyzer::{
     Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, FuncAnalyzer,
 };
 use crate::frontend::radeco_containers::RadecoFunction;
-use crate::middle::ir::{MArity, MOpcode, WidthSpec};
+use crate::middle::ir::{self, MArity, MOpcode, WidthSpec};
 use crate::middle::ssa::cfg_traits::{CFGMod, CFG};
 use crate::middle::ssa::graph_traits::{ConditionInfo, Graph};
 use crate::middle::ssa::ssa_traits::{NodeData, NodeType, ValueInfo, ValueType};
@@ -31,7 +31,7 @@ use std::u64;
 macro_rules! node_data_from_g {
     ($g:ident, $i:ident) => {
         $g.node_data(*$i).unwrap_or_else(|_x| {
-            radeco_err!("RegisterState found, {:?}", _x);
+            radeco_err!(target: NAME, "RegisterState found, {:?}", _x);
             NodeData {
                 vt: ValueInfo::new(ValueType::Invalid, WidthSpec::Unknown),
                 nt: NodeType::Undefined,
@@ -103,6 +103,7 @@ pub struct SCCP {
     cfg_worklist: VecDeque<<SSAStorage as CFG>::CFEdgeRef>,
     executable: HashMap<<SSAStorage as CFG>::CFEdgeRef, bool>,
     expr_val: HashMap<<SSAStorage as SSA>::ValueRef, LatticeValue>,
+    assumptions: HashMap<<SSAStorage as SSA>::ValueRef, u64>,
 }
 
 impl SCCP {
@@ -112,15 +113,28 @@ impl SCCP {
             cfg_worklist: VecDeque::new(),
             executable: HashMap::new(),
             expr_val: HashMap::new(),
+            assumptions: HashMap::new(),
         }
     }
 
+    /// Pins values to known constants before the propagation starts.
+    ///
+    /// The assumed constants are taken as given: they are never lowered by the propagation, even
+    /// for the arguments of the function which are otherwise considered overdefined.
+    pub fn set_assumptions(&mut self, assumptions: HashMap<<SSAStorage as SSA>::ValueRef, u64>) {
+        self.assumptions = assumptions;
+    }
+
     pub fn dump(&self) {
         println!("{:?}", self.expr_val);
     }
 
     fn visit_phi(&mut self, g: &SSAStorage, i: &<SSAStorage as SSA>::ValueRef) -> LatticeValue {
-        let operands = g.operands_of(*i);
+        if let Some(&c) = self.assumptions.get(i) {
+            return LatticeValue::Const(c);
+        }
+
+        let sources = g.phi_sources(*i);
         let mut phi_val = self.get_value(g, i);
 
         // If "overdefined" return it.
@@ -130,8 +144,9 @@ impl SCCP {
 
         let invalid_block = g.invalid_action().expect("Invalid Action is not defind");
         let parent_block = g.block_for(*i).unwrap_or(invalid_block);
-        for op in &operands {
-            let operand_block = g.block_for(*op).unwrap_or(invalid_block);
+        for (pred, op) in &sources {
+            // Unless it is known, assume the operand flows from the block it is defined in.
+            let operand_block = pred.unwrap_or_else(|| g.block_for(*op).unwrap_or(invalid_block));
             let op_val = self.get_value(g, op);
 
             if op_val.is_undefined() {
@@ -166,7 +181,7 @@ impl SCCP {
 
         let cond_val = self.get_value(g, i);
         let block = g.selector_for(*i).unwrap_or_else(|| {
-            radeco_err!("Victim value is not a selector");
+            radeco_err!(target: NAME, "Victim value is not a selector");
             g.invalid_action().unwrap()
         });
         let invalid_edge = g.invalid_edge().expect("Invalid Edge is not defined");
@@ -183,12 +198,12 @@ impl SCCP {
                 self.cfg_worklist.push_back(false_branch);
             }
             LatticeValue::Top => {
-                radeco_warn!("`cond_val` is undefined");
+                radeco_warn!(target: NAME, "`cond_val` is undefined");
                 self.cfg_worklist.push_back(true_branch);
                 self.cfg_worklist.push_back(false_branch);
             }
             LatticeValue::Const(cval) => {
-                if cval == 0 {
+                if cval != 0 {
                     self.cfgwl_push(&true_branch);
                 } else {
                     self.cfgwl_push(&false_branch);
@@ -219,18 +234,26 @@ impl SCCP {
 
         let mut val: u64 = match opcode {
             MOpcode::OpZeroExt(_) | MOpcode::OpSignExt(_) => {
-                // Nothing to do in case of widen as the value cannot change.
-                const_val
-            }
-            MOpcode::OpNarrow(size) => {
-                // Max size is 64. Therefore, we can _never_ narrow to 64.
-                assert!(size < 64);
-                let mask = (1 << (size)) - 1;
-                const_val & mask
+                let signed = if let MOpcode::OpSignExt(_) = opcode {
+                    true
+                } else {
+                    false
+                };
+                let w = g
+                    .node_data(operand)
+                    .ok()
+                    .and_then(|nd| nd.vt.width().get_width())
+                    .unwrap_or(64);
+                ir::interpret_const(const_val, w, signed) as u64
             }
+            MOpcode::OpNarrow(size) => ir::interpret_const(const_val, size, false) as u64,
             MOpcode::OpMov => const_val as u64,
             MOpcode::OpNot => !const_val as u64,
-            MOpcode::OpCall => {
+            MOpcode::OpCall | MOpcode::OpIndirectJump => {
+                return LatticeValue::Bottom;
+            }
+            // Floating point values are not folded.
+            MOpcode::OpFToI | MOpcode::OpIToF => {
                 return LatticeValue::Bottom;
             }
             _ => unreachable!(),
@@ -239,9 +262,7 @@ impl SCCP {
         // We should consider width.
         let ndata = node_data_from_g!(g, i);
         let w = ndata.vt.width().get_width().unwrap_or(64);
-        if w < 64 {
-            val = val & ((1 << (w)) - 1);
-        }
+        val = ir::interpret_const(val, w, false) as u64;
 
         LatticeValue::Const(val)
     }
@@ -252,9 +273,17 @@ impl SCCP {
         i: &<SSAStorage as SSA>::ValueRef,
         opcode: MOpcode,
     ) -> LatticeValue {
-        // Do not reason about load/stores.
+        // Do not reason about load/stores, assumptions or floating point arithmetic.
         match opcode {
-            MOpcode::OpLoad | MOpcode::OpStore => return LatticeValue::Bottom,
+            MOpcode::OpLoad
+            | MOpcode::OpStore
+            | MOpcode::OpAssume
+            | MOpcode::OpFAdd
+            | MOpcode::OpFDiv
+            | MOpcode::OpFEq
+            | MOpcode::OpFLt
+            | MOpcode::OpFMul
+            | MOpcode::OpFSub => return LatticeValue::Bottom,
             _ => {}
         }
 
@@ -318,9 +347,7 @@ impl SCCP {
         // We should consider width.
         let ndata = node_data_from_g!(g, i);
         let w = ndata.vt.width().get_width().unwrap_or(64);
-        if w < 64 {
-            val = val & ((1 << (w)) - 1);
-        }
+        val = ir::interpret_const(val, w, false) as u64;
 
         LatticeValue::Const(val)
     }
@@ -343,7 +370,7 @@ impl SCCP {
         i: &<SSAStorage as SSA>::ValueRef,
     ) -> LatticeValue {
         let expr = g.node_data(*i).unwrap_or_else(|_x| {
-            radeco_err!("RegisterState found, {:?}", _x);
+            radeco_err!(target: NAME, "RegisterState found, {:?}", _x);
             NodeData {
                 vt: ValueInfo::new(ValueType::Invalid, WidthSpec::Unknown),
                 nt: NodeType::Undefined,
@@ -352,12 +379,14 @@ impl SCCP {
         let opcode = if let NodeType::Op(opcode) = expr.nt {
             opcode
         } else {
-            radeco_err!("Found something other than an expression!");
+            radeco_err!(target: NAME, "Found something other than an expression!");
             MOpcode::OpInvalid
         };
 
         let val = if let MOpcode::OpConst(v) = opcode {
             LatticeValue::Const(v as u64)
+        } else if let Some(&c) = self.assumptions.get(i) {
+            LatticeValue::Const(c)
         } else {
             match opcode.arity() {
                 MArity::Unary => self.evaluate_unary_op(g, i, opcode),
@@ -372,6 +401,7 @@ impl SCCP {
         // TODO: Handle the case where the selector of a block may belong to a
         // different block.
         if g.is_selector(*i) {
+            self.set_value(i, val);
             self.evaluate_control_flow(g, i);
         }
 
@@ -394,6 +424,9 @@ impl SCCP {
     // Determines the Initial value
     fn init_val(&self, g: &SSAStorage, i: &<SSAStorage as SSA>::ValueRef) -> LatticeValue {
         //TODO replace unwrap
+        if let Some(&c) = self.assumptions.get(i) {
+            return LatticeValue::Const(c);
+        }
         let node_data = g.node_data(*i).unwrap();
         match node_data.nt {
             NodeType::Op(MOpcode::OpConst(v)) => LatticeValue::Const(v),
@@ -436,7 +469,7 @@ impl SCCP {
             return;
         }
         let owner_block = g.block_for(*i).unwrap_or_else(|| {
-            radeco_err!("Value node doesn't belong to any block");
+            radeco_err!(target: NAME, "Value node doesn't belong to any block");
             g.invalid_action().unwrap()
         });
         if self.is_block_executable(g, &owner_block) {
@@ -473,7 +506,11 @@ impl FuncAnalyzer for SCCP {
                 self.cfgwl_push(next);
             }
             for arg in g.operands_of(registers_in_err!(g, entry_node)) {
-                self.set_value(&arg, LatticeValue::Bottom);
+                let v = match self.assumptions.get(&arg) {
+                    Some(&c) => LatticeValue::Const(c),
+                    None => LatticeValue::Bottom,
+                };
+                self.set_value(&arg, v);
             }
         }
 
@@ -523,7 +560,7 @@ impl FuncAnalyzer for SCCP {
             while let Some(e) = self.ssa_worklist.pop_front() {
                 let t = if g.is_expr(e) {
                     let block_of = g.block_for(e).unwrap_or_else(|| {
-                        radeco_err!("Value node doesn't belong to any block");
+                        radeco_err!(target: NAME, "Value node doesn't belong to any block");
                         g.invalid_action().unwrap()
                     });
                     if self.is_block_executable(&mut g, &block_of) {
@@ -549,15 +586,42 @@ impl FuncAnalyzer for SCCP {
                 continue;
             }
             if let LatticeValue::Const(val) = *v {
-                radeco_trace!("{:?} with {:?} --> Const {:#}", k, g.node_data(*k), val);
+                radeco_trace!(
+                    target: NAME,
+                    "{:?} with {:?} --> Const {:#}",
+                    k,
+                    g.node_data(*k),
+                    val
+                );
                 let ndata = node_data_from_g!(g, k);
                 let w = ndata.vt.width().get_width();
                 // BUG: Width may be changed just using a simple replace.
-                let const_node = g.insert_const(val, w).unwrap_or_else(|| {
-                    radeco_err!("Cannot insert new constants");
+                let const_node = g.insert_const(val, w).unwrap_or_else(|_e| {
+                    radeco_err!(target: NAME, "Cannot insert new constants: {}", _e);
                     g.invalid_value().unwrap()
                 });
-                g.replace_value(*k, const_node);
+                // Pinned arguments stay in the entry register state, only their uses are folded.
+                if g.is_comment(*k) {
+                    let entry_state = g.entry_node().and_then(|entry| g.registers_in(entry));
+                    let pinned = entry_state.map(|regs| {
+                        let indices = g
+                            .sparse_operands_of(regs)
+                            .into_iter()
+                            .filter(|&(_, operand)| operand == *k)
+                            .map(|(i, _)| i)
+                            .collect::<Vec<_>>();
+                        (regs, indices)
+                    });
+                    g.replace_uses(*k, const_node);
+                    if let Some((regs, indices)) = pinned {
+                        g.op_unuse(regs, const_node);
+                        for i in indices {
+                            g.op_use(regs, i, *k);
+                        }
+                    }
+                } else {
+                    g.replace_value(*k, const_node);
+                }
             }
         }
         let blocks = g.blocks();
@@ -588,7 +652,86 @@ impl FuncAnalyzer for SCCP {
 
 #[cfg(test)]
 mod test {
-    use super::{meet, LatticeValue};
+    use super::*;
+    use crate::analysis::analyzer::all;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const BRANCH_ON_ARG_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.check(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown1 = %1 == #x2a;
+        JMP IF %3 0x001004.0000 ELSE 0x001008.0000
+    bb_0x001004.0000(sz 0x4):
+        [@0x001004.0001] %4: $Unknown64 = %1 + #x1;
+        JMP 0x00100C.0000
+    bb_0x001008.0000(sz 0x4):
+        JMP 0x00100C.0000
+    bb_0x00100C.0000(sz 0x1):
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %4;
+        $mem = %2;
+}
+";
+
+    // Runs SCCP over `BRANCH_ON_ARG_TXT`, with `rdi` pinned to the given constant, and returns
+    // the starting addresses of the blocks left.
+    fn sccp_branch_on_arg(rdi: Option<u64>) -> Vec<u64> {
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ir_reader::parse_il(BRANCH_ON_ARG_TXT, REGISTER_FILE.clone());
+
+        let mut sccp = SCCP::new();
+        if let Some(c) = rdi {
+            let ssa = rfn.ssa();
+            let cmp = ssa
+                .values()
+                .into_iter()
+                .find(|&v| ssa.opcode(v) == Some(MOpcode::OpEq))
+                .unwrap();
+            let mut assumptions = HashMap::new();
+            assumptions.insert(ssa.operands_of(cmp)[0], c);
+            sccp.set_assumptions(assumptions);
+        }
+        sccp.analyze(&mut rfn, Some(all));
+
+        let ssa = rfn.ssa();
+        ssa.blocks()
+            .into_iter()
+            .filter_map(|b| ssa.starting_address(b))
+            .map(|a| a.address)
+            .collect()
+    }
+
+    #[test]
+    fn assumption_folds_branch() {
+        let blocks = sccp_branch_on_arg(None);
+        assert!(blocks.contains(&0x1004) && blocks.contains(&0x1008));
+
+        let blocks = sccp_branch_on_arg(Some(0x2a));
+        assert!(blocks.contains(&0x1004) && !blocks.contains(&0x1008));
+
+        let blocks = sccp_branch_on_arg(Some(0));
+        assert!(!blocks.contains(&0x1004) && blocks.contains(&0x1008));
+    }
 
     #[test]
     fn test_meet() {
@@ -0,0 +1,249 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Multiplication by a constant recovery
+//!
+//! Multiplications by a constant are often compiled into shifts of the multiplicand which are
+//! added up, or subtracted from one another: `x * 10` becomes `(x << 3) + (x << 1)`, and `x * 3`
+//! becomes `(x << 2) - x`. This pass folds such sums back into an `OpMul` by the constant.
+//!
+//! A sum is only folded if each of its terms is a shift, a multiplication by a constant, or the
+//! value itself, all of the same value, and at least one of them is a shift. The constant must be
+//! positive and greater than one, otherwise the sum is left as it is.
+
+use crate::analysis::analyzer::{AnalyzerInfo, AnalyzerKind};
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::{SSAMod, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+type SSARef = <SSAStorage as SSA>::ValueRef;
+
+// A multiple of a value: the value, its factor and the number of shifts it was made of.
+type Multiple = (SSARef, u64, usize);
+
+const NAME: &str = "shift_mul";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::ShiftMul,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+/// Replaces the sums of shifts of `ssa` with the multiplications they compute. Returns the number
+/// of recovered multiplications.
+pub fn recover_multiplications(ssa: &mut SSAStorage) -> usize {
+    let sums = ssa
+        .blocks()
+        .into_iter()
+        .flat_map(|b| ssa.exprs_in(b))
+        .filter(|&e| match ssa.opcode(e) {
+            Some(MOpcode::OpAdd) | Some(MOpcode::OpSub) => true,
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+    let mut recovered = 0;
+    // Inner sums come first, so that outer ones see them as multiplications once folded.
+    for sum in sums {
+        if let Some((value, factor)) = constant_multiple(ssa, sum) {
+            radeco_trace!("shift_mul|{:?} * {}", value, factor);
+            if multiply(ssa, sum, value, factor).is_some() {
+                recovered += 1;
+            }
+        }
+    }
+    recovered
+}
+
+// Returns the value `sum` is a multiple of, and the factor, if it is one.
+fn constant_multiple(ssa: &SSAStorage, sum: SSARef) -> Option<(SSARef, u64)> {
+    let (value, factor, shifts) = combine(ssa, sum)?;
+    let width = ssa.node_data(sum).ok()?.vt.width().get_width()?;
+    if shifts == 0 || ssa.constant_value(value).is_some() || width == 0 || width > 64 {
+        return None;
+    }
+    let factor = if width == 64 {
+        factor
+    } else {
+        factor & ((1 << width) - 1)
+    };
+    // The factor must read the same as an unsigned and as a signed number.
+    if factor < 2 || factor >> (width - 1) != 0 {
+        return None;
+    }
+    Some((value, factor))
+}
+
+// Adds up the multiples the operands of `sum` are, if they are multiples of the same value.
+fn combine(ssa: &SSAStorage, sum: SSARef) -> Option<Multiple> {
+    let operands = ssa.operands_of(sum);
+    let (lhs, lfactor, lshifts) = multiple(ssa, *operands.get(0)?);
+    let (rhs, rfactor, rshifts) = multiple(ssa, *operands.get(1)?);
+    if lhs != rhs {
+        return None;
+    }
+    let factor = match ssa.opcode(sum) {
+        Some(MOpcode::OpAdd) => lfactor.wrapping_add(rfactor),
+        Some(MOpcode::OpSub) => lfactor.wrapping_sub(rfactor),
+        _ => return None,
+    };
+    Some((lhs, factor, lshifts + rshifts))
+}
+
+// Returns the multiple `node` is. Any value is a multiple of itself.
+fn multiple(ssa: &SSAStorage, node: SSARef) -> Multiple {
+    let operands = ssa.operands_of(node);
+    let constant = |i: usize| operands.get(i).and_then(|&o| ssa.constant_value(o));
+    let found = match ssa.opcode(node) {
+        Some(MOpcode::OpAdd) | Some(MOpcode::OpSub) => combine(ssa, node),
+        Some(MOpcode::OpLsl) => match constant(1) {
+            Some(k) if k < 64 => Some((operands[0], 1 << k, 1)),
+            _ => None,
+        },
+        Some(MOpcode::OpMul) => match (constant(0), constant(1)) {
+            (None, Some(c)) => Some((operands[0], c, 0)),
+            (Some(c), None) => Some((operands[1], c, 0)),
+            _ => None,
+        },
+        _ => None,
+    };
+    found.unwrap_or((node, 1, 0))
+}
+
+// Replaces `sum` with the multiplication of `value` by `factor`.
+fn multiply(ssa: &mut SSAStorage, sum: SSARef, value: SSARef, factor: u64) -> Option<()> {
+    let vt = ssa.node_data(sum).ok()?.vt;
+    let addr = ssa.address(sum)?;
+    let blk = ssa.block_for(sum)?;
+    let factor = ssa.insert_const(factor, None).ok()?;
+    let mul = ssa.insert_op(MOpcode::OpMul, vt, Some(addr.address)).ok()?;
+    ssa.op_use(mul, 0, value);
+    ssa.op_use(mul, 1, factor);
+    ssa.insert_into_block(mul, blk, addr);
+    ssa.replace_value(sum, mul);
+    Some(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use crate::middle::ssa::verifier;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    // rax = (rdi << 3) + (rdi << 1)
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const MUL10_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.mul10(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0xC):
+        [@0x001000.0001] %3: $Unknown64 = %1 << #x3;
+        [@0x001004.0001] %4: $Unknown64 = %1 << #x1;
+        [@0x001008.0001] %5: $Unknown64 = %3 + %4;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %5;
+        $mem = %2;
+}
+";
+
+    // rax = (rdi << 2) - rdi
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const MUL3_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.mul3(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %3: $Unknown64 = %1 << #x2;
+        [@0x001004.0001] %4: $Unknown64 = %3 - %1;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %4;
+        $mem = %2;
+}
+";
+
+    // rax = (rdi << 1) + rsi, and rdx = rdi - (rdi << 2), which is not a positive multiple.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const NOT_MUL_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.not_mul(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Mem = $mem;
+    bb_0x001000.0000(sz 0xC):
+        [@0x001000.0001] %4: $Unknown64 = %1 << #x1;
+        [@0x001004.0001] %5: $Unknown64 = %4 + %2;
+        [@0x001008.0001] %6: $Unknown64 = %1 << #x2;
+        [@0x00100C.0001] %7: $Unknown64 = %1 - %6;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %5;
+        $rdx = %7;
+        $mem = %3;
+}
+";
+
+    // Asserts that `il` computes `rdi * factor`, once its multiplications are recovered.
+    fn assert_multiplied(il: &str, factor: u64) {
+        let mut ssa = ir_reader::parse_il(il, REGISTER_FILE.clone());
+        assert_eq!(recover_multiplications(&mut ssa), 1);
+        assert!(verifier::verify(&ssa).is_ok());
+
+        let mul = ssa
+            .values()
+            .into_iter()
+            .find(|&v| ssa.opcode(v) == Some(MOpcode::OpMul))
+            .unwrap();
+        let operands = ssa.operands_of(mul);
+        assert_eq!(ssa.comment(operands[0]), Some("rdi".to_owned()));
+        assert_eq!(ssa.constant_value(operands[1]), Some(factor));
+        assert!(ssa.values().into_iter().all(|v| match ssa.opcode(v) {
+            Some(MOpcode::OpAdd) | Some(MOpcode::OpSub) => false,
+            _ => true,
+        }));
+    }
+
+    #[test]
+    fn shift_add_mul() {
+        assert_multiplied(MUL10_TXT, 10);
+    }
+
+    #[test]
+    fn shift_sub_mul() {
+        assert_multiplied(MUL3_TXT, 3);
+    }
+
+    #[test]
+    fn other_sums_left_alone() {
+        let mut ssa = ir_reader::parse_il(NOT_MUL_TXT, REGISTER_FILE.clone());
+        assert_eq!(recover_multiplications(&mut ssa), 0);
+    }
+}
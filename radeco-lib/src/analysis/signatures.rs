@@ -0,0 +1,291 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Database of function signatures.
+//!
+//! Signatures are read from a text file, one per line, as the name of the function followed by
+//! its C return type and the C types of its arguments:
+//!
+//! ```text
+//! # Lines starting with `#` are comments.
+//! strlen: size_t(const char *)
+//! memcpy: void *(void *, const void *, size_t)
+//! printf: int(const char *, ...)
+//! abort: void()
+//! ```
+//!
+//! An empty or `void` argument list stands for no arguments, and a trailing `...` for variadic
+//! functions. Types are kept as written, so function pointer types are not supported.
+//!
+//! Once loaded, the signatures type the arguments and the return value of the functions called
+//! from a module, which the backends then use for their variables.
+
+use crate::frontend::radeco_containers::{BindingType, RadecoFunction, RadecoModule};
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::{error, fmt, fs, io};
+
+/// Signature of a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    /// Return type, `void` if the function returns nothing.
+    pub ret: String,
+    pub args: Vec<String>,
+    /// Whether the function takes more arguments than `args`.
+    pub variadic: bool,
+}
+
+/// Error of a line of a signature database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Line of the error, starting from 1.
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl error::Error for ParseError {}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid signature at line {}: {}",
+            self.line, self.reason
+        )
+    }
+}
+
+impl Signature {
+    /// Parses `name: ret(arg1, arg2, ...)`.
+    pub fn parse(s: &str) -> Result<Signature, &'static str> {
+        let colon = s.find(':').ok_or("expected `:` after the name")?;
+        let name = s[..colon].trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err("invalid name");
+        }
+        let rest = s[colon + 1..].trim();
+        let open = rest.find('(').ok_or("expected `(` after the return type")?;
+        if !rest.ends_with(')') {
+            return Err("expected `)` at the end of the arguments");
+        }
+        let ret = rest[..open].trim();
+        if ret.is_empty() {
+            return Err("missing return type");
+        }
+
+        let mut args = rest[open + 1..rest.len() - 1]
+            .split(',')
+            .map(|arg| arg.trim().to_owned())
+            .collect::<Vec<_>>();
+        if args.len() == 1 && (args[0].is_empty() || args[0] == "void") {
+            args.clear();
+        }
+        let variadic = args.last().map_or(false, |arg| arg == "...");
+        if variadic {
+            args.pop();
+        }
+        if args.iter().any(|arg| arg.is_empty() || arg == "...") {
+            return Err("invalid argument type");
+        }
+
+        Ok(Signature {
+            name: name.to_owned(),
+            ret: ret.to_owned(),
+            args,
+            variadic,
+        })
+    }
+
+    /// Types the argument and return bindings of `rfn`. Returns `true` if any was typed.
+    pub fn apply(&self, rfn: &mut RadecoFunction) -> bool {
+        let mut typed = false;
+        for binding in rfn.bindings_mut().iter_mut() {
+            let ty = match binding.btype {
+                BindingType::RegisterArgument(i) | BindingType::StackArgument(i) => {
+                    self.args.get(i)
+                }
+                BindingType::Return if self.ret != "void" => Some(&self.ret),
+                _ => None,
+            };
+            if let Some(ty) = ty {
+                binding.type_str = ty.clone();
+                typed = true;
+            }
+        }
+        typed
+    }
+}
+
+/// Signatures of functions, by name.
+#[derive(Debug, Clone, Default)]
+pub struct SignatureDb {
+    signatures: HashMap<String, Signature>,
+}
+
+impl SignatureDb {
+    pub fn new() -> SignatureDb {
+        SignatureDb::default()
+    }
+
+    /// Parses the signatures of `text`, in the format described in the module documentation.
+    /// A later signature of a function replaces an earlier one.
+    pub fn parse(text: &str) -> Result<SignatureDb, ParseError> {
+        let mut db = SignatureDb::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let sig = Signature::parse(line).map_err(|reason| ParseError {
+                line: i + 1,
+                reason,
+            })?;
+            db.insert(sig);
+        }
+        Ok(db)
+    }
+
+    /// Reads the signatures of the file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<SignatureDb> {
+        let text = fs::read_to_string(path)?;
+        SignatureDb::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn insert(&mut self, sig: Signature) {
+        self.signatures.insert(sig.name.clone(), sig);
+    }
+
+    /// Signature of the function `name`, ignoring prefixes added by r2 such as `sym.imp.`.
+    pub fn get(&self, name: &str) -> Option<&Signature> {
+        self.signatures.get(name).or_else(|| {
+            let name = name.rsplit('.').next().unwrap_or(name);
+            self.signatures.get(name)
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+
+    /// Types the functions called from `rmod` which have a signature. Returns the number of
+    /// typed functions.
+    pub fn apply(&self, rmod: &mut RadecoModule) -> usize {
+        // Every edge of the call graph is a resolved call site.
+        let mut callees = rmod
+            .callgraph
+            .edge_indices()
+            .filter_map(|edge| rmod.callgraph.edge_endpoints(edge))
+            .map(|(_, callee)| rmod.callgraph[callee])
+            .collect::<Vec<_>>();
+        callees.sort();
+        callees.dedup();
+
+        let mut typed = 0;
+        for addr in callees {
+            let rfn = match rmod.functions.get_mut(&addr) {
+                Some(rfn) => rfn,
+                None => continue,
+            };
+            let sig = match self.get(&rfn.name) {
+                Some(sig) => sig,
+                None => continue,
+            };
+            radeco_trace!("signatures|{:#x}|{:?}", addr, sig);
+            if sig.apply(rfn) {
+                typed += 1;
+            }
+        }
+        typed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frontend::radeco_containers::{CallContextInfo, VarBinding};
+    use petgraph::graph::NodeIndex;
+    use std::borrow::Cow;
+
+    const SIGNATURES: &str = "\
+# libgreet
+greet: int(const char *, size_t)
+shout: void(const char *, ...)
+";
+
+    fn add_function(rmod: &mut RadecoModule, addr: u64, name: &str) {
+        let mut rfn = RadecoFunction::new();
+        rfn.name = Cow::from(name.to_owned());
+        rfn.offset = addr;
+        for btype in vec![
+            BindingType::RegisterArgument(0),
+            BindingType::RegisterArgument(1),
+            BindingType::Return,
+        ] {
+            let binding = VarBinding::new(btype, String::new(), None, NodeIndex::end(), None);
+            rfn.bindings_mut().push(binding);
+        }
+        rmod.callgraph.add_node(addr);
+        rmod.functions.insert(addr, rfn);
+    }
+
+    #[test]
+    fn parse_signatures() {
+        let db = SignatureDb::parse(SIGNATURES).unwrap();
+        assert_eq!(db.len(), 2);
+        assert_eq!(
+            db.get("greet"),
+            Some(&Signature {
+                name: "greet".to_owned(),
+                ret: "int".to_owned(),
+                args: vec!["const char *".to_owned(), "size_t".to_owned()],
+                variadic: false,
+            })
+        );
+        let shout = db.get("sym.imp.shout").unwrap();
+        assert_eq!(shout.ret, "void");
+        assert_eq!(shout.args, vec!["const char *".to_owned()]);
+        assert!(shout.variadic);
+
+        let err = SignatureDb::parse("greet: int(\nshout void()").unwrap_err();
+        assert_eq!(err.line, 1);
+        assert_eq!(Signature::parse("abort: void(void)").unwrap().args.len(), 0);
+    }
+
+    #[test]
+    fn type_called_function() {
+        let mut rmod = RadecoModule::new("greeter".to_owned());
+        add_function(&mut rmod, 0x1000, "main");
+        add_function(&mut rmod, 0x2000, "sym.imp.greet");
+        add_function(&mut rmod, 0x3000, "shout");
+        let mut cctx = CallContextInfo::default();
+        cctx.csite = 0x1004;
+        let main = rmod.callgraph_node(0x1000).unwrap();
+        let greet = rmod.callgraph_node(0x2000).unwrap();
+        rmod.callgraph.add_edge(main, greet, cctx);
+
+        let db = SignatureDb::parse(SIGNATURES).unwrap();
+        // `shout` is never called.
+        assert_eq!(db.apply(&mut rmod), 1);
+
+        let types = |addr| {
+            rmod.functions[&addr]
+                .bindings()
+                .iter()
+                .map(|b| b.type_str.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(types(0x2000), vec!["const char *", "size_t", "int"]);
+        assert_eq!(types(0x3000), vec!["", "", ""]);
+        assert_eq!(types(0x1000), vec!["", "", ""]);
+    }
+}
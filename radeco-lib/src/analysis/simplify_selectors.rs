@@ -0,0 +1,292 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Selector simplification
+//!
+//! Short-circuit conditions are compiled into chains of conditional jumps: `if (a && b)` jumps
+//! out on `!a`, and otherwise tests `b` in a block of its own, which jumps to the same place on
+//! `!b`. This pass folds such a block into its predecessor, whose selector becomes the `OpAnd` of
+//! both selectors. Dually, `if (a || b)` is folded into an `OpOr`.
+//!
+//! A block is only folded if its predecessor is the only one, it computes nothing but pure
+//! operations, which are moved to the predecessor, and the phis of the shared successor receive
+//! the same value from both blocks.
+
+use crate::analysis::analyzer::{AnalyzerInfo, AnalyzerKind};
+use crate::middle::ir::{MAddress, MOpcode};
+use crate::middle::ssa::cfg_traits::{CFGMod, CFG};
+use crate::middle::ssa::graph_traits::Graph;
+use crate::middle::ssa::ssa_traits::{SSAMod, ValueInfo, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::NodeIndex;
+
+const FALSE_EDGE: u8 = 0;
+const TRUE_EDGE: u8 = 1;
+
+const NAME: &str = "simplify_selectors";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::SimplifySelectors,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+/// Folds the chains of conditional jumps of `ssa` into combined selectors. Returns the number of
+/// folded blocks.
+pub fn simplify_selectors(ssa: &mut SSAStorage) -> usize {
+    let mut folded = 0;
+    loop {
+        let mut changed = false;
+        for block in ssa.blocks() {
+            if !ssa.is_block(block) {
+                continue;
+            }
+            // `a && b`: the test of `b` is on the true side, `a || b`: on the false side.
+            for &(side, ref opcode) in &[(TRUE_EDGE, MOpcode::OpAnd), (FALSE_EDGE, MOpcode::OpOr)] {
+                if fold_into(ssa, block, side, opcode.clone()) {
+                    folded += 1;
+                    changed = true;
+                    break;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    folded
+}
+
+// Folds the successor of `block` on its `side` into it, if that successor is tested with a
+// selector whose other side is the one of `block`. Returns `true` if it was folded.
+fn fold_into(ssa: &mut SSAStorage, block: NodeIndex, side: u8, opcode: MOpcode) -> bool {
+    let (inner, other) = match ssa.conditional_blocks(block) {
+        Some(ci) if side == TRUE_EDGE => (ci.true_side, ci.false_side),
+        Some(ci) => (ci.false_side, ci.true_side),
+        None => return false,
+    };
+    let (inner_side, inner_other) = match ssa.conditional_blocks(inner) {
+        Some(ci) if side == TRUE_EDGE => (ci.true_side, ci.false_side),
+        Some(ci) => (ci.false_side, ci.true_side),
+        None => return false,
+    };
+    if inner == block || inner_other != other || [block, inner].contains(&inner_side) {
+        return false;
+    }
+    if inner_side == other || ssa.preds_of(inner) != vec![block] {
+        return false;
+    }
+    if !ssa.phis_in(inner).is_empty() {
+        return false;
+    }
+    let (outer_sel, inner_sel) = match (ssa.selector_in(block), ssa.selector_in(inner)) {
+        (Some(outer_sel), Some(inner_sel)) => (outer_sel, inner_sel),
+        _ => return false,
+    };
+    if !is_boolean(ssa, outer_sel) || !is_boolean(ssa, inner_sel) {
+        return false;
+    }
+    let exprs = ssa.exprs_in(inner);
+    if !exprs.iter().all(|&e| is_pure(ssa, e)) {
+        return false;
+    }
+    // Once folded, `other` is no more entered from `inner`.
+    let agree = ssa
+        .phis_in(other)
+        .into_iter()
+        .all(|phi| ssa.phi_operand_from(phi, block) == ssa.phi_operand_from(phi, inner));
+    if !agree {
+        return false;
+    }
+
+    radeco_trace!(
+        "simplify_selectors|{:?} into {:?} with {:?}",
+        inner,
+        block,
+        opcode
+    );
+    let mut addr = end_of(ssa, block);
+    for expr in exprs {
+        addr = addr.next();
+        ssa.remove_edges_between(expr, inner);
+        ssa.insert_into_block(expr, block, addr);
+    }
+    let combined = match ssa.insert_op(opcode, scalar!(1), None) {
+        Ok(combined) => combined,
+        Err(_e) => {
+            radeco_err!("Cannot insert new values: {}", _e);
+            return false;
+        }
+    };
+    ssa.op_use(combined, 0, outer_sel);
+    ssa.op_use(combined, 1, inner_sel);
+    ssa.insert_into_block(combined, block, addr.next());
+    // The only edge from `block` to `outer_sel` is the one marking it as the selector.
+    ssa.remove_edges_between(block, outer_sel);
+    ssa.set_selector(combined, block);
+
+    for (edge, label) in ssa.outgoing_edges(block) {
+        if label == side {
+            ssa.remove_control_edge(edge);
+        }
+    }
+    ssa.insert_control_edge(block, inner_side, side);
+    ssa.redirect_phi_sources(inner_side, inner, block);
+    ssa.remove_block(inner);
+    true
+}
+
+fn is_boolean(ssa: &SSAStorage, node: NodeIndex) -> bool {
+    ssa.node_data(node)
+        .ok()
+        .map_or(false, |nd| nd.vt.width().get_width() == Some(1))
+}
+
+// Operations which may be evaluated even if the condition they are tested on is not reached.
+fn is_pure(ssa: &SSAStorage, node: NodeIndex) -> bool {
+    match ssa.opcode(node) {
+        Some(MOpcode::OpLoad)
+        | Some(MOpcode::OpStore)
+        | Some(MOpcode::OpCall)
+        | Some(MOpcode::OpIndirectJump)
+        | Some(MOpcode::OpDiv)
+        | Some(MOpcode::OpMod)
+        | Some(MOpcode::OpInvalid)
+        | None => false,
+        Some(_) => true,
+    }
+}
+
+// Address of the last value of `block`, or its starting address if it is empty.
+fn end_of(ssa: &SSAStorage, block: NodeIndex) -> MAddress {
+    ssa.exprs_in(block)
+        .last()
+        .and_then(|&e| ssa.address(e))
+        .or_else(|| ssa.starting_address(block))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use crate::middle::ssa::ssa_builder::SSABuilder;
+    use crate::middle::ssa::verifier;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    // if (rdi == 0 && rsi == 0) { rax = 1 } else { rax = 2 }
+    fn build_and() -> SSAStorage {
+        let mut b = SSABuilder::new(REGISTER_FILE.clone());
+        let rdi = b.register("rdi");
+        let rsi = b.register("rsi");
+        let mem = b.register("mem");
+        let head = b.block(0x1000, 0x4);
+        let inner = b.block(0x1004, 0x4);
+        let then_bb = b.block(0x1008, 0x4);
+        let else_bb = b.block(0x100C, 0x4);
+        let join = b.block(0x1010, 0x1);
+
+        let zero = b.constant(0);
+        let rdi_zero = b.op(head, MOpcode::OpEq, 1, &[rdi, zero]);
+        let rsi_zero = b.op(inner, MOpcode::OpEq, 1, &[rsi, zero]);
+        let (one, two) = (b.constant(1), b.constant(2));
+        let rax = b.phi(join, 64, &[(then_bb, one), (else_bb, two)]);
+        b.branch(head, rdi_zero, inner, else_bb)
+            .branch(inner, rsi_zero, then_bb, else_bb)
+            .jump(then_bb, join)
+            .jump(else_bb, join)
+            .ret(join)
+            .exit_register("rax", rax)
+            .exit_register("mem", mem);
+        b.finish()
+    }
+
+    // if (rdi == 0 || rsi == 0) { rax = 1 } else { rax = 2 }
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const OR_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.either(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Mem = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %4: $Unknown1 = %1 == #x0;
+        JMP IF %4 0x001008.0000 ELSE 0x001004.0000
+    bb_0x001004.0000(sz 0x4):
+        [@0x001004.0001] %5: $Unknown1 = %2 == #x0;
+        JMP IF %5 0x001008.0000 ELSE 0x00100C.0000
+    bb_0x001008.0000(sz 0x4):
+        JMP 0x001010.0000
+    bb_0x00100C.0000(sz 0x4):
+        JMP 0x001010.0000
+    bb_0x001010.0000(sz 0x1):
+        %6: $Unknown64 = Phi(#x1 from 0x001008.0000, #x2 from 0x00100C.0000);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %6;
+        $mem = %3;
+}
+";
+
+    fn block_at(ssa: &SSAStorage, addr: u64) -> NodeIndex {
+        ssa.blocks()
+            .into_iter()
+            .find(|&b| ssa.starting_address(b) == Some(MAddress::new(addr, 0)))
+            .unwrap()
+    }
+
+    // Asserts that the first block of `ssa` is tested on `opcode` of both comparisons, and jumps
+    // to 0x1008 when true and 0x100C otherwise.
+    fn assert_combined(mut ssa: SSAStorage, opcode: MOpcode) {
+        assert_eq!(simplify_selectors(&mut ssa), 1);
+        assert!(verifier::verify(&ssa).is_ok());
+
+        let head = block_at(&ssa, 0x1000);
+        assert!(ssa
+            .blocks()
+            .into_iter()
+            .all(|b| ssa.starting_address(b) != Some(MAddress::new(0x1004, 0))));
+        let selector = ssa.selector_in(head).unwrap();
+        assert_eq!(ssa.opcode(selector), Some(opcode));
+        let operands = ssa.operands_of(selector);
+        assert_eq!(operands.len(), 2);
+        assert!(operands
+            .iter()
+            .all(|&o| ssa.opcode(o) == Some(MOpcode::OpEq) && ssa.block_for(o) == Some(head)));
+        let ci = ssa.conditional_blocks(head).unwrap();
+        assert_eq!(ci.true_side, block_at(&ssa, 0x1008));
+        assert_eq!(ci.false_side, block_at(&ssa, 0x100C));
+    }
+
+    #[test]
+    fn fold_short_circuit_and() {
+        assert_combined(build_and(), MOpcode::OpAnd);
+    }
+
+    #[test]
+    fn fold_short_circuit_or() {
+        let ssa = ir_reader::parse_il(OR_TXT, REGISTER_FILE.clone());
+        assert_combined(ssa, MOpcode::OpOr);
+    }
+}
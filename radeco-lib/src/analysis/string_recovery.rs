@@ -0,0 +1,193 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Recovers string literals referenced by a function.
+//!
+//! For every address in the `datarefs` of a function which holds a string known to the loader,
+//! the expressions referring to that address, either as a constant operand or through their
+//! cross-references, are annotated with the string. The annotation is a comment, and is printed by
+//! the [`ir_writer`](::middle::ir_writer) before the expression, e.g. `; "Hello, world"`.
+
+use crate::analysis::analyzer::{
+    Action, Analyzer, AnalyzerInfo, AnalyzerKind, AnalyzerResult, Change, ModuleAnalyzer,
+};
+use crate::frontend::radeco_containers::RadecoModule;
+use crate::middle::ssa::ssa_traits::{SSAExtra, SSA};
+
+use std::any::Any;
+use std::collections::HashMap;
+
+const NAME: &str = "string_recovery";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::StringRecovery,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
+/// Number of bytes of a string shown in a comment before it is truncated.
+const MAX_LEN: usize = 64;
+
+#[derive(Debug)]
+pub struct StringRecovery;
+
+impl StringRecovery {
+    pub fn new() -> Self {
+        StringRecovery
+    }
+}
+
+impl Analyzer for StringRecovery {
+    fn info(&self) -> &'static AnalyzerInfo {
+        &INFO
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl ModuleAnalyzer for StringRecovery {
+    fn analyze<T: FnMut(Box<dyn Change>) -> Action>(
+        &mut self,
+        rmod: &mut RadecoModule,
+        _policy: Option<T>,
+    ) -> Option<Box<dyn AnalyzerResult>> {
+        let mut comments = HashMap::new();
+        for rfn in rmod.functions.values() {
+            for &addr in rfn.datarefs() {
+                if !comments.contains_key(&addr) {
+                    let comment = rmod.string_at(addr).map(|s| format_string(&s));
+                    comments.insert(addr, comment);
+                }
+            }
+        }
+
+        for rfn in rmod.functions.values_mut() {
+            let strings = rfn
+                .datarefs()
+                .iter()
+                .filter_map(|a| comments[a].clone().map(|c| (*a, c)))
+                .collect::<HashMap<_, _>>();
+            if strings.is_empty() {
+                continue;
+            }
+
+            let ssa = rfn.ssa_mut();
+            for value in ssa.values() {
+                // Loads and stores of a string are recorded as referring to it.
+                let xref = ssa
+                    .xrefs_of(&value)
+                    .into_iter()
+                    .find_map(|a| strings.get(&a));
+                if let Some(comment) = xref {
+                    radeco_trace!("string_recovery|{:?}|{}", value, comment);
+                    ssa.set_comment(&value, comment.clone());
+                    continue;
+                }
+                let comment = match ssa.constant_value(value).and_then(|c| strings.get(&c)) {
+                    Some(comment) => comment.clone(),
+                    None => continue,
+                };
+                for user in ssa.uses_of(value) {
+                    if ssa.is_expr(user) {
+                        radeco_trace!("string_recovery|{:?}|{}", user, comment);
+                        ssa.set_comment(&user, comment.clone());
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Formats the bytes of a string as a quoted literal, or as hex if they are not printable.
+/// Strings longer than `MAX_LEN` bytes are truncated.
+fn format_string(bytes: &[u8]) -> String {
+    let (shown, rest) = if bytes.len() > MAX_LEN {
+        (&bytes[..MAX_LEN], "...")
+    } else {
+        (bytes, "")
+    };
+
+    let printable = shown
+        .iter()
+        .all(|&b| b.is_ascii_graphic() || b == b' ' || b == b'\n' || b == b'\t' || b == b'\r');
+    if printable {
+        let escaped = shown
+            .iter()
+            .map(|&b| match b {
+                b'"' => "\\\"".to_owned(),
+                b'\\' => "\\\\".to_owned(),
+                b'\n' => "\\n".to_owned(),
+                b'\t' => "\\t".to_owned(),
+                b'\r' => "\\r".to_owned(),
+                _ => (b as char).to_string(),
+            })
+            .collect::<String>();
+        format!("\"{}\"{}", escaped, rest)
+    } else {
+        let hex = shown
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!("{}{}", hex, rest)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frontend::radeco_containers::ProjectLoader;
+    use crate::frontend::radeco_source::FileSource;
+    use crate::middle::ir_writer;
+    use std::rc::Rc;
+
+    #[test]
+    fn format_printable_and_binary() {
+        assert_eq!(format_string(b"Hello, world"), "\"Hello, world\"");
+        assert_eq!(format_string(b"say \"hi\"\n"), "\"say \\\"hi\\\"\\n\"");
+        assert_eq!(format_string(&[0xde, 0xad, 0x00, 0x2a]), "de ad 00 2a");
+        assert_eq!(
+            format_string(&[b'a'; 100]),
+            format!("\"{}\"...", "a".repeat(MAX_LEN))
+        );
+    }
+
+    #[test]
+    fn recover_string_comment() {
+        let fsource = Rc::new(FileSource::open("./test_files/bin1_filesource/bin1"));
+        let mut rproj = ProjectLoader::new().source(fsource).load();
+        let rmod = rproj.iter_mut().next().unwrap().module;
+
+        StringRecovery::new().analyze(rmod, None::<fn(_) -> _>);
+
+        let rfn = rmod.functions.values().find(|f| f.name == "main").unwrap();
+        let ssa = rfn.ssa();
+        let commented = ssa
+            .values()
+            .into_iter()
+            .filter_map(|v| ssa.comments(&v))
+            .collect::<Vec<_>>();
+        // mov edi, 0x4006b4
+        assert!(commented.contains(&"\"%d %d %d\"".to_owned()));
+        let xref = ssa
+            .values()
+            .into_iter()
+            .find(|v| ssa.xrefs_of(v) == vec![0x4006b4])
+            .unwrap();
+        assert_eq!(ssa.comments(&xref), Some("\"%d %d %d\"".to_owned()));
+        assert!(commented.contains(&"\"Success\"".to_owned()));
+
+        let mut il = String::new();
+        ir_writer::emit_il(&mut il, Some(rfn.name.to_string()), ssa).unwrap();
+        assert!(il.contains("; \"%d %d %d\"\n"));
+    }
+}
@@ -511,10 +511,18 @@ impl Add for StridedInterval {
                 ((self.lb ^ other.lb) | !(self.lb ^ lb)) & (!self.ub & !other.ub & ub),
                 self.k
             );
+            let s = gcd(self.s, other.s);
             if (u < 0) || (v < 0) {
-                StridedInterval::default_k(self.k)
+                // The sum wraps around, but wrapping keeps the residue modulo a stride which
+                // divides 2^k, e.g. a pointer incremented by 8 stays aligned.
+                if s > 0 && (s as Unum).is_power_of_two() {
+                    let lb = min_in_k_bits!(self.k) + lb.rem_euclid(s);
+                    StridedInterval::new(self.k, s, lb, max_in_k_bits!(self.k))
+                } else {
+                    StridedInterval::default_k(self.k)
+                }
             } else {
-                StridedInterval::new(self.k, gcd(self.s, other.s), lb, ub)
+                StridedInterval::new(self.k, s, lb, ub)
             }
         }
     }
@@ -1254,8 +1262,13 @@ impl AbstractSet for StridedInterval {
             let mut s = gcd(self.s, other.s);
             // Trick to avoid overflow
             let (min_lb, max_lb) = (cmp::min(self.lb, other.lb), cmp::max(self.lb, other.lb));
-            // add 2 * s to make sure all arguments in gcd is positive
-            s = gcd(s, max_lb % s - min_lb % s + 2 * s);
+            if s == 0 {
+                // Two constants, the only stride relating them is their difference
+                s = max_lb.wrapping_sub(min_lb);
+            } else {
+                // add 2 * s to make sure all arguments in gcd is positive
+                s = gcd(s, max_lb % s - min_lb % s + 2 * s);
+            }
             StridedInterval::new(
                 self.k,
                 s,
@@ -1631,6 +1644,14 @@ mod test {
         let op1 = StridedInterval::new(4, 4, -1, 3);
         let op2 = StridedInterval::new(4, 2, -2, 4);
         assert_eq!(StridedInterval::new(4, 2, -3, 7), op1 + op2);
+
+        let op1 = StridedInterval::new(4, 2, 1, 5);
+        let op2 = StridedInterval::new(4, 0, 4, 4);
+        assert_eq!(StridedInterval::new(4, 2, -7, 7), op1 + op2);
+
+        let op1 = StridedInterval::new(4, 3, 1, 7);
+        let op2 = StridedInterval::new(4, 0, 3, 3);
+        assert_eq!(StridedInterval::new(4, 1, -8, 7), op1 + op2);
     }
 
     #[test]
@@ -1961,6 +1982,11 @@ mod test {
         let op2 = StridedInterval::new(16, 15, 6, 606);
         assert_eq!(StridedInterval::new(16, 5, 1, 901), op1.join(&op2));
 
+        let op1 = StridedInterval::from((16, 4));
+        let op2 = StridedInterval::from((16, 10));
+        assert_eq!(StridedInterval::new(16, 6, 4, 10), op1.join(&op2));
+        assert_eq!(op1, op1.join(&op1));
+
         let op1 = StridedInterval::new(16, 30, 1, 901);
         let op2 = StridedInterval::null();
         assert_eq!(StridedInterval::new(16, 30, 1, 901), op1.join(&op2));
@@ -0,0 +1,621 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Intraprocedural value set analysis over strided intervals.
+//!
+//! Every SSA value of a function is mapped to a strided interval over-approximating the integers
+//! it may hold. Phi nodes join the sets of their operands, except for the phis of loop headers,
+//! which are iterated to a fixpoint: their sets are joined for the first rounds, then widened so
+//! that values growing at each iteration, such as an incremented pointer, converge. If no
+//! fixpoint is reached within the iteration cap, the loop-carried values are given the full range
+//! of their width.
+//!
+//! A load from a single address of the initialized memory given to the analysis, see
+//! `MemoryImage`, holds the constant stored there. Stores and loads at a known offset below the
+//! stack pointer the function was entered with write and read the a-locs of an `AbstractStack`,
+//! at the granularity chosen in the `VSAConfig`.
+
+use petgraph::graph::NodeIndex;
+use std::collections::{HashMap, HashSet};
+
+use super::abstract_set::abstract_set::{AbstractSet, Inum};
+use super::abstract_set::strided_interval::StridedInterval;
+use super::memory::MemoryImage;
+use super::stack::{AbstractStack, StackGranularity};
+use super::store::AbstractStore;
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::SSA;
+use crate::middle::ssa::ssastorage::SSAStorage;
+use crate::middle::ssa::utils;
+
+const LOG_CATEGORY: &str = "vsa";
+
+#[derive(Clone, Copy, Debug)]
+pub struct VSAConfig {
+    /// Granularity of the a-locs of the stack frame.
+    pub stack_granularity: StackGranularity,
+    /// Number of rounds in which the sets of loop headers and a-locs are joined before being
+    /// widened.
+    pub widening_delay: usize,
+}
+
+impl Default for VSAConfig {
+    fn default() -> VSAConfig {
+        VSAConfig {
+            stack_granularity: StackGranularity::Byte,
+            widening_delay: 2,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ValueSetAnalysis {
+    max_iterations: usize,
+    config: VSAConfig,
+    memory: MemoryImage,
+}
+
+impl ValueSetAnalysis {
+    /// Creates an analysis which gives up looking for a fixpoint after `max_iterations` rounds.
+    pub fn new(max_iterations: usize) -> ValueSetAnalysis {
+        ValueSetAnalysis {
+            max_iterations: max_iterations,
+            config: VSAConfig::default(),
+            memory: MemoryImage::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: VSAConfig) -> ValueSetAnalysis {
+        self.config = config;
+        self
+    }
+
+    /// Reads the values of the loads from `memory`, e.g. `RadecoModule::memory_image`.
+    pub fn with_memory(mut self, memory: MemoryImage) -> ValueSetAnalysis {
+        self.memory = memory;
+        self
+    }
+
+    pub fn analyze(&self, ssa: &SSAStorage) -> AbstractStore<StridedInterval> {
+        self.analyze_frame(ssa).0
+    }
+
+    /// Returns the sets of the values of `ssa` along with those of its stack frame.
+    pub fn analyze_frame(
+        &self,
+        ssa: &SSAStorage,
+    ) -> (AbstractStore<StridedInterval>, AbstractStack) {
+        let (order, headers) = traverse(ssa);
+        let header_phis = headers
+            .iter()
+            .flat_map(|&b| ssa.phis_in(b))
+            .collect::<Vec<_>>();
+        let frame = Frame::new(ssa, &order, self.config.stack_granularity);
+
+        let mut loops = AbstractStore::new();
+        let mut stack = AbstractStack::new(self.config.stack_granularity);
+        for round in 0..self.max_iterations {
+            let (values, stored) = self.evaluate(ssa, &frame, &order, &headers, &loops, &stack);
+            // Sets flowing into the loop headers, from both the loop entries and the back edges
+            let mut incoming = AbstractStore::new();
+            for &phi in &header_phis {
+                if let Some(set) = join_operands(ssa, &values, phi) {
+                    incoming.set(phi, set);
+                }
+            }
+            let (next, next_stack) = if round < self.config.widening_delay {
+                (loops.join(&incoming), stack.join(&stored))
+            } else {
+                (loops.widen(&incoming), stack.widen(&stored))
+            };
+            if next == loops && next_stack == stack {
+                return (values, stack);
+            }
+            loops = next;
+            stack = next_stack;
+        }
+
+        radeco_warn!(
+            target: LOG_CATEGORY,
+            "No fixpoint after {} iterations",
+            self.max_iterations
+        );
+        for &phi in &header_phis {
+            loops.set(phi, top(ssa, phi));
+        }
+        stack.clobber();
+        let (values, _) = self.evaluate(ssa, &frame, &order, &headers, &loops, &stack);
+        (values, stack)
+    }
+
+    /// Evaluates the values of the blocks in `order`, taking the sets of the phis of `headers`
+    /// from `loops` and those of the loaded a-locs from `stack`. Returns them along with the sets
+    /// stored to the stack.
+    fn evaluate(
+        &self,
+        ssa: &SSAStorage,
+        frame: &Frame,
+        order: &[NodeIndex],
+        headers: &HashSet<NodeIndex>,
+        loops: &AbstractStore<StridedInterval>,
+        stack: &AbstractStack,
+    ) -> (AbstractStore<StridedInterval>, AbstractStack) {
+        let mut values = AbstractStore::new();
+        let mut stored = AbstractStack::new(stack.granularity());
+        for &block in order {
+            for phi in ssa.phis_in(block) {
+                let set = if headers.contains(&block) {
+                    loops.get(phi)
+                } else {
+                    join_operands(ssa, &values, phi)
+                };
+                if let Some(set) = set {
+                    values.set(phi, set);
+                }
+            }
+            for expr in ssa.exprs_in(block) {
+                let set = match (ssa.opcode(expr), frame.local(ssa, expr)) {
+                    (Some(MOpcode::OpStore), Some(offset)) => {
+                        let value = ssa.operands_of(expr)[2];
+                        if let Some(set) = operand(ssa, &values, value) {
+                            stored.store(offset, set);
+                        }
+                        Some(top(ssa, expr))
+                    }
+                    (Some(MOpcode::OpLoad), Some(offset))
+                        if frame.is_written(offset, width(ssa, expr)) =>
+                    {
+                        // `None` until a store reaches the a-locs
+                        stack.load(offset, width(ssa, expr))
+                    }
+                    (Some(MOpcode::OpLoad), Some(_)) => {
+                        // Never stored by the function, e.g. an argument on the stack
+                        Some(top(ssa, expr))
+                    }
+                    _ => transfer(ssa, &self.memory, &values, expr),
+                };
+                if let Some(set) = set {
+                    values.set(expr, set);
+                }
+            }
+        }
+        (values, stored)
+    }
+}
+
+/// Offsets from the stack pointer at the function entry of the values of a function.
+struct Frame {
+    offsets: HashMap<NodeIndex, i64>,
+    granularity: StackGranularity,
+    // A-locs the stores to the locals start in
+    written: HashSet<i64>,
+}
+
+impl Frame {
+    fn new(ssa: &SSAStorage, order: &[NodeIndex], granularity: StackGranularity) -> Frame {
+        let mut offsets = HashMap::new();
+        if let Some(sp) = stack_pointer(ssa) {
+            offsets.insert(sp, 0);
+            // Offsets only flow forward, but loop headers see those of the back edges late
+            loop {
+                let known = offsets.len();
+                for &block in order {
+                    let nodes = ssa.phis_in(block).into_iter().chain(ssa.exprs_in(block));
+                    for node in nodes {
+                        if !offsets.contains_key(&node) {
+                            if let Some(offset) = offset_of(ssa, &offsets, node) {
+                                offsets.insert(node, offset);
+                            }
+                        }
+                    }
+                }
+                if offsets.len() == known {
+                    break;
+                }
+            }
+        }
+        let mut frame = Frame {
+            offsets: offsets,
+            granularity: granularity,
+            written: HashSet::new(),
+        };
+        for &block in order {
+            for expr in ssa.exprs_in(block) {
+                if ssa.opcode(expr) != Some(MOpcode::OpStore) {
+                    continue;
+                }
+                if let Some(offset) = frame.local(ssa, expr) {
+                    let k = width(ssa, ssa.operands_of(expr)[2]);
+                    frame.written.extend(granularity.a_locs(offset, k));
+                }
+            }
+        }
+        frame
+    }
+
+    /// Returns the offset of the local accessed by the load or store `expr`, if its address is
+    /// known and below the stack pointer at the function entry.
+    fn local(&self, ssa: &SSAStorage, expr: NodeIndex) -> Option<i64> {
+        let operands = ssa.operands_of(expr);
+        let offset = *self.offsets.get(operands.get(1)?)?;
+        if offset < 0 {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+
+    /// Whether a store may write the a-locs a `k` bits wide load at `offset` starts in. If not,
+    /// the load can only read part of a wider value, if anything stored by the function.
+    fn is_written(&self, offset: i64, k: u8) -> bool {
+        self.granularity
+            .a_locs(offset, k)
+            .iter()
+            .any(|a_loc| self.written.contains(a_loc))
+    }
+}
+
+/// Returns the value of the stack pointer at the function entry.
+fn stack_pointer(ssa: &SSAStorage) -> Option<NodeIndex> {
+    let sp_id = ssa.regfile.register_id_by_alias("SP")?;
+    let entry_state = ssa.registers_in(ssa.entry_node()?)?;
+    let entry_regs = utils::register_state_info(entry_state, ssa);
+    entry_regs.get(sp_id).map(|&(sp, _)| sp)
+}
+
+/// Returns the offset of `node` from the stack pointer, given those of its operands.
+fn offset_of(ssa: &SSAStorage, offsets: &HashMap<NodeIndex, i64>, node: NodeIndex) -> Option<i64> {
+    let operands = ssa.operands_of(node);
+    let offset = |op: NodeIndex| offsets.get(&op).cloned();
+    let constant = |op: NodeIndex| ssa.constant_value(op).map(|c| c as i64);
+    if ssa.is_phi(node) {
+        // Every incoming value must be at the same offset
+        let first = offset(*operands.first()?)?;
+        return if operands.iter().all(|&op| offset(op) == Some(first)) {
+            Some(first)
+        } else {
+            None
+        };
+    }
+    match (ssa.opcode(node)?, operands.as_slice()) {
+        (MOpcode::OpMov, &[a]) => offset(a),
+        (MOpcode::OpAdd, &[a, b]) => match (offset(a), offset(b)) {
+            (Some(o), None) => Some(o.wrapping_add(constant(b)?)),
+            (None, Some(o)) => Some(o.wrapping_add(constant(a)?)),
+            _ => None,
+        },
+        (MOpcode::OpSub, &[a, b]) => Some(offset(a)?.wrapping_sub(constant(b)?)),
+        _ => None,
+    }
+}
+
+/// Returns the blocks reachable from the entry in reverse postorder, along with the loop headers,
+/// i.e. the targets of back edges.
+fn traverse(ssa: &SSAStorage) -> (Vec<NodeIndex>, HashSet<NodeIndex>) {
+    let mut postorder = Vec::new();
+    let mut headers = HashSet::new();
+    let entry = match ssa.entry_node() {
+        Some(entry) => entry,
+        None => return (postorder, headers),
+    };
+
+    let mut visited = HashSet::new();
+    let mut on_stack = HashSet::new();
+    let mut stack = vec![(entry, ssa.succs_of(entry).into_iter())];
+    visited.insert(entry);
+    on_stack.insert(entry);
+    while let Some((block, next)) = stack.last_mut().map(|top| (top.0, top.1.next())) {
+        match next {
+            Some(succ) if on_stack.contains(&succ) => {
+                headers.insert(succ);
+            }
+            Some(succ) => {
+                if visited.insert(succ) {
+                    on_stack.insert(succ);
+                    stack.push((succ, ssa.succs_of(succ).into_iter()));
+                }
+            }
+            None => {
+                on_stack.remove(&block);
+                postorder.push(block);
+                stack.pop();
+            }
+        }
+    }
+    postorder.reverse();
+    (postorder, headers)
+}
+
+/// Returns the set of `expr` given the sets of its operands, or `None` if one of them was not
+/// reached.
+fn transfer(
+    ssa: &SSAStorage,
+    memory: &MemoryImage,
+    values: &AbstractStore<StridedInterval>,
+    expr: NodeIndex,
+) -> Option<StridedInterval> {
+    let opcode = ssa.opcode(expr)?;
+    let operands = ssa
+        .operands_of(expr)
+        .into_iter()
+        .map(|op| operand(ssa, values, op))
+        .collect::<Option<Vec<_>>>()?;
+    let k = width(ssa, expr);
+    let set = match (opcode, operands.as_slice()) {
+        (MOpcode::OpConst(c), _) => StridedInterval::from((k, c as Inum)),
+        (MOpcode::OpMov, &[a]) => a,
+        (MOpcode::OpNarrow(w), &[a]) if !a.is_empty() => a.narrow(w as u8),
+        (MOpcode::OpSignExt(w), &[a]) if !a.is_empty() => a.sign_extend(w as u8),
+        (MOpcode::OpZeroExt(w), &[a]) if !a.is_empty() => a.zero_extend(w as u8),
+        (MOpcode::OpAdd, &[a, b]) if arithmetic(a, b, k) => a + b,
+        (MOpcode::OpSub, &[a, b]) if arithmetic(a, b, k) => a - b,
+        (MOpcode::OpMul, &[a, b]) if arithmetic(a, b, k) => a * b,
+        (MOpcode::OpLoad, &[_, addr]) if !addr.is_empty() && addr.lb == addr.ub => {
+            match memory.read(addr.lb as u64, u16::from(k)) {
+                Some(c) => StridedInterval::from((k, c as Inum)),
+                None => StridedInterval::default_k(k),
+            }
+        }
+        _ => StridedInterval::default_k(k),
+    };
+    Some(set)
+}
+
+/// Returns the set of the operand `node`, or `None` if it was not reached.
+fn operand(
+    ssa: &SSAStorage,
+    values: &AbstractStore<StridedInterval>,
+    node: NodeIndex,
+) -> Option<StridedInterval> {
+    if let Some(set) = values.get(node) {
+        Some(set)
+    } else if let Some(c) = ssa.constant_value(node) {
+        Some(StridedInterval::from((width(ssa, node), c as Inum)))
+    } else if ssa.is_comment(node) {
+        // Registers at the function entry may hold anything
+        Some(top(ssa, node))
+    } else {
+        None
+    }
+}
+
+/// Joins the sets of the operands of `phi` reached so far.
+fn join_operands(
+    ssa: &SSAStorage,
+    values: &AbstractStore<StridedInterval>,
+    phi: NodeIndex,
+) -> Option<StridedInterval> {
+    ssa.operands_of(phi)
+        .into_iter()
+        .filter_map(|op| operand(ssa, values, op))
+        .fold(None, |acc, set| match acc {
+            Some(acc) => Some(acc.join(&set)),
+            None => Some(set),
+        })
+}
+
+fn arithmetic(a: StridedInterval, b: StridedInterval, k: u8) -> bool {
+    !a.is_empty() && !b.is_empty() && a.k == k && b.k == k
+}
+
+fn width(ssa: &SSAStorage, node: NodeIndex) -> u8 {
+    let width = ssa
+        .node_data(node)
+        .ok()
+        .and_then(|nd| nd.vt.width().get_width())
+        .unwrap_or(64);
+    if width > 64 {
+        64
+    } else {
+        width as u8
+    }
+}
+
+fn top(ssa: &SSAStorage, node: NodeIndex) -> StridedInterval {
+    StridedInterval::default_k(width(ssa, node))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::analysis::vsa::abstract_set::abstract_set::Container;
+    use crate::analysis::vsa::memory::Endian;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const POINTER_LOOP_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.walk(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown64 = #x1000 + #x1000;
+        JMP 0x001004.0000
+    bb_0x001004.0000(sz 0x8):
+        %4: $Unknown64 = Phi(%3, %5);
+        [@0x001004.0001] %5: $Unknown64 = %4 + #x8;
+        [@0x001008.0001] %6: $Unknown1 = %5 == %1;
+        JMP IF %6 0x00100C.0000 ELSE 0x001004.0000
+    bb_0x00100C.0000(sz 0x1):
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %5;
+        $mem = %2;
+}
+";
+
+    fn loop_phi(ssa: &SSAStorage) -> NodeIndex {
+        ssa.blocks()
+            .into_iter()
+            .flat_map(|b| ssa.phis_in(b))
+            .next()
+            .unwrap()
+    }
+
+    #[test]
+    fn widen_pointer_loop() {
+        let ssa = ir_reader::parse_il(POINTER_LOOP_TXT, REGISTER_FILE.clone());
+        let phi = loop_phi(&ssa);
+
+        let store = ValueSetAnalysis::new(10).analyze(&ssa);
+        let ptr = store.get(phi).unwrap();
+        assert_eq!(ptr.s, 8);
+        assert!(ptr.contains(&0x2000));
+        assert!(ptr.contains(&0x3000));
+        assert!(!ptr.contains(&0x2004));
+    }
+
+    #[test]
+    fn give_up_after_max_iterations() {
+        let ssa = ir_reader::parse_il(POINTER_LOOP_TXT, REGISTER_FILE.clone());
+        let phi = loop_phi(&ssa);
+
+        let store = ValueSetAnalysis::new(1).analyze(&ssa);
+        assert_eq!(store.get(phi), Some(StridedInterval::default_k(64)));
+    }
+
+    // Reads a 32 bits big-endian word and a byte of it.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const LOAD_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.load(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %3: $Unknown32 = Load(%2, #x2000);
+        [@0x001004.0001] %4: $Unknown8 = Load(%2, #x2007);
+        [@0x001004.0002] %5: $Unknown32 = Load(%2, %1);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %1;
+        $mem = %2;
+}
+";
+
+    #[test]
+    fn load_from_memory_image() {
+        let ssa = ir_reader::parse_il(LOAD_TXT, REGISTER_FILE.clone());
+        let exprs = ssa
+            .blocks()
+            .into_iter()
+            .flat_map(|b| ssa.exprs_in(b))
+            .collect::<Vec<_>>();
+        let mut image = MemoryImage::new(Endian::Big);
+        image.add_region(0x2000, vec![0xde, 0xad, 0xbe, 0xef, 0x00, 0x00, 0x00, 0x2a]);
+
+        let store = ValueSetAnalysis::new(10).with_memory(image).analyze(&ssa);
+        assert_eq!(
+            store.get(exprs[0]),
+            Some(StridedInterval::from((32, 0xdead_beef)))
+        );
+        assert_eq!(store.get(exprs[1]), Some(StridedInterval::from((8, 0x2a))));
+        // The address is unknown, so is the loaded value.
+        assert_eq!(store.get(exprs[2]), Some(StridedInterval::default_k(32)));
+    }
+
+    // Stores a word every 8 bytes of its frame, then reads two of them back.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const FRAME_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.frame(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rsp;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x40):
+        [@0x001000.0001] %3: $Unknown64 = %1 - #x8;
+        [@0x001000.0002] %4: $Mem = Store(%2, %3, #x1);
+        [@0x001004.0001] %5: $Unknown64 = %1 - #x10;
+        [@0x001004.0002] %6: $Mem = Store(%4, %5, #x2);
+        [@0x001008.0001] %7: $Unknown64 = %1 - #x18;
+        [@0x001008.0002] %8: $Mem = Store(%6, %7, #x3);
+        [@0x00100c.0001] %9: $Unknown64 = %1 - #x20;
+        [@0x00100c.0002] %10: $Mem = Store(%8, %9, #x4);
+        [@0x001010.0001] %11: $Unknown64 = %1 - #x28;
+        [@0x001010.0002] %12: $Mem = Store(%10, %11, #x5);
+        [@0x001014.0001] %13: $Unknown64 = %1 - #x30;
+        [@0x001014.0002] %14: $Mem = Store(%12, %13, #x6);
+        [@0x001018.0001] %15: $Unknown64 = %1 - #x38;
+        [@0x001018.0002] %16: $Mem = Store(%14, %15, #x7);
+        [@0x00101c.0001] %17: $Unknown64 = %1 - #x40;
+        [@0x00101c.0002] %18: $Mem = Store(%16, %17, #x8);
+        [@0x001020.0001] %19: $Unknown64 = Load(%18, %5);
+        [@0x001024.0001] %20: $Unknown64 = %1 - #x48;
+        [@0x001024.0002] %21: $Unknown64 = Load(%18, %20);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %19;
+        $rsp = %1;
+        $mem = %18;
+}
+";
+
+    fn frame(granularity: StackGranularity) -> (AbstractStore<StridedInterval>, AbstractStack) {
+        let ssa = ir_reader::parse_il(FRAME_TXT, REGISTER_FILE.clone());
+        let config = VSAConfig {
+            stack_granularity: granularity,
+            ..VSAConfig::default()
+        };
+        let vsa = ValueSetAnalysis::new(10).with_config(config);
+        let (values, stack) = vsa.analyze_frame(&ssa);
+        let exprs = ssa
+            .blocks()
+            .into_iter()
+            .flat_map(|b| ssa.exprs_in(b))
+            .collect::<Vec<_>>();
+        // The two loads
+        let mut loads = AbstractStore::new();
+        for (i, &load) in [exprs[16], exprs[18]].iter().enumerate() {
+            if let Some(set) = values.get(load) {
+                loads.set(NodeIndex::new(i), set);
+            }
+        }
+        (loads, stack)
+    }
+
+    #[test]
+    fn stack_granularity() {
+        let (bytes, byte_stack) = frame(StackGranularity::Byte);
+        assert_eq!(byte_stack.len(), 8);
+        assert_eq!(
+            bytes.get(NodeIndex::new(0)),
+            Some(StridedInterval::from((64, 2)))
+        );
+        // Never stored to
+        assert_eq!(
+            bytes.get(NodeIndex::new(1)),
+            Some(StridedInterval::default_k(64))
+        );
+
+        // Coarser, hence cheaper, but rsp-16 shares its slot with rsp-8
+        let (slots, slot_stack) = frame(StackGranularity::Slot(16));
+        assert_eq!(slot_stack.len(), 4);
+        assert!(slot_stack.len() < byte_stack.len());
+        assert_eq!(
+            slots.get(NodeIndex::new(0)),
+            Some(StridedInterval::new(64, 1, 1, 2))
+        );
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Helpers to read constants out of the memory image of the binary.
+//!
+//! Values such as jump-table entries or initialized globals are stored as a sequence of bytes
+//! whose order depends on the endianness of the target.
+
+use r2api::structs::LBinInfo;
+
+/// Byte order of multi-byte values of the target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+impl Default for Endian {
+    fn default() -> Endian {
+        Endian::Little
+    }
+}
+
+impl Endian {
+    /// Returns the endianness reported by r2 in `bin_info`, if any.
+    pub fn from_bin_info(bin_info: &LBinInfo) -> Option<Endian> {
+        let endian = bin_info.bin.as_ref()?.endian.as_ref()?;
+        match endian.as_str() {
+            "little" => Some(Endian::Little),
+            "big" => Some(Endian::Big),
+            _ => None,
+        }
+    }
+}
+
+/// Initialized memory of the binary, from which the value set analysis reads the constants loaded
+/// by a function.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryImage {
+    endian: Endian,
+    regions: Vec<(u64, Vec<u8>)>,
+}
+
+impl MemoryImage {
+    /// Creates an image without any memory, whose values are stored in `endian` order.
+    pub fn new(endian: Endian) -> MemoryImage {
+        MemoryImage {
+            endian: endian,
+            regions: Vec::new(),
+        }
+    }
+
+    /// Adds `bytes` starting at `addr` to the image.
+    pub fn add_region(&mut self, addr: u64, bytes: Vec<u8>) {
+        self.regions.push((addr, bytes));
+    }
+
+    /// Returns the `width` bits wide value at `addr`, if all its bytes are in a single region.
+    pub fn read(&self, addr: u64, width: u16) -> Option<u64> {
+        let len = (u64::from(width) + 7) / 8;
+        self.regions.iter().find_map(|(start, bytes)| {
+            let offset = addr.checked_sub(*start)?;
+            let end = offset.checked_add(len)?;
+            if end > bytes.len() as u64 {
+                return None;
+            }
+            assemble_bytes(&bytes[offset as usize..end as usize], self.endian)
+        })
+    }
+}
+
+/// Assembles `bytes`, as read from memory in increasing address order, into an integer.
+///
+/// Returns `None` if `bytes` is empty or does not fit in a `u64`.
+pub fn assemble_bytes(bytes: &[u8], endian: Endian) -> Option<u64> {
+    if bytes.is_empty() || bytes.len() > 8 {
+        return None;
+    }
+    let fold = |acc: u64, b: &u8| (acc << 8) | u64::from(*b);
+    Some(match endian {
+        Endian::Big => bytes.iter().fold(0, fold),
+        Endian::Little => bytes.iter().rev().fold(0, fold),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frontend::radeco_source::{FileSource, Source};
+    use std::fs;
+
+    const BE_FIXTURE: &str = "./test_files/be_fixture/be_fixture";
+
+    #[test]
+    fn assemble_big_endian_constant() {
+        let fsource = FileSource::open(BE_FIXTURE);
+        let bin_info = fsource.bin_info().unwrap();
+        let endian = Endian::from_bin_info(&bin_info).unwrap();
+        assert_eq!(endian, Endian::Big);
+
+        let data = fs::read(format!("{}.data", BE_FIXTURE)).unwrap();
+        assert_eq!(assemble_bytes(&data[..4], endian), Some(0xdead_beef));
+        assert_eq!(
+            assemble_bytes(&data[..4], Endian::Little),
+            Some(0xefbe_adde)
+        );
+    }
+
+    #[test]
+    fn read_image() {
+        let data = fs::read(format!("{}.data", BE_FIXTURE)).unwrap();
+        let mut image = MemoryImage::new(Endian::Big);
+        image.add_region(0x1000, data);
+        assert_eq!(image.read(0x1000, 32), Some(0xdead_beef));
+        assert_eq!(image.read(0x1004, 32), Some(0x2a));
+        assert_eq!(image.read(0x1006, 16), Some(0x2a));
+        assert_eq!(image.read(0x1006, 32), None);
+        assert_eq!(image.read(0xfff, 8), None);
+    }
+
+    #[test]
+    fn assemble_out_of_range() {
+        assert_eq!(assemble_bytes(&[], Endian::Little), None);
+        assert_eq!(assemble_bytes(&[0; 9], Endian::Big), None);
+        assert_eq!(assemble_bytes(&[0x34, 0x12], Endian::Little), Some(0x1234));
+    }
+}
@@ -16,3 +16,8 @@ pub mod abstract_set {
     pub mod polynomial;
     pub mod strided_interval;
 }
+
+pub mod analysis;
+pub mod memory;
+pub mod stack;
+pub mod store;
@@ -0,0 +1,259 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Abstract stack frame of value set analysis.
+//!
+//! The locals of a function are stored below the stack pointer it was entered with. Each store
+//! to such an offset writes an abstract location, or a-loc, whose set is the join of every value
+//! stored there. How finely offsets are told apart is set by the `StackGranularity`: tracking
+//! every byte is precise, while rounding offsets down to slots keeps large frames small.
+//!
+//! An access may overlap several a-locs, e.g. an unaligned one straddling two slots, or one
+//! reading part of a wider value. It then aliases all of them: a load gets the full range of its
+//! width, and a store gives every a-loc it overlaps the full range of theirs.
+
+use std::collections::HashMap;
+
+use super::abstract_set::abstract_set::AbstractSet;
+use super::abstract_set::strided_interval::StridedInterval;
+
+/// Granularity at which offsets in the stack frame are told apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StackGranularity {
+    /// Every byte offset is a distinct a-loc.
+    Byte,
+    /// Offsets are rounded down to slots of the given size in bytes, e.g. 8 on x86_64. A size of
+    /// 0 is taken as 1, i.e. `Byte`.
+    Slot(u8),
+}
+
+impl Default for StackGranularity {
+    fn default() -> StackGranularity {
+        StackGranularity::Byte
+    }
+}
+
+impl StackGranularity {
+    /// Returns the offset of the a-loc holding `offset`.
+    pub fn a_loc(&self, offset: i64) -> i64 {
+        offset.div_euclid(self.size()) * self.size()
+    }
+
+    /// Returns the a-locs a `k` bits wide access at `offset` starts in: the one holding `offset`
+    /// and, for slots, those of its following bytes.
+    pub fn a_locs(&self, offset: i64, k: u8) -> Vec<i64> {
+        match *self {
+            StackGranularity::Byte => vec![offset],
+            StackGranularity::Slot(_) => {
+                let last = offset.saturating_add(bytes(k).max(1) - 1);
+                (self.a_loc(offset)..=self.a_loc(last))
+                    .step_by(self.size() as usize)
+                    .collect()
+            }
+        }
+    }
+
+    fn size(&self) -> i64 {
+        match *self {
+            StackGranularity::Byte => 1,
+            StackGranularity::Slot(size) => i64::from(size.max(1)),
+        }
+    }
+}
+
+/// Maps the a-locs of a stack frame, by offset from the stack pointer at the function entry, to
+/// the set of the values stored there.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AbstractStack {
+    granularity: StackGranularity,
+    a_locs: HashMap<i64, StridedInterval>,
+}
+
+impl AbstractStack {
+    pub fn new(granularity: StackGranularity) -> AbstractStack {
+        AbstractStack {
+            granularity: granularity,
+            a_locs: HashMap::new(),
+        }
+    }
+
+    pub fn granularity(&self) -> StackGranularity {
+        self.granularity
+    }
+
+    /// Number of a-locs stored to.
+    pub fn len(&self) -> usize {
+        self.a_locs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.a_locs.is_empty()
+    }
+
+    /// Returns the a-locs overlapped by a `k` bits wide access at `offset`, sorted by offset.
+    pub fn overlapped(&self, offset: i64, k: u8) -> Vec<i64> {
+        let end = offset.saturating_add(bytes(k));
+        let mut a_locs = self.granularity.a_locs(offset, k);
+        for (&a_loc, set) in &self.a_locs {
+            // Stored values may be wider than a slot
+            let a_loc_end = a_loc.saturating_add(self.granularity.size().max(bytes(set.k)));
+            if a_loc < end && a_loc_end > offset && !a_locs.contains(&a_loc) {
+                a_locs.push(a_loc);
+            }
+        }
+        a_locs.sort();
+        a_locs
+    }
+
+    /// Returns the set of a `k` bits wide load at `offset`, or `None` if nothing was stored to the
+    /// a-locs it overlaps.
+    pub fn load(&self, offset: i64, k: u8) -> Option<StridedInterval> {
+        let a_locs = self.overlapped(offset, k);
+        let mut stored = a_locs.iter().filter_map(|a_loc| self.a_locs.get(a_loc));
+        match (a_locs.len(), stored.next()) {
+            (_, None) => None,
+            (1, Some(set)) if set.k == k => Some(*set),
+            // Part of a wider value, or several narrower ones
+            _ => Some(StridedInterval::default_k(k)),
+        }
+    }
+
+    /// Joins `set` into the a-loc holding `offset`, or clobbers the a-locs the store overlaps if
+    /// there are several.
+    pub fn store(&mut self, offset: i64, set: StridedInterval) {
+        let a_locs = self.overlapped(offset, set.k);
+        let clobbered = a_locs.len() > 1;
+        for a_loc in a_locs {
+            let set = if clobbered {
+                StridedInterval::default_k(set.k)
+            } else {
+                set
+            };
+            let merged = match self.a_locs.get(&a_loc) {
+                Some(prev) => merge(prev, &set, |a, b| a.join(b)),
+                None => set,
+            };
+            self.a_locs.insert(a_loc, merged);
+        }
+    }
+
+    /// Gives every a-loc the full range of its width.
+    pub fn clobber(&mut self) {
+        for set in self.a_locs.values_mut() {
+            *set = StridedInterval::default_k(set.k);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&i64, &StridedInterval)> {
+        self.a_locs.iter()
+    }
+
+    /// Joins the sets of every a-loc in `self` and `other`.
+    pub fn join(&self, other: &AbstractStack) -> AbstractStack {
+        self.combine(other, |a, b| a.join(b))
+    }
+
+    /// Widens the sets of every a-loc in `self` by those in `other`.
+    pub fn widen(&self, other: &AbstractStack) -> AbstractStack {
+        self.combine(other, |a, b| a.widen(b))
+    }
+
+    fn combine<F: Fn(&StridedInterval, &StridedInterval) -> StridedInterval>(
+        &self,
+        other: &AbstractStack,
+        f: F,
+    ) -> AbstractStack {
+        let mut a_locs = self.a_locs.clone();
+        for (a_loc, set) in &other.a_locs {
+            let combined = match self.a_locs.get(a_loc) {
+                Some(prev) => merge(prev, set, &f),
+                None => *set,
+            };
+            a_locs.insert(*a_loc, combined);
+        }
+        AbstractStack {
+            granularity: self.granularity,
+            a_locs: a_locs,
+        }
+    }
+}
+
+/// Number of bytes of a `k` bits wide value.
+fn bytes(k: u8) -> i64 {
+    (i64::from(k) + 7) / 8
+}
+
+/// Combines two sets of an a-loc with `f`, or gives up on it if they have different widths.
+fn merge<F: Fn(&StridedInterval, &StridedInterval) -> StridedInterval>(
+    a: &StridedInterval,
+    b: &StridedInterval,
+    f: F,
+) -> StridedInterval {
+    if a.k == b.k {
+        f(a, b)
+    } else {
+        StridedInterval::default_k(a.k.max(b.k))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn slot_offsets() {
+        let slot = StackGranularity::Slot(8);
+        assert_eq!(slot.a_loc(-1), -8);
+        assert_eq!(slot.a_loc(-8), -8);
+        assert_eq!(slot.a_loc(-9), -16);
+        assert_eq!(StackGranularity::Byte.a_loc(-9), -9);
+        // Not a valid slot size, but must not divide by zero
+        assert_eq!(StackGranularity::Slot(0).a_loc(-9), -9);
+        assert_eq!(slot.a_locs(-12, 64), vec![-16, -8]);
+        assert_eq!(slot.a_locs(-8, 64), vec![-8]);
+    }
+
+    #[test]
+    fn store_and_load() {
+        let mut stack = AbstractStack::new(StackGranularity::Slot(8));
+        stack.store(-8, StridedInterval::from((64, 1)));
+        stack.store(-8, StridedInterval::from((64, 3)));
+        assert_eq!(stack.len(), 1);
+        assert_eq!(stack.load(-8, 64), Some(StridedInterval::new(64, 2, 1, 3)));
+        assert_eq!(stack.load(-8, 32), Some(StridedInterval::default_k(32)));
+        assert_eq!(stack.load(-16, 64), None);
+
+        stack.store(-8, StridedInterval::from((32, 5)));
+        assert_eq!(stack.load(-8, 64), Some(StridedInterval::default_k(64)));
+    }
+
+    #[test]
+    fn straddling_access_aliases_both_slots() {
+        let mut stack = AbstractStack::new(StackGranularity::Slot(2));
+        stack.store(-4, StridedInterval::from((16, 1)));
+        stack.store(-2, StridedInterval::from((16, 2)));
+
+        // The 4 bytes at -3 span the slots at -4, -2 and 0, of which two were stored to.
+        assert_eq!(stack.overlapped(-3, 32), vec![-4, -2, 0]);
+        assert_eq!(stack.load(-3, 32), Some(StridedInterval::default_k(32)));
+        assert_eq!(stack.load(-2, 16), Some(StridedInterval::from((16, 2))));
+
+        stack.store(-4, StridedInterval::from((32, 3)));
+        assert_eq!(stack.load(-4, 16), Some(StridedInterval::default_k(16)));
+        assert_eq!(stack.load(-2, 16), Some(StridedInterval::default_k(16)));
+    }
+
+    #[test]
+    fn load_part_of_wider_value() {
+        let mut stack = AbstractStack::new(StackGranularity::Byte);
+        stack.store(-8, StridedInterval::from((64, 1)));
+        stack.store(-4, StridedInterval::from((32, 2)));
+        assert_eq!(stack.overlapped(-4, 32), vec![-8, -4]);
+        assert_eq!(stack.load(-4, 32), Some(StridedInterval::default_k(32)));
+        assert_eq!(stack.load(-16, 32), None);
+    }
+}
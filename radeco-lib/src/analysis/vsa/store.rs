@@ -0,0 +1,64 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Abstract store of value set analysis, mapping SSA values to abstract sets of integers.
+
+use petgraph::graph::NodeIndex;
+use std::collections::HashMap;
+
+use super::abstract_set::abstract_set::AbstractSet;
+
+/// Maps SSA values to the abstract set of integers they may hold. Values missing from the store
+/// have not been reached by the analysis yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AbstractStore<T: AbstractSet> {
+    values: HashMap<NodeIndex, T>,
+}
+
+impl<T: AbstractSet> AbstractStore<T> {
+    pub fn new() -> AbstractStore<T> {
+        AbstractStore {
+            values: HashMap::new(),
+        }
+    }
+
+    /// Returns the abstract set of `node`, or `None` if `node` was not reached.
+    pub fn get(&self, node: NodeIndex) -> Option<T> {
+        self.values.get(&node).cloned()
+    }
+
+    pub fn set(&mut self, node: NodeIndex, value: T) {
+        self.values.insert(node, value);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&NodeIndex, &T)> {
+        self.values.iter()
+    }
+
+    /// Joins the sets of every value in `self` and `other`, e.g. where control flow merges.
+    pub fn join(&self, other: &AbstractStore<T>) -> AbstractStore<T> {
+        self.combine(other, |a, b| a.join(b))
+    }
+
+    /// Widens the sets of every value in `self` by those in `other`, e.g. at loop headers, so
+    /// that a value growing at each iteration reaches a fixpoint.
+    pub fn widen(&self, other: &AbstractStore<T>) -> AbstractStore<T> {
+        self.combine(other, |a, b| a.widen(b))
+    }
+
+    fn combine<F: Fn(&T, &T) -> T>(&self, other: &AbstractStore<T>, f: F) -> AbstractStore<T> {
+        let mut values = self.values.clone();
+        for (node, value) in &other.values {
+            let combined = match self.values.get(node) {
+                Some(prev) => f(prev, value),
+                None => *value,
+            };
+            values.insert(*node, combined);
+        }
+        AbstractStore { values: values }
+    }
+}
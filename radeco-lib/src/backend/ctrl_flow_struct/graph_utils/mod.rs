@@ -12,11 +12,13 @@ pub use self::ncd::nearest_common_dominator;
 
 use self::ix_bit_set::{IndexLike, IxBitSet};
 
+use petgraph::algo;
+use petgraph::algo::dominators::Dominators;
 use petgraph::graph::IndexType;
 use petgraph::prelude::*;
 use petgraph::visit::{
     IntoEdges, IntoEdgesDirected, IntoNeighbors, IntoNeighborsDirected, IntoNodeIdentifiers,
-    VisitMap, Visitable, Walker,
+    Reversed, VisitMap, Visitable, Walker,
 };
 
 use std::collections::HashMap;
@@ -204,6 +206,19 @@ where
     }
 }
 
+/// Computes the post-dominators of every node that can reach `exit`.
+///
+/// A node `d` *post-dominates* a node `n` if every path from `n` to `exit`
+/// passes through `d`. This is computed by running the dominator algorithm on
+/// the reversed graph, rooted at `exit`.
+pub fn post_dominators<G>(graph: G, exit: G::NodeId) -> Dominators<G::NodeId>
+where
+    G: IntoNeighborsDirected + Visitable,
+    G::NodeId: Eq + Hash,
+{
+    algo::dominators::simple_fast(Reversed(graph), exit)
+}
+
 /// Computes the transitive closure of the given directed acyclic graph.
 /// Returns, for each node, the set of nodes reachable from that node.
 pub fn dag_transitive_closure<G>(graph: G) -> HashMap<G::NodeId, IxBitSet<G::NodeId>>
@@ -148,6 +148,57 @@ fn qc_dominated_by(mut graph: StableDiGraph<(), ()>, root_i: usize, h_i: usize)
     TestResult::from_bool(dom_set == true_dom_set)
 }
 
+/// Tests that `post_dominators` agrees with the definition of post-dominance
+#[quickcheck]
+fn qc_post_dominators(graph: StableDiGraph<(), ()>, exit_i: usize) -> TestResult {
+    let nodes: Vec<_> = graph.node_indices().collect();
+    if nodes.is_empty() {
+        return TestResult::discard();
+    }
+    let exit = nodes[exit_i % nodes.len()];
+    let reaches_exit: IxBitSet<_> = Dfs::new(Reversed(&graph), exit)
+        .iter(Reversed(&graph))
+        .collect();
+
+    println!("graph: {:?}", graph);
+    println!("exit: {:?}", exit);
+
+    let pdoms = post_dominators(&graph, exit);
+
+    for &n in &nodes {
+        let pdom_set: Option<IxBitSet<_>> = pdoms.dominators(n).map(|ds| ds.collect());
+        if !reaches_exit.contains(n) {
+            if pdom_set.is_some() {
+                println!("{:?} can't reach exit but has post-dominators", n);
+                return TestResult::failed();
+            }
+            continue;
+        }
+        // `d` post-dominates `n` iff `exit` is unreachable from `n` once `d`
+        // is removed
+        let true_pdom_set: IxBitSet<_> = nodes
+            .iter()
+            .cloned()
+            .filter(|&d| {
+                if d == n || d == exit {
+                    return true;
+                }
+                let mut dfs = Dfs::new(&graph, n);
+                dfs.discovered.visit(d);
+                !dfs.iter(&graph).any(|m| m == exit)
+            })
+            .collect();
+        if pdom_set.as_ref() != Some(&true_pdom_set) {
+            println!("wrong post-dominators of {:?}:", n);
+            println!("  real: {:?}", true_pdom_set);
+            println!("  computed: {:?}", pdom_set);
+            return TestResult::failed();
+        }
+    }
+
+    TestResult::passed()
+}
+
 #[quickcheck]
 fn qc_dag_transitive_closure(graph: StableDiGraph<(), ()>) -> TestResult {
     if algo::is_cyclic_directed(&graph) {
@@ -7,6 +7,7 @@
 use super::c_ast;
 use super::c_ast::Ty;
 use super::c_cfg::{CCFGRef, CCFG};
+use super::expr_tree;
 use crate::frontend::radeco_containers::RadecoFunction;
 use crate::middle::ir::{MAddress, MOpcode};
 use crate::middle::ssa::cfg_traits::CFG;
@@ -166,6 +167,17 @@ impl<'a> CCFGBuilder<'a> {
 
     fn recover_action(&mut self, node: SSARef) -> CCFGRef {
         debug_assert!(self.is_recover_action(node));
+        // Temporaries are assigned where they are computed.
+        if let Some(&src) = self.datamap.temp_exprs.get(&node) {
+            let dst = self.datamap.var_map[&node];
+            let ret = self.assign(dst, src);
+            if is_debug() {
+                let addr = self.addr_str(node);
+                self.cfg
+                    .debug_info_at(ret, format!("{:?} = {:?} @ {}", dst, src, addr));
+            }
+            return ret;
+        }
         let op = self.ssa.opcode(node).unwrap_or(MOpcode::OpInvalid);
         radeco_trace!("CCFGBuilder::recover {:?} @ {:?}", op, node);
         match op {
@@ -232,7 +244,7 @@ impl<'a> CCFGBuilder<'a> {
         match op {
             MOpcode::OpCall | MOpcode::OpStore => true,
             MOpcode::OpLoad if self.datamap.is_used_by_call_store(node) => true,
-            _ => false,
+            _ => self.datamap.temp_exprs.contains_key(&node),
         }
     }
 
@@ -410,6 +422,10 @@ struct CCFGDataMap<'a> {
     // a map from the name of register to node data
     pub reg_map: HashMap<String, CCFGRef>,
     pub const_nodes: HashSet<SSARef>,
+    // Values kept as temporaries instead of being inlined into their consumer
+    temps: HashSet<SSARef>,
+    // a map from temporaries to the expression assigned to them
+    pub temp_exprs: HashMap<SSARef, CCFGRef>,
     seen: HashSet<SSARef>,
     regvar_version: u32,
 }
@@ -422,6 +438,8 @@ impl<'a> CCFGDataMap<'a> {
             var_map: HashMap::new(),
             reg_map: HashMap::new(),
             const_nodes: HashSet::new(),
+            temps: expr_tree::temporaries(rfn.ssa()),
+            temp_exprs: HashMap::new(),
             seen: HashSet::new(),
             regvar_version: 0,
         }
@@ -474,7 +492,19 @@ impl<'a> CCFGDataMap<'a> {
             expr_node,
             expr
         );
-        self.var_map.insert(ret_node, expr_node);
+        self.bind(ret_node, expr_node, cfg);
+    }
+
+    // Maps `node` to `expr_node`, or to a new temporary holding it if `node` is not inlined.
+    fn bind(&mut self, node: SSARef, expr_node: CCFGRef, cfg: &mut CCFG) {
+        if self.temps.contains(&node) {
+            let name = format!("t{}", self.temp_exprs.len());
+            let var = cfg.var(&name, None);
+            self.temp_exprs.insert(node, expr_node);
+            self.var_map.insert(node, var);
+        } else {
+            self.var_map.insert(node, expr_node);
+        }
     }
 
     fn deref(&self, node: SSARef, cfg: &mut CCFG) -> CCFGRef {
@@ -520,6 +550,13 @@ impl<'a> CCFGDataMap<'a> {
             MOpcode::OpRor => unimplemented!(),
             MOpcode::OpSub => Some(c_ast::Expr::Sub),
             MOpcode::OpXor => Some(c_ast::Expr::Xor),
+            // TODO Add float types
+            MOpcode::OpFAdd => Some(c_ast::Expr::Add),
+            MOpcode::OpFDiv => Some(c_ast::Expr::Div),
+            MOpcode::OpFEq => Some(c_ast::Expr::Eq),
+            MOpcode::OpFLt => Some(c_ast::Expr::Lt),
+            MOpcode::OpFMul => Some(c_ast::Expr::Mul),
+            MOpcode::OpFSub => Some(c_ast::Expr::Sub),
             // TODO Add `Narrow` info
             MOpcode::OpNarrow(size) => Some(c_ast::Expr::Cast(*size as usize)),
             // TODO Add `SignExt`
@@ -537,6 +574,8 @@ impl<'a> CCFGDataMap<'a> {
             (MOpcode::OpStore, None) => (None, operands),
             (MOpcode::OpLoad, None) => (None, operands),
             (MOpcode::OpCall, None) => (None, operands),
+            (MOpcode::OpIndirectJump, None) => (None, operands),
+            (MOpcode::OpAssume, None) => (None, operands),
             (_, exp) => (exp, operands),
         }
     }
@@ -596,11 +635,16 @@ impl<'a> CCFGDataMap<'a> {
                         self.add_regvar(ret_node, cfg);
                     } else {
                         let derefed = self.deref(ops[1], cfg);
-                        self.var_map.insert(ret_node, derefed);
+                        self.bind(ret_node, derefed, cfg);
                     }
                 }
                 MOpcode::OpCall => self.update_data_graph_by_call(ret_node, cfg),
-                MOpcode::OpMov => {
+                // TODO Emit a computed goto, indirect jumps are dropped for now
+                MOpcode::OpIndirectJump => {}
+                // Assumptions have no runtime semantics
+                MOpcode::OpAssume => {}
+                // TODO Add float conversions, they are forwarded like `OpMov` for now
+                MOpcode::OpMov | MOpcode::OpFToI | MOpcode::OpIToF => {
                     // ops[0] is forwarded to `ret_node`
                     let cfg_node = *self.var_map.get(&ops[0]).expect("This can not be `None`");
                     self.var_map.insert(ret_node, cfg_node);
@@ -0,0 +1,199 @@
+//! Expression tree reconstruction.
+//!
+//! Every operation of the SSA is a node of its own, printing them one by one yields one
+//! assignment per operation. Instead, a value used only once is inlined into its consumer, so
+//! that chains of operations are printed as one nested expression. Values used more than once
+//! are kept as temporaries, assigned where they are computed.
+//!
+//! A value reading memory is not inlined past a `Store` or a call, since its consumer would
+//! then read memory after it was written. It is not inlined into another block either.
+
+use crate::middle::ir::MOpcode;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::SSA;
+use crate::middle::ssa::ssastorage::{NodeData, SSAStorage};
+
+use std::collections::{HashMap, HashSet};
+
+type SSARef = <SSAStorage as SSA>::ValueRef;
+
+/// Returns the values of `ssa` which must be kept as temporaries rather than inlined into
+/// their consumer.
+pub fn temporaries(ssa: &SSAStorage) -> HashSet<SSARef> {
+    let mut reads_mem = HashMap::new();
+    let mut temps = HashSet::new();
+    for block in ssa.blocks() {
+        let exprs = ssa.exprs_in(block);
+        let position = exprs
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| (n, i))
+            .collect::<HashMap<_, _>>();
+        // Position in the block at which each value is evaluated, that is the one of the node
+        // it ends up inlined in. `None` if it is not evaluated within this block.
+        let mut evaluated_at: HashMap<SSARef, Option<usize>> = HashMap::new();
+        for (i, &node) in exprs.iter().enumerate().rev() {
+            if !is_inlinable(ssa, node) {
+                evaluated_at.insert(node, Some(i));
+                continue;
+            }
+            let uses = value_uses(ssa, node);
+            let consumer_at = match (uses.as_slice(), ssa.is_selector(node)) {
+                (&[], true) => Some(exprs.len()),
+                (&[consumer], false) if position.contains_key(&consumer) => {
+                    evaluated_at.get(&consumer).cloned().and_then(|at| at)
+                }
+                _ => None,
+            };
+            let single_use = uses.len() + ssa.is_selector(node) as usize == 1;
+            // A value consumed in another block, or by a phi, is never inlined.
+            let inlined = single_use
+                && consumer_at.map_or(false, |end| {
+                    !reads_memory(ssa, node, &mut reads_mem)
+                        || !exprs[i + 1..end.min(exprs.len())]
+                            .iter()
+                            .any(|&n| writes_memory(ssa, n))
+                });
+            if inlined {
+                evaluated_at.insert(node, consumer_at);
+            } else {
+                temps.insert(node);
+                evaluated_at.insert(node, Some(i));
+            }
+        }
+    }
+    temps
+}
+
+// Operations which are printed as an expression, and may be inlined.
+fn is_inlinable(ssa: &SSAStorage, node: SSARef) -> bool {
+    match ssa.opcode(node) {
+        Some(MOpcode::OpStore)
+        | Some(MOpcode::OpCall)
+        | Some(MOpcode::OpIndirectJump)
+        | Some(MOpcode::OpAssume)
+        | Some(MOpcode::OpMov)
+        | Some(MOpcode::OpFToI)
+        | Some(MOpcode::OpIToF)
+        | Some(MOpcode::OpInvalid)
+        | None => false,
+        Some(_) => true,
+    }
+}
+
+// Uses of `node` as an operand, register states only record it and are left out.
+fn value_uses(ssa: &SSAStorage, node: SSARef) -> Vec<SSARef> {
+    ssa.uses_of(node)
+        .into_iter()
+        .filter(|&u| match ssa.g[u] {
+            NodeData::RegisterState => false,
+            _ => true,
+        })
+        .collect()
+}
+
+fn writes_memory(ssa: &SSAStorage, node: SSARef) -> bool {
+    match ssa.opcode(node) {
+        Some(MOpcode::OpStore) | Some(MOpcode::OpCall) => true,
+        _ => false,
+    }
+}
+
+// Returns `true` if `node` is a `Load`, or is computed from one.
+fn reads_memory(ssa: &SSAStorage, node: SSARef, memo: &mut HashMap<SSARef, bool>) -> bool {
+    if let Some(&reads) = memo.get(&node) {
+        return reads;
+    }
+    let reads = match ssa.opcode(node) {
+        Some(MOpcode::OpLoad) => true,
+        Some(MOpcode::OpStore) | Some(MOpcode::OpCall) | None => false,
+        Some(_) => ssa
+            .operands_of(node)
+            .into_iter()
+            .any(|o| ssa.is_expr(o) && reads_memory(ssa, o, memo)),
+    };
+    memo.insert(node, reads);
+    reads
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::backend::lang_c::c_cfg_builder;
+    use crate::frontend::radeco_containers::RadecoFunction;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    // %3, %4 and %5 form a chain of single uses, %6 is used twice, %8 is read before a store
+    // and used after it, %11 is read right before its use.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const EXPRS_TXT: &str = "\
+; radeco-ir v1
+define-fun main(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %14: $Mem = $mem;
+    bb_0x001000.0000(sz 0x20):
+        [@0x001000.0001] %3: $Unknown64 = %1 + %2;
+        [@0x001000.0002] %4: $Unknown64 = %3 * %1;
+        [@0x001000.0003] %5: $Unknown64 = %4 - #x1;
+        [@0x001004.0001] %6: $Unknown64 = %1 ^ %2;
+        [@0x001004.0002] %7: $Unknown64 = %6 & %5;
+        [@0x001008.0001] %8: $Unknown64 = Load(%14, %1);
+        [@0x00100C.0001] %9: $Mem = Store(%14, %2, %6);
+        [@0x001010.0001] %10: $Unknown64 = %8 + #x1;
+        [@0x001014.0001] %11: $Unknown64 = Load(%9, %2);
+        [@0x001014.0002] %12: $Unknown64 = %11 + %7;
+        [@0x001018.0001] %13: $Mem = Store(%9, %10, %12);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %12;
+        $mem = %13;
+}
+";
+
+    #[test]
+    fn single_uses_inlined() {
+        let ssa = ir_reader::parse_il(EXPRS_TXT, REGISTER_FILE.clone());
+        let block = ssa
+            .blocks()
+            .into_iter()
+            .find(|&b| ssa.starting_address(b).map(|a| a.address) == Some(0x1000))
+            .unwrap();
+        let exprs = ssa.exprs_in(block);
+        let first = |opcode: &MOpcode| {
+            exprs
+                .iter()
+                .cloned()
+                .find(|&e| ssa.opcode(e).as_ref() == Some(opcode))
+                .unwrap()
+        };
+        // `%3`, `%4` and `%5` are inlined as `((%1 + %2) * %1) - 1`, as are `%7`, `%10` and
+        // `%11`.
+        let expected = [first(&MOpcode::OpXor), first(&MOpcode::OpLoad)]
+            .iter()
+            .cloned()
+            .collect::<HashSet<_>>();
+        assert_eq!(temporaries(&ssa), expected);
+
+        let mut rfn = RadecoFunction::default();
+        *rfn.ssa_mut() = ssa;
+        let ccfg = c_cfg_builder::recover_c_cfg(&rfn, &HashMap::new(), &HashMap::new());
+        let code = ccfg.to_c_ast().print();
+        assert!(code.contains("((rdi + rsi) * rdi) - 0x1"), "{}", code);
+    }
+}
@@ -8,6 +8,7 @@
 pub mod c_ast;
 pub mod c_cfg;
 pub mod c_cfg_builder;
+pub mod expr_tree;
 
 #[cfg(test)]
 mod test;
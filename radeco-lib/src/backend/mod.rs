@@ -12,3 +12,5 @@ pub mod x86 {
     pub mod x86_idioms;
 }
 pub mod ctrl_flow_struct;
+pub mod pseudocode;
+pub mod variables;
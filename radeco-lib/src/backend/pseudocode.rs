@@ -0,0 +1,143 @@
+// Copyright (c) 2015, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! C-like pseudocode of a function.
+//!
+//! The SSA of the function is lowered to a C control flow graph, which is structured into
+//! `if`/`else` and loops, and then printed. Calls are given the arguments recovered from the
+//! calling convention. No type is recovered yet, every variable is an `int`.
+
+use crate::backend::lang_c::c_cfg::ctrl_flow_struct;
+use crate::backend::lang_c::c_cfg_builder;
+use crate::frontend::radeco_containers::RadecoFunction;
+
+use std::collections::HashMap;
+
+/// Emits the pseudocode of `rfn`. Calls to the addresses of `fname_map` are printed with its
+/// names, and constants pointing to `strings` with its strings.
+pub fn emit(
+    rfn: &RadecoFunction,
+    fname_map: &HashMap<u64, String>,
+    strings: &HashMap<u64, String>,
+) -> Result<String, &'static str> {
+    let ccfg = c_cfg_builder::recover_c_cfg(rfn, fname_map, strings);
+    ctrl_flow_struct::structure_and_convert(ccfg).map(|cast| cast.print())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    // if (rdi == 0) { foo(rdi) } else { bar(rdi) }
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const IF_ELSE_TXT: &str = "\
+; radeco-ir v1
+define-fun main(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown1 = %1 == #x0;
+        JMP IF %3 0x001004.0000 ELSE 0x001008.0000
+    bb_0x001004.0000(sz 0x4):
+        [@0x001004.0001] (%4: $Mem = $mem) = CALL #x2000($rdi=%1, $mem=%2);
+        JMP 0x00100C.0000
+    bb_0x001008.0000(sz 0x4):
+        [@0x001008.0001] (%5: $Mem = $mem) = CALL #x3000($rdi=%1, $mem=%2);
+        JMP 0x00100C.0000
+    bb_0x00100C.0000(sz 0x1):
+        %6: $Mem = Phi(%4, %5);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %1;
+        $mem = %6;
+}
+";
+
+    // init(rdi); while (rdi != 0) { foo(rdi); rdi -= 1 }
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const WHILE_TXT: &str = "\
+; radeco-ir v1
+define-fun main(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] (%3: $Mem = $mem) = CALL #x4000($rdi=%1, $mem=%2);
+        JMP 0x001004.0000
+    bb_0x001004.0000(sz 0x4):
+        %4: $Unknown64 = Phi(%1, %7);
+        %5: $Mem = Phi(%3, %8);
+        [@0x001004.0001] %6: $Unknown1 = %4 == #x0;
+        JMP IF %6 0x00100C.0000 ELSE 0x001008.0000
+    bb_0x001008.0000(sz 0x4):
+        [@0x001008.0001] (%8: $Mem = $mem) = CALL #x2000($rdi=%4, $mem=%5);
+        [@0x001008.0002] %7: $Unknown64 = %4 - #x1;
+        JMP 0x001004.0000
+    bb_0x00100C.0000(sz 0x1):
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %4;
+        $mem = %5;
+}
+";
+
+    fn emit_il(il: &str) -> String {
+        let mut rfn = RadecoFunction::default();
+        *rfn.ssa_mut() = ir_reader::parse_il(il, REGISTER_FILE.clone());
+        let fname_map = [(0x2000, "foo"), (0x3000, "bar"), (0x4000, "init")]
+            .iter()
+            .map(|&(addr, name)| (addr, name.to_owned()))
+            .collect();
+        emit(&rfn, &fname_map, &HashMap::new()).unwrap()
+    }
+
+    // Asserts that each of `words` is found in `code`, after the previous one.
+    fn assert_in_order(code: &str, words: &[&str]) {
+        let mut rest = code;
+        for word in words {
+            match rest.find(word) {
+                Some(pos) => rest = &rest[pos + word.len()..],
+                None => panic!("`{}` not found in order in:\n{}", word, code),
+            }
+        }
+    }
+
+    #[test]
+    fn emit_if_else() {
+        let code = emit_il(IF_ELSE_TXT);
+        assert_in_order(&code, &["if", "(", "{", "} else {", "}"]);
+        // Each branch holds one of the calls.
+        let foo = code.find("foo(").unwrap();
+        let bar = code.find("bar(").unwrap();
+        let els = code.find("else").unwrap();
+        assert!((foo < els) != (bar < els), "{}", code);
+    }
+
+    #[test]
+    fn emit_while() {
+        let code = emit_il(WHILE_TXT);
+        assert_in_order(&code, &["init(", "while", "(", "{", "foo(", "}"]);
+        assert!(!code.contains("goto"), "{}", code);
+    }
+}
@@ -0,0 +1,253 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Variables of a function, as JSON.
+//!
+//! The machine-readable companion to the IR and the pseudocode of a function, e.g. for an IDE
+//! plugin: its local variables, arguments and return type, as recovered from its bindings.
+//!
+//! ```json
+//! {
+//!   "name": "main",
+//!   "offset": 4195741,
+//!   "locals": [{ "name": "local_8h", "base": "rbp", "offset": -8, "type": "int", "size": 4 }],
+//!   "args": [{ "name": "s", "index": 0, "register": "rdi", "type": "char *", "size": 8 }],
+//!   "return_type": "int"
+//! }
+//! ```
+//!
+//! A local lives at `offset` from the `base` register, the stack pointer if it was found on the
+//! stack. An argument passed on the stack has no `register`. The type of a variable is `null`
+//! when none was recovered, as is its size when it cannot be told from the type.
+
+use crate::frontend::radeco_containers::{BindingType, RadecoFunction, VarBinding};
+use crate::middle::regfile::RegisterId;
+
+use serde_json;
+
+/// Variables of a function.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionVariables {
+    pub name: String,
+    pub offset: u64,
+    pub locals: Vec<LocalVariable>,
+    pub args: Vec<Argument>,
+    pub return_type: Option<String>,
+}
+
+/// Local variable of a function.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalVariable {
+    pub name: String,
+    pub base: Option<String>,
+    pub offset: i64,
+    #[serde(rename = "type")]
+    pub ty: Option<String>,
+    pub size: Option<u64>,
+}
+
+/// Argument of a function.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Argument {
+    pub name: String,
+    pub index: usize,
+    pub register: Option<String>,
+    #[serde(rename = "type")]
+    pub ty: Option<String>,
+    pub size: Option<u64>,
+}
+
+impl FunctionVariables {
+    /// Collects the variables of `rfn` from its bindings.
+    pub fn of(rfn: &RadecoFunction) -> FunctionVariables {
+        let regfile = &rfn.ssa().regfile;
+        let ptr_size = regfile.pc_width().unwrap_or(64) / 8;
+        let sp = regfile
+            .get_name_by_alias(&"SP".to_owned())
+            .map(|s| s.to_owned());
+        let mut vars = FunctionVariables {
+            name: rfn.name.to_string(),
+            offset: rfn.offset,
+            locals: Vec::new(),
+            args: Vec::new(),
+            return_type: None,
+        };
+        for binding in rfn.bindings() {
+            let ty = type_of(binding);
+            let size = ty.as_ref().and_then(|ty| type_size(ty, ptr_size));
+            match binding.btype {
+                BindingType::RegisterLocal(ref base, offset) => vars.locals.push(LocalVariable {
+                    name: binding.name().to_owned(),
+                    base: Some(base.clone()),
+                    offset,
+                    ty,
+                    size,
+                }),
+                BindingType::StackLocal(offset) => vars.locals.push(LocalVariable {
+                    name: binding.name().to_owned(),
+                    base: sp.clone(),
+                    offset: offset as i64,
+                    ty,
+                    size,
+                }),
+                BindingType::RegisterArgument(index) => {
+                    let register = binding
+                        .ridx
+                        .and_then(|id| regfile.get_name(RegisterId::from_usize(id as usize)))
+                        .map(|name| name.to_owned());
+                    vars.args
+                        .push(Argument::new(binding, index, register, ty, size));
+                }
+                BindingType::StackArgument(index) => {
+                    vars.args
+                        .push(Argument::new(binding, index, None, ty, size));
+                }
+                BindingType::Return => vars.return_type = ty,
+                BindingType::Unknown => (),
+            }
+        }
+        vars.args.sort_by_key(|arg| arg.index);
+        vars
+    }
+}
+
+impl Argument {
+    fn new(
+        binding: &VarBinding,
+        index: usize,
+        register: Option<String>,
+        ty: Option<String>,
+        size: Option<u64>,
+    ) -> Argument {
+        // Arguments found from the calling convention are not named.
+        let name = match binding.name() {
+            "" => format!("arg{}", index),
+            name => name.to_owned(),
+        };
+        Argument {
+            name,
+            index,
+            register,
+            ty,
+            size,
+        }
+    }
+}
+
+/// Emits the variables of `rfn` as JSON.
+pub fn emit_json(rfn: &RadecoFunction) -> String {
+    serde_json::to_string_pretty(&FunctionVariables::of(rfn))
+        .expect("Variables are always serializable")
+}
+
+fn type_of(binding: &VarBinding) -> Option<String> {
+    match binding.type_str.trim() {
+        "" => None,
+        ty => Some(ty.to_owned()),
+    }
+}
+
+// Size in bytes of the C type `ty`, if it is a pointer or a well-known scalar.
+fn type_size(ty: &str, ptr_size: u64) -> Option<u64> {
+    if ty.ends_with('*') {
+        return Some(ptr_size);
+    }
+    let ty = ty
+        .trim_start_matches("const ")
+        .trim_start_matches("unsigned ")
+        .trim_start_matches("signed ");
+    match ty {
+        "char" | "bool" | "_Bool" | "int8_t" | "uint8_t" => Some(1),
+        "short" | "int16_t" | "uint16_t" => Some(2),
+        "int" | "float" | "int32_t" | "uint32_t" => Some(4),
+        "long long" | "double" | "int64_t" | "uint64_t" => Some(8),
+        "long" | "size_t" | "ssize_t" | "intptr_t" | "uintptr_t" => Some(ptr_size),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::regfile::SubRegisterFile;
+    use petgraph::graph::NodeIndex;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    // int greet(char *) { int count; long total; ... }
+    fn greet() -> RadecoFunction {
+        let mut rfn = RadecoFunction::new();
+        rfn.name = "sym.greet".into();
+        rfn.offset = 0x1000;
+        rfn.ssa_mut().regfile = REGISTER_FILE.clone();
+        let rdi = REGISTER_FILE.register_id_by_name("rdi").unwrap();
+        let binding = |btype, ty: &str, name: Option<&str>, ridx| {
+            VarBinding::new(
+                btype,
+                ty.to_owned(),
+                name.map(|n| n.to_owned()),
+                NodeIndex::end(),
+                ridx,
+            )
+        };
+        rfn.bindings_mut().extend(vec![
+            binding(
+                BindingType::RegisterArgument(0),
+                "char *",
+                None,
+                Some(rdi.to_u8() as u64),
+            ),
+            binding(BindingType::Return, "int", None, None),
+            binding(
+                BindingType::RegisterLocal("rbp".to_owned(), -4),
+                "int",
+                Some("count"),
+                None,
+            ),
+            binding(
+                BindingType::RegisterLocal("rbp".to_owned(), -16),
+                "long",
+                Some("total"),
+                None,
+            ),
+        ]);
+        rfn
+    }
+
+    #[test]
+    fn locals_and_pointer_argument() {
+        let json = emit_json(&greet());
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let expected: serde_json::Value = serde_json::from_str(
+            r#"{
+                "name": "sym.greet",
+                "offset": 4096,
+                "locals": [
+                    { "name": "count", "base": "rbp", "offset": -4, "type": "int", "size": 4 },
+                    { "name": "total", "base": "rbp", "offset": -16, "type": "long", "size": 8 }
+                ],
+                "args": [
+                    { "name": "arg0", "index": 0, "register": "rdi", "type": "char *", "size": 8 }
+                ],
+                "return_type": "int"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(value, expected);
+        let vars: FunctionVariables = serde_json::from_str(&json).unwrap();
+        assert_eq!(vars, FunctionVariables::of(&greet()));
+    }
+}
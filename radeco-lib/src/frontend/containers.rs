@@ -217,8 +217,8 @@ fn fix_call_info(rfn: &mut DefaultFnTy) {
                                 }),
                                 None,
                             )
-                            .unwrap_or_else(|| {
-                                radeco_err!("Cannot insert new constants");
+                            .unwrap_or_else(|_e| {
+                                radeco_err!("Cannot insert new constants: {}", _e);
                                 ssa.invalid_value().unwrap()
                             });
                         ssa.op_unuse(*call_node, *arg_node);
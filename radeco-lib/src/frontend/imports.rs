@@ -17,6 +17,7 @@ impl ImportInfo {
     pub fn new_stub(plt: u64, name: Cow<'static, str>) -> ImportInfo {
         let mut rfn = RadecoFunction::default();
         rfn.name = name.clone();
+        rfn.set_noreturn(is_noreturn_name(&name));
         ImportInfo {
             plt: plt,
             name: name,
@@ -24,3 +25,48 @@ impl ImportInfo {
         }
     }
 }
+
+/// Well-known functions which never return to their caller.
+pub const NORETURN_FUNCTIONS: &[&str] = &[
+    "__assert_fail",
+    "__cxa_throw",
+    "__stack_chk_fail",
+    "_Exit",
+    "_exit",
+    "abort",
+    "err",
+    "errx",
+    "exit",
+    "longjmp",
+    "pthread_exit",
+    "quick_exit",
+    "siglongjmp",
+    "verr",
+    "verrx",
+];
+
+/// Returns `true` if `name` is the name of an import in `NORETURN_FUNCTIONS`, either bare as in the
+/// import table or with the `sym.imp.` prefix added by r2. Local functions such as `sym.exit` are
+/// not assumed to be the well-known ones.
+pub fn is_noreturn_name(name: &str) -> bool {
+    let name = if name.starts_with("sym.imp.") {
+        &name["sym.imp.".len()..]
+    } else {
+        name
+    };
+    NORETURN_FUNCTIONS.contains(&name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn noreturn_names_are_imports() {
+        assert!(is_noreturn_name("exit"));
+        assert!(is_noreturn_name("sym.imp.abort"));
+        assert!(!is_noreturn_name("sym.exit"));
+        assert!(!is_noreturn_name("sym.my.exit"));
+        assert!(!is_noreturn_name("sym.imp.puts"));
+    }
+}
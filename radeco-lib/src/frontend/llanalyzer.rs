@@ -7,7 +7,7 @@ use petgraph::graph::NodeIndex;
 use petgraph::Direction;
 use r2api::structs::FunctionInfo;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 /// Converts call graph information from `Source`, represented in FunctionInfo,
 /// into an actual graph with links.
 pub fn load_call_graph(finfos: &[FunctionInfo], rmod: &RadecoModule) -> CallGraph {
@@ -45,22 +45,57 @@ pub fn load_call_graph(finfos: &[FunctionInfo], rmod: &RadecoModule) -> CallGrap
     // Initial callsite analysis to fix up CallContextInfo on the edges.
     for (offset, node) in &node_map {
         if let Some(rfn) = rmod.functions.get(offset) {
-            let mut csites = analyze_callsite_initial(rfn);
-            // Get callees of the node to update the cctx information that we just discovered
-            let mut edges = cg.neighbors_directed(*node, Direction::Outgoing).detach();
-            while let Some(call_edge) = edges.next_edge(&cg) {
-                if let Some(cctx) = cg.edge_weight_mut(call_edge) {
-                    if let Some(new_cctx) = csites.remove(&cctx.csite) {
-                        *cctx = new_cctx;
-                    }
-                }
-            }
+            update_call_sites(&mut cg, *node, rfn);
         }
     }
 
     cg
 }
 
+/// Points the CallContextInfo of the calls from `rfn`, whose node in `cg` is `node`, to the call
+/// sites of its SSA, e.g. once the SSA is constructed again.
+pub fn update_call_sites(cg: &mut CallGraph, node: NodeIndex, rfn: &RadecoFunction) {
+    let mut csites = analyze_callsite_initial(rfn);
+    // Get callees of the node to update the cctx information that we just discovered
+    let mut edges = cg.neighbors_directed(node, Direction::Outgoing).detach();
+    while let Some(call_edge) = edges.next_edge(cg) {
+        if let Some(cctx) = cg.edge_weight_mut(call_edge) {
+            if let Some(new_cctx) = csites.remove(&cctx.csite) {
+                *cctx = new_cctx;
+            }
+        }
+    }
+}
+
+/// Adds to `cg` the direct calls found in the disassembly of the functions of `rmod` which are
+/// missing from it. Both the caller and the callee must already have a node in `cg`.
+pub fn scan_direct_calls(cg: &mut CallGraph, rmod: &RadecoModule) {
+    let node_map = cg
+        .node_indices()
+        .map(|n| (cg[n], n))
+        .collect::<HashMap<_, _>>();
+    for (offset, rfn) in &rmod.functions {
+        let cn = match node_map.get(offset) {
+            Some(&cn) => cn,
+            None => continue,
+        };
+        let known = cg
+            .edges_directed(cn, Direction::Outgoing)
+            .map(|e| e.weight().csite)
+            .collect::<HashSet<_>>();
+        for (csite, target) in rfn.direct_calls() {
+            if known.contains(&csite) {
+                continue;
+            }
+            if let Some(&tn) = node_map.get(&target) {
+                let mut cctx = CallContextInfo::default();
+                cctx.csite = csite;
+                cg.add_edge(cn, tn, cctx);
+            }
+        }
+    }
+}
+
 /// Iterates through nodes in SSA for rfn and initializes the inital CallContextInfo
 fn analyze_callsite_initial(rfn: &RadecoFunction) -> HashMap<u64, CallContextInfo> {
     let mut cctxs = HashMap::new();
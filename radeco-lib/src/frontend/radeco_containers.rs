@@ -29,24 +29,28 @@
 //!
 //! For more examples of loading, check the `examples/` directory of this project.
 
-use crate::frontend::imports::ImportInfo;
+use crate::analysis::analyzer::{AnalyzerKind, PrerequisiteError};
+use crate::analysis::vsa::memory::{Endian, MemoryImage};
+use crate::frontend::imports::{self, ImportInfo};
 use crate::frontend::llanalyzer;
 use crate::frontend::radeco_source::Source;
-use crate::frontend::ssaconstructor::SSAConstruct;
+use crate::frontend::ssaconstructor::{self, ConstructError, SSAConstruct};
 
+use crate::middle::ir::MAddress;
 use crate::middle::regfile::{RegisterUsage, SubRegisterFile};
 use crate::middle::ssa::cfg_traits::CFG;
 use crate::middle::ssa::ssa_traits::{NodeType, SSA};
 
 use crate::middle::ssa::ssastorage::SSAStorage;
+use crate::middle::ssa::utils;
 use petgraph::Direction;
 
 use petgraph::graph::{Graph, NodeIndex};
 use petgraph::visit::EdgeRef;
 use r2api::api_trait::R2Api;
 use r2api::structs::{
-    LCCInfo, LEntryInfo, LExportInfo, LOpInfo, LRelocInfo, LSectionInfo, LStringInfo, LSymbolInfo,
-    LSymbolType, LVarInfo,
+    LCCInfo, LEntryInfo, LExportInfo, LOpInfo, LRegInfo, LRelocInfo, LSectionInfo, LStringInfo,
+    LSymbolInfo, LSymbolType, LVarInfo,
 };
 
 use r2pipe::r2::R2;
@@ -55,10 +59,14 @@ use std::borrow::Cow;
 use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::collections::btree_map;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error;
 use std::fmt;
+use std::mem;
+use std::ops::Range;
 use std::rc::Rc;
 use std::slice;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 use std::sync::Arc;
 
 // use cpuprofiler::PROFILER;
@@ -109,41 +117,42 @@ pub mod loader_defaults {
         if let Some(ref src) = source {
             let mut new_fl = FLResult::default();
 
-            // FIXME -> Handle errors properly.
+            // The listing of functions is checked by `ModuleLoader::try_load`, a source which
+            // does not know about relocations or imports only lacks the kind of its functions.
             let relocs = src
                 .relocs()
-                .expect("failed to get relocs")
+                .unwrap_or_default()
                 .into_iter()
-                .map(|reloc| reloc.name.unwrap())
+                .filter_map(|reloc| reloc.name)
                 .collect::<Vec<_>>();
             let imports = src
                 .imports()
-                .expect("failed to get imports")
+                .unwrap_or_default()
                 .into_iter()
-                .map(|import| import.name.unwrap())
+                .filter_map(|import| import.name)
                 .collect::<Vec<_>>();
 
-            let functions = src.functions().expect("failed to get functions");
+            let functions = src.functions().unwrap_or_default();
 
             functions
                 .into_iter()
-                .map(|f| {
-                    let name = f.clone().name.unwrap();
+                .filter_map(|f| {
+                    let name = f.name.clone()?;
 
                     if relocs.iter().any(|reloc| name.ends_with(reloc)) {
-                        (f, FunctionKind::Relocated)
+                        Some((f, FunctionKind::Relocated))
                     } else if imports.iter().any(|import| name.ends_with(import)) {
-                        (f, FunctionKind::Imported)
+                        Some((f, FunctionKind::Imported))
                     } else {
-                        (f, FunctionKind::Local)
+                        Some((f, FunctionKind::Local))
                     }
                 })
                 .for_each(|(function, kind)| {
                     let mut rfn = RadecoFunction::default();
-                    rfn.offset = function.offset.unwrap();
-                    rfn.size = function.size.unwrap();
-                    rfn.name = Cow::from(function.name.as_ref().unwrap().to_owned());
-                    rfn.callconv_name = function.calltype.as_ref().unwrap().to_owned();
+                    rfn.offset = function.offset.unwrap_or_default();
+                    rfn.size = function.size.unwrap_or_default();
+                    rfn.name = Cow::from(function.name.unwrap_or_default());
+                    rfn.callconv_name = function.calltype.unwrap_or_default();
                     rfn.kind = kind;
                     new_fl.functions.insert(rfn.offset, rfn);
                     new_fl.new = new_fl.new + 1;
@@ -211,6 +220,12 @@ pub struct RadecoModule {
     pub functions: BTreeMap<u64, RadecoFunction>,
     /// Source used to load this module
     pub source: Option<Rc<dyn Source>>,
+    /// Address ranges of memory-mapped I/O, whose accesses must be preserved
+    volatile: Vec<Range<u64>>,
+    /// Byte order of the target, as reported by the loader
+    endian: Endian,
+    /// Number of SSA constructions done while loading
+    ssa_constructions: usize,
 }
 
 impl fmt::Debug for RadecoModule {
@@ -364,6 +379,26 @@ impl Default for FunctionKind {
     }
 }
 
+/// What is known about the data at the target of a data reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataTarget {
+    /// A symbol starts at the target
+    Symbol(String),
+    /// A string literal starts at the target
+    String(String),
+}
+
+/// A reference from a function to a data address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataRef {
+    /// Address of the referencing instruction, if it could be identified
+    pub from: Option<u64>,
+    /// Referenced data address
+    pub to: u64,
+    /// Symbol or string at `to`, if known
+    pub target: Option<DataTarget>,
+}
+
 #[derive(Debug, Clone, Default)]
 /// Container to store information about identified function.
 /// Used as a basic unit in intra-functional analysis.
@@ -374,6 +409,14 @@ pub struct RadecoFunction {
     pub instructions: Vec<LOpInfo>,
     /// Is current function known to be recursive
     is_recursive: Option<bool>,
+    /// Is current function known to never return to its caller
+    noreturn: bool,
+    /// Error reported when constructing the SSA of the function, if any
+    construct_error: Option<ConstructError>,
+    /// Analyzers which were run on the function
+    analyzed: HashSet<AnalyzerKind>,
+    /// Store each load reads from, as found by the Memory SSA
+    clobbering_stores: HashMap<NodeIndex, NodeIndex>,
     /// Human readable name for the function. Taken either from
     /// the symbol table or assigned based on offset.
     pub name: Cow<'static, str>,
@@ -383,6 +426,8 @@ pub struct RadecoFunction {
     size: u64,
     /// List of (data-) addresses this function references
     datarefs: Vec<u64>,
+    /// Symbols and strings known to be at the addresses in `datarefs`
+    dataref_targets: HashMap<u64, DataTarget>,
     /// Constructed SSA for the function
     ssa: SSAStorage,
     /// Node index in the module-level callgraph
@@ -413,6 +458,8 @@ pub struct ProjectLoader<'a> {
     filter_modules: Option<fn(&RadecoModule) -> bool>,
     source: Option<Rc<dyn Source>>,
     mloader: Option<ModuleLoader<'a>>,
+    record_esil: bool,
+    max_insns: Option<usize>,
 }
 
 impl<'a> ProjectLoader<'a> {
@@ -424,6 +471,8 @@ impl<'a> ProjectLoader<'a> {
             filter_modules: None,
             source: None,
             mloader: None,
+            record_esil: false,
+            max_insns: None,
         }
     }
     // TODO:
@@ -471,11 +520,34 @@ impl<'a> ProjectLoader<'a> {
         self
     }
 
+    /// Record the ESIL of the instructions on the SSA, see `ModuleLoader::record_esil`.
+    /// Only used if no `ModuleLoader` is configured.
+    pub fn record_esil(mut self) -> ProjectLoader<'a> {
+        self.record_esil = true;
+        self
+    }
+
+    /// Skip the functions of more than `max` instructions, see `ModuleLoader::max_insns`.
+    /// Only used if no `ModuleLoader` is configured.
+    pub fn max_insns(mut self, max: usize) -> ProjectLoader<'a> {
+        self.max_insns = Some(max);
+        self
+    }
+
     /// Kick everything off based on the config/defaults
-    pub fn load(mut self) -> RadecoProject {
+    ///
+    /// Panics if the project cannot be loaded, see `try_load`.
+    pub fn load(self) -> RadecoProject {
+        self.try_load().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Kick everything off based on the config/defaults, failing if the source does not provide
+    /// the information needed to load the project.
+    pub fn try_load(mut self) -> Result<RadecoProject, FrontendError> {
         if self.source.is_none() {
             // Load r2 source.
-            let mut r2 = R2::new(Some(&self.path)).expect("Unable to open r2");
+            let mut r2 =
+                R2::new(Some(&self.path)).map_err(|e| FrontendError::OpenFailed(e.to_string()))?;
             let _ = r2.raw("e bin.minstr=1".to_string());
             //New r2 process is launched thus it needs to analyze
             r2.analyze_all();
@@ -488,41 +560,47 @@ impl<'a> ProjectLoader<'a> {
         // TODO: Load more arch specific information from the source
 
         if self.mloader.is_none() {
-            self.mloader = Some(
-                ModuleLoader::default()
-                    .source(Rc::clone(source))
-                    .build_ssa()
-                    .build_callgraph()
-                    .load_datarefs()
-                    .load_locals()
-                    .parallel()
-                    // .assume_cc()
-                    .stub_imports(),
-            );
+            let mloader = ModuleLoader::default()
+                .source(Rc::clone(source))
+                .build_ssa()
+                .build_callgraph()
+                .load_datarefs()
+                .load_locals()
+                .parallel()
+                // .assume_cc()
+                .stub_imports();
+            let mloader = if self.record_esil {
+                mloader.record_esil()
+            } else {
+                mloader
+            };
+            self.mloader = Some(match self.max_insns {
+                Some(max) => mloader.max_insns(max),
+                None => mloader,
+            });
         }
 
+        let reg_p = source
+            .register_profile()
+            .map_err(|e| FrontendError::MissingRegisterProfile(e.to_string()))?;
         let mut mod_map = Vec::new();
 
         {
             let mod_loader = self.mloader.as_mut().unwrap();
             // TODO: Set name correctly
-            mod_map.push(mod_loader.load(Rc::clone(source)));
+            mod_map.push(mod_loader.try_load_with_profile(Rc::clone(source), &reg_p)?);
         }
 
         // Clear out irrelevant fields in self and move it into project loader
         // XXX: Do when needed!
         // self.mod_loader = None;
-        let regfile = SubRegisterFile::new(
-            &source
-                .register_profile()
-                .expect("Unable to load register profile"),
-        );
+        let regfile = SubRegisterFile::new(&reg_p);
 
-        RadecoProject {
+        Ok(RadecoProject {
             modules: mod_map,
             // XXX
             reginfo: Arc::new(regfile),
-        }
+        })
     }
 }
 
@@ -629,6 +707,10 @@ pub struct ModuleLoader<'a> {
     parallel: bool,
     assume_cc: bool,
     stub_imports: bool,
+    record_esil: bool,
+    forward_thunks: bool,
+    max_insns: Option<usize>,
+    delay_slots: bool,
 }
 
 impl<'a> ModuleLoader<'a> {
@@ -692,6 +774,35 @@ impl<'a> ModuleLoader<'a> {
         self
     }
 
+    /// Record on the SSA of the functions the ESIL of the instructions it was built from, so
+    /// that it can be printed along with the IL. Only used if `build_ssa` is set.
+    pub fn record_esil(mut self) -> ModuleLoader<'a> {
+        self.record_esil = true;
+        self
+    }
+
+    /// Reports the calls to thunks jumping to an import, such as PLT stubs r2 did not name, as
+    /// calls to the import in the call graph. Only used if `build_callgraph` is set.
+    pub fn forward_thunks(mut self) -> ModuleLoader<'a> {
+        self.forward_thunks = true;
+        self
+    }
+
+    /// Do not construct the SSA of the functions of more than `max` instructions, they are left
+    /// empty with a `ConstructError::TooLarge`. Only used if `build_ssa` is set.
+    pub fn max_insns(mut self, max: usize) -> ModuleLoader<'a> {
+        self.max_insns = Some(max);
+        self
+    }
+
+    /// Constructs the SSA of an architecture with branch delay slots, such as MIPS or SPARC: the
+    /// instruction following a branch, a call or a return executes before control is transferred.
+    /// This is the default for MIPS and SPARC binaries. Only used if `build_ssa` is set.
+    pub fn delay_slots(mut self) -> ModuleLoader<'a> {
+        self.delay_slots = true;
+        self
+    }
+
     fn init_fn_bindings(rfn: &mut RadecoFunction, sub_reg_f: &SubRegisterFile) {
         // Setup binding information for functions based on reg_p. Note that this essential
         // marks the "potential" arguments without worrying about if they're ever used. Future
@@ -778,13 +889,54 @@ impl<'a> ModuleLoader<'a> {
     }
 
     /// Kick everything off and load module information based on config and defaults
+    ///
+    /// Panics if the module cannot be loaded, see `try_load`.
     pub fn load(&mut self, src: Rc<dyn Source>) -> RadecoModule {
+        self.try_load(src).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Kick everything off and load module information based on config and defaults, failing if
+    /// the source does not provide the information of the binary, its register profile or a
+    /// well-formed listing of its functions.
+    pub fn try_load(&mut self, src: Rc<dyn Source>) -> Result<RadecoModule, FrontendError> {
+        let reg_p = {
+            let source = self.source.as_ref().unwrap_or(&src);
+            source
+                .register_profile()
+                .map_err(|e| FrontendError::MissingRegisterProfile(e.to_string()))?
+        };
+        self.try_load_with_profile(src, &reg_p)
+    }
+
+    /// Same as `try_load`, with the register profile already read from the source.
+    fn try_load_with_profile(
+        &mut self,
+        src: Rc<dyn Source>,
+        reg_p: &LRegInfo,
+    ) -> Result<RadecoModule, FrontendError> {
         let source = if self.source.is_some() {
             self.source.as_ref().unwrap()
         } else {
             &src
         };
 
+        let bin_info = source
+            .bin_info()
+            .map_err(|e| FrontendError::MissingBinInfo(e.to_string()))?;
+        let functions = source
+            .functions()
+            .map_err(|e| FrontendError::MalformedFunctions(e.to_string()))?;
+        if let Some(f) = functions
+            .iter()
+            .find(|f| f.name.is_none() || f.offset.is_none() || f.size.is_none())
+        {
+            let what = match f.name {
+                Some(ref name) => format!("function {} lacks its offset or size", name),
+                None => "a function has no name".to_owned(),
+            };
+            return Err(FrontendError::MalformedFunctions(what));
+        }
+
         if self.floader.is_none() {
             self.floader = Some(FunctionLoader::default().include_defaults());
         }
@@ -805,6 +957,15 @@ impl<'a> ModuleLoader<'a> {
             Err(_e) => radeco_warn!(_e),
         }
 
+        rmod.endian = Endian::from_bin_info(&bin_info).unwrap_or_default();
+        // The branches of MIPS and SPARC have a delay slot.
+        let delay_slots = self.delay_slots
+            || bin_info
+                .bin
+                .as_ref()
+                .and_then(|bin| bin.arch.as_ref())
+                .map_or(false, |arch| arch == "mips" || arch == "sparc");
+
         match source.imports() {
             // TODO: Set the node in callgraph, either now or later.
             Ok(import_info) => {
@@ -878,6 +1039,18 @@ impl<'a> ModuleLoader<'a> {
             }
         }
 
+        // Seed the no-return attribute from the well-known functions. Import stubs are seeded
+        // when they are created.
+        for rfn in rmod.functions.values_mut() {
+            let imported = match rfn.kind {
+                FunctionKind::Imported => true,
+                _ => false,
+            };
+            if imported && imports::is_noreturn_name(&rfn.name) {
+                rfn.set_noreturn(true);
+            }
+        }
+
         // Load calling conventions for all functions and imports
         for (&rfn_addr, rfn) in &mut rmod.functions {
             rfn.callconv = source.cc_info_of(rfn_addr).ok();
@@ -890,21 +1063,93 @@ impl<'a> ModuleLoader<'a> {
             }
         }
 
+        // Load the data references first, the SSA records the ones of every instruction.
+        if self.load_datarefs {
+            for info in &functions {
+                let datarefs = info.datarefs.clone().unwrap_or_default();
+                let targets = datarefs
+                    .iter()
+                    .filter_map(|&addr| rmod.data_target(addr).map(|t| (addr, t)))
+                    .collect();
+                if let Some(rfn) = rmod.functions.get_mut(&info.offset.unwrap()) {
+                    rfn.datarefs = datarefs;
+                    rfn.dataref_targets = targets;
+                }
+            }
+        }
+
         // Optionally construct the SSA.
-        let reg_p = source
-            .register_profile()
-            .expect("Unable to load register profile");
-        let sub_reg_f = SubRegisterFile::new(&reg_p);
+        let sub_reg_f = SubRegisterFile::new(reg_p);
         if self.build_ssa {
-            if self.parallel {
+            // A function whose paths all end in a call to a no-return function does not return
+            // either, so construct again the callers of the newly found no-return functions until
+            // no more are found.
+            let mut noreturn = rmod.noreturn_addresses();
+            let mut pending = rmod.functions.keys().cloned().collect::<HashSet<_>>();
+            while !pending.is_empty() {
                 let ascc = self.assume_cc;
-                rmod.functions.par_iter_mut().for_each(|(_, rfn)| {
-                    SSAConstruct::<SSAStorage>::construct(rfn, &reg_p, ascc, true);
-                });
-            } else {
-                for rfn in rmod.functions.values_mut() {
-                    SSAConstruct::<SSAStorage>::construct(rfn, &reg_p, self.assume_cc, true);
+                let record_esil = self.record_esil;
+                let max_insns = self.max_insns;
+                let targets = &noreturn;
+                let constructions = AtomicUsize::new(0);
+                let construct = |(addr, rfn): (&u64, &mut RadecoFunction)| {
+                    if pending.contains(addr) {
+                        *rfn.ssa_mut() = SSAStorage::new();
+                        match max_insns {
+                            Some(max) if rfn.instructions().len() > max => {
+                                radeco_warn!(
+                                    "Skipping {}: {} instructions",
+                                    rfn.name,
+                                    rfn.instructions().len()
+                                );
+                                SSAConstruct::<SSAStorage>::construct_skipped(rfn, reg_p, max);
+                                return;
+                            }
+                            _ => {}
+                        }
+                        constructions.fetch_add(1, AtomicOrdering::Relaxed);
+                        let branches = if delay_slots {
+                            ssaconstructor::delay_slot_branches(rfn.instructions())
+                        } else {
+                            HashSet::new()
+                        };
+                        let xrefs = rfn.instruction_xrefs();
+                        SSAConstruct::<SSAStorage>::construct_configured(
+                            rfn,
+                            Arc::new(SubRegisterFile::new(reg_p)),
+                            ascc,
+                            true,
+                            targets,
+                            |constr| {
+                                constr.set_delay_slots(branches);
+                                constr.set_xrefs(xrefs);
+                            },
+                        );
+                        if record_esil {
+                            let ops = rfn.instructions().to_vec();
+                            ssaconstructor::record_esil(rfn.ssa_mut(), &ops);
+                        }
+                    }
+                };
+                if self.parallel {
+                    rmod.functions.par_iter_mut().for_each(construct);
+                } else {
+                    rmod.functions.iter_mut().for_each(construct);
                 }
+                rmod.ssa_constructions += constructions.into_inner();
+
+                let found = rmod
+                    .noreturn_addresses()
+                    .difference(&noreturn)
+                    .cloned()
+                    .collect::<HashSet<_>>();
+                pending = rmod
+                    .functions
+                    .iter()
+                    .filter(|(_, rfn)| rfn.calls_any(&found))
+                    .map(|(&addr, _)| addr)
+                    .collect();
+                noreturn.extend(found);
             }
         }
 
@@ -912,25 +1157,26 @@ impl<'a> ModuleLoader<'a> {
             for ifn in rmod.imports.values_mut() {
                 SSAConstruct::<SSAStorage>::construct(
                     &mut ifn.rfn.borrow_mut(),
-                    &reg_p,
+                    reg_p,
                     self.assume_cc,
                     true,
                 );
+                rmod.ssa_constructions += 1;
             }
         }
 
         // Load optional information. These need support from `Source` for analysis
-        if self.build_callgraph || self.load_datarefs || self.load_locals {
-            let aux_info = match source.functions() {
-                Ok(info) => info,
-                Err(_e) => {
-                    radeco_warn!(_e);
-                    Vec::new()
-                }
-            };
+        if self.build_callgraph || self.load_locals {
+            let aux_info = functions;
 
             if self.build_callgraph {
                 rmod.callgraph = llanalyzer::load_call_graph(aux_info.as_slice(), &rmod);
+                if !self.build_ssa {
+                    // Without SSA, fall back to the direct calls found in the disassembly.
+                    let mut callgraph = mem::replace(&mut rmod.callgraph, CallGraph::new());
+                    llanalyzer::scan_direct_calls(&mut callgraph, &rmod);
+                    rmod.callgraph = callgraph;
+                }
                 // Iterate through nodes and associate nodes with the correct functions
                 for nidx in rmod.callgraph.node_indices() {
                     if let Some(cg_addr) = rmod.callgraph.node_weight(nidx) {
@@ -944,13 +1190,8 @@ impl<'a> ModuleLoader<'a> {
                         }
                     }
                 }
-            }
-
-            if self.load_datarefs {
-                for info in &aux_info {
-                    if let Some(mut rfn) = rmod.functions.get_mut(&info.offset.unwrap()) {
-                        rfn.datarefs = info.datarefs.clone().unwrap_or_default();
-                    }
+                if self.forward_thunks {
+                    rmod.forward_thunks();
                 }
             }
 
@@ -997,7 +1238,7 @@ impl<'a> ModuleLoader<'a> {
         // Set source
         rmod.source = Some(Rc::clone(&source));
 
-        rmod
+        Ok(rmod)
     }
 
     /// Setup a function loader for the module
@@ -1131,6 +1372,57 @@ impl RadecoProject {
     }
 }
 
+/// Error returned when merging two `RadecoModule`s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeError {
+    /// A function or import of the merged module starts at an address already in use.
+    AddressCollision(u64),
+}
+
+impl error::Error for MergeError {}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MergeError::AddressCollision(addr) => {
+                write!(f, "address {:#x} is used by both modules", addr)
+            }
+        }
+    }
+}
+
+/// Error returned when a `RadecoProject` or `RadecoModule` cannot be loaded from its `Source`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrontendError {
+    /// The binary could not be opened in r2.
+    OpenFailed(String),
+    /// The source does not provide the information of the binary.
+    MissingBinInfo(String),
+    /// The source does not provide the register profile of the architecture.
+    MissingRegisterProfile(String),
+    /// The source does not provide a listing of functions, or one without name, offset or size.
+    MalformedFunctions(String),
+}
+
+impl error::Error for FrontendError {}
+
+impl fmt::Display for FrontendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FrontendError::OpenFailed(ref e) => write!(f, "unable to open the binary: {}", e),
+            FrontendError::MissingBinInfo(ref e) => {
+                write!(f, "unable to load the binary information: {}", e)
+            }
+            FrontendError::MissingRegisterProfile(ref e) => {
+                write!(f, "unable to load the register profile: {}", e)
+            }
+            FrontendError::MalformedFunctions(ref e) => {
+                write!(f, "unable to load the functions: {}", e)
+            }
+        }
+    }
+}
+
 impl RadecoModule {
     pub fn new(path: String) -> RadecoModule {
         let mut rmod = RadecoModule::default();
@@ -1142,6 +1434,12 @@ impl RadecoModule {
         &*self.name
     }
 
+    /// Number of times the SSA of a function or import stub was constructed while loading this
+    /// module. Functions constructed again to propagate the no-return attribute count each time.
+    pub fn ssa_constructions(&self) -> usize {
+        self.ssa_constructions
+    }
+
     pub fn function(&self, offset: u64) -> Option<&RadecoFunction> {
         self.functions.get(&offset)
     }
@@ -1150,6 +1448,24 @@ impl RadecoModule {
         self.functions.get_mut(&offset)
     }
 
+    /// Offsets of all the functions of the module, in increasing order.
+    pub fn function_offsets(&self) -> Vec<u64> {
+        self.functions.keys().cloned().collect()
+    }
+
+    /// Moves the function at `offset` out of the module, e.g. to analyze it with the rest of the
+    /// module borrowed, without copying its SSA. The function keeps its node in the callgraph and
+    /// is put back with `restore_function`.
+    pub fn take_function(&mut self, offset: u64) -> Option<RadecoFunction> {
+        self.functions.remove(&offset)
+    }
+
+    /// Puts back a function moved out with `take_function`. Returns the function which was
+    /// replaced, if any.
+    pub fn restore_function(&mut self, rfn: RadecoFunction) -> Option<RadecoFunction> {
+        self.functions.insert(rfn.offset, rfn)
+    }
+
     pub fn function_rename(&mut self, offset: u64, new_name: &str) -> Option<String> {
         assert!(!new_name.is_empty());
         let mut old_name = None;
@@ -1184,6 +1500,78 @@ impl RadecoModule {
         &self.strings
     }
 
+    /// Marks accesses to `range` as volatile, e.g. for memory-mapped I/O regions. Loads and
+    /// stores that may fall in a volatile range are never removed.
+    pub fn add_volatile_range(&mut self, range: Range<u64>) {
+        self.volatile.push(range);
+    }
+
+    pub fn volatile_ranges(&self) -> &[Range<u64>] {
+        &self.volatile
+    }
+
+    /// Returns `true` if `addr` falls in one of the volatile ranges of this module.
+    pub fn is_volatile(&self, addr: u64) -> bool {
+        self.volatile.iter().any(|r| r.contains(&addr))
+    }
+
+    pub fn endian(&self) -> Endian {
+        self.endian
+    }
+
+    /// Returns the initialized memory known to this module, i.e. the bytes of the strings found
+    /// by the loader, for the value set analysis to read constants from.
+    pub fn memory_image(&self) -> MemoryImage {
+        let mut image = MemoryImage::new(self.endian());
+        for string in self.strings.iter() {
+            if let Some(addr) = string.vaddr {
+                if let Some(bytes) = self.string_at(addr) {
+                    image.add_region(addr, bytes);
+                }
+            }
+        }
+        image
+    }
+
+    /// Returns the bytes of the string at `addr`, if the loader found one there.
+    pub fn string_at(&self, addr: u64) -> Option<Vec<u8>> {
+        self.strings
+            .iter()
+            .find(|s| s.vaddr == Some(addr))
+            .and_then(|s| s.string.as_ref())
+            // r2 reports strings base64 encoded
+            .and_then(|s| base64::decode(s).ok())
+    }
+
+    /// Returns the addresses of the functions and imports known to never return.
+    pub fn noreturn_addresses(&self) -> HashSet<u64> {
+        let functions = self
+            .functions
+            .iter()
+            .filter(|(_, rfn)| rfn.is_noreturn())
+            .map(|(&addr, _)| addr);
+        let imports = self
+            .imports
+            .iter()
+            .filter(|(_, ifn)| ifn.rfn.borrow().is_noreturn())
+            .map(|(&addr, _)| addr);
+        functions.chain(imports).collect()
+    }
+
+    /// Returns the string or, failing that, the symbol starting at `addr`.
+    pub fn data_target(&self, addr: u64) -> Option<DataTarget> {
+        let string = self
+            .string_at(addr)
+            .map(|bytes| DataTarget::String(String::from_utf8_lossy(&bytes).into_owned()));
+        string.or_else(|| {
+            self.symbols
+                .iter()
+                .find(|s| s.vaddr == Some(addr))
+                .and_then(|s| s.name.clone())
+                .map(DataTarget::Symbol)
+        })
+    }
+
     pub fn callees_of(&self, rfn: &RadecoFunction) -> Vec<(u64, NodeIndex)> {
         // TODO More efficient implementation
         let csite_nodes = rfn
@@ -1194,6 +1582,294 @@ impl RadecoModule {
             .flat_map(|cn| self.callgraph.callees(cn).collect::<Vec<_>>())
             .collect::<Vec<_>>()
     }
+
+    /// Offsets of the function at `offset` and of the functions it reaches through at most
+    /// `depth` nested calls, in increasing order. Empty if there is no function at `offset`.
+    pub fn callees_within(&self, offset: u64, depth: usize) -> Vec<u64> {
+        let mut frontier = match self.function(offset) {
+            Some(rfn) => vec![rfn.cgid],
+            None => return Vec::new(),
+        };
+        let mut seen = frontier.iter().cloned().collect::<HashSet<_>>();
+        for _ in 0..depth {
+            let mut next = Vec::new();
+            for node in frontier {
+                for (_, callee) in self.callgraph.callees(node) {
+                    if seen.insert(callee) {
+                        next.push(callee);
+                    }
+                }
+            }
+            frontier = next;
+        }
+        let mut offsets = seen
+            .into_iter()
+            .map(|n| self.callgraph[n])
+            .collect::<Vec<_>>();
+        offsets.sort();
+        offsets.dedup();
+        offsets
+    }
+
+    /// Merges `other`, e.g. a shared library, into this module.
+    ///
+    /// The functions, imports, symbols, strings and call graph of `other` are added to this
+    /// module. Calls to the PLT stub of an import of either module are then redirected in the
+    /// call graph to the local function of the other module with the same name. It is an error
+    /// for a function or an import of `other` to start at an address already used by this module,
+    /// see `merge_rebased`.
+    pub fn merge(&mut self, other: RadecoModule) -> Result<(), MergeError> {
+        self.merge_rebased(other, 0)
+    }
+
+    /// Same as `merge`, but first moves every address of `other` by `offset`.
+    ///
+    /// The SSA of the functions of `other` is not rebased, it has to be constructed again.
+    pub fn merge_rebased(
+        &mut self,
+        mut other: RadecoModule,
+        offset: u64,
+    ) -> Result<(), MergeError> {
+        other.rebase(offset);
+        let collision = other
+            .functions
+            .keys()
+            .chain(other.imports.keys())
+            .find(|addr| self.functions.contains_key(addr) || self.imports.contains_key(addr));
+        if let Some(&addr) = collision {
+            return Err(MergeError::AddressCollision(addr));
+        }
+
+        let ours = self.exported_functions();
+        let theirs = other.exported_functions();
+        let mut links = self
+            .imports
+            .values()
+            .filter_map(|ifn| theirs.get(import_name(&ifn.name)).map(|&f| (ifn.plt, f)))
+            .collect::<Vec<_>>();
+        links.extend(
+            other
+                .imports
+                .values()
+                .filter_map(|ifn| ours.get(import_name(&ifn.name)).map(|&f| (ifn.plt, f))),
+        );
+
+        // Copy the call graph of `other` and associate its nodes with the merged functions.
+        let mut nodes = HashMap::new();
+        for nidx in other.callgraph.node_indices() {
+            let addr = other.callgraph[nidx];
+            let new_idx = self.callgraph.add_node(addr);
+            nodes.insert(nidx, new_idx);
+            if let Some(rfn) = other.functions.get_mut(&addr) {
+                rfn.cgid = new_idx;
+            }
+            if let Some(ifn) = other.imports.get_mut(&addr) {
+                ifn.rfn.borrow_mut().cgid = new_idx;
+            }
+        }
+        for edge in other.callgraph.edge_references() {
+            self.callgraph.add_edge(
+                nodes[&edge.source()],
+                nodes[&edge.target()],
+                edge.weight().clone(),
+            );
+        }
+
+        self.functions.append(&mut other.functions);
+        self.imports.extend(other.imports.drain());
+        self.symbols.append(&mut other.symbols);
+        self.strings.append(&mut other.strings);
+        let mut sections = (*self.sections).clone();
+        sections.extend(other.sections.iter().cloned());
+        self.sections = Arc::new(sections);
+        self.volatile.append(&mut other.volatile);
+
+        for (plt, target) in links {
+            radeco_trace!("merge_link|{:#x}|{:#x}", plt, target);
+            self.redirect_calls(plt, target);
+        }
+        Ok(())
+    }
+
+    // Moves every address known to this module by `offset`.
+    fn rebase(&mut self, offset: u64) {
+        if offset == 0 {
+            return;
+        }
+        let rebase = |addr: u64| addr.wrapping_add(offset);
+
+        let functions = mem::replace(&mut self.functions, BTreeMap::new());
+        self.functions = functions
+            .into_iter()
+            .map(|(addr, mut rfn)| {
+                rfn.offset = rebase(rfn.offset);
+                for inst in &mut rfn.instructions {
+                    inst.offset = inst.offset.map(rebase);
+                }
+                rfn.datarefs = rfn.datarefs.iter().map(|&a| rebase(a)).collect();
+                rfn.dataref_targets = rfn
+                    .dataref_targets
+                    .drain()
+                    .map(|(a, t)| (rebase(a), t))
+                    .collect();
+                (rebase(addr), rfn)
+            })
+            .collect();
+        self.imports = self
+            .imports
+            .drain()
+            .map(|(addr, mut ifn)| {
+                ifn.plt = rebase(ifn.plt);
+                (rebase(addr), ifn)
+            })
+            .collect();
+
+        for sym in &mut self.symbols {
+            sym.vaddr = sym.vaddr.map(rebase);
+        }
+        for string in &mut self.strings {
+            string.vaddr = string.vaddr.map(rebase);
+        }
+        let mut sections = (*self.sections).clone();
+        for section in &mut sections {
+            section.vaddr = section.vaddr.map(rebase);
+        }
+        self.sections = Arc::new(sections);
+        for range in &mut self.volatile {
+            *range = rebase(range.start)..rebase(range.end);
+        }
+
+        for addr in self.callgraph.node_weights_mut() {
+            *addr = rebase(*addr);
+        }
+        for cctx in self.callgraph.edge_weights_mut() {
+            cctx.csite = rebase(cctx.csite);
+        }
+    }
+
+    // Returns the address of every local function by name, without the prefixes added by r2.
+    fn exported_functions(&self) -> HashMap<String, u64> {
+        self.functions
+            .iter()
+            .filter(|(_, rfn)| rfn.kind == FunctionKind::Local)
+            .map(|(&addr, rfn)| (import_name(&rfn.name).to_owned(), addr))
+            .collect()
+    }
+
+    /// Redirects in the call graph the calls to thunks, i.e. local functions made of a single jump
+    /// through the GOT entry of an import, to the import.
+    pub fn forward_thunks(&mut self) {
+        let imports = self
+            .imports
+            .iter()
+            .map(|(&addr, ifn)| (import_name(&ifn.name), addr))
+            .collect::<HashMap<_, _>>();
+        let links = self
+            .functions
+            .iter()
+            .filter(|(_, rfn)| rfn.kind == FunctionKind::Local)
+            .filter_map(|(&addr, rfn)| {
+                let pointer = rfn.thunk_pointer()?;
+                let reloc = self.relocs.iter().find(|r| r.vaddr == Some(pointer))?;
+                let target = *imports.get(import_name(reloc.name.as_ref()?))?;
+                if target == addr {
+                    None
+                } else {
+                    Some((addr, target))
+                }
+            })
+            .collect::<Vec<_>>();
+        for (thunk, target) in links {
+            radeco_trace!("forward_thunk|{:#x}|{:#x}", thunk, target);
+            self.redirect_calls(thunk, target);
+        }
+    }
+
+    // Redirects the calls to the PLT stub at `plt` to the function at `target`.
+    fn redirect_calls(&mut self, plt: u64, target: u64) {
+        let plt_node = match self.callgraph_node(plt) {
+            Some(node) => node,
+            None => return,
+        };
+        let target_node = match self.callgraph_node(target) {
+            Some(node) => node,
+            None => {
+                let node = self.callgraph.add_node(target);
+                if let Some(rfn) = self.functions.get_mut(&target) {
+                    rfn.cgid = node;
+                }
+                if let Some(ifn) = self.imports.get(&target) {
+                    ifn.rfn.borrow_mut().cgid = node;
+                }
+                node
+            }
+        };
+
+        let callers = self
+            .callgraph
+            .neighbors_directed(plt_node, Direction::Incoming)
+            .collect::<Vec<_>>();
+        for caller in callers {
+            while let Some(edge) = self.callgraph.find_edge(caller, plt_node) {
+                if let Some(cctx) = self.callgraph.remove_edge(edge) {
+                    self.callgraph.add_edge(caller, target_node, cctx);
+                }
+            }
+        }
+    }
+
+    /// Returns the node of the call graph for the function or import at `addr`.
+    pub fn callgraph_node(&self, addr: u64) -> Option<NodeIndex> {
+        self.callgraph
+            .node_indices()
+            .find(|&n| self.callgraph[n] == addr)
+    }
+
+    /// Offsets of the functions calling the function or import at `addr`, in increasing order.
+    /// Only the calls resolved in the call graph are accounted for, the call sites are found with
+    /// `RadecoFunction::call_sites`.
+    pub fn code_xrefs_to(&self, addr: u64) -> Vec<u64> {
+        let node = match self.callgraph_node(addr) {
+            Some(node) => node,
+            None => return Vec::new(),
+        };
+        let mut callers = self
+            .callgraph
+            .edges_directed(node, Direction::Incoming)
+            .map(|e| self.callgraph[e.source()])
+            .collect::<Vec<_>>();
+        callers.sort();
+        callers.dedup();
+        callers
+    }
+
+    /// Offsets of the functions referencing the data at `addr`, in increasing order. The
+    /// referencing instructions are found with `RadecoFunction::data_references`.
+    pub fn data_xrefs_to(&self, addr: u64) -> Vec<u64> {
+        self.functions
+            .iter()
+            .filter(|(_, rfn)| rfn.datarefs.contains(&addr))
+            .map(|(&offset, _)| offset)
+            .collect()
+    }
+
+    /// Returns true if the function at `addr` is only ever called from the call sites in the call
+    /// graph, i.e. it is a local function which is neither exported, an entry point nor
+    /// referenced as data (e.g. to be called through a pointer).
+    pub fn callers_known(&self, addr: u64) -> bool {
+        match self.functions.get(&addr) {
+            Some(rfn) if rfn.kind == FunctionKind::Local => {}
+            _ => return false,
+        }
+        let exported = self.exports.iter().any(|e| e.vaddr == Some(addr));
+        let entrypoint = self.entrypoint.iter().any(|e| e.vaddr == Some(addr));
+        !exported && !entrypoint && self.data_xrefs_to(addr).is_empty()
+    }
+}
+
+// Strips the prefixes added by r2, e.g. `sym.imp.` or `sym.`, from a function name.
+fn import_name(name: &str) -> &str {
+    name.rsplit('.').next().unwrap_or(name)
 }
 
 impl RadecoFunction {
@@ -1238,10 +1914,293 @@ impl RadecoFunction {
         call_graph.callees(self.cgid).map(|(_, n)| n).collect()
     }
 
+    /// Size of the function in bytes, or 0 if it is unknown.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
     pub fn datarefs(&self) -> &Vec<u64> {
         &self.datarefs
     }
 
+    /// Returns the direct calls of this function found in its disassembly, as pairs of call site
+    /// and target addresses.
+    pub fn direct_calls(&self) -> Vec<(u64, u64)> {
+        self.instructions
+            .iter()
+            .filter(|inst| inst.optype.as_ref().map_or(false, |ty| ty == "call"))
+            .filter_map(|inst| {
+                let target = inst
+                    .esil
+                    .as_ref()
+                    .and_then(|esil| ssaconstructor::call_target(esil))?;
+                Some((inst.offset?, target))
+            })
+            .collect()
+    }
+
+    /// Returns the address of the pointer this function jumps through, if it is a thunk made of
+    /// that single indirect jump, like a PLT stub.
+    pub fn thunk_pointer(&self) -> Option<u64> {
+        match self.instructions.as_slice() {
+            [inst] if inst.optype.as_ref().map_or(false, |ty| ty.ends_with("jmp")) => {
+                let next = inst.offset? + inst.size?;
+                ssaconstructor::jump_pointer(inst.esil.as_ref()?, next)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the constants used by the operations and phis of this function, as triples of the
+    /// address they are used at, their value and their width, ordered by address. A constant is
+    /// listed once for every use.
+    pub fn constants(&self) -> Vec<(MAddress, u64, u16)> {
+        let ssa = &self.ssa;
+        let mut constants = ssa
+            .blocks()
+            .into_iter()
+            .flat_map(|b| ssa.phis_in(b).into_iter().chain(ssa.exprs_in(b)))
+            .flat_map(|user| {
+                let addr = ssa.address(user);
+                ssa.operands_of(user)
+                    .into_iter()
+                    .filter_map(move |operand| {
+                        let value = ssa.constant_value(operand)?;
+                        let width = ssa
+                            .node_data(operand)
+                            .ok()
+                            .and_then(|nd| nd.vt.width().get_width())
+                            .unwrap_or(64);
+                        Some((addr?, value, width))
+                    })
+            })
+            .collect::<Vec<_>>();
+        constants.sort();
+        constants
+    }
+
+    // Returns `true` if this function directly calls one of the functions at `targets`.
+    fn calls_any(&self, targets: &HashSet<u64>) -> bool {
+        self.direct_calls().iter().any(|&(_, t)| targets.contains(&t))
+    }
+
+    /// Returns `true` if this function never returns to its caller.
+    pub fn is_noreturn(&self) -> bool {
+        self.noreturn
+    }
+
+    pub fn set_noreturn(&mut self, noreturn: bool) {
+        self.noreturn = noreturn;
+    }
+
+    /// Returns the error reported when constructing the SSA of this function, e.g. because r2
+    /// provided no ESIL for its instructions.
+    pub fn construct_error(&self) -> Option<&ConstructError> {
+        self.construct_error.as_ref()
+    }
+
+    pub fn set_construct_error(&mut self, error: Option<ConstructError>) {
+        self.construct_error = error;
+    }
+
+    /// Records that the `kind` analyzer was run on this function.
+    pub fn mark_analyzed(&mut self, kind: AnalyzerKind) {
+        self.analyzed.insert(kind);
+    }
+
+    /// Returns `true` if the `kind` analyzer was run on this function.
+    pub fn analyzed(&self, kind: AnalyzerKind) -> bool {
+        self.analyzed.contains(&kind)
+    }
+
+    /// Checks that every analyzer of `kinds` was run on this function, e.g. before running one
+    /// which relies on their results.
+    pub fn requires(&self, kinds: &[AnalyzerKind]) -> Result<(), PrerequisiteError> {
+        let missing = kinds
+            .iter()
+            .cloned()
+            .filter(|&kind| !self.analyzed(kind))
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(PrerequisiteError { missing })
+        }
+    }
+
+    /// Forgets that the `kind` analyzer was run on this function, once its results are out of
+    /// date.
+    pub fn invalidate(&mut self, kind: AnalyzerKind) {
+        self.analyzed.remove(&kind);
+    }
+
+    /// Records the store each load reads from, as found by the Memory SSA of this function.
+    pub fn set_clobbering_stores(&mut self, stores: HashMap<NodeIndex, NodeIndex>) {
+        self.clobbering_stores = stores;
+        self.mark_analyzed(AnalyzerKind::MemorySSA);
+    }
+
+    /// Returns the store `load` reads from, if the Memory SSA found a single one.
+    pub fn clobbering_store(&self, load: NodeIndex) -> Option<NodeIndex> {
+        self.clobbering_stores.get(&load).cloned()
+    }
+
+    /// Returns the number of basic blocks in the CFG, not counting its entry and exit nodes.
+    pub fn block_count(&self) -> usize {
+        let entry = self.ssa.entry_node();
+        self.ssa
+            .blocks()
+            .into_iter()
+            .filter(|&b| Some(b) != entry)
+            .count()
+    }
+
+    /// Returns the number of instructions in each basic block, keyed by the starting address of
+    /// the block.
+    pub fn instruction_counts(&self) -> BTreeMap<MAddress, usize> {
+        let entry = self.ssa.entry_node();
+        let mut counts = BTreeMap::new();
+        let mut sizes = BTreeMap::new();
+        for block in self.ssa.blocks() {
+            if Some(block) == entry {
+                continue;
+            }
+            if let Some(start) = self.ssa.starting_address(block) {
+                counts.insert(start, 0);
+                sizes.insert(start, self.ssa.block_size(block).unwrap_or(0));
+            }
+        }
+        for inst in &self.instructions {
+            let offset = inst.offset.unwrap_or(0);
+            // The instruction is in the last block starting before it, unless it was removed.
+            if let Some((start, count)) = counts.range_mut(..=MAddress::new(offset, 0)).next_back()
+            {
+                let size = sizes[start];
+                if size == 0 || offset < start.address + size {
+                    *count += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    /// Returns the number of instructions in the basic blocks of this function.
+    pub fn instruction_count(&self) -> usize {
+        self.instruction_counts().values().sum()
+    }
+
+    /// Returns the cyclomatic complexity of the CFG, i.e. `edges - nodes + 2`.
+    pub fn cyclomatic_complexity(&self) -> usize {
+        if self.ssa.entry_node().is_none() {
+            return 0;
+        }
+        let blocks = self.ssa.blocks();
+        let edges = blocks
+            .iter()
+            .map(|&b| self.ssa.outgoing_edges(b).len())
+            .sum::<usize>();
+        // `blocks` does not include the exit node.
+        let nodes = blocks.len() + 1;
+        (edges + 2).saturating_sub(nodes)
+    }
+
+    /// Returns the data addresses referenced by each instruction of this function, as found by
+    /// `data_references`.
+    pub fn instruction_xrefs(&self) -> HashMap<u64, Vec<u64>> {
+        let mut xrefs = HashMap::new();
+        for dataref in self.data_references() {
+            if let Some(from) = dataref.from {
+                xrefs.entry(from).or_insert_with(Vec::new).push(dataref.to);
+            }
+        }
+        xrefs
+    }
+
+    /// Returns the data references of this function.
+    ///
+    /// Referencing instructions are found by looking for the referenced address among the
+    /// constants of their ESIL. A data address that no instruction mentions is reported once,
+    /// without a referencing instruction.
+    pub fn data_references(&self) -> Vec<DataRef> {
+        let mut refs = Vec::new();
+        for inst in &self.instructions {
+            let esil = match inst.esil {
+                Some(ref esil) => esil,
+                None => continue,
+            };
+            let mut consts = esil
+                .split(',')
+                .filter_map(|t| {
+                    if t.starts_with("0x") {
+                        u64::from_str_radix(&t[2..], 16).ok()
+                    } else {
+                        t.parse::<u64>().ok()
+                    }
+                })
+                .filter(|c| self.datarefs.contains(c))
+                .collect::<Vec<_>>();
+            consts.dedup();
+            refs.extend(consts.into_iter().map(|to| DataRef {
+                from: inst.offset,
+                to: to,
+                target: self.dataref_targets.get(&to).cloned(),
+            }));
+        }
+        let mut unreferenced = self
+            .datarefs
+            .iter()
+            .filter(|&&to| refs.iter().all(|r| r.to != to))
+            .cloned()
+            .collect::<Vec<_>>();
+        unreferenced.sort();
+        unreferenced.dedup();
+        refs.extend(unreferenced.into_iter().map(|to| DataRef {
+            from: None,
+            to: to,
+            target: self.dataref_targets.get(&to).cloned(),
+        }));
+        refs
+    }
+
+    /// Returns the value of the register `reg` at the entry of this function, i.e. the node of the
+    /// entry register state holding it. The whole register is looked up for a subregister.
+    pub fn entry_value_of(&self, reg: &str) -> Option<NodeIndex> {
+        let ssa = &self.ssa;
+        let id = ssa.regfile.register_id_by_name(reg)?;
+        let entry_state = ssa.entry_node().and_then(|n| ssa.registers_in(n))?;
+        utils::register_state_info(entry_state, ssa)
+            .get(id)
+            .map(|&(value, _)| value)
+    }
+
+    /// Returns the names of the registers this function modifies, i.e. those whose value in the
+    /// final register state is not their value at the entry. Memory is never reported.
+    pub fn clobbered_registers(&self) -> HashSet<String> {
+        let ssa = &self.ssa;
+        let states = (
+            ssa.entry_node().and_then(|n| ssa.registers_in(n)),
+            ssa.exit_node().and_then(|n| ssa.registers_in(n)),
+        );
+        let (entry_state, exit_state) = match states {
+            (Some(entry), Some(exit)) => (
+                utils::register_state_info(entry, ssa),
+                utils::register_state_info(exit, ssa),
+            ),
+            _ => return HashSet::new(),
+        };
+
+        ssa.regfile
+            .iter_register_ids()
+            .filter(|&id| id != ssa.regfile.mem_id())
+            .filter(|&id| match (entry_state.get(id), exit_state.get(id)) {
+                (Some(&(at_entry, _)), Some(&(at_exit, _))) => at_entry != at_exit,
+                (None, Some(_)) => true,
+                (_, None) => false,
+            })
+            .filter_map(|id| ssa.regfile.get_name(id).map(|name| name.to_owned()))
+            .collect()
+    }
+
     pub fn locals(&self) -> VarBindings {
         self.bindings
             .iter()
@@ -1353,6 +2312,411 @@ pub struct CallContextInfo {
 
 #[cfg(test)]
 mod test {
+    use super::*;
+    use crate::frontend::radeco_source::{FileSource, SourceErr};
+    use crate::middle::ir_reader;
+    use r2api::structs::{FunctionInfo, LFlagInfo, LRegInfo};
+    use serde_json;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const STRAIGHT_LINE_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.straight_line(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64(*?) = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x5):
+        [@0x001000.0001] %3: $Unknown64(*?) = %1 + #x1;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %3;
+        $mem = %2;
+}
+";
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const IF_ELSE_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.if_else(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64(*?) = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown1 = %1 == #x0;
+        JMP IF %3 0x001008.0000 ELSE 0x001004.0000
+    bb_0x001004.0000(sz 0x4):
+        JMP 0x00100C.0000
+    bb_0x001008.0000(sz 0x4):
+        JMP 0x00100C.0000
+    bb_0x00100C.0000(sz 0x1):
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %1;
+        $mem = %2;
+}
+";
+
+    fn function_from_il(il: &str, offsets: &[u64]) -> RadecoFunction {
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ir_reader::parse_il(il, REGISTER_FILE.clone());
+        let insts = offsets
+            .iter()
+            .map(|o| format!("{{\"offset\":{}}}", o))
+            .collect::<Vec<_>>();
+        rfn.instructions = serde_json::from_str(&format!("[{}]", insts.join(","))).unwrap();
+        rfn
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const WRITES_RAX_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.writes_rax(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rax;
+        %2: $Unknown64(*?) = $rdi;
+        %3: $Mem = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %4: $Unknown64 = %2 + #x1;
+        [@0x001004.0001] %5: $Mem = Store(%3, %2, %4);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %4;
+        $rdi = %2;
+        $mem = %5;
+}
+";
+
+    #[test]
+    fn clobbered_registers_only_written() {
+        let rfn = function_from_il(WRITES_RAX_TXT, &[0x1000, 0x1004]);
+        let expected = ["rax"].iter().map(|r| r.to_string()).collect::<HashSet<_>>();
+        assert_eq!(rfn.clobbered_registers(), expected);
+    }
+
+    #[test]
+    fn entry_value_of_register() {
+        use crate::middle::ir::MOpcode;
+
+        let rfn = function_from_il(WRITES_RAX_TXT, &[0x1000, 0x1004]);
+        let ssa = rfn.ssa();
+        let add = ssa
+            .values()
+            .into_iter()
+            .find(|&v| ssa.opcode(v) == Some(MOpcode::OpAdd))
+            .unwrap();
+        let rdi = ssa.operands_of(add)[0];
+        assert_eq!(rfn.entry_value_of("rdi"), Some(rdi));
+        assert_eq!(rfn.entry_value_of("edi"), Some(rdi));
+        assert!(ssa.is_comment(rdi));
+
+        let rax = rfn.entry_value_of("rax").unwrap();
+        assert_ne!(rax, rdi);
+        assert!(ssa.is_comment(rax));
+        assert_eq!(rfn.entry_value_of("nonexistent"), None);
+    }
+
+    #[test]
+    fn constants_with_widths() {
+        use crate::middle::ir::MOpcode;
+        use crate::middle::ssa::ssa_builder::SSABuilder;
+
+        // rax = (u32)(rdi + 0x67452301) ^ 0x5a827999; *rdi = 0x67452301
+        let mut b = SSABuilder::new(REGISTER_FILE.clone());
+        let rdi = b.register("rdi");
+        let mem = b.register("mem");
+        let bb = b.block(0x1000, 0x10);
+        let h0 = b.constant(0x6745_2301);
+        let sum = b.op(bb, MOpcode::OpAdd, 64, &[rdi, h0]);
+        let low = b.op(bb, MOpcode::OpNarrow(32), 32, &[sum]);
+        let k0 = b.sized_constant(0x5a82_7999, 32);
+        let mixed = b.op(bb, MOpcode::OpXor, 32, &[low, k0]);
+        let stored = b.op(bb, MOpcode::OpStore, 0, &[mem, rdi, h0]);
+        b.ret(bb)
+            .exit_register("rax", mixed)
+            .exit_register("mem", stored);
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = b.finish();
+
+        assert_eq!(
+            rfn.constants(),
+            vec![
+                (MAddress::new(0x1000, 1), 0x6745_2301, 64),
+                (MAddress::new(0x1000, 3), 0x5a82_7999, 32),
+                (MAddress::new(0x1000, 4), 0x6745_2301, 64),
+            ]
+        );
+    }
+
+    #[test]
+    fn straight_line_complexity() {
+        let rfn = function_from_il(STRAIGHT_LINE_TXT, &[0x1000, 0x1004]);
+        assert_eq!(rfn.cyclomatic_complexity(), 1);
+        assert_eq!(rfn.block_count(), 1);
+        assert_eq!(rfn.instruction_count(), 2);
+    }
+
+    #[test]
+    fn if_else_complexity() {
+        let rfn = function_from_il(IF_ELSE_TXT, &[0x1000, 0x1002, 0x1004, 0x1008, 0x100c]);
+        assert_eq!(rfn.cyclomatic_complexity(), 2);
+        assert_eq!(rfn.block_count(), 4);
+        assert_eq!(rfn.instruction_count(), 5);
+        let counts = rfn.instruction_counts();
+        assert_eq!(counts[&MAddress::new(0x1000, 0)], 2);
+        assert_eq!(counts[&MAddress::new(0x100c, 0)], 1);
+    }
+
+    #[test]
+    fn data_references_report_strings() {
+        let source = FileSource::open("./test_files/bin1_filesource/bin1");
+        let rproj = ProjectLoader::new().source(Rc::new(source)).load();
+        let rmod = rproj.iter().next().unwrap().module;
+        let main = rmod.functions.values().find(|f| f.name == "main").unwrap();
+
+        let refs = main.data_references();
+        // mov edi, 0x4006b4
+        assert!(refs.contains(&DataRef {
+            from: Some(0x4005b4),
+            to: 0x4006b4,
+            target: Some(DataTarget::String("%d %d %d".to_owned())),
+        }));
+    }
+
+    #[test]
+    fn memory_image_holds_strings() {
+        let source = FileSource::open("./test_files/bin1_filesource/bin1");
+        let rproj = ProjectLoader::new().source(Rc::new(source)).load();
+        let rmod = rproj.iter().next().unwrap().module;
+
+        // "%d %d %d", read as little-endian.
+        let image = rmod.memory_image();
+        assert_eq!(rmod.endian(), Endian::Little);
+        assert_eq!(image.read(0x4006b4, 16), Some(0x6425));
+        assert_eq!(image.read(0x4006b4, 8), Some(u64::from(b'%')));
+    }
+
+    #[test]
+    fn callgraph_without_ssa() {
+        let source: Rc<dyn Source> = Rc::new(FileSource::open("./test_files/bin1_filesource/bin1"));
+        let mloader = ModuleLoader::default()
+            .source(Rc::clone(&source))
+            .build_callgraph();
+        let rproj = ProjectLoader::new()
+            .source(source)
+            .module_loader(mloader)
+            .load();
+        let rmod = rproj.iter().next().unwrap().module;
+        assert_eq!(rmod.ssa_constructions(), 0);
+
+        let main = rmod.functions.values().find(|f| f.name == "main").unwrap();
+        assert_eq!(main.ssa().entry_node(), None);
+        // call sym.imp.puts
+        let callees = main
+            .callees(&rmod.callgraph)
+            .into_iter()
+            .map(|n| rmod.callgraph[n])
+            .collect::<Vec<_>>();
+        assert!(callees.contains(&0x400470));
+    }
+
+    // A source answering like r2 on a binary it could not make sense of: it has a register
+    // profile, but no information on the binary.
+    struct NoBinInfoSource;
+
+    impl Source for NoBinInfoSource {
+        fn functions(&self) -> Result<Vec<FunctionInfo>, SourceErr> {
+            Ok(Vec::new())
+        }
+        fn instructions_at(&self, _: u64) -> Result<Vec<LOpInfo>, SourceErr> {
+            Ok(Vec::new())
+        }
+        fn register_profile(&self) -> Result<LRegInfo, SourceErr> {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE)?;
+            Ok(serde_json::from_str(&*s)?)
+        }
+        fn flags(&self) -> Result<Vec<LFlagInfo>, SourceErr> {
+            Ok(Vec::new())
+        }
+        fn sections(&self) -> Result<Vec<LSectionInfo>, SourceErr> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn missing_bin_info_is_an_error() {
+        let res = ProjectLoader::new().source(Rc::new(NoBinInfoSource)).try_load();
+        match res {
+            Err(FrontendError::MissingBinInfo(_)) => (),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("loaded a project without binary information"),
+        }
+    }
+
+    // Adds the function `name` at `addr`, along with its node in the call graph.
+    fn add_function(rmod: &mut RadecoModule, addr: u64, name: &str, kind: FunctionKind) {
+        let mut rfn = RadecoFunction::new();
+        rfn.name = Cow::from(name.to_owned());
+        rfn.offset = addr;
+        rfn.kind = kind;
+        rfn.cgid = rmod.callgraph.add_node(addr);
+        rmod.functions.insert(addr, rfn);
+    }
+
+    #[test]
+    fn xrefs_to_callee() {
+        let mut rmod = RadecoModule::new("xrefs".to_owned());
+        add_function(&mut rmod, 0x1000, "main", FunctionKind::Local);
+        add_function(&mut rmod, 0x2000, "sym.other", FunctionKind::Local);
+        add_function(&mut rmod, 0x3000, "sym.callee", FunctionKind::Local);
+        // Both call `sym.callee`, `main` twice.
+        for &(caller, csite) in &[(0x1000, 0x1004), (0x1000, 0x1010), (0x2000, 0x2008)] {
+            let mut cctx = CallContextInfo::default();
+            cctx.csite = csite;
+            let (from, to) = (rmod.functions[&caller].cgid, rmod.functions[&0x3000].cgid);
+            rmod.callgraph.add_edge(from, to, cctx);
+        }
+        rmod.function_mut(0x2000).unwrap().datarefs = vec![0x601040];
+
+        assert_eq!(rmod.code_xrefs_to(0x3000), vec![0x1000, 0x2000]);
+        assert!(rmod.code_xrefs_to(0x1000).is_empty());
+        assert!(rmod.code_xrefs_to(0x4000).is_empty());
+        assert_eq!(rmod.data_xrefs_to(0x601040), vec![0x2000]);
+        assert!(rmod.data_xrefs_to(0x3000).is_empty());
+    }
+
+    #[test]
+    fn callers_known_of_local_functions() {
+        let mut rmod = RadecoModule::new("callers".to_owned());
+        add_function(&mut rmod, 0x1000, "main", FunctionKind::Local);
+        add_function(&mut rmod, 0x2000, "sym.callback", FunctionKind::Local);
+        add_function(&mut rmod, 0x3000, "sym.helper", FunctionKind::Local);
+        add_function(&mut rmod, 0x4000, "sym.imp.puts", FunctionKind::Imported);
+        // `main` takes the address of `sym.callback`.
+        rmod.function_mut(0x1000).unwrap().datarefs = vec![0x2000];
+        let mut entry = LEntryInfo::default();
+        entry.vaddr = Some(0x1000);
+        rmod.entrypoint = vec![entry];
+
+        assert!(!rmod.callers_known(0x1000));
+        assert!(!rmod.callers_known(0x2000));
+        assert!(rmod.callers_known(0x3000));
+        assert!(!rmod.callers_known(0x4000));
+        assert!(!rmod.callers_known(0x5000));
+    }
+
+    #[test]
+    fn merge_resolves_plt_call() {
+        let mut main = RadecoModule::new("main".to_owned());
+        add_function(&mut main, 0x1000, "main", FunctionKind::Local);
+        add_function(&mut main, 0x1100, "sym.imp.puts", FunctionKind::Imported);
+        main.imports
+            .insert(0x1100, ImportInfo::new_stub(0x1100, Cow::from("puts")));
+        let mut cctx = CallContextInfo::default();
+        cctx.csite = 0x1004;
+        let caller = main.functions[&0x1000].cgid;
+        let stub = main.functions[&0x1100].cgid;
+        main.callgraph.add_edge(caller, stub, cctx);
+
+        let libc = || {
+            let mut libc = RadecoModule::new("libc".to_owned());
+            add_function(&mut libc, 0x1000, "sym.puts", FunctionKind::Local);
+            libc
+        };
+        assert_eq!(
+            main.merge(libc()),
+            Err(MergeError::AddressCollision(0x1000))
+        );
+        assert_eq!(main.functions.len(), 2);
+
+        assert_eq!(main.merge_rebased(libc(), 0x7000_0000), Ok(()));
+        let puts = &main.functions[&0x7000_1000];
+        assert_eq!(puts.name, "sym.puts");
+        assert_eq!(main.callgraph[puts.cgid], 0x7000_1000);
+
+        let main_fn = &main.functions[&0x1000];
+        let callees = main_fn
+            .callees(&main.callgraph)
+            .into_iter()
+            .map(|n| main.callgraph[n])
+            .collect::<Vec<_>>();
+        assert_eq!(callees, vec![0x7000_1000]);
+        assert_eq!(main_fn.call_sites(&main.callgraph)[0].csite, 0x1004);
+    }
+
+    #[test]
+    fn call_through_thunk() {
+        let mut rmod = RadecoModule::new("main".to_owned());
+        add_function(&mut rmod, 0x1000, "main", FunctionKind::Local);
+        add_function(&mut rmod, 0x1100, "fcn.00001100", FunctionKind::Local);
+        add_function(&mut rmod, 0x1200, "sym.imp.puts", FunctionKind::Imported);
+        rmod.imports
+            .insert(0x1200, ImportInfo::new_stub(0x1200, Cow::from("puts")));
+        // jmp qword [rip + 0x1ef2], through the GOT entry of `puts` at 0x2ff8.
+        let insts = r#"[{"esil": "0x1ef2,rip,+,[8],rip,=", "offset": 4352, "size": 6,
+            "opcode": "jmp qword [rip + 0x1ef2]", "type": "ujmp"}]"#;
+        rmod.functions.get_mut(&0x1100).unwrap().instructions =
+            serde_json::from_str(insts).unwrap();
+        let relocs = r#"[{"is_ifunc": false, "name": "puts", "paddr": 12280, "type": "SET_64",
+            "vaddr": 12280}]"#;
+        rmod.relocs = serde_json::from_str(relocs).unwrap();
+        let mut cctx = CallContextInfo::default();
+        cctx.csite = 0x1004;
+        let caller = rmod.functions[&0x1000].cgid;
+        let thunk = rmod.functions[&0x1100].cgid;
+        rmod.callgraph.add_edge(caller, thunk, cctx);
+
+        rmod.forward_thunks();
+
+        let callees = rmod.functions[&0x1000]
+            .callees(&rmod.callgraph)
+            .into_iter()
+            .map(|n| rmod.functions[&rmod.callgraph[n]].name.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(callees, vec!["sym.imp.puts"]);
+    }
+
+    #[test]
+    fn callees_up_to_depth() {
+        // main -> a -> b -> c
+        let mut rmod = RadecoModule::new("chain".to_owned());
+        let names = ["main", "a", "b", "c"];
+        for (i, name) in names.iter().enumerate() {
+            let addr = 0x1000 + 0x100 * i as u64;
+            add_function(&mut rmod, addr, name, FunctionKind::Local);
+        }
+        for i in 0..names.len() as u64 - 1 {
+            let mut cctx = CallContextInfo::default();
+            cctx.csite = 0x1004 + 0x100 * i;
+            let caller = rmod.functions[&(0x1000 + 0x100 * i)].cgid;
+            let callee = rmod.functions[&(0x1100 + 0x100 * i)].cgid;
+            rmod.callgraph.add_edge(caller, callee, cctx);
+        }
+        let selected = |depth| {
+            rmod.callees_within(0x1000, depth)
+                .into_iter()
+                .map(|off| rmod.functions[&off].name.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(selected(0), vec!["main"]);
+        assert_eq!(selected(2), vec!["main", "a", "b"]);
+        assert_eq!(selected(5), vec!["main", "a", "b", "c"]);
+        assert!(rmod.callees_within(0x2000, 2).is_empty());
+    }
+
     #[test]
     fn test_fn_loader() {
         // let ld = |x: &FLResult, y: &RadecoModule| -> FLResult { unimplemented!() };
@@ -12,8 +12,8 @@ use std::rc::Rc;
 
 use r2api::api_trait::R2Api;
 use r2api::structs::{
-    FunctionInfo, LCCInfo, LEntryInfo, LExportInfo, LFlagInfo, LFunctionInfo, LImportInfo, LOpInfo,
-    LRegInfo, LRelocInfo, LSectionInfo, LStringInfo, LSymbolInfo, LVarInfo,
+    FunctionInfo, LBinInfo, LCCInfo, LEntryInfo, LExportInfo, LFlagInfo, LFunctionInfo,
+    LImportInfo, LOpInfo, LRegInfo, LRelocInfo, LSectionInfo, LStringInfo, LSymbolInfo, LVarInfo,
 };
 
 #[derive(Debug)]
@@ -84,6 +84,9 @@ pub trait Source {
     fn raw(&self, _cmd: String) -> Result<String, SourceErr> {
         unimplemented!()
     }
+    fn bin_info(&self) -> Result<LBinInfo, SourceErr> {
+        Err(SourceErr::SrcErr("`Source::bin_info` is not implemented"))
+    }
 
     fn send(&self, _: String) -> Result<(), SourceErr> {
         Ok(())
@@ -241,6 +244,10 @@ impl<R: R2Api> Source for WrappedR2Api<R> {
         Ok(self.try_borrow_mut()?.raw(cmd))
     }
 
+    fn bin_info(&self) -> Result<LBinInfo, SourceErr> {
+        Ok(self.try_borrow_mut()?.bin_info()?)
+    }
+
     fn send(&self, s: String) -> Result<(), SourceErr> {
         let _ = self.try_borrow_mut()?.raw(s);
         Ok(())
@@ -297,6 +304,7 @@ mod suffix {
     pub const LOCAL: &'static str = "locals";
     pub const CCINFO: &'static str = "ccinfo";
     pub const ENTRY: &'static str = "entrypoint";
+    pub const BIN_INFO: &'static str = "bin_info";
 }
 
 impl FileSource {
@@ -396,12 +404,16 @@ impl Source for FileSource {
     fn raw(&self, _cmd: String) -> Result<String, SourceErr> {
         Err(SourceErr::SrcErr("`Source::raw` is not implemented"))
     }
+
+    fn bin_info(&self) -> Result<LBinInfo, SourceErr> {
+        Ok(serde_json::from_str(&self.read_file(suffix::BIN_INFO)?)?)
+    }
 }
 
 impl<R: R2Api> From<WrappedR2Api<R>> for FileSource {
     fn from(r2: WrappedR2Api<R>) -> FileSource {
         let bin_info = r2.borrow_mut().bin_info().expect("Failed to load bin_info");
-        let fname = bin_info.core.unwrap().file.unwrap();
+        let fname = bin_info.core.clone().unwrap().file.unwrap();
         let fname = Path::new(&fname).file_stem().unwrap();
         let mut dir = PathBuf::from(".");
         dir.push(&fname);
@@ -467,6 +479,11 @@ impl<R: R2Api> From<WrappedR2Api<R>> for FileSource {
                 }
             }
 
+            {
+                let json_str = serde_json::to_string(&bin_info).expect("Failed to encode to json");
+                fsource.write_file(suffix::BIN_INFO, &json_str);
+            }
+
             {
                 let reg = r2.register_profile().expect("Failed to load register info");
                 let json_str = serde_json::to_string(&reg).expect("Failed to encode to json");
@@ -20,15 +20,18 @@ use crate::frontend::radeco_containers::RadecoFunction;
 use crate::middle::ir::{self, MAddress, MOpcode};
 use crate::middle::phiplacement::PhiPlacer;
 use crate::middle::regfile::SubRegisterFile;
+use crate::middle::ssa::cfg_traits::CFG;
 use crate::middle::ssa::graph_traits::Graph;
-use crate::middle::ssa::ssa_traits::{SSAExtra, SSAMod, ValueInfo};
+use crate::middle::ssa::ssa_traits::{SSAExtra, SSAMod, ValueInfo, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
 
 use r2api::structs::{LOpInfo, LRegInfo};
 
 // use regex::Regex;
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::{cmp, fmt, u64};
+use std::{cmp, error, fmt, mem, u64};
 
 pub type VarId = usize;
 
@@ -36,6 +39,218 @@ const FALSE_EDGE: u8 = 0;
 const TRUE_EDGE: u8 = 1;
 const UNCOND_EDGE: u8 = 2;
 
+/// Category of the log messages of SSA construction.
+const LOG_CATEGORY: &str = "ssaconstruct";
+
+// Returns `true` if the operands of `token` have to be interpreted as signed values.
+// NOTE: ESIL has no signed variant of `*` and `/`, so only comparisons are signed for now.
+fn is_signed(token: &Token) -> bool {
+    match *token {
+        Token::ELt | Token::EGt => true,
+        _ => false,
+    }
+}
+
+// Whether the ESIL internal flag variables (`$z`, `$c`, ...) following `token` refer to it.
+fn sets_flags(token: &Token) -> bool {
+    match *token {
+        Token::EAdd
+        | Token::ESub
+        | Token::ECmp
+        | Token::EMul
+        | Token::EDiv
+        | Token::EMod
+        | Token::EAnd
+        | Token::EOr
+        | Token::EXor
+        | Token::ELsl
+        | Token::ELsr
+        | Token::ERor
+        | Token::ERol => true,
+        _ => false,
+    }
+}
+
+// Mask of the `bits` lowest bits.
+fn genmask(bits: u64) -> u64 {
+    if bits >= 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+/// Returns the target of a direct call, i.e. the constant pushed first by its ESIL.
+pub fn call_target(esil: &str) -> Option<u64> {
+    parse_u64(esil.split(',').next()?)
+}
+
+/// Returns the address of the pointer an indirect jump loads its target from, e.g. the GOT entry
+/// of `jmp qword [rip + 0x200ba2]`. `next` is the address of the following instruction, which the
+/// program counter holds when it is read.
+pub fn jump_pointer(esil: &str, next: u64) -> Option<u64> {
+    let is_load = |token: &str| token.starts_with('[') && token.ends_with(']');
+    match esil.split(',').collect::<Vec<_>>().as_slice() {
+        [offset, pc, "+", load, dst, "="] if pc == dst && is_load(load) => {
+            Some(next.wrapping_add(parse_u64(offset)?))
+        }
+        [addr, load, _, "="] if is_load(load) => parse_u64(addr),
+        _ => None,
+    }
+}
+
+/// Returns the offsets of the branches, calls and returns of `ops`. On an architecture with
+/// delay slots, such as MIPS or SPARC, these are the instructions followed by one, see
+/// `SSAConstruct::set_delay_slots`.
+pub fn delay_slot_branches(ops: &[LOpInfo]) -> HashSet<u64> {
+    let transfers_control =
+        |ty: &String| ty.ends_with("jmp") || ty.ends_with("call") || ty.ends_with("ret");
+    ops.iter()
+        .filter(|op| op.optype.as_ref().map_or(false, transfers_control))
+        .filter_map(|op| op.offset)
+        .collect()
+}
+
+// Parses an unsigned integer in decimal, or in hexadecimal if prefixed by `0x`.
+fn parse_u64(s: &str) -> Option<u64> {
+    if s.starts_with("0x") {
+        u64::from_str_radix(&s[2..], 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+/// What construction does with the instructions which have no effect, such as padding `nop`s or
+/// `endbr64`. These are the instructions typed as `nop` by r2, or whose ESIL is empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NopPolicy {
+    /// Leave them out of the SSA. A block still starts at their address if control flows to it.
+    Drop,
+    /// Translate each of them into an `OpNop` at its address, so that every instruction is mapped
+    /// to a node.
+    Keep,
+}
+
+impl Default for NopPolicy {
+    fn default() -> NopPolicy {
+        NopPolicy::Drop
+    }
+}
+
+// Whether the instruction `op` has no effect.
+fn is_nop(op: &LOpInfo) -> bool {
+    op.optype.as_ref().map_or(false, |ty| ty == "nop")
+        || op
+            .esil
+            .as_ref()
+            .map_or(false, |esil| esil.trim().is_empty())
+}
+
+/// Instructions setting up or tearing down a stack frame as a whole.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameOp {
+    /// `leave`: `mov sp, bp; pop bp`.
+    Leave,
+    /// `enter size, 0`: `push bp; mov bp, sp; sub sp, size`.
+    Enter(u64),
+}
+
+impl FrameOp {
+    /// Recognizes a frame instruction from its disassembly, e.g. `enter 0x10, 0`. `enter` is only
+    /// recognized with a nesting level of 0.
+    pub fn parse(disasm: &str) -> Option<FrameOp> {
+        let mut words = disasm.trim().splitn(2, ' ');
+        match (words.next()?, words.next()) {
+            ("leave", None) => Some(FrameOp::Leave),
+            ("enter", Some(args)) => {
+                let mut args = args.split(',').map(str::trim);
+                let size = parse_u64(args.next()?)?;
+                match (args.next().and_then(parse_u64), args.next()) {
+                    (Some(0), None) => Some(FrameOp::Enter(size)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns `true` if every block flowing to the exit of `ssa` contains one of the calls at `calls`.
+pub fn only_exits_through(ssa: &SSAStorage, calls: &[u64]) -> bool {
+    let exit = match ssa.exit_node() {
+        Some(exit) => exit,
+        None => return false,
+    };
+    let preds = ssa.preds_of(exit);
+    !preds.is_empty()
+        && preds.into_iter().all(|b| {
+            ssa.exprs_in(b).into_iter().any(|e| {
+                ssa.opcode(e) == Some(MOpcode::OpCall)
+                    && ssa.address(e).map_or(false, |a| calls.contains(&a.address))
+            })
+        })
+}
+
+/// Records on every value of `ssa` the ESIL of the instruction of `ops` it was translated from, to
+/// be printed along with the IL, see `EmitOptions::esil`.
+pub fn record_esil<T: SSAExtra>(ssa: &mut T, ops: &[LOpInfo]) {
+    let esil = ops
+        .iter()
+        .filter_map(|op| Some((op.offset?, op.esil.clone()?)))
+        .collect::<HashMap<_, _>>();
+    for value in ssa.values() {
+        let insn_esil = ssa.address(value).and_then(|a| esil.get(&a.address));
+        if let Some(insn_esil) = insn_esil {
+            ssa.set_esil(&value, insn_esil.clone());
+        }
+    }
+}
+
+/// Default maximum number of nested ESIL `?{` allowed by `SSAConstruct`.
+pub const MAX_NESTING: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConstructError {
+    /// More than `max` ESIL `?{` were nested; `address` is where the limit was exceeded.
+    NestingTooDeep { address: MAddress, max: usize },
+    /// The function ended while these `?{` were still waiting for their false branch.
+    UnclosedIf(Vec<MAddress>),
+    /// None of the instructions of the function has ESIL, its architecture is likely not
+    /// supported by r2.
+    NoEsil,
+    /// The function has `insns` instructions, more than the `max` its SSA may be constructed from.
+    TooLarge { insns: usize, max: usize },
+}
+
+impl error::Error for ConstructError {}
+
+impl fmt::Display for ConstructError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConstructError::NestingTooDeep { ref address, max } => {
+                write!(f, "esil nesting deeper than {} at {}", max, address)
+            }
+            ConstructError::UnclosedIf(ref addrs) => {
+                write!(f, "unclosed esil if at")?;
+                for addr in addrs {
+                    write!(f, " {}", addr)?;
+                }
+                Ok(())
+            }
+            ConstructError::NoEsil => {
+                write!(f, "no ESIL available, is this architecture supported?")
+            }
+            ConstructError::TooLarge { insns, max } => {
+                write!(
+                    f,
+                    "{} instructions, more than the maximum of {}",
+                    insns, max
+                )
+            }
+        }
+    }
+}
+
 pub struct SSAConstruct<'a, T>
 where
     T: 'a
@@ -54,12 +269,37 @@ where
     // Used to keep track of esil if-else. The reference to the ITE node and the address of this
     // instruction.
     nesting: Vec<(T::ValueRef, MAddress)>,
+    max_nesting: usize,
     // Used to keep track of the offset within an instruction.
     instruction_offset: u64,
     needs_new_block: bool,
     mem_id: u64,
     assume_cc: bool,
     replace_pc: bool,
+    // Addresses of the functions which never return to their caller.
+    noreturn: HashSet<u64>,
+    // Offsets of the calls to functions in `noreturn`.
+    noreturn_calls: Vec<u64>,
+    // Starting addresses of the blocks following a call to a function in `noreturn`.
+    fallthrough: Vec<MAddress>,
+    // Starting addresses of the blocks following a jump to itself, e.g. `jmp $`.
+    after_self_loops: Vec<MAddress>,
+    // First operand and result of the last arithmetic operation, to which the ESIL internal flag
+    // variables refer.
+    flag_operands: Option<(T::ValueRef, T::ValueRef)>,
+    // Whether jumps to a computed target are recorded as `OpIndirectJump`.
+    indirect_jumps: bool,
+    // Address range `[start, end)` of the function, outside of which instructions are ignored.
+    bounds: Option<(u64, u64)>,
+    // Offsets of the control transfers followed by a delay slot.
+    delay_slots: HashSet<u64>,
+    // Addresses referred to by the memory accesses of each instruction, by offset.
+    xrefs: HashMap<u64, Vec<u64>>,
+    // Values read from segment registers, which memory addresses may be based on.
+    segment_reads: HashMap<T::ValueRef, String>,
+    nop_policy: NopPolicy,
+    // Called on every block once construction is done.
+    on_block: Option<Box<dyn FnMut(&T, T::ActionRef) + 'a>>,
 }
 
 impl<'a, T> SSAConstruct<'a, T>
@@ -80,31 +320,178 @@ where
             regfile: regfile,
             intermediates: Vec::new(),
             nesting: Vec::new(),
+            max_nesting: MAX_NESTING,
             instruction_offset: 0,
             needs_new_block: true,
             mem_id: 0,
             assume_cc: false,
             replace_pc: true,
+            noreturn: HashSet::new(),
+            noreturn_calls: Vec::new(),
+            fallthrough: Vec::new(),
+            after_self_loops: Vec::new(),
+            flag_operands: None,
+            indirect_jumps: false,
+            bounds: None,
+            delay_slots: HashSet::new(),
+            xrefs: HashMap::new(),
+            segment_reads: HashMap::new(),
+            nop_policy: NopPolicy::default(),
+            on_block: None,
         };
 
         // Add all the registers to the variable list.
         sc.phiplacer
             .add_variables(sc.regfile.whole_registers.clone());
         // Add a new variable for "memory".
-        sc.phiplacer
-            .add_variables(vec![ValueInfo::new_scalar(ir::WidthSpec::Known(0))]);
+        sc.phiplacer.add_variables(vec![*MEM_VALUEINFO]);
         sc
     }
 
     // Helper wrapper.
     pub fn construct(rfn: &mut RadecoFunction, ri: &LRegInfo, assume_cc: bool, replace_pc: bool) {
+        SSAConstruct::<T>::construct_noreturn(rfn, ri, assume_cc, replace_pc, &HashSet::new());
+    }
+
+    /// Same as `construct`, but the functions at the addresses in `noreturn` never return to
+    /// their caller. `rfn` is marked as no-return if all its paths end in a call to one of them.
+    pub fn construct_noreturn(
+        rfn: &mut RadecoFunction,
+        ri: &LRegInfo,
+        assume_cc: bool,
+        replace_pc: bool,
+        noreturn: &HashSet<u64>,
+    ) {
+        let regfile = Arc::new(SubRegisterFile::new(ri));
+        SSAConstruct::<T>::construct_with_regfile(rfn, regfile, assume_cc, replace_pc, noreturn);
+    }
+
+    /// Same as `construct_noreturn`, but with the register file `regfile` rather than the one of
+    /// a register profile, e.g. to construct again a function with the registers of its SSA.
+    pub fn construct_with_regfile(
+        rfn: &mut RadecoFunction,
+        regfile: Arc<SubRegisterFile>,
+        assume_cc: bool,
+        replace_pc: bool,
+        noreturn: &HashSet<u64>,
+    ) {
+        SSAConstruct::<T>::construct_configured(
+            rfn,
+            regfile,
+            assume_cc,
+            replace_pc,
+            noreturn,
+            |_| {},
+        );
+    }
+
+    /// Same as `construct_with_regfile`, but `configure` is given the constructor before it
+    /// runs, e.g. to set the delay slots or the xrefs of the instructions.
+    pub fn construct_configured<F>(
+        rfn: &mut RadecoFunction,
+        regfile: Arc<SubRegisterFile>,
+        assume_cc: bool,
+        replace_pc: bool,
+        noreturn: &HashSet<u64>,
+        configure: F,
+    ) where
+        F: FnOnce(&mut SSAConstruct<'_, SSAStorage>),
+    {
         let instructions = rfn.instructions().to_vec();
+        let (rfn_offset, rfn_size) = (rfn.offset, rfn.size());
+        rfn.ssa_mut().regfile = regfile.clone();
+        let (noreturn_calls, construct_error) = {
+            let mut constr = SSAConstruct::new(rfn.ssa_mut(), &regfile);
+            constr.assume_cc = assume_cc;
+            constr.replace_pc = replace_pc;
+            constr.set_noreturn(noreturn.clone());
+            if rfn_size > 0 {
+                constr.set_bounds(rfn_offset, rfn_offset + rfn_size);
+            }
+            configure(&mut constr);
+            let res = constr.try_run(instructions.as_slice());
+            if let Err(ref _e) = res {
+                radeco_err!(target: LOG_CATEGORY, "{}", _e);
+            }
+            (constr.noreturn_calls, res.err())
+        };
+        if !noreturn_calls.is_empty() && only_exits_through(rfn.ssa(), &noreturn_calls) {
+            rfn.set_noreturn(true);
+        }
+        rfn.set_construct_error(construct_error);
+    }
+
+    /// Constructs an empty SSA for `rfn` rather than constructing it from its instructions, which
+    /// are more than `max`. Its construction error is `ConstructError::TooLarge`.
+    pub fn construct_skipped(rfn: &mut RadecoFunction, ri: &LRegInfo, max: usize) {
+        let insns = rfn.instructions().len();
         let regfile = Arc::new(SubRegisterFile::new(ri));
         rfn.ssa_mut().regfile = regfile.clone();
-        let mut constr = SSAConstruct::new(rfn.ssa_mut(), &regfile);
-        constr.assume_cc = assume_cc;
-        constr.replace_pc = replace_pc;
-        constr.run(instructions.as_slice());
+        {
+            let mut constr = SSAConstruct::new(rfn.ssa_mut(), &regfile);
+            let _ = constr.try_run(&[]);
+        }
+        rfn.set_construct_error(Some(ConstructError::TooLarge { insns, max }));
+    }
+
+    /// Sets the addresses of the functions which never return to their caller.
+    ///
+    /// Control does not fall through calls to these functions: the block containing the call
+    /// flows to the exit node, and the code that would only be reached after the call is dropped.
+    pub fn set_noreturn(&mut self, noreturn: HashSet<u64>) {
+        self.noreturn = noreturn;
+    }
+
+    /// Records the jumps to a computed target as `OpIndirectJump`s of the target rather than as
+    /// the selector of their block, so that they can be told apart from conditional jumps.
+    pub fn set_indirect_jumps(&mut self, indirect_jumps: bool) {
+        self.indirect_jumps = indirect_jumps;
+    }
+
+    /// Restricts construction to the instructions in `[start, end)`, e.g. the extent of the
+    /// function reported by r2, ignoring the instructions which spill past it. Control falling
+    /// through to an ignored instruction goes to the exit node instead.
+    pub fn set_bounds(&mut self, start: u64, end: u64) {
+        self.bounds = Some((start, end));
+    }
+
+    /// Sets the offsets of the branches, calls and returns followed by a delay slot, as on MIPS
+    /// and SPARC. The instruction in the delay slot executes before control is transferred, so it
+    /// is placed in the block of the branch, right before the `?{` or the write to the PC.
+    pub fn set_delay_slots(&mut self, branches: HashSet<u64>) {
+        self.delay_slots = branches;
+    }
+
+    /// Sets the addresses referred to by the instructions, by offset, e.g. as found by
+    /// `RadecoFunction::instruction_xrefs`. They are recorded on the `Load`s and `Store`s of the
+    /// instruction and on its expressions using them as constants, see `SSAExtra::xrefs_of`.
+    pub fn set_xrefs(&mut self, xrefs: HashMap<u64, Vec<u64>>) {
+        self.xrefs = xrefs;
+    }
+
+    /// Sets the maximum number of nested ESIL `?{`. Deeper input makes `try_run` fail.
+    pub fn set_max_nesting(&mut self, depth: usize) {
+        self.max_nesting = depth;
+    }
+
+    /// Sets what to do with the instructions which have no effect, dropped by default.
+    pub fn set_nop_policy(&mut self, policy: NopPolicy) {
+        self.nop_policy = policy;
+    }
+
+    /// Sets a function called with every block of the SSA once it is complete, in increasing
+    /// order of address, so that a consumer can process the blocks one by one, e.g. to serialize
+    /// them.
+    ///
+    /// A block is only complete once all the instructions are translated, as a later jump may
+    /// still split it or add phis to it. The function is thus called at the end of `try_run`,
+    /// unless construction is aborted: the whole SSA is still built before the first call, this
+    /// does not lower the memory construction takes.
+    pub fn set_block_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(&T, T::ActionRef) + 'a,
+    {
+        self.on_block = Some(Box::new(callback));
     }
 
     fn set_mem_id(&mut self, id: u64) {
@@ -144,7 +531,11 @@ where
                     let value = address.address + length.unwrap();
                     self.phiplacer.add_const(address, value, None)
                 } else {
-                    self.phiplacer.read_register(address, name)
+                    let value = self.phiplacer.read_register(address, name);
+                    if self.regfile.is_segment(name) {
+                        self.segment_reads.insert(value, name.clone());
+                    }
+                    value
                 }
             }
             // We arrive at this case only when we have popped an operand that we have pushed
@@ -204,8 +595,13 @@ where
         let mut lhs = self.process_in(&operands[0], address, Some(op_length));
         let mut rhs = self.process_in(&operands[1], address, Some(op_length));
 
-        self.phiplacer
-            .narrow_const_operand(address, &mut lhs, &mut rhs);
+        // A peek yields a value as wide as the memory access. Extend it to the width of the
+        // other operand before the operand sizes are compared.
+        if sets_flags(token) || is_signed(token) {
+            self.extend_peek(token, address, &mut lhs, rhs);
+            self.extend_peek(token, address, &mut rhs, lhs);
+        }
+        self.phiplacer.fit_const_operand(&mut lhs, &mut rhs);
 
         // Check if the two operands are of compatible sizes for compare
         let lhs_size = lhs.map_or(0, |i| self.phiplacer.operand_width(&i));
@@ -251,15 +647,30 @@ where
                                 Some(UNCOND_EDGE),
                             );
                             self.needs_new_block = true;
+                        } else if self.pops_target(rhs, *address) {
+                            // The target is popped from the stack, e.g. by a `ret` whose type
+                            // was not reported: control returns to the caller.
+                            radeco_trace!(target: LOG_CATEGORY, "ssa_construct_pop_pc|{}", address);
+                            self.phiplacer.add_return(*address, UNCOND_EDGE);
+                            self.needs_new_block = true;
                         } else {
                             // Indirect CF transfer
                             if let Some(ref jump_idx) = rhs {
-                                self.phiplacer
-                                    .add_indirect_cf(jump_idx, address, UNCOND_EDGE);
+                                if self.indirect_jumps {
+                                    self.phiplacer.add_indirect_jump(
+                                        jump_idx,
+                                        address,
+                                        UNCOND_EDGE,
+                                    );
+                                } else {
+                                    self.phiplacer
+                                        .add_indirect_cf(jump_idx, address, UNCOND_EDGE);
+                                }
                                 // Next instruction should begin in a new block
                                 self.needs_new_block = true;
                             } else {
                                 radeco_warn!(
+                                    target: LOG_CATEGORY,
                                     "Found a indirect jump without a corresponding target \
                                      expression"
                                 );
@@ -276,10 +687,11 @@ where
                 } else {
                     // This means that we're performing a memory write. So we need to emit an
                     // OpStore operation.
-                    radeco_trace!("Memory Write");
+                    radeco_trace!(target: LOG_CATEGORY, "Memory Write");
                     let op_node = self
                         .phiplacer
                         .add_op(&MOpcode::OpStore, address, *MEM_VALUEINFO);
+                    self.add_xrefs(&op_node, address);
                     self.phiplacer.op_use(
                         &op_node,
                         0,
@@ -309,7 +721,7 @@ where
                 // represented throught the control flow edges of the block to which ITE belongs
                 // to. For clarity, we will add comments to show the same.
                 // Hence: 0 -> compare statement. 1 -> T. 2 -> F.
-                let true_address = MAddress::new(address.address, address.offset + 1);
+                let true_address = address.next();
                 let _true_block =
                     self.phiplacer
                         .add_block(true_address, Some(*address), Some(TRUE_EDGE));
@@ -375,35 +787,77 @@ where
                 MOpcode::OpMod,
                 ValueInfo::new_scalar(ir::WidthSpec::from(result_size)),
             ),
+            Token::EFAdd => (
+                MOpcode::OpFAdd,
+                ValueInfo::new_float(ir::WidthSpec::from(result_size)),
+            ),
+            Token::EFSub => (
+                MOpcode::OpFSub,
+                ValueInfo::new_float(ir::WidthSpec::from(result_size)),
+            ),
+            Token::EFMul => (
+                MOpcode::OpFMul,
+                ValueInfo::new_float(ir::WidthSpec::from(result_size)),
+            ),
+            Token::EFDiv => (
+                MOpcode::OpFDiv,
+                ValueInfo::new_float(ir::WidthSpec::from(result_size)),
+            ),
+            Token::EFCmp => (
+                MOpcode::OpFEq,
+                ValueInfo::new_scalar(ir::WidthSpec::Known(1)),
+            ),
+            Token::EFLt => (
+                MOpcode::OpFLt,
+                ValueInfo::new_scalar(ir::WidthSpec::Known(1)),
+            ),
+            Token::EI2F => (
+                MOpcode::OpIToF,
+                ValueInfo::new_float(ir::WidthSpec::from(result_size)),
+            ),
+            Token::EF2I => (
+                MOpcode::OpFToI,
+                ValueInfo::new_scalar(ir::WidthSpec::from(result_size)),
+            ),
             Token::EPoke(_) => {
                 // TODO: rhs has to be cast to size 'n' if it's size is not already n.
+                let segment = lhs.as_ref().and_then(|addr| self.segment_of(addr));
                 let mem_id = self.mem_id();
                 let mem = self.phiplacer.read_variable(address, mem_id);
                 let op_node = self
                     .phiplacer
-                    .add_op(&MOpcode::OpStore, address, scalar!(0));
+                    .add_op(&MOpcode::OpStore, address, *MEM_VALUEINFO);
+                self.add_xrefs(&op_node, address);
 
                 self.phiplacer.op_use(&op_node, 0, &mem);
                 self.phiplacer
                     .op_use(&op_node, 1, lhs.as_ref().expect("lhs cannot be `None`"));
                 self.phiplacer
                     .op_use(&op_node, 2, rhs.as_ref().expect("rhs cannot be `None`"));
+                if let Some(segment) = segment {
+                    self.phiplacer.set_segment(&op_node, segment);
+                }
 
                 self.phiplacer
                     .write_variable(*address, self.mem_id, op_node);
                 return None;
             }
             Token::EPeek(n) => {
-                let mem = self.phiplacer.read_variable(address, self.mem_id);
+                let segment = lhs.as_ref().and_then(|addr| self.segment_of(addr));
+                let mem = self.phiplacer.read_variable(address, self.mem_id());
                 let op_node = self.phiplacer.add_op(
                     &MOpcode::OpLoad,
                     address,
                     ValueInfo::new_unresolved(ir::WidthSpec::from(n as u16)),
                 );
+                self.add_xrefs(&op_node, address);
 
                 self.phiplacer.op_use(&op_node, 0, &mem);
                 self.phiplacer
                     .op_use(&op_node, 1, lhs.as_ref().expect("lhs cannot be `None`"));
+                if let Some(segment) = segment {
+                    self.phiplacer.set_segment(&op_node, segment);
+                }
                 return Some(op_node);
             }
             Token::EPop => unreachable!(),
@@ -412,6 +866,25 @@ where
             Token::EEndIf | Token::ENop => {
                 return None;
             }
+            Token::IZero(_)
+            | Token::ICarry(_)
+            | Token::IBorrow(_)
+            | Token::IParity(_)
+            | Token::IOverflow(_)
+            | Token::ISign(_) => {
+                return Some(self.process_flag(token, address));
+            }
+            Token::ISize(_) => {
+                let size = self.regfile.pc_width().unwrap_or(64);
+                return Some(self.phiplacer.add_const(address, size, None));
+            }
+            Token::IAddress(_) => {
+                let value = address.address;
+                return Some(self.phiplacer.add_const(address, value, None));
+            }
+            Token::IConstant(value) => {
+                return Some(self.phiplacer.add_const(address, value, None));
+            }
             // Anything else is considered invalid. Log this as a warning and move on.
             // We may not
             // want to panic here as we can still achieve a reasonable decompilation
@@ -422,14 +895,54 @@ where
             }
         };
 
-        // Insert `widen` cast of the two are not of same size and rhs is_some.
+        // A memory state is only ever an operand of a load or a store. Anything else is malformed
+        // ESIL, which yields an undefined value rather than an operation on a width 0 operand.
+        let is_memory =
+            |v: Option<T::ValueRef>| v.map_or(false, |i| self.phiplacer.operand_is_memory(&i));
+        if is_memory(lhs) || is_memory(rhs) {
+            radeco_warn!(
+                target: LOG_CATEGORY,
+                "Memory used as an operand of {:?} at {}",
+                token,
+                address
+            );
+            if sets_flags(token) {
+                self.flag_operands = None;
+            }
+            let width = match vt.width().get_width() {
+                Some(0) => ir::WidthSpec::Unknown,
+                _ => *vt.width(),
+            };
+            return Some(
+                self.phiplacer
+                    .add_undefined(*address, ValueInfo::new_scalar(width)),
+            );
+        }
+
+        // Arithmetic on floating point operands yields a floating point value.
+        let is_float = lhs.map_or(false, |i| self.phiplacer.operand_is_float(&i))
+            || rhs.map_or(false, |i| self.phiplacer.operand_is_float(&i));
+        let vt = match *token {
+            Token::ECmp | Token::ELt | Token::EGt | Token::EFCmp | Token::EFLt | Token::EF2I => vt,
+            _ if is_float => ValueInfo::new_float(*vt.width()),
+            _ => vt,
+        };
+
+        // Insert `widen` cast of the two are not of same size and rhs is_some. Operands of signed
+        // operations are sign extended, all the others are zero extended.
         if rhs.is_some() {
+            let signed = is_signed(token);
+            let widen = |width| {
+                if signed {
+                    MOpcode::OpSignExt(width)
+                } else {
+                    MOpcode::OpZeroExt(width)
+                }
+            };
             let (lhs, rhs) = match lhs_size.cmp(&rhs_size) {
                 cmp::Ordering::Greater => {
                     let vt = ValueInfo::new_unresolved(ir::WidthSpec::from(lhs_size));
-                    let casted_rhs =
-                        self.phiplacer
-                            .add_op(&MOpcode::OpZeroExt(lhs_size), address, vt);
+                    let casted_rhs = self.phiplacer.add_op(&widen(lhs_size), address, vt);
                     self.phiplacer
                         .op_use(&casted_rhs, 0, rhs.as_ref().expect(""));
                     self.phiplacer.propagate_reginfo(&casted_rhs);
@@ -437,9 +950,7 @@ where
                 }
                 cmp::Ordering::Less => {
                     let vt = ValueInfo::new_unresolved(ir::WidthSpec::from(rhs_size));
-                    let casted_lhs =
-                        self.phiplacer
-                            .add_op(&MOpcode::OpZeroExt(rhs_size), address, vt);
+                    let casted_lhs = self.phiplacer.add_op(&widen(rhs_size), address, vt);
                     self.phiplacer.op_use(
                         &casted_lhs,
                         0,
@@ -453,6 +964,9 @@ where
             let op_node_ = self.phiplacer.add_op(&op, address, vt);
             self.phiplacer.op_use(&op_node_, 0, &lhs);
             self.phiplacer.op_use(&op_node_, 1, &rhs);
+            if sets_flags(token) {
+                self.flag_operands = Some((lhs, op_node_));
+            }
             Some(op_node_)
         } else {
             // There is only one operand, that is lhs. No need for cast.
@@ -462,6 +976,160 @@ where
         }
     }
 
+    // Extends `operand` right after its load if it is a peek narrower than `other`. A constant
+    // `other` only widens the peek if its value does not fit in the width of the access.
+    fn extend_peek(
+        &mut self,
+        token: &Token,
+        address: &mut MAddress,
+        operand: &mut Option<T::ValueRef>,
+        other: Option<T::ValueRef>,
+    ) {
+        let (load, other) = match (*operand, other) {
+            (Some(load), Some(other)) => (load, other),
+            _ => return,
+        };
+        if self.phiplacer.operand_opcode(&load) != Some(MOpcode::OpLoad) {
+            return;
+        }
+
+        let load_width = self.phiplacer.operand_width(&load);
+        let width = self.phiplacer.operand_width(&other);
+        let fits = match self.phiplacer.operand_opcode(&other) {
+            Some(MOpcode::OpConst(value)) => value <= genmask(u64::from(load_width)),
+            _ => false,
+        };
+        if load_width >= width || fits {
+            return;
+        }
+
+        let opcode = if is_signed(token) {
+            MOpcode::OpSignExt(width)
+        } else {
+            MOpcode::OpZeroExt(width)
+        };
+        let vt = ValueInfo::new_unresolved(ir::WidthSpec::from(width));
+        let ext = self.phiplacer.add_op(&opcode, address, vt);
+        self.phiplacer.op_use(&ext, 0, &load);
+        *operand = Some(ext);
+    }
+
+    // Computes the ESIL internal flag variable `token` from the first operand (`old`) and the
+    // result (`cur`) of the last arithmetic operation, as r2 does.
+    fn process_flag(&mut self, token: &Token, address: &mut MAddress) -> T::ValueRef {
+        let flag_vt = ValueInfo::new_scalar(ir::WidthSpec::Known(1));
+        let (old, cur) = match self.flag_operands {
+            Some(operands) => operands,
+            None => {
+                radeco_warn!(
+                    target: LOG_CATEGORY,
+                    "{:?} at {} follows no arithmetic operation",
+                    token,
+                    address
+                );
+                return self.phiplacer.add_undefined(*address, flag_vt);
+            }
+        };
+        let width = self.phiplacer.operand_width(&cur);
+
+        match *token {
+            // cur == 0
+            Token::IZero(_) => {
+                let zero = self.add_sized_const(address, 0, width);
+                self.add_binop(MOpcode::OpEq, address, flag_vt, cur, zero)
+            }
+            // Carry out of bit `bit`: (cur & mask) < (old & mask)
+            Token::ICarry(bit) => self.below(address, u64::from(bit) + 1, cur, old),
+            // Borrow from bit `bit`: (old & mask) < (cur & mask)
+            Token::IBorrow(bit) => self.below(address, u64::from(bit), old, cur),
+            // Even number of bits set in the lowest byte of cur.
+            Token::IParity(_) => {
+                let vt = ValueInfo::new_unresolved(ir::WidthSpec::from(width));
+                let ff = self.add_sized_const(address, 0xff, width);
+                let mut x = self.add_binop(MOpcode::OpAnd, address, vt, cur, ff);
+                for &shift in &[4, 2, 1] {
+                    let amount = self.add_sized_const(address, shift, width);
+                    let shifted = self.add_binop(MOpcode::OpLsr, address, vt, x, amount);
+                    x = self.add_binop(MOpcode::OpXor, address, vt, x, shifted);
+                }
+                let one = self.add_sized_const(address, 1, width);
+                let low = self.add_binop(MOpcode::OpAnd, address, vt, x, one);
+                let zero = self.add_sized_const(address, 0, width);
+                self.add_binop(MOpcode::OpEq, address, flag_vt, low, zero)
+            }
+            // Carry into the sign bit differs from the carry out of it.
+            Token::IOverflow(_) => {
+                let bits = u64::from(width);
+                let carry_in = self.below(address, bits.saturating_sub(1), cur, old);
+                let carry_out = self.below(address, bits, cur, old);
+                self.add_binop(MOpcode::OpXor, address, flag_vt, carry_in, carry_out)
+            }
+            // Most significant bit of cur.
+            Token::ISign(_) => {
+                let vt = ValueInfo::new_unresolved(ir::WidthSpec::from(width));
+                let amount = self.add_sized_const(address, u64::from(width) - 1, width);
+                let msb = self.add_binop(MOpcode::OpLsr, address, vt, cur, amount);
+                let flag = self
+                    .phiplacer
+                    .add_op(&MOpcode::OpNarrow(1), address, flag_vt);
+                self.phiplacer.op_use(&flag, 0, &msb);
+                flag
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // Whether the `bits` lowest bits of `lhs` are below those of `rhs`.
+    fn below(
+        &mut self,
+        address: &mut MAddress,
+        bits: u64,
+        lhs: T::ValueRef,
+        rhs: T::ValueRef,
+    ) -> T::ValueRef {
+        let width = self.phiplacer.operand_width(&lhs);
+        let (lhs, rhs) = if bits < u64::from(width) {
+            let vt = ValueInfo::new_unresolved(ir::WidthSpec::from(width));
+            let mask = self.add_sized_const(address, genmask(bits), width);
+            (
+                self.add_binop(MOpcode::OpAnd, address, vt, lhs, mask),
+                self.add_binop(MOpcode::OpAnd, address, vt, rhs, mask),
+            )
+        } else {
+            (lhs, rhs)
+        };
+        let flag_vt = ValueInfo::new_scalar(ir::WidthSpec::Known(1));
+        self.add_binop(MOpcode::OpLt, address, flag_vt, lhs, rhs)
+    }
+
+    fn add_binop(
+        &mut self,
+        op: MOpcode,
+        address: &mut MAddress,
+        vt: ValueInfo,
+        lhs: T::ValueRef,
+        rhs: T::ValueRef,
+    ) -> T::ValueRef {
+        let node = self.phiplacer.add_op(&op, address, vt);
+        self.phiplacer.op_use(&node, 0, &lhs);
+        self.phiplacer.op_use(&node, 1, &rhs);
+        node
+    }
+
+    // Adds the constant `value`, narrowed to `width` bits.
+    fn add_sized_const(&mut self, address: &mut MAddress, value: u64, width: u16) -> T::ValueRef {
+        let value_node = self.phiplacer.add_const(address, value, None);
+        if width >= 64 {
+            return value_node;
+        }
+        let vt = ValueInfo::new_scalar(ir::WidthSpec::from(width));
+        let narrow = self
+            .phiplacer
+            .add_op(&MOpcode::OpNarrow(width), address, vt);
+        self.phiplacer.op_use(&narrow, 0, &value_node);
+        narrow
+    }
+
     fn init_blocks(&mut self) {
         // Create a start block with all registers as variables defined in this block.
         // Seal this block as the start block cannot have any more successors.
@@ -510,31 +1178,77 @@ where
     // it into its SSA
     // form.
     pub fn run(&mut self, op_info: &[LOpInfo]) {
-        let mut p = Parser::init(
-            Some(
-                self.regfile
-                    .named_registers
-                    .iter()
-                    .map(|(n, v)| (n.clone(), v.width as u64))
-                    .collect(),
-            ),
-            Some(64),
-        );
+        if let Err(_e) = self.try_run(op_info) {
+            radeco_err!(target: LOG_CATEGORY, "{}", _e);
+        }
+    }
+
+    /// Same as `run`, but reports malformed if-else nesting in the ESIL.
+    ///
+    /// If the nesting gets deeper than the configured maximum, construction is aborted and the
+    /// SSA is left incomplete. If some `?{` never get a false branch, the SSA is still finished
+    /// and the addresses of those `?{` are returned. If none of the instructions has ESIL, the
+    /// SSA is an empty function and `ConstructError::NoEsil` is returned.
+    pub fn try_run(&mut self, op_info: &[LOpInfo]) -> Result<(), ConstructError> {
+        let mut p = self.parser();
+        let no_esil = !op_info.is_empty() && op_info.iter().all(|op| op.esil.is_none());
 
         let mut current_address = MAddress::new(0, 0);
+        let mut in_delay_slot = false;
+        // The branch whose delay slot is also a jump target, and the `?{` it leaves open. Its
+        // fall-through is past the delay slot, which executes once on that path.
+        let mut after_delay_slot = None;
+        let jump_targets = if self.delay_slots.is_empty() {
+            HashSet::new()
+        } else {
+            self.jump_targets(op_info)
+        };
         self.init_blocks();
-        for op in op_info {
+        for (i, op) in op_info.iter().enumerate() {
             if op.esil.is_none() {
                 continue;
             }
 
             let offset = op.offset.unwrap_or(0);
+            // This instruction was already placed along with the branch it follows. If it is
+            // also the target of a jump, it starts a block of its own.
+            let mut falls_through = true;
+            if in_delay_slot {
+                in_delay_slot = false;
+                if !jump_targets.contains(&offset) {
+                    continue;
+                }
+                let nesting = mem::replace(&mut self.nesting, Vec::new());
+                after_delay_slot = Some((current_address, nesting));
+                self.needs_new_block = true;
+                falls_through = false;
+            } else if after_delay_slot.is_some() {
+                self.needs_new_block = true;
+            }
+            if self
+                .bounds
+                .map_or(false, |(start, end)| offset < start || offset >= end)
+            {
+                radeco_trace!(target: LOG_CATEGORY, "ssa_construct_out_of_bounds|{:#x}", offset);
+                if !self.needs_new_block {
+                    self.phiplacer.maybe_add_return(current_address);
+                    self.needs_new_block = true;
+                }
+                continue;
+            }
+
+            let mut delay_slot = if self.delay_slots.contains(&offset) {
+                op_info[i + 1..].iter().find(|slot| slot.esil.is_some())
+            } else {
+                None
+            };
+            in_delay_slot = delay_slot.is_some();
 
             // Get ESIL string
             let esil_str = if let Some(ref esil_str_) = op.esil {
                 esil_str_
             } else {
-                radeco_warn!("No ESIL string found at: {}", offset);
+                radeco_warn!(target: LOG_CATEGORY, "No ESIL string found at: {}", offset);
                 continue;
             };
 
@@ -544,11 +1258,25 @@ where
             let next_address = MAddress::new(offset, self.instruction_offset);
             if self.needs_new_block {
                 self.needs_new_block = false;
+                // Control never falls through an infinite loop such as `jmp $`.
+                if self.phiplacer.is_self_loop(current_address) {
+                    self.after_self_loops.push(next_address);
+                }
                 self.phiplacer.add_block(next_address, None, None);
+                // This is the fall-through of the last no-return call.
+                if self.fallthrough.len() < self.noreturn_calls.len() {
+                    self.fallthrough.push(next_address);
+                }
             }
 
             current_address.offset = 0;
-            self.phiplacer.maybe_add_edge(current_address, next_address);
+            if falls_through {
+                self.phiplacer.maybe_add_edge(current_address, next_address);
+                if let Some((branch, nesting)) = after_delay_slot.take() {
+                    self.phiplacer.maybe_add_edge(branch, next_address);
+                    self.nesting.extend(nesting);
+                }
+            }
             current_address = next_address;
 
             // If the nesting vector has a non zero length, then we need to make another
@@ -567,7 +1295,20 @@ where
                 self.phiplacer.op_use(src_node, 2, &false_comment);
             }
 
-            radeco_trace!("ssa_construct_esil|{}|{:?}", current_address, esil_str);
+            radeco_trace!(
+                target: LOG_CATEGORY,
+                "ssa_construct_esil|{}|{:?}",
+                current_address,
+                esil_str
+            );
+
+            if is_nop(op) {
+                if self.nop_policy == NopPolicy::Keep {
+                    self.phiplacer
+                        .add_op(&MOpcode::OpNop, &mut current_address, scalar!(0));
+                }
+                continue;
+            }
 
             // Handle call separately.
             // NOTE: This is a hack.
@@ -589,6 +1330,10 @@ where
                 if let Some(call_ty) = opt_call_ty {
                     let is_real_call = &*call_ty == "call" || &*call_ty == "ucall";
 
+                    if let Some(slot) = delay_slot.take() {
+                        self.process_delay_slot(slot, &mut current_address)?;
+                    }
+
                     let unknown_str = "unknown".to_owned();
 
                     let value_type = if &*call_ty == "call" {
@@ -688,17 +1433,41 @@ where
                     }
 
                     self.phiplacer.op_use(&op_call, 0, &call_operand);
+
+                    // Control never comes back from a no-return function, so this block ends here
+                    // and does not fall through to the next instruction.
+                    let target = call_target(esil_str);
+                    if is_real_call && target.map_or(false, |t| self.noreturn.contains(&t)) {
+                        radeco_trace!(
+                            target: LOG_CATEGORY,
+                            "ssa_construct_noreturn|{}",
+                            current_address
+                        );
+                        self.phiplacer.add_return(current_address, UNCOND_EDGE);
+                        self.needs_new_block = true;
+                        self.noreturn_calls.push(offset);
+                    }
                     continue;
                 }
             }
 
             // Handle returns separately
             if op.optype.as_ref().map_or(false, |ty| ty == "ret") {
+                if let Some(slot) = delay_slot.take() {
+                    self.process_delay_slot(slot, &mut current_address)?;
+                }
                 self.phiplacer.add_return(current_address, UNCOND_EDGE);
                 self.needs_new_block = true;
                 continue;
             }
 
+            // Frame instructions are translated as a whole rather than from their ESIL.
+            if let Some(frame_op) = op.opcode.as_ref().and_then(|o| FrameOp::parse(o)) {
+                if self.process_frame_op(frame_op, &mut current_address) {
+                    continue;
+                }
+            }
+
             /*
             // Some overrides as we do not support all esil and don't want to panic.
             let overrides = &["GOTO", "TRAP", "$", "TODO", "REPEAT"];
@@ -722,50 +1491,295 @@ where
             }
             */
 
-            loop {
-                let token_opt = match p.parse::<_, Tokenizer>(esil_str) {
-                    Ok(token_opt_) => token_opt_,
+            self.process_esil(
+                &mut p,
+                esil_str,
+                &mut current_address,
+                op.size.unwrap_or(0),
+                &mut delay_slot,
+            )?;
+            // The branch did not transfer control, e.g. its ESIL is not understood.
+            if let Some(slot) = delay_slot {
+                self.process_delay_slot(slot, &mut current_address)?;
+            }
+        }
+        // BUG: The last block may not have the biggest address, which means current_address
+        // may be not in the last basic block
+        // self.phiplacer.add_edge(current_address, MAddress::exit(), UNCOND_EDGE);
+        self.phiplacer.gather_exits();
+        self.phiplacer.finish(op_info);
+        let mut unreachable = self.fallthrough.clone();
+        unreachable.extend(&self.after_self_loops);
+        self.phiplacer.prune_unreachable(&unreachable);
+        self.complete_blocks();
+        self.phiplacer.add_constant_xrefs(&self.xrefs);
+
+        if no_esil {
+            Err(ConstructError::NoEsil)
+        } else if self.nesting.is_empty() {
+            Ok(())
+        } else {
+            let unclosed = self.nesting.drain(..).map(|(_, addr)| addr).collect();
+            Err(ConstructError::UnclosedIf(unclosed))
+        }
+    }
+
+    // Hands the blocks to the callback set with `set_block_callback`, if any.
+    fn complete_blocks(&mut self) {
+        let mut callback = match self.on_block.take() {
+            Some(callback) => callback,
+            None => return,
+        };
+        let ssa = self.phiplacer.ssa();
+        let mut blocks = ssa.blocks();
+        blocks.sort_by_key(|&b| ssa.starting_address(b));
+        for block in blocks {
+            callback(ssa, block);
+        }
+        self.on_block = Some(callback);
+    }
+
+    fn parser(&self) -> Parser {
+        let mut p = Parser::init(
+            Some(
+                self.regfile
+                    .named_registers
+                    .iter()
+                    .map(|(n, v)| (n.clone(), v.width as u64))
+                    .collect(),
+            ),
+            Some(self.regfile.pc_width().unwrap_or(64)),
+        );
+        p.set_expand_internal(false);
+        p
+    }
+
+    // Translates the ESIL of an instruction of `size` bytes. If `delay_slot` is set, that
+    // instruction is translated right before the first transfer of control.
+    fn process_esil(
+        &mut self,
+        p: &mut Parser,
+        esil_str: &str,
+        current_address: &mut MAddress,
+        size: u64,
+        delay_slot: &mut Option<&LOpInfo>,
+    ) -> Result<(), ConstructError> {
+        loop {
+            let token_opt = match p.parse::<_, Tokenizer>(esil_str) {
+                Ok(token_opt_) => token_opt_,
+                Err(_err) => {
+                    radeco_err!(target: LOG_CATEGORY, "{}", _err.to_string());
+                    continue;
+                }
+            };
+
+            if let Some(ref token) = token_opt {
+                radeco_trace!(
+                    target: LOG_CATEGORY,
+                    "ssa_construct_token|{}|{:?}",
+                    current_address,
+                    token
+                );
+                let (lhs, rhs) = match p.fetch_operands(token) {
+                    Ok(operands_opt) => operands_opt,
                     Err(_err) => {
-                        radeco_err!("{}", _err.to_string());
+                        radeco_err!(target: LOG_CATEGORY, "{}", _err.to_string());
                         continue;
                     }
                 };
 
-                if let Some(ref token) = token_opt {
-                    radeco_trace!("ssa_construct_token|{}|{:?}", current_address, token);
-                    let (lhs, rhs) = match p.fetch_operands(token) {
-                        Ok(operands_opt) => operands_opt,
-                        Err(_err) => {
-                            radeco_err!("{}", _err.to_string());
-                            continue;
-                        }
-                    };
-
-                    // Determine what to do with the operands and get the result.
-                    let result = self.process_op(
-                        token,
-                        &mut current_address,
-                        &[lhs, rhs],
-                        op.size.unwrap_or(0),
-                    );
-                    if let Some(result_) = self.process_out(result, current_address) {
-                        p.push(result_);
+                if delay_slot.is_some() && self.transfers_control(token, &lhs) {
+                    if let Some(slot) = delay_slot.take() {
+                        self.process_delay_slot(slot, current_address)?;
                     }
-                    current_address.offset += 1;
-                } else {
-                    break;
                 }
+
+                // Determine what to do with the operands and get the result.
+                let result = self.process_op(token, current_address, &[lhs, rhs], size);
+                if let Some(result_) = self.process_out(result, *current_address) {
+                    p.push(result_);
+                }
+                if self.nesting.len() > self.max_nesting {
+                    return Err(ConstructError::NestingTooDeep {
+                        address: *current_address,
+                        max: self.max_nesting,
+                    });
+                }
+                current_address.inc_offset();
+            } else {
+                return Ok(());
             }
         }
-        // BUG: The last block may not have the biggest address, which means current_address
-        // may be not in the last basic block
-        // self.phiplacer.add_edge(current_address, MAddress::new(u64::MAX, 0), UNCOND_EDGE);
-        self.phiplacer.gather_exits();
-        self.phiplacer.finish(op_info);
     }
 
-    #[allow(dead_code)]
-    fn process_memory_op(
+    // Returns the targets of the direct jumps of `op_info`, i.e. the constants written to PC.
+    fn jump_targets(&self, op_info: &[LOpInfo]) -> HashSet<u64> {
+        let pc = match self.regfile.alias_info.get("PC") {
+            Some(pc) => pc,
+            None => return HashSet::new(),
+        };
+        op_info
+            .iter()
+            .filter_map(|op| op.esil.as_ref())
+            .flat_map(|esil| {
+                let tokens = esil.split(',').collect::<Vec<_>>();
+                tokens
+                    .windows(3)
+                    .filter(|w| w[1] == pc && w[2] == "=")
+                    .filter_map(|w| parse_u64(w[0]))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    // Translates the instruction in the delay slot of a branch into the block of the branch.
+    fn process_delay_slot(
+        &mut self,
+        slot: &LOpInfo,
+        current_address: &mut MAddress,
+    ) -> Result<(), ConstructError> {
+        let esil_str = match slot.esil {
+            Some(ref esil_str) => esil_str,
+            None => return Ok(()),
+        };
+        radeco_trace!(
+            target: LOG_CATEGORY,
+            "ssa_construct_delay_slot|{}|{:?}",
+            current_address,
+            esil_str
+        );
+        let mut p = self.parser();
+        self.process_esil(
+            &mut p,
+            esil_str,
+            current_address,
+            slot.size.unwrap_or(0),
+            &mut None,
+        )
+    }
+
+    // Returns `true` if `target` is loaded from the top of the stack.
+    fn pops_target(&self, target: Option<T::ValueRef>, address: MAddress) -> bool {
+        let (target, sp) = match (target, self.regfile.alias_info.get("SP")) {
+            (Some(target), Some(sp)) => (target, sp),
+            _ => return false,
+        };
+        match self.phiplacer.register_def(address, sp) {
+            Some(top) => self.phiplacer.is_load_from(&target, &top),
+            None => false,
+        }
+    }
+
+    // Returns the name of the segment register read to compute the memory address `addr`, e.g.
+    // `fs` in `0x28,fs,+,[8]`. A real mode segment is shifted before it is added to the offset.
+    fn segment_of(&self, addr: &T::ValueRef) -> Option<String> {
+        if let Some(name) = self.segment_reads.get(addr) {
+            return Some(name.clone());
+        }
+        match self.phiplacer.operand_opcode(addr) {
+            Some(MOpcode::OpAdd) => self
+                .phiplacer
+                .operands_of(addr)
+                .iter()
+                .filter_map(|op| self.segment_of(op))
+                .next(),
+            Some(MOpcode::OpLsl) | Some(MOpcode::OpMul) => self
+                .phiplacer
+                .operands_of(addr)
+                .iter()
+                .filter_map(|op| self.segment_reads.get(op).cloned())
+                .next(),
+            _ => None,
+        }
+    }
+
+    // Records the addresses referred to by the instruction at `address` on `node`.
+    fn add_xrefs(&mut self, node: &T::ValueRef, address: &MAddress) {
+        if let Some(xrefs) = self.xrefs.get(&address.address) {
+            self.phiplacer.add_xrefs(node, xrefs);
+        }
+    }
+
+    // Translates a frame instruction. All the registers are read before any of them is written,
+    // so that no intermediate stack pointer is ever seen. The stack pointer is moved by an
+    // `OpAdd` of a constant and a register, or an `OpSub` of a register and a constant, as
+    // expected by the stack offset analyses. Returns `false` if the register profile has no
+    // stack or frame pointer.
+    fn process_frame_op(&mut self, frame_op: FrameOp, address: &mut MAddress) -> bool {
+        let (sp, bp) = match (
+            self.regfile.alias_info.get("SP"),
+            self.regfile.alias_info.get("BP"),
+        ) {
+            (Some(sp), Some(bp)) => (sp.clone(), bp.clone()),
+            _ => return false,
+        };
+        let width = match self.regfile.get_subregister(&bp) {
+            Some(reg) => reg.width,
+            None => return false,
+        };
+        radeco_trace!(
+            target: LOG_CATEGORY,
+            "ssa_construct_frame|{}|{:?}",
+            address,
+            frame_op
+        );
+        let vt = ValueInfo::new_scalar(ir::WidthSpec::from(width as u16));
+        let slot_size = self.phiplacer.add_const(address, width / 8, None);
+        match frame_op {
+            FrameOp::Leave => {
+                let frame = self.phiplacer.read_register(address, &bp);
+                let mem = self.phiplacer.read_variable(address, self.mem_id());
+                let saved_bp = self.phiplacer.add_op(
+                    &MOpcode::OpLoad,
+                    address,
+                    ValueInfo::new_unresolved(ir::WidthSpec::from(width as u16)),
+                );
+                self.phiplacer.op_use(&saved_bp, 0, &mem);
+                self.phiplacer.op_use(&saved_bp, 1, &frame);
+                let new_sp = self.add_binop(MOpcode::OpAdd, address, vt, slot_size, frame);
+                // Without copies, the restored registers are recognized by `utils::restores_frame`.
+                self.phiplacer.bind_register(address, &sp, new_sp);
+                self.phiplacer.bind_register(address, &bp, saved_bp);
+            }
+            FrameOp::Enter(size) => {
+                let old_sp = self.phiplacer.read_register(address, &sp);
+                let old_bp = self.phiplacer.read_register(address, &bp);
+                let mem = self.phiplacer.read_variable(address, self.mem_id());
+                let frame = self.add_binop(MOpcode::OpSub, address, vt, old_sp, slot_size);
+                let store = self
+                    .phiplacer
+                    .add_op(&MOpcode::OpStore, address, *MEM_VALUEINFO);
+                self.phiplacer.op_use(&store, 0, &mem);
+                self.phiplacer.op_use(&store, 1, &frame);
+                self.phiplacer.op_use(&store, 2, &old_bp);
+                let new_sp = if size == 0 {
+                    frame
+                } else {
+                    let size = self.phiplacer.add_const(address, size, None);
+                    self.add_binop(MOpcode::OpSub, address, vt, frame, size)
+                };
+                self.phiplacer
+                    .write_variable(*address, self.mem_id(), store);
+                self.phiplacer.write_register(address, &sp, new_sp);
+                self.phiplacer.write_register(address, &bp, frame);
+            }
+        }
+        true
+    }
+
+    // Returns `true` if `token` transfers control, i.e. is a `?{` or a write to the PC.
+    fn transfers_control(&self, token: &Token, dst: &Option<Token>) -> bool {
+        match (token, dst) {
+            (&Token::EIf, _) => true,
+            (&Token::EEq, &Some(Token::EIdentifier(ref name))) => {
+                self.regfile.alias_info.get("PC") == Some(name)
+            }
+            _ => false,
+        }
+    }
+
+    #[allow(dead_code)]
+    fn process_memory_op(
         &mut self,
         base: &Option<String>,
         index: &Option<String>,
@@ -928,6 +1942,7 @@ mod test {
     use crate::analysis::sccp::SCCP;
     use crate::middle::dot;
     use crate::middle::ir_writer;
+    use quickcheck::{Arbitrary, Gen, QuickCheck, TestResult};
     use r2api::structs::{LFunctionInfo, LRegInfo};
     use serde_json;
     use std::fs::File;
@@ -936,13 +1951,17 @@ mod test {
 
     const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
 
+    fn load_register_profile(path: &str) -> LRegInfo {
+        let mut register_profile = File::open(path).unwrap();
+        let mut s = String::new();
+        register_profile.read_to_string(&mut s).unwrap();
+        serde_json::from_str(&*s).unwrap()
+    }
+
     fn before_test(reg_profile: &mut LRegInfo, instructions: &mut LFunctionInfo, from: &str) {
         // Enable for debugging only.
         // enable_logging!();
-        let mut register_profile = File::open(REGISTER_PROFILE).unwrap();
-        let mut s = String::new();
-        register_profile.read_to_string(&mut s).unwrap();
-        *reg_profile = serde_json::from_str(&*s).unwrap();
+        *reg_profile = load_register_profile(REGISTER_PROFILE);
         let mut instruction_file = File::open(from).unwrap();
         let mut s = String::new();
         instruction_file.read_to_string(&mut s).unwrap();
@@ -1040,9 +2059,1533 @@ mod test {
         ir_writer::emit_il(&mut il, Some("main".to_owned()), rfn.ssa()).unwrap();
         println!("{}", il);
     }
+
+    fn construct_ops(
+        ops: &str,
+        max_nesting: usize,
+    ) -> (RadecoFunction, Result<(), ConstructError>) {
+        let mut reg_profile = Default::default();
+        let mut instructions = Default::default();
+        before_test(
+            &mut reg_profile,
+            &mut instructions,
+            "test_files/tiny_sccp_test_instructions.json",
+        );
+        let ops: Vec<LOpInfo> = serde_json::from_str(ops).unwrap();
+        let mut rfn = RadecoFunction::default();
+        let res = {
+            let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+            rfn.ssa_mut().regfile = regfile.clone();
+            let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &*regfile);
+            constructor.set_max_nesting(max_nesting);
+            constructor.try_run(ops.as_slice())
+        };
+        (rfn, res)
+    }
+
+    #[test]
+    fn ssa_function_bounds() {
+        use crate::middle::ssa::cfg_traits::CFG;
+        use crate::middle::ssa::verifier;
+
+        // The function is 3 bytes long, the following instructions belong to the next one.
+        let ops = r#"[
+            {"offset":4096,"size":3,"esil":"1,rdi,+,rax,=","type":"add"},
+            {"offset":4099,"size":3,"esil":"2,rax,+,rax,=","type":"add"},
+            {"offset":4102,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"}
+        ]"#;
+        let ops: Vec<LOpInfo> = serde_json::from_str(ops).unwrap();
+        let reg_profile = load_register_profile("test_files/x86_register_profile.json");
+        let mut rfn = RadecoFunction::default();
+        {
+            let regfile = SubRegisterFile::new(&reg_profile);
+            let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &regfile);
+            constructor.set_bounds(4096, 4099);
+            assert_eq!(constructor.try_run(ops.as_slice()), Ok(()));
+        }
+
+        let ssa = rfn.ssa();
+        assert!(verifier::verify(ssa).is_ok());
+        let ops = ssa
+            .values()
+            .into_iter()
+            .filter(|&v| ssa.opcode(v).is_some())
+            .collect::<Vec<_>>();
+        assert!(ops
+            .iter()
+            .all(|&v| ssa.address(v).map_or(true, |a| a.address < 4099)));
+        let adds = ops
+            .iter()
+            .filter(|&&v| ssa.opcode(v) == Some(MOpcode::OpAdd))
+            .count();
+        assert_eq!(adds, 1);
+
+        // The only block falls through the end of the function, to the exit node.
+        let block = ssa
+            .blocks()
+            .into_iter()
+            .find(|&b| ssa.starting_address(b) == Some(MAddress::new(4096, 0)))
+            .unwrap();
+        assert_eq!(ssa.succs_of(block), vec![ssa.exit_node().unwrap()]);
+    }
+
+    #[test]
+    fn ssa_self_loop() {
+        use crate::middle::ssa::cfg_traits::CFG;
+        use crate::middle::ssa::utils;
+
+        // The code after `jmp $` is never executed.
+        let ops = r#"[
+            {"offset":4096,"size":3,"esil":"1,rdi,+,rax,=","type":"add"},
+            {"offset":4099,"size":2,"esil":"4099,rip,=","type":"jmp"},
+            {"offset":4101,"size":3,"esil":"2,rax,+,rax,=","type":"add"},
+            {"offset":4104,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"}
+        ]"#;
+        let (rfn, res) = construct_ops(ops, MAX_NESTING);
+        assert_eq!(res, Ok(()));
+
+        let ssa = rfn.ssa();
+        let block_at = |addr| {
+            ssa.blocks()
+                .into_iter()
+                .find(|&b| ssa.starting_address(b) == Some(MAddress::new(addr, 0)))
+        };
+        let jmp = block_at(4099).unwrap();
+        assert_eq!(utils::self_loops(ssa), vec![jmp]);
+        assert_eq!(block_at(4101), None);
+        assert!(ssa
+            .values()
+            .into_iter()
+            .filter(|&v| ssa.opcode(v).is_some())
+            .all(|v| ssa.address(v).map_or(true, |a| a.address < 4101)));
+
+        // Nothing reached from the entry node leads to the exit node.
+        let stuck = utils::without_exit(ssa);
+        assert!(stuck.contains(&ssa.entry_node().unwrap()));
+        assert!(stuck.contains(&block_at(4096).unwrap()));
+        assert!(stuck.contains(&jmp));
+        assert!(ssa.preds_of(ssa.exit_node().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn ssa_conditional_self_loop() {
+        use crate::middle::ssa::cfg_traits::CFG;
+        use crate::middle::ssa::verifier;
+
+        // The conditional jump loops on itself, splitting its block before its false side is
+        // known.
+        let ops = r#"[
+            {"offset":4096,"size":4,"esil":"0,rdx,=","type":"mov"},
+            {"offset":4100,"size":4,"esil":"zf,?{,4100,rip,=,}","type":"cjmp"},
+            {"offset":4104,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"}
+        ]"#;
+        let (rfn, res) = construct_ops(ops, MAX_NESTING);
+        assert_eq!(res, Ok(()));
+        let ssa = rfn.ssa();
+        assert!(verifier::verify(ssa).is_ok());
+        let cjmp = ssa
+            .blocks()
+            .into_iter()
+            .find(|&b| ssa.starting_address(b) == Some(MAddress::new(4100, 0)))
+            .unwrap();
+        assert!(ssa.selector_in(cjmp).is_some());
+        assert_eq!(ssa.succs_of(cjmp).len(), 2);
+    }
+
+    #[test]
+    fn ssa_function_at_top_address() {
+        use crate::middle::ssa::cfg_traits::CFG;
+        use crate::middle::ssa::verifier;
+
+        for &base in &[0xffff_ffffu64, u64::max_value() - 8] {
+            let ops = format!(
+                r#"[
+                    {{"offset":{},"size":3,"esil":"1,rdi,+,rax,=","type":"add"}},
+                    {{"offset":{},"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"}}
+                ]"#,
+                base,
+                base + 3
+            );
+            let (rfn, res) = construct_ops(&ops, MAX_NESTING);
+            assert_eq!(res, Ok(()));
+
+            let ssa = rfn.ssa();
+            assert!(verifier::verify(ssa).is_ok());
+            let exit = ssa.exit_node().unwrap();
+            assert_eq!(ssa.starting_address(exit), Some(MAddress::exit()));
+            let block = ssa
+                .blocks()
+                .into_iter()
+                .find(|&b| ssa.starting_address(b) == Some(MAddress::new(base, 0)))
+                .unwrap();
+            assert_ne!(block, exit);
+            assert_eq!(ssa.succs_of(block), vec![exit]);
+        }
+    }
+
+    #[test]
+    fn ssa_delay_slot() {
+        use crate::middle::ssa::cfg_traits::CFG;
+        use crate::middle::ssa::verifier;
+
+        // `bltu a0, a1, 0x1010` followed by `addiu v0, a0, 1` in its delay slot.
+        let ops = r#"[
+            {"offset":4096,"size":4,"esil":"a1,a0,<,?{,4112,pc,=,}","type":"cjmp"},
+            {"offset":4100,"size":4,"esil":"1,a0,+,v0,=","type":"add"},
+            {"offset":4104,"size":4,"esil":"2,v0,+,v0,=","type":"add"},
+            {"offset":4108,"size":4,"esil":"ra,pc,=","type":"ret"},
+            {"offset":4112,"size":4,"esil":"ra,pc,=","type":"ret"}
+        ]"#;
+        let ops: Vec<LOpInfo> = serde_json::from_str(ops).unwrap();
+        let reg_profile = load_register_profile("test_files/mips_register_profile.json");
+        let mut rfn = RadecoFunction::default();
+        let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+        rfn.ssa_mut().regfile = regfile.clone();
+        {
+            let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &regfile);
+            constructor.set_delay_slots(vec![4096].into_iter().collect());
+            assert_eq!(constructor.try_run(ops.as_slice()), Ok(()));
+        }
+
+        let ssa = rfn.ssa();
+        assert!(verifier::verify(ssa).is_ok());
+        let branch = ssa
+            .blocks()
+            .into_iter()
+            .find(|&b| ssa.starting_address(b) == Some(MAddress::new(4096, 0)))
+            .unwrap();
+        let cond = ssa.selector_in(branch).unwrap();
+
+        // The delay slot executes before the branch is taken, on both of its paths, but after
+        // the condition is evaluated.
+        let slot = ssa
+            .exprs_in(branch)
+            .into_iter()
+            .find(|&e| {
+                ssa.opcode(e) == Some(MOpcode::OpAdd)
+                    && ssa
+                        .operands_of(e)
+                        .iter()
+                        .any(|&o| ssa.constant_value(o) == Some(1))
+            })
+            .unwrap();
+        assert!(ssa.address(cond).unwrap() < ssa.address(slot).unwrap());
+        assert!(ssa
+            .blocks()
+            .into_iter()
+            .all(|b| ssa.starting_address(b).unwrap().address != 4100));
+        assert_eq!(ssa.succs_of(branch).len(), 2);
+    }
+
+    #[test]
+    fn ssa_delay_slot_jump_target() {
+        use crate::middle::ssa::cfg_traits::CFG;
+        use crate::middle::ssa::verifier;
+
+        // `bltu a0, a1, 0x1004` to its own delay slot `addiu v0, v0, 1`.
+        let ops = r#"[
+            {"offset":4096,"size":4,"esil":"a1,a0,<,?{,4100,pc,=,}","type":"cjmp"},
+            {"offset":4100,"size":4,"esil":"1,v0,+,v0,=","type":"add"},
+            {"offset":4104,"size":4,"esil":"ra,pc,=","type":"ret"}
+        ]"#;
+        let ops: Vec<LOpInfo> = serde_json::from_str(ops).unwrap();
+        let reg_profile = load_register_profile("test_files/mips_register_profile.json");
+        let mut rfn = RadecoFunction::default();
+        let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+        rfn.ssa_mut().regfile = regfile.clone();
+        {
+            let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &regfile);
+            constructor.set_delay_slots(vec![4096].into_iter().collect());
+            assert_eq!(constructor.try_run(ops.as_slice()), Ok(()));
+        }
+
+        let ssa = rfn.ssa();
+        assert!(verifier::verify(ssa).is_ok());
+        let block_at = |address| {
+            ssa.blocks()
+                .into_iter()
+                .find(|&b| ssa.starting_address(b) == Some(MAddress::new(address, 0)))
+                .unwrap()
+        };
+        let (branch, target, after) = (block_at(4096), block_at(4100), block_at(4104));
+        let adds = |block| {
+            ssa.exprs_in(block)
+                .into_iter()
+                .filter(|&e| ssa.opcode(e) == Some(MOpcode::OpAdd))
+                .count()
+        };
+        // The slot executes once on the fall-through path, and once more when the branch is
+        // taken.
+        assert_eq!(adds(branch), 1);
+        assert_eq!(adds(target), 1);
+        let succs = ssa.succs_of(branch);
+        assert_eq!(succs.len(), 2);
+        assert!(succs.contains(&after));
+        let taken = succs.into_iter().find(|&b| b != after).unwrap();
+        assert_eq!(ssa.succs_of(taken), vec![target]);
+        assert_eq!(ssa.succs_of(target), vec![after]);
+    }
+
+    #[test]
+    fn ssa_nop_policy() {
+        use crate::middle::ssa::verifier;
+
+        // `endbr64; add rdi, 1; nop; ret`
+        let ops = r#"[
+            {"offset":4096,"size":4,"esil":"","type":"nop"},
+            {"offset":4100,"size":4,"esil":"1,rdi,+,rdi,=","type":"add"},
+            {"offset":4104,"size":1,"esil":"","type":"nop"},
+            {"offset":4105,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"}
+        ]"#;
+        let ops: Vec<LOpInfo> = serde_json::from_str(ops).unwrap();
+        let reg_profile = load_register_profile(REGISTER_PROFILE);
+        let construct = |policy| {
+            let mut rfn = RadecoFunction::default();
+            {
+                let regfile = SubRegisterFile::new(&reg_profile);
+                let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &regfile);
+                constructor.set_nop_policy(policy);
+                assert_eq!(constructor.try_run(ops.as_slice()), Ok(()));
+            }
+            assert!(verifier::verify(rfn.ssa()).is_ok());
+            rfn
+        };
+        let nops = |rfn: &RadecoFunction| {
+            let ssa = rfn.ssa();
+            let mut addrs = ssa
+                .values()
+                .into_iter()
+                .filter(|&v| ssa.opcode(v) == Some(MOpcode::OpNop))
+                .map(|v| ssa.address(v).unwrap())
+                .collect::<Vec<_>>();
+            addrs.sort();
+            addrs
+        };
+
+        // Dropped nops leave no expression at their address, though the entry block still starts
+        // at the first one.
+        let dropped = construct(NopPolicy::Drop);
+        assert!(nops(&dropped).is_empty());
+        let ssa = dropped.ssa();
+        assert!(ssa
+            .blocks()
+            .into_iter()
+            .flat_map(|b| ssa.exprs_in(b))
+            .filter_map(|v| ssa.address(v))
+            .all(|addr| addr.address != 4096 && addr.address != 4104));
+        assert!(ssa
+            .values()
+            .into_iter()
+            .any(|v| ssa.opcode(v) == Some(MOpcode::OpAdd)));
+
+        // Kept nops are one `OpNop` per instruction, at its address.
+        let kept = construct(NopPolicy::Keep);
+        assert_eq!(
+            nops(&kept),
+            vec![MAddress::new(4096, 0), MAddress::new(4104, 0)]
+        );
+    }
+
+    #[test]
+    fn ssa_load_xrefs() {
+        // `mov eax, dword [0x601040]` followed by `add eax, 1`.
+        let ops = r#"[
+            {"offset":4096,"size":7,"esil":"0x601040,[4],rax,=","type":"mov"},
+            {"offset":4103,"size":3,"esil":"1,rax,+,rax,=","type":"add"}
+        ]"#;
+        let ops: Vec<LOpInfo> = serde_json::from_str(ops).unwrap();
+        let reg_profile = load_register_profile(REGISTER_PROFILE);
+        let mut rfn = RadecoFunction::default();
+        {
+            let regfile = SubRegisterFile::new(&reg_profile);
+            let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &regfile);
+            constructor.set_xrefs(vec![(4096, vec![0x601040])].into_iter().collect());
+            assert_eq!(constructor.try_run(ops.as_slice()), Ok(()));
+        }
+
+        let ssa = rfn.ssa();
+        let load = ssa
+            .values()
+            .into_iter()
+            .find(|&v| ssa.opcode(v) == Some(MOpcode::OpLoad))
+            .unwrap();
+        assert_eq!(ssa.xrefs_of(&load), vec![0x601040]);
+        let add = ssa
+            .values()
+            .into_iter()
+            .find(|&v| ssa.opcode(v) == Some(MOpcode::OpAdd))
+            .unwrap();
+        assert!(ssa.xrefs_of(&add).is_empty());
+    }
+
+    #[test]
+    fn ssa_constant_xrefs() {
+        // `mov edi, 0x601040`, the address is only used as a constant.
+        let ops = r#"[
+            {"offset":4096,"size":5,"esil":"6295616,edi,=,0xffffffff,rdi,&=","type":"mov"}
+        ]"#;
+        let ops: Vec<LOpInfo> = serde_json::from_str(ops).unwrap();
+        let reg_profile = load_register_profile(REGISTER_PROFILE);
+        let mut rfn = RadecoFunction::default();
+        {
+            let regfile = SubRegisterFile::new(&reg_profile);
+            let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &regfile);
+            constructor.set_xrefs(vec![(4096, vec![0x601040])].into_iter().collect());
+            assert_eq!(constructor.try_run(ops.as_slice()), Ok(()));
+        }
+
+        let ssa = rfn.ssa();
+        let referring = ssa
+            .values()
+            .into_iter()
+            .filter(|v| !ssa.xrefs_of(v).is_empty())
+            .collect::<Vec<_>>();
+        assert!(!referring.is_empty());
+        for value in referring {
+            assert_eq!(ssa.xrefs_of(&value), vec![0x601040]);
+            let operands = ssa.operands_of(value);
+            assert!(operands
+                .into_iter()
+                .any(|op| ssa.constant_value(op) == Some(0x601040)));
+        }
+    }
+
+    #[test]
+    fn ssa_segmented_load() {
+        use crate::middle::ssa::utils;
+        use crate::middle::ssa::verifier;
+
+        // `mov rax, qword fs:[0x28]` followed by `mov rdx, qword [0x28]`.
+        let ops = r#"[
+            {"offset":4096,"size":9,"esil":"0x28,fs,+,[8],rax,=","type":"mov"},
+            {"offset":4105,"size":8,"esil":"0x28,[8],rdx,=","type":"mov"}
+        ]"#;
+        let ops: Vec<LOpInfo> = serde_json::from_str(ops).unwrap();
+        let reg_profile = load_register_profile(REGISTER_PROFILE);
+        let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+        let mut rfn = RadecoFunction::default();
+        rfn.ssa_mut().regfile = regfile.clone();
+        {
+            let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &*regfile);
+            assert_eq!(constructor.try_run(ops.as_slice()), Ok(()));
+        }
+
+        let ssa = rfn.ssa();
+        assert!(verifier::verify(ssa).is_ok());
+        let load_at = |offset| {
+            ssa.values()
+                .into_iter()
+                .find(|&v| {
+                    ssa.opcode(v) == Some(MOpcode::OpLoad)
+                        && ssa.address(v).map(|a| a.address) == Some(offset)
+                })
+                .unwrap()
+        };
+        let segmented = load_at(4096);
+        assert_eq!(ssa.operands_of(segmented).len(), 2);
+        assert_eq!(ssa.segment_of(&segmented), Some("fs".to_owned()));
+        // The address is still the sum of the segment base and the offset.
+        let fs = regfile.register_id_by_name("fs").unwrap();
+        let entry_state = ssa.registers_in(ssa.entry_node().unwrap()).unwrap();
+        let fs_base = utils::register_state_info(entry_state, ssa)[fs].0;
+        let addr = ssa.operands_of(segmented)[1];
+        assert!(ssa.operands_of(addr).contains(&fs_base));
+
+        let plain = load_at(4105);
+        assert_eq!(ssa.operands_of(plain).len(), 2);
+        assert_eq!(ssa.segment_of(&plain), None);
+    }
+
+    #[test]
+    fn ssa_esil_comments() {
+        let ops = r#"[
+            {"offset":4096,"size":3,"esil":"rsi,rdi,+,rax,=","type":"add"},
+            {"offset":4099,"size":4,"esil":"2,rax,*,rax,=","type":"mul"}
+        ]"#;
+        let ops: Vec<LOpInfo> = serde_json::from_str(ops).unwrap();
+        let reg_profile = load_register_profile(REGISTER_PROFILE);
+        let mut rfn = RadecoFunction::default();
+        {
+            let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+            rfn.ssa_mut().regfile = regfile.clone();
+            let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &*regfile);
+            assert_eq!(constructor.try_run(ops.as_slice()), Ok(()));
+        }
+        record_esil(rfn.ssa_mut(), &ops);
+
+        let opts = ir_writer::EmitOptions {
+            esil: true,
+            ..Default::default()
+        };
+        let mut il = String::new();
+        ir_writer::emit_il_with(&mut il, Some("main".to_owned()), rfn.ssa(), opts).unwrap();
+        let lines = il.lines().map(str::trim).collect::<Vec<_>>();
+        // Each ESIL is followed by the operation it was translated to.
+        for &(esil, op) in &[("rsi,rdi,+,rax,=", " + "), ("2,rax,*,rax,=", " * ")] {
+            let at = lines
+                .iter()
+                .position(|&l| l == format!("; esil: {}", esil))
+                .unwrap_or_else(|| panic!("no ESIL {} in:\n{}", esil, il));
+            assert!(lines[at + 1].contains(op), "{}", il);
+        }
+    }
+
+    #[test]
+    fn frame_op_parse() {
+        assert_eq!(FrameOp::parse("leave"), Some(FrameOp::Leave));
+        assert_eq!(FrameOp::parse("enter 0x10, 0"), Some(FrameOp::Enter(16)));
+        assert_eq!(FrameOp::parse("enter 8, 1"), None);
+        assert_eq!(FrameOp::parse("leave 1"), None);
+        assert_eq!(FrameOp::parse("lea rbp, [rsp]"), None);
+    }
+
+    #[test]
+    fn jump_pointer_parse() {
+        // jmp qword [rip + 0x200ba2]
+        let esil = "0x200ba2,rip,+,[8],rip,=";
+        assert_eq!(jump_pointer(esil, 0x400476), Some(0x601018));
+        // jmp dword [0x804a00c]
+        assert_eq!(jump_pointer("0x804a00c,[4],eip,=", 0), Some(0x804a00c));
+        // jmp rax
+        assert_eq!(jump_pointer("rax,rip,=", 0x1002), None);
+    }
+
+    #[test]
+    fn ssa_leave_restores_frame() {
+        use crate::middle::ssa::utils;
+        use crate::middle::ssa::verifier;
+
+        // push rbp; mov rbp, rsp; leave; ret
+        let ops = r#"[
+            {"offset":4096,"size":1,"esil":"8,rsp,-=,rbp,rsp,=[8]","type":"upush",
+             "opcode":"push rbp"},
+            {"offset":4097,"size":3,"esil":"rsp,rbp,=","type":"mov","opcode":"mov rbp, rsp"},
+            {"offset":4100,"size":1,"esil":"rbp,rsp,=,rsp,[8],rbp,=,8,rsp,+=","type":"pop",
+             "opcode":"leave"},
+            {"offset":4101,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret","opcode":"ret"}
+        ]"#;
+        let (rfn, res) = construct_ops(ops, MAX_NESTING);
+        assert_eq!(res, Ok(()));
+        let ssa = rfn.ssa();
+        assert!(verifier::verify(ssa).is_ok());
+        assert!(utils::restores_frame(ssa));
+
+        let exit_state = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        let frs = utils::register_state_info(exit_state, ssa);
+        let reg = |name| frs[ssa.regfile.register_id_by_name(name).unwrap()].0;
+        let (rsp, rbp) = (reg("rsp"), reg("rbp"));
+        let uncopied = |mut value| {
+            while ssa.opcode(value) == Some(MOpcode::OpMov) {
+                value = ssa.operands_of(value)[0];
+            }
+            value
+        };
+        // rbp is reloaded from the slot `push rbp` saved it to.
+        assert_eq!(ssa.opcode(rbp), Some(MOpcode::OpLoad));
+        let load = ssa.operands_of(rbp);
+        assert_eq!(ssa.opcode(load[0]), Some(MOpcode::OpStore));
+        let store = ssa.operands_of(load[0]);
+        assert_eq!(uncopied(store[1]), uncopied(load[1]));
+        assert_eq!(ssa.comment(store[2]), Some("rbp".to_owned()));
+        // rsp points right above that slot, where it was at the entry.
+        assert_eq!(ssa.opcode(rsp), Some(MOpcode::OpAdd));
+        let add = ssa.operands_of(rsp);
+        assert_eq!(ssa.constant(add[0]), Some(8));
+        assert_eq!(add[1], load[1]);
+    }
+
+    #[test]
+    fn ssa_unclosed_if() {
+        let closed = r#"[
+            {"offset":4096,"size":2,"esil":"zf,?{,4200,rip,=,}","type":"cjmp"},
+            {"offset":4098,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"}
+        ]"#;
+        assert_eq!(construct_ops(closed, MAX_NESTING).1, Ok(()));
+
+        let unclosed = r#"[
+            {"offset":4096,"size":2,"esil":"zf,?{,4200,rip,=","type":"cjmp"}
+        ]"#;
+        match construct_ops(unclosed, MAX_NESTING).1 {
+            Err(ConstructError::UnclosedIf(addrs)) => {
+                assert_eq!(addrs.len(), 1);
+                assert_eq!(addrs[0].address, 4096);
+            }
+            r => panic!("expected an unclosed if, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn ssa_no_ops() {
+        use crate::middle::ssa::cfg_traits::CFG;
+        use crate::middle::ssa::verifier;
+
+        let mut reg_profile = Default::default();
+        let mut instructions = Default::default();
+        before_test(
+            &mut reg_profile,
+            &mut instructions,
+            "test_files/tiny_sccp_test_instructions.json",
+        );
+        let mut rfn = RadecoFunction::default();
+        {
+            let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+            rfn.ssa_mut().regfile = regfile.clone();
+            let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &*regfile);
+            assert_eq!(constructor.try_run(&[]), Ok(()));
+        }
+
+        let ssa = rfn.ssa();
+        let entry = ssa.entry_node().unwrap();
+        let exit = ssa.exit_node().unwrap();
+        assert_eq!(ssa.blocks(), vec![entry]);
+        assert_eq!(ssa.succs_of(entry), vec![exit]);
+        assert_eq!(ssa.preds_of(exit), vec![entry]);
+        assert!(verifier::verify(ssa).is_ok());
+
+        let mut dce = DCE::new();
+        dce.analyze(&mut rfn, Some(all));
+        assert!(verifier::verify(rfn.ssa()).is_ok());
+    }
+
+    #[test]
+    fn ssa_no_esil() {
+        use crate::middle::ssa::cfg_traits::CFG;
+
+        // The architecture of the function is not supported by r2.
+        let ops = r#"[
+            {"offset":4096,"size":4,"type":"mov"},
+            {"offset":4100,"size":4,"type":"ret"}
+        ]"#;
+        let (rfn, res) = construct_ops(ops, MAX_NESTING);
+        assert_eq!(res, Err(ConstructError::NoEsil));
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "no ESIL available, is this architecture supported?"
+        );
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.blocks(), vec![ssa.entry_node().unwrap()]);
+
+        // The error is kept along with the function for the frontends to report it.
+        let reg_profile = load_register_profile(REGISTER_PROFILE);
+        let mut rfn = RadecoFunction::default();
+        rfn.instructions = serde_json::from_str(ops).unwrap();
+        SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, false, true);
+        assert_eq!(rfn.construct_error(), Some(&ConstructError::NoEsil));
+    }
+
+    #[test]
+    fn ssa_indirect_jump() {
+        use crate::middle::ir_reader;
+        use crate::middle::regfile::RegisterId;
+        use crate::middle::ssa::cfg_traits::CFG;
+        use crate::middle::ssa::utils;
+
+        let mut reg_profile = Default::default();
+        let mut instructions = Default::default();
+        before_test(
+            &mut reg_profile,
+            &mut instructions,
+            "test_files/tiny_sccp_test_instructions.json",
+        );
+        // jmp rax
+        let ops: Vec<LOpInfo> =
+            serde_json::from_str(r#"[{"offset":4096,"size":2,"esil":"rax,rip,=","type":"ujmp"}]"#)
+                .unwrap();
+        let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+        let mut rfn = RadecoFunction::default();
+        rfn.ssa_mut().regfile = regfile.clone();
+        {
+            let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &*regfile);
+            constructor.set_indirect_jumps(true);
+            assert_eq!(constructor.try_run(&ops), Ok(()));
+        }
+
+        let ssa = rfn.ssa();
+        let jumps = ssa
+            .values()
+            .into_iter()
+            .filter(|&v| ssa.opcode(v) == Some(MOpcode::OpIndirectJump))
+            .collect::<Vec<_>>();
+        assert_eq!(jumps.len(), 1);
+        let entry = ssa.entry_node().unwrap();
+        let ers = utils::register_state_info(ssa.registers_in(entry).unwrap(), ssa);
+        let rax_i = ssa.regfile.register_id_by_name("rax").unwrap().to_usize();
+        assert_eq!(
+            ssa.operands_of(jumps[0]),
+            vec![ers[RegisterId::from_usize(rax_i)].0]
+        );
+        let block = ssa.block_for(jumps[0]).unwrap();
+        assert_eq!(ssa.selector_in(block), None);
+
+        let mut il = String::new();
+        ir_writer::emit_il(&mut il, Some("sym.jmp".to_owned()), ssa).unwrap();
+        assert!(il.contains(": $Unknown0 = IndirectJump(%"));
+        let parsed = ir_reader::parse_il(&il, regfile.clone());
+        let mut emitted = String::new();
+        ir_writer::emit_il(&mut emitted, Some("sym.jmp".to_owned()), &parsed).unwrap();
+        assert_eq!(il, emitted);
+    }
+
+    #[test]
+    fn ssa_popped_pc_returns() {
+        use crate::middle::ssa::cfg_traits::CFG;
+
+        // A `ret` whose type is not reported pops the return address into the PC.
+        let ops = r#"[
+            {"offset":4096,"size":3,"esil":"1,rdi,+,rax,=","type":"add"},
+            {"offset":4099,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","opcode":"ret"}
+        ]"#;
+        let (rfn, res) = construct_ops(ops, MAX_NESTING);
+        assert_eq!(res, Ok(()));
+        let ssa = rfn.ssa();
+        let block = ssa
+            .blocks()
+            .into_iter()
+            .find(|&b| ssa.starting_address(b) == Some(MAddress::new(4096, 0)))
+            .unwrap();
+        assert_eq!(ssa.succs_of(block), vec![ssa.exit_node().unwrap()]);
+        assert_eq!(ssa.selector_in(block), None);
+        assert!(ssa
+            .values()
+            .into_iter()
+            .all(|v| ssa.opcode(v) != Some(MOpcode::OpITE)));
+    }
+
+    #[test]
+    fn ssa_unified_exit() {
+        use crate::middle::ssa::utils;
+        use crate::middle::ssa::verifier;
+
+        // test edi, edi; je 0x100a; mov eax, 1; ret; mov eax, 2; ret
+        let ops = r#"[
+            {"offset":4096,"size":2,"esil":"edi,edi,&,$z,zf,=","type":"test"},
+            {"offset":4098,"size":2,"esil":"zf,?{,4106,rip,=,}","type":"cjmp"},
+            {"offset":4100,"size":5,"esil":"1,eax,=","type":"mov"},
+            {"offset":4105,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"},
+            {"offset":4106,"size":5,"esil":"2,eax,=","type":"mov"},
+            {"offset":4111,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"}
+        ]"#;
+        let (rfn, res) = construct_ops(ops, MAX_NESTING);
+        assert_eq!(res, Ok(()));
+        let ssa = rfn.ssa();
+        assert!(verifier::verify(ssa).is_ok());
+
+        // Both `ret`s flow to the single exit node.
+        let exit = ssa.exit_node().unwrap();
+        let mut rets = ssa.preds_of(exit);
+        rets.sort();
+        assert_eq!(rets.len(), 2);
+        assert!(rets.iter().all(|&b| ssa.succs_of(b) == vec![exit]));
+
+        // Where `rax` is merged from both of them.
+        let exit_state = ssa.registers_in(exit).unwrap();
+        let frs = utils::register_state_info(exit_state, ssa);
+        let rax = frs[ssa.regfile.register_id_by_name("rax").unwrap()].0;
+        assert!(ssa.is_phi(rax));
+        assert!(ssa.phis_in(exit).contains(&rax));
+        let mut merged = ssa
+            .operands_of(rax)
+            .into_iter()
+            .map(|v| ssa.block_for(v).unwrap())
+            .collect::<Vec<_>>();
+        merged.sort();
+        assert_eq!(merged, rets);
+    }
+
+    #[test]
+    fn ssa_block_callback() {
+        let mut reg_profile = Default::default();
+        let mut instructions = Default::default();
+        before_test(
+            &mut reg_profile,
+            &mut instructions,
+            "test_files/tiny_sccp_test_instructions.json",
+        );
+        // test edi, edi; je 0x100a; mov eax, 1; ret; mov eax, 2; ret
+        let ops: Vec<LOpInfo> = serde_json::from_str(
+            r#"[
+            {"offset":4096,"size":2,"esil":"edi,edi,&,$z,zf,=","type":"test"},
+            {"offset":4098,"size":2,"esil":"zf,?{,4106,rip,=,}","type":"cjmp"},
+            {"offset":4100,"size":5,"esil":"1,eax,=","type":"mov"},
+            {"offset":4105,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"},
+            {"offset":4106,"size":5,"esil":"2,eax,=","type":"mov"},
+            {"offset":4111,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"}
+        ]"#,
+        )
+        .unwrap();
+
+        let mut completed = Vec::new();
+        let mut ssa = SSAStorage::new();
+        {
+            let regfile = SubRegisterFile::new(&reg_profile);
+            let mut constructor = SSAConstruct::new(&mut ssa, &regfile);
+            constructor.set_block_callback(|ssa: &SSAStorage, block| {
+                assert!(ssa.is_block(block));
+                completed.push(ssa.starting_address(block).unwrap().address);
+            });
+            assert_eq!(constructor.try_run(ops.as_slice()), Ok(()));
+        }
+        // The entry node, then each block once, including the one the conditional jump at 4098
+        // goes through when taken.
+        assert_eq!(completed, vec![0, 4096, 4098, 4100, 4106]);
+        assert_eq!(completed.len(), ssa.blocks().len());
+    }
+
+    #[test]
+    fn ssa_nesting_too_deep() {
+        let nested = r#"[
+            {"offset":4096,"size":2,"esil":"zf,?{,zf,?{,zf,?{,4200,rip,=,},},}","type":"cjmp"},
+            {"offset":4098,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"}
+        ]"#;
+        match construct_ops(nested, 2).1 {
+            Err(ConstructError::NestingTooDeep { address, max }) => {
+                assert_eq!(address.address, 4096);
+                assert_eq!(max, 2);
+            }
+            r => panic!("expected too deep nesting, got {:?}", r),
+        }
+    }
+
+    fn widen_opcodes(esil: &str) -> Vec<MOpcode> {
+        use crate::middle::ssa::ssa_traits::SSA;
+
+        let ops = format!(
+            r#"[{{"offset":4096,"size":3,"esil":"{}","type":"mov"}}]"#,
+            esil
+        );
+        let (rfn, res) = construct_ops(&ops, MAX_NESTING);
+        assert_eq!(res, Ok(()));
+        let ssa = rfn.ssa();
+        ssa.values()
+            .into_iter()
+            .filter_map(|v| ssa.opcode(v))
+            .filter(|op| match *op {
+                MOpcode::OpSignExt(_) | MOpcode::OpZeroExt(_) => true,
+                _ => false,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ssa_float_values() {
+        use crate::middle::ssa::ssa_traits::SSA;
+
+        // movsd xmm0, xmm1; addsd xmm0, xmm1
+        let ops = r#"[
+            {"offset":4096,"size":4,"esil":"xmm1l,xmm0l,=","type":"mov"},
+            {"offset":4100,"size":4,"esil":"xmm1l,xmm0l,+,xmm0l,=","type":"add"}
+        ]"#;
+        let (rfn, res) = construct_ops(ops, MAX_NESTING);
+        assert_eq!(res, Ok(()));
+        let ssa = rfn.ssa();
+        let adds = ssa
+            .values()
+            .into_iter()
+            .filter(|&v| ssa.opcode(v) == Some(MOpcode::OpAdd))
+            .collect::<Vec<_>>();
+        assert_eq!(adds.len(), 1);
+        assert!(ssa.node_data(adds[0]).unwrap().vt.is_float());
+        assert!(ssa
+            .operands_of(adds[0])
+            .iter()
+            .all(|&o| ssa.node_data(o).unwrap().vt.is_float()));
+
+        let mut il = String::new();
+        ir_writer::emit_il(&mut il, None, ssa).unwrap();
+        assert!(il.contains("$Float64"));
+    }
+
+    #[test]
+    fn ssa_float_ops() {
+        use crate::middle::ssa::ssa_traits::SSA;
+
+        // mov rax, 4; cvtsi2sd xmm0, rax; mulsd xmm0, xmm1; addsd xmm0, xmm1;
+        // comisd xmm0, xmm1; cvttsd2si rax, xmm0
+        let ops = r#"[
+            {"offset":4096,"size":7,"esil":"4,rax,=","type":"mov"},
+            {"offset":4103,"size":5,"esil":"rax,I2D,xmm0l,=","type":"mov"},
+            {"offset":4108,"size":4,"esil":"xmm1l,xmm0l,F*,xmm0l,=","type":"mul"},
+            {"offset":4112,"size":4,"esil":"xmm1l,xmm0l,F+,xmm0l,=","type":"add"},
+            {"offset":4116,"size":4,"esil":"xmm1l,xmm0l,F<,cf,=","type":"cmp"},
+            {"offset":4120,"size":5,"esil":"xmm0l,D2I,rax,=","type":"mov"}
+        ]"#;
+        let (mut rfn, res) = construct_ops(ops, MAX_NESTING);
+        assert_eq!(res, Ok(()));
+
+        let fp_ops = [
+            MOpcode::OpIToF,
+            MOpcode::OpFMul,
+            MOpcode::OpFAdd,
+            MOpcode::OpFLt,
+            MOpcode::OpFToI,
+        ];
+        let count_fp_ops = |rfn: &RadecoFunction| {
+            let ssa = rfn.ssa();
+            ssa.values()
+                .into_iter()
+                .filter_map(|v| ssa.opcode(v))
+                .filter(|op| fp_ops.contains(op))
+                .count()
+        };
+        assert_eq!(count_fp_ops(&rfn), fp_ops.len());
+        {
+            let ssa = rfn.ssa();
+            for v in ssa.values() {
+                match ssa.opcode(v) {
+                    Some(MOpcode::OpFAdd) | Some(MOpcode::OpIToF) => {
+                        assert!(ssa.node_data(v).unwrap().vt.is_float())
+                    }
+                    Some(MOpcode::OpFLt) | Some(MOpcode::OpFToI) => {
+                        assert!(!ssa.node_data(v).unwrap().vt.is_float())
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // Integer analyses must leave the floating point operations alone.
+        let mut dce = DCE::new();
+        dce.analyze(&mut rfn, Some(all));
+        let mut sccp = SCCP::new();
+        sccp.analyze(&mut rfn, Some(all));
+        dce.analyze(&mut rfn, Some(all));
+        assert_eq!(count_fp_ops(&rfn), fp_ops.len());
+    }
+
+    #[test]
+    fn ssa_signed_compare_sign_extends() {
+        let widens = widen_opcodes("eax,rbx,<,cf,=");
+        assert_eq!(widens, vec![MOpcode::OpSignExt(64)]);
+    }
+
+    #[test]
+    fn ssa_unsigned_add_zero_extends() {
+        let widens = widen_opcodes("eax,rbx,+,rcx,=");
+        assert_eq!(widens, vec![MOpcode::OpZeroExt(64)]);
+    }
+
+    // Returns the width of the only `OpAdd` constructed from `esil` and its operands.
+    fn peek_add(esil: &str) -> (RadecoFunction, u16, Vec<<SSAStorage as SSA>::ValueRef>) {
+        let ops = format!(
+            r#"[{{"offset":4096,"size":3,"esil":"{}","type":"add"}}]"#,
+            esil
+        );
+        let (rfn, res) = construct_ops(&ops, MAX_NESTING);
+        assert_eq!(res, Ok(()));
+        let (width, operands) = {
+            let ssa = rfn.ssa();
+            let adds = ssa
+                .values()
+                .into_iter()
+                .filter(|&v| ssa.opcode(v) == Some(MOpcode::OpAdd))
+                .collect::<Vec<_>>();
+            assert_eq!(adds.len(), 1);
+            let vt = ssa.node_data(adds[0]).unwrap().vt;
+            (vt.width().get_width().unwrap(), ssa.operands_of(adds[0]))
+        };
+        (rfn, width, operands)
+    }
+
+    #[test]
+    fn ssa_peek_extended_at_load() {
+        for esil in &["ebx,rax,[1],+,ecx,=", "rax,[1],ebx,+,ecx,="] {
+            let (rfn, width, operands) = peek_add(esil);
+            let ssa = rfn.ssa();
+            assert_eq!(width, 32);
+            let ext = operands
+                .iter()
+                .cloned()
+                .find(|&v| ssa.opcode(v) == Some(MOpcode::OpZeroExt(32)))
+                .unwrap_or_else(|| panic!("peek not extended in {}", esil));
+            let load = ssa.operands_of(ext)[0];
+            assert_eq!(ssa.opcode(load), Some(MOpcode::OpLoad));
+            let load_vt = ssa.node_data(load).unwrap().vt;
+            assert_eq!(load_vt.width().get_width(), Some(8));
+        }
+
+        // A constant which does not fit in a byte does not truncate the sum.
+        let (rfn, width, operands) = peek_add("0x1234,rax,[1],+,rcx,=");
+        let ssa = rfn.ssa();
+        assert_eq!(width, 64);
+        assert!(operands
+            .iter()
+            .any(|&v| ssa.opcode(v) == Some(MOpcode::OpZeroExt(64))));
+        assert!(operands
+            .iter()
+            .any(|&v| ssa.constant_value(v) == Some(0x1234)));
+    }
+
+    #[test]
+    fn ssa_noreturn_prunes_fallthrough() {
+        use crate::middle::ssa::verifier;
+
+        // mov edi, 1; call exit; add ebx, eax; ret
+        let ops = r#"[
+            {"offset":4096,"size":5,"esil":"1,edi,=","type":"mov"},
+            {"offset":4101,"size":5,"esil":"8192,rip,8,rsp,-=,rsp,=[],rip,=","type":"call"},
+            {"offset":4106,"size":2,"esil":"eax,ebx,+=","type":"add"},
+            {"offset":4108,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"}
+        ]"#;
+        let construct = |noreturn: HashSet<u64>| {
+            let mut reg_profile = Default::default();
+            let mut instructions = Default::default();
+            before_test(
+                &mut reg_profile,
+                &mut instructions,
+                "test_files/tiny_sccp_test_instructions.json",
+            );
+            let mut rfn = RadecoFunction::default();
+            rfn.instructions = serde_json::from_str(ops).unwrap();
+            SSAConstruct::<SSAStorage>::construct_noreturn(
+                &mut rfn,
+                &reg_profile,
+                false,
+                true,
+                &noreturn,
+            );
+            rfn
+        };
+        // Operations of the instructions following the call, the phis of the exit node are left
+        // out.
+        let after_call = |rfn: &RadecoFunction| {
+            let ssa = rfn.ssa();
+            ssa.blocks()
+                .into_iter()
+                .flat_map(|b| ssa.exprs_in(b))
+                .filter(|&v| ssa.address(v).map_or(false, |a| a.address > 4101))
+                .count()
+        };
+
+        let returning = construct(HashSet::new());
+        assert!(!returning.is_noreturn());
+        assert!(after_call(&returning) > 0);
+
+        // `exit` at 0x2000 never returns, so nothing after the call is reachable.
+        let noreturn = construct(vec![0x2000].into_iter().collect());
+        assert!(noreturn.is_noreturn());
+        assert_eq!(after_call(&noreturn), 0);
+        assert!(verifier::verify(noreturn.ssa()).is_ok());
+    }
+
+    // Constructs `esil` followed by a return and returns the final value of the register `reg`.
+    fn final_value(esil: &str, reg: &str) -> (RadecoFunction, <SSAStorage as SSA>::ValueRef) {
+        use crate::middle::regfile::RegisterId;
+        use crate::middle::ssa::cfg_traits::CFG;
+        use crate::middle::ssa::utils;
+
+        let ops = format!(
+            r#"[
+                {{"offset":4096,"size":3,"esil":"{}","type":"add"}},
+                {{"offset":4099,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"}}
+            ]"#,
+            esil
+        );
+        let (rfn, res) = construct_ops(&ops, MAX_NESTING);
+        assert_eq!(res, Ok(()));
+        let value = {
+            let ssa = rfn.ssa();
+            let exit = ssa.exit_node().unwrap();
+            let frs = utils::register_state_info(ssa.registers_in(exit).unwrap(), ssa);
+            let reg_i = ssa.regfile.register_id_by_name(reg).unwrap().to_usize();
+            // Look through the copy made by the write to the register.
+            let mut value = frs[RegisterId::from_usize(reg_i)].0;
+            while ssa.opcode(value) == Some(MOpcode::OpMov) {
+                value = ssa.operands_of(value)[0];
+            }
+            value
+        };
+        (rfn, value)
+    }
+
+    #[test]
+    fn ssa_zero_flag() {
+        let (rfn, zf) = final_value("rax,rbx,+=,$z,zf,=", "zf");
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.opcode(zf), Some(MOpcode::OpEq));
+        let ops = ssa.operands_of(zf);
+        assert_eq!(ssa.opcode(ops[0]), Some(MOpcode::OpAdd));
+        assert_eq!(ssa.constant_value(ops[1]), Some(0));
+    }
+
+    #[test]
+    fn ssa_carry_flag() {
+        // Carry out of the whole register: rbx + rax < rbx
+        let (rfn, cf) = final_value("rax,rbx,+=,$c63,cf,=", "cf");
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.opcode(cf), Some(MOpcode::OpLt));
+        let ops = ssa.operands_of(cf);
+        assert_eq!(ssa.opcode(ops[0]), Some(MOpcode::OpAdd));
+        assert_eq!(ops[1], ssa.operands_of(ops[0])[0]);
+
+        // Carry out of the lowest byte: (rbx + rax) & 0xff < rbx & 0xff
+        let (rfn, cf) = final_value("rax,rbx,+=,$c7,cf,=", "cf");
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.opcode(cf), Some(MOpcode::OpLt));
+        let ops = ssa.operands_of(cf);
+        let masked = ops
+            .iter()
+            .map(|&op| {
+                assert_eq!(ssa.opcode(op), Some(MOpcode::OpAnd));
+                let and_ops = ssa.operands_of(op);
+                assert_eq!(ssa.constant_value(and_ops[1]), Some(0xff));
+                and_ops[0]
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ssa.opcode(masked[0]), Some(MOpcode::OpAdd));
+        assert_eq!(masked[1], ssa.operands_of(masked[0])[0]);
+        assert_eq!(ssa.node_data(cf).unwrap().vt.width().get_width(), Some(1));
+    }
+
+    #[test]
+    fn ssa_borrow_flag() {
+        // Borrow from the lowest nibble: rbx & 0xf < (rbx - rax) & 0xf
+        let (rfn, af) = final_value("rax,rbx,-=,$b4,af,=", "af");
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.opcode(af), Some(MOpcode::OpLt));
+        assert_eq!(ssa.node_data(af).unwrap().vt.width().get_width(), Some(1));
+        let masked = ssa
+            .operands_of(af)
+            .iter()
+            .map(|&op| {
+                assert_eq!(ssa.opcode(op), Some(MOpcode::OpAnd));
+                let and_ops = ssa.operands_of(op);
+                assert_eq!(ssa.constant_value(and_ops[1]), Some(0xf));
+                and_ops[0]
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ssa.opcode(masked[1]), Some(MOpcode::OpSub));
+        assert_eq!(masked[0], ssa.operands_of(masked[1])[0]);
+
+        // Borrow out of the whole register: rbx < rbx - rax
+        let (rfn, cf) = final_value("rax,rbx,-=,$b64,cf,=", "cf");
+        let ssa = rfn.ssa();
+        assert_eq!(ssa.opcode(cf), Some(MOpcode::OpLt));
+        let ops = ssa.operands_of(cf);
+        assert_eq!(ssa.opcode(ops[1]), Some(MOpcode::OpSub));
+        assert_eq!(ops[0], ssa.operands_of(ops[1])[0]);
+    }
+
+    #[test]
+    fn ssa_const_takes_operand_width() {
+        // A mask and a shift amount on 32 bits registers.
+        let cases = [
+            ("0xff,eax,&,rax,=", MOpcode::OpAnd, 0xff),
+            ("4,ecx,<<,rcx,=", MOpcode::OpLsl, 4),
+        ];
+        for &(esil, ref opcode, value) in &cases {
+            let (rfn, _) = final_value(esil, "rax");
+            let ssa = rfn.ssa();
+            let width = |v| ssa.node_data(v).unwrap().vt.width().get_width();
+            let op = ssa
+                .values()
+                .into_iter()
+                .find(|&v| ssa.opcode(v).as_ref() == Some(opcode))
+                .unwrap();
+            assert_eq!(width(op), Some(32), "{}", esil);
+            let operand = ssa
+                .operands_of(op)
+                .into_iter()
+                .find(|&v| ssa.constant(v).is_some())
+                .unwrap();
+            assert_eq!(ssa.constant(operand), Some(value), "{}", esil);
+            assert_eq!(width(operand), Some(32), "{}", esil);
+
+            // No constant is cast.
+            assert!(ssa.values().into_iter().all(|v| match ssa.opcode(v) {
+                Some(MOpcode::OpNarrow(_)) | Some(MOpcode::OpZeroExt(_)) => {
+                    ssa.constant(ssa.operands_of(v)[0]).is_none()
+                }
+                _ => true,
+            }));
+        }
+    }
+
+    // Register profile, instructions computing `res = arg + 1` and returning, and name of `res`.
+    const ARCH_ARITH: &[(&str, &str, &str)] = &[
+        (
+            "test_files/x86_register_profile.json",
+            r#"[
+                {"offset":4096,"size":4,"esil":"1,rdi,+,rax,=","type":"add"},
+                {"offset":4100,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"}
+            ]"#,
+            "rax",
+        ),
+        (
+            "test_files/arm_register_profile.json",
+            r#"[
+                {"offset":4096,"size":4,"esil":"1,r1,+,r0,=","type":"add"},
+                {"offset":4100,"size":4,"esil":"lr,pc,=","type":"ret"}
+            ]"#,
+            "r0",
+        ),
+        (
+            "test_files/aarch64_register_profile.json",
+            r#"[
+                {"offset":4096,"size":4,"esil":"1,x1,+,x0,=","type":"add"},
+                {"offset":4100,"size":4,"esil":"lr,pc,=","type":"ret"}
+            ]"#,
+            "x0",
+        ),
+        (
+            "test_files/mips_register_profile.json",
+            r#"[
+                {"offset":4096,"size":4,"esil":"1,a1,+,v0,=","type":"add"},
+                {"offset":4100,"size":4,"esil":"ra,pc,=","type":"ret"}
+            ]"#,
+            "v0",
+        ),
+    ];
+
+    #[test]
+    fn ssa_arith_all_archs() {
+        use crate::middle::regfile::RegisterId;
+        use crate::middle::ssa::cfg_traits::CFG;
+        use crate::middle::ssa::ssa_traits::SSA;
+        use crate::middle::ssa::utils;
+        use crate::middle::ssa::verifier;
+
+        for &(profile, ops, res) in ARCH_ARITH {
+            let reg_profile = load_register_profile(profile);
+            let mut rfn = RadecoFunction::default();
+            rfn.instructions = serde_json::from_str(ops).unwrap();
+            SSAConstruct::<SSAStorage>::construct(&mut rfn, &reg_profile, false, true);
+
+            let ssa = rfn.ssa();
+            assert!(verifier::verify(ssa).is_ok(), "{}", profile);
+            let word = ssa.regfile.pc_width().unwrap();
+
+            // Flags are tracked on their own, not as part of a flag word.
+            if let Some(zf) = ssa.regfile.register_id_by_name("zf") {
+                assert_eq!(ssa.regfile.get_width(zf), Some(1), "{}", profile);
+            }
+
+            // The constant has the word size of the platform, so no extension is needed.
+            let opcodes = ssa
+                .values()
+                .into_iter()
+                .filter_map(|v| ssa.opcode(v))
+                .collect::<Vec<_>>();
+            assert!(
+                opcodes.iter().all(|op| match *op {
+                    MOpcode::OpSignExt(_) | MOpcode::OpZeroExt(_) | MOpcode::OpNarrow(_) => false,
+                    _ => true,
+                }),
+                "{}: {:?}",
+                profile,
+                opcodes
+            );
+            let adds = ssa
+                .values()
+                .into_iter()
+                .filter(|&v| ssa.opcode(v) == Some(MOpcode::OpAdd))
+                .collect::<Vec<_>>();
+            assert_eq!(adds.len(), 1, "{}", profile);
+            assert_eq!(
+                ssa.node_data(adds[0]).unwrap().vt.width().get_width(),
+                Some(word as u16),
+                "{}",
+                profile
+            );
+
+            let exit = ssa.exit_node().unwrap();
+            let frs = utils::register_state_info(ssa.registers_in(exit).unwrap(), ssa);
+            let res_i = ssa.regfile.register_id_by_name(res).unwrap().to_usize();
+            // The result register holds the sum, through the copy made by the write to it.
+            let mut value = frs[RegisterId::from_usize(res_i)].0;
+            while ssa.opcode(value) == Some(MOpcode::OpMov) {
+                value = ssa.operands_of(value)[0];
+            }
+            assert_eq!(ssa.opcode(value), Some(MOpcode::OpAdd), "{}", profile);
+        }
+    }
+
+    #[test]
+    fn ssa_memory_type() {
+        use crate::middle::ssa::ssa_traits::ValueType;
+        use crate::middle::ssa::utils;
+
+        let ops = r#"[
+            {"offset":4096,"size":3,"esil":"rax,rdi,=[8]","type":"mov"},
+            {"offset":4099,"size":1,"esil":"rsp,[8],rip,=,8,rsp,+=","type":"ret"}
+        ]"#;
+        let (rfn, res) = construct_ops(ops, MAX_NESTING);
+        assert_eq!(res, Ok(()));
+        let ssa = rfn.ssa();
+
+        let entry_state = ssa.registers_in(ssa.entry_node().unwrap()).unwrap();
+        let ers = utils::register_state_info(entry_state, ssa);
+        let &(mem, vi) = ers.get(ssa.regfile.mem_id()).unwrap();
+        assert_eq!(vi.vty, ValueType::Memory);
+        let store = ssa
+            .values()
+            .into_iter()
+            .find(|&v| ssa.opcode(v) == Some(MOpcode::OpStore))
+            .unwrap();
+        assert!(ssa.node_data(store).unwrap().vt.is_memory());
+        assert_eq!(ssa.operands_of(store)[0], mem);
+
+        let mut il = String::new();
+        ir_writer::emit_il(&mut il, None, ssa).unwrap();
+        assert!(il.contains(": $Mem = $mem;"));
+        assert!(il.contains(": $Mem = Store("));
+        assert!(!il.contains("$Unknown0"));
+
+        let dot = dot::emit_dot(ssa);
+        assert!(dot.contains("[mem] OpStore"));
+        assert!(!dot.contains("[i0]"));
+    }
+
+    #[test]
+    fn ssa_memory_operand_undefined() {
+        use crate::middle::ssa::ssa_traits::NodeType;
+
+        let reg_profile = load_register_profile(REGISTER_PROFILE);
+        let regfile = SubRegisterFile::new(&reg_profile);
+        let mut rfn = RadecoFunction::default();
+        let results = {
+            let mut constructor = SSAConstruct::new(rfn.ssa_mut(), &regfile);
+            constructor.init_blocks();
+            let mut address = MAddress::new(0x1000, 0);
+            constructor.phiplacer.add_block(address, None, None);
+            let mem_id = constructor.mem_id();
+            let mem = constructor.phiplacer.read_variable(&mut address, mem_id);
+            constructor.intermediates.push(mem);
+
+            // Malformed ESIL `mem,rax,==,$z,zf,:=` and `1,mem,+`, i.e. compare and add to memory.
+            let rax = Some(Token::EIdentifier("rax".to_owned()));
+            let cmp = constructor.process_op(
+                &Token::ECmp,
+                &mut address,
+                &[Some(Token::EEntry(0, None)), rax],
+                3,
+            );
+            let zf = constructor.process_op(&Token::IZero(0), &mut address, &[None, None], 3);
+            let one = Some(Token::EConstant(1));
+            let add = constructor.process_op(
+                &Token::EAdd,
+                &mut address,
+                &[Some(Token::EEntry(0, None)), one],
+                3,
+            );
+            vec![cmp.unwrap(), zf.unwrap(), add.unwrap()]
+        };
+
+        let ssa = rfn.ssa();
+        for &res in &results {
+            let nd = ssa.node_data(res).unwrap();
+            assert!(match nd.nt {
+                NodeType::Undefined => true,
+                _ => false,
+            });
+            assert!(!nd.vt.is_memory());
+            assert_ne!(nd.vt.width().get_width(), Some(0));
+        }
+        // Memory only ever flows into loads and stores.
+        for v in ssa.values() {
+            match ssa.opcode(v) {
+                Some(MOpcode::OpLoad) | Some(MOpcode::OpStore) | None => continue,
+                _ => (),
+            }
+            assert!(ssa
+                .operands_of(v)
+                .iter()
+                .all(|&o| !ssa.node_data(o).unwrap().vt.is_memory()));
+        }
+    }
+
+    const QC_REGISTERS: &[&str] = &["rax", "rbx", "rcx", "rdx", "rsi", "rdi"];
+    const QC_BINOPS: &[&str] = &["+", "-", "*", "&", "|", "^", "<<", ">>"];
+    const QC_BASE: u64 = 0x1000;
+    const QC_SIZE: u64 = 4;
+
+    #[derive(Clone, Copy, Debug)]
+    enum QcOperand {
+        Reg(usize),
+        Const(u8),
+    }
+
+    impl QcOperand {
+        fn esil(&self) -> String {
+            match *self {
+                QcOperand::Reg(r) => QC_REGISTERS[r].to_owned(),
+                QcOperand::Const(c) => c.to_string(),
+            }
+        }
+    }
+
+    impl Arbitrary for QcOperand {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            if bool::arbitrary(g) {
+                QcOperand::Reg(usize::arbitrary(g) % QC_REGISTERS.len())
+            } else {
+                QcOperand::Const(u8::arbitrary(g))
+            }
+        }
+    }
+
+    // Jump targets are indices of instructions, taken modulo the length of the program so that
+    // they stay valid when instructions are dropped.
+    #[derive(Clone, Copy, Debug)]
+    enum QcInst {
+        Mov(QcOperand, usize),
+        BinOp(usize, QcOperand, usize),
+        Load(usize, usize),
+        Store(QcOperand, usize),
+        Cmp(usize, QcOperand),
+        CondMov(QcOperand, usize),
+        Jmp(usize),
+        CondJmp(usize),
+    }
+
+    impl QcInst {
+        fn esil(&self, len: usize) -> (String, &'static str) {
+            let reg = |r: usize| QC_REGISTERS[r];
+            let target = |t: usize| QC_BASE + (t % len) as u64 * QC_SIZE;
+            match *self {
+                QcInst::Mov(src, dst) => (format!("{},{},=", src.esil(), reg(dst)), "mov"),
+                QcInst::BinOp(op, src, dst) => {
+                    let esil = format!("{},{},{}=,$z,zf,=", src.esil(), reg(dst), QC_BINOPS[op]);
+                    (esil, "add")
+                }
+                QcInst::Load(addr, dst) => (format!("{},[8],{},=", reg(addr), reg(dst)), "mov"),
+                QcInst::Store(src, addr) => (format!("{},{},=[8]", src.esil(), reg(addr)), "mov"),
+                QcInst::Cmp(a, b) => (format!("{},{},==,$z,zf,=", b.esil(), reg(a)), "cmp"),
+                QcInst::CondMov(src, dst) => {
+                    (format!("zf,?{{,{},{},=,}}", src.esil(), reg(dst)), "cmov")
+                }
+                QcInst::Jmp(t) => (format!("{},rip,=", target(t)), "jmp"),
+                QcInst::CondJmp(t) => (format!("zf,?{{,{},rip,=,}}", target(t)), "cjmp"),
+            }
+        }
+
+        // Returns a simpler instruction defining the same register, if any.
+        fn simplify(&self) -> Option<QcInst> {
+            match *self {
+                QcInst::Mov(QcOperand::Const(0), _) => None,
+                QcInst::Mov(_, dst) | QcInst::BinOp(_, _, dst) | QcInst::Load(_, dst) => {
+                    Some(QcInst::Mov(QcOperand::Const(0), dst))
+                }
+                QcInst::CondMov(src, dst) => Some(QcInst::Mov(src, dst)),
+                QcInst::CondJmp(t) => Some(QcInst::Jmp(t)),
+                QcInst::Store(..) | QcInst::Cmp(..) | QcInst::Jmp(_) => None,
+            }
+        }
+    }
+
+    impl Arbitrary for QcInst {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            let reg = |g: &mut G| usize::arbitrary(g) % QC_REGISTERS.len();
+            match usize::arbitrary(g) % 8 {
+                0 => QcInst::Mov(QcOperand::arbitrary(g), reg(g)),
+                1 => QcInst::BinOp(
+                    usize::arbitrary(g) % QC_BINOPS.len(),
+                    QcOperand::arbitrary(g),
+                    reg(g),
+                ),
+                2 => QcInst::Load(reg(g), reg(g)),
+                3 => QcInst::Store(QcOperand::arbitrary(g), reg(g)),
+                4 => QcInst::Cmp(reg(g), QcOperand::arbitrary(g)),
+                5 => QcInst::CondMov(QcOperand::arbitrary(g), reg(g)),
+                6 => QcInst::Jmp(usize::arbitrary(g)),
+                _ => QcInst::CondJmp(usize::arbitrary(g)),
+            }
+        }
+    }
+
+    /// A well-formed function: straight-line code, conditional blocks and jumps within the
+    /// function, always ending with a `ret`.
+    #[derive(Clone)]
+    struct QcProgram(Vec<QcInst>);
+
+    impl QcProgram {
+        fn esil(&self) -> Vec<(u64, String, &'static str)> {
+            let len = self.0.len() + 1;
+            self.0
+                .iter()
+                .map(|inst| inst.esil(len))
+                .chain(Some(("rsp,[8],rip,=,8,rsp,+=".to_owned(), "ret")))
+                .enumerate()
+                .map(|(i, (esil, ty))| (QC_BASE + i as u64 * QC_SIZE, esil, ty))
+                .collect()
+        }
+
+        fn ops(&self) -> Vec<LOpInfo> {
+            let ops = self
+                .esil()
+                .into_iter()
+                .map(|(offset, esil, ty)| {
+                    format!(
+                        r#"{{"offset":{},"size":{},"esil":"{}","type":"{}"}}"#,
+                        offset, QC_SIZE, esil, ty
+                    )
+                })
+                .collect::<Vec<_>>();
+            serde_json::from_str(&format!("[{}]", ops.join(","))).unwrap()
+        }
+    }
+
+    // Shows the ESIL of the program, which is what quickcheck prints for a failing case.
+    impl fmt::Debug for QcProgram {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            writeln!(f)?;
+            for (offset, esil, _) in self.esil() {
+                writeln!(f, "    {:#x}: {}", offset, esil)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl Arbitrary for QcProgram {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            let len = usize::arbitrary(g) % 32;
+            QcProgram((0..len).map(|_| QcInst::arbitrary(g)).collect())
+        }
+
+        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+            let insts = self.0.clone();
+            let dropped = (0..insts.len()).map({
+                let insts = insts.clone();
+                move |i| {
+                    let mut insts = insts.clone();
+                    insts.remove(i);
+                    QcProgram(insts)
+                }
+            });
+            let simplified = (0..insts.len()).filter_map(move |i| {
+                let mut insts = insts.clone();
+                insts[i] = insts[i].simplify()?;
+                Some(QcProgram(insts))
+            });
+            Box::new(dropped.chain(simplified))
+        }
+    }
+
+    /// Tests that constructing the SSA of any well-formed function succeeds and yields a valid SSA.
+    #[test]
+    fn qc_construct() {
+        fn construct_verifies(prog: QcProgram) -> TestResult {
+            use crate::middle::ssa::verifier;
+
+            let reg_profile = load_register_profile(REGISTER_PROFILE);
+            let regfile = Arc::new(SubRegisterFile::new(&reg_profile));
+            let mut rfn = RadecoFunction::default();
+            rfn.ssa_mut().regfile = regfile.clone();
+            let res = SSAConstruct::new(rfn.ssa_mut(), &*regfile).try_run(&prog.ops());
+            if let Err(err) = res {
+                return TestResult::error(err.to_string());
+            }
+            match verifier::verify(rfn.ssa()) {
+                Ok(()) => TestResult::passed(),
+                Err(err) => TestResult::error(format!("{:?}", err)),
+            }
+        }
+        QuickCheck::new()
+            .tests(200)
+            .quickcheck(construct_verifies as fn(QcProgram) -> TestResult);
+    }
 }
 
 lazy_static! {
-    /// A `ValueInfo` for `{mem}` comments
-    static ref MEM_VALUEINFO: ValueInfo = scalar!(0);
+    /// A `ValueInfo` for `{mem}` comments and the other memory states
+    static ref MEM_VALUEINFO: ValueInfo = ValueInfo::new_memory();
 }
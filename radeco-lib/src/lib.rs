@@ -41,11 +41,13 @@
 #![feature(box_patterns)]
 #![feature(box_syntax)]
 #![feature(slice_patterns)]
-#![feature(try_trait)]
 //#[cfg(test)] #[macro_use] extern crate quickcheck_macros;
 
 extern crate petgraph;
 extern crate regex;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate serde_json;
 
 #[macro_use]
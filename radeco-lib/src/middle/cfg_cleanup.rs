@@ -0,0 +1,457 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Transformations of the control flow graph which preserve the semantics of the SSA.
+
+use crate::middle::ir::MAddress;
+use crate::middle::ssa::cfg_traits::{CFGMod, CFG};
+use crate::middle::ssa::graph_traits::Graph;
+use crate::middle::ssa::ssa_traits::{SSAMod, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use petgraph::graph::{EdgeIndex, NodeIndex};
+use std::collections::{HashMap, HashSet};
+
+const UNCOND_EDGE: u8 = 2;
+
+/// Splits the critical edges of `ssa`, i.e. the edges from a block with several successors to a
+/// block with several predecessors, by inserting an empty block on each of them. Returns the
+/// inserted blocks.
+pub fn split_critical_edges(ssa: &mut SSAStorage) -> Vec<NodeIndex> {
+    let mut critical = Vec::new();
+    for block in ssa.blocks() {
+        let edges = ssa.outgoing_edges(block);
+        if edges.len() < 2 {
+            continue;
+        }
+        for (edge, label) in edges {
+            let target = match ssa.edge_info(edge) {
+                Some(info) => info.target,
+                None => continue,
+            };
+            if ssa.preds_of(target).len() > 1 {
+                critical.push((block, edge, label));
+            }
+        }
+    }
+    // Split the false edges before the true ones, for the inserted blocks to follow that order
+    critical.sort_by_key(|&(block, _, label)| (block, label));
+
+    critical
+        .into_iter()
+        .filter_map(|(_, edge, _)| split_edge(ssa, edge))
+        .collect()
+}
+
+/// Splits the control flow `edge` by inserting an empty block on it. Returns the inserted block.
+///
+/// The inserted block is placed right after the last micro-op of the block the edge is leaving,
+/// and after the blocks previously inserted there, so that its address does not collide with the
+/// one of another block.
+pub fn split_edge(ssa: &mut SSAStorage, edge: EdgeIndex) -> Option<NodeIndex> {
+    let (block, target) = match ssa.edge_info(edge) {
+        Some(info) => (info.source, info.target),
+        None => return None,
+    };
+    let label = ssa
+        .outgoing_edges(block)
+        .into_iter()
+        .find(|&(e, _)| e == edge)
+        .map(|(_, label)| label)?;
+    let taken = ssa
+        .blocks()
+        .into_iter()
+        .filter_map(|b| ssa.starting_address(b))
+        .collect::<HashSet<_>>();
+    let mut addr = end_of(ssa, block).next();
+    while taken.contains(&addr) {
+        addr = addr.next();
+    }
+
+    let split = match ssa.insert_block(addr) {
+        Ok(split) => split,
+        Err(_e) => {
+            radeco_err!("Cannot insert a block @ {}: {}", addr, _e);
+            return None;
+        }
+    };
+    radeco_trace!("split_edge|{:?} -> {:?} @ {}", block, target, addr);
+    ssa.remove_control_edge(edge);
+    ssa.insert_control_edge(block, split, label);
+    ssa.insert_control_edge(split, target, UNCOND_EDGE);
+    if ssa.preds_of(target).contains(&block) {
+        // Another edge of `block` still goes to `target`, so both pass the same values to its phis.
+        for phi in ssa.phis_in(target) {
+            if let Some(operand) = ssa.phi_operand_from(phi, block) {
+                ssa.phi_use_from(phi, split, operand);
+            }
+        }
+    } else {
+        ssa.redirect_phi_sources(target, block, split);
+    }
+    Some(split)
+}
+
+/// Merges the blocks which compute the same values and have the same successors, e.g. duplicated
+/// function epilogues, by redirecting the predecessors of one of them to the other. Returns the
+/// number of removed blocks.
+///
+/// Blocks are only merged if the phis of their successors receive the same value from both, as
+/// they could not tell them apart afterwards.
+pub fn merge_identical_blocks(ssa: &mut SSAStorage) -> usize {
+    let mut merged = 0;
+    while let Some((keep, drop, values)) = find_identical_blocks(ssa) {
+        radeco_trace!("merge_identical_blocks|{:?} <- {:?}", keep, drop);
+        let keep_preds = ssa.preds_of(keep);
+        for (edge, label) in ssa.incoming_edges(drop) {
+            let pred = match ssa.edge_info(edge) {
+                Some(info) => info.source,
+                None => continue,
+            };
+            if keep_preds.contains(&pred) {
+                // Both sides of the branch now go to `keep`, so the branch is dropped.
+                ssa.remove_data_edge(edge);
+            } else {
+                ssa.remove_control_edge(edge);
+                ssa.insert_control_edge(pred, keep, label);
+            }
+        }
+        for (old, new) in values {
+            ssa.replace_value(old, new);
+        }
+        ssa.remove_block(drop);
+        merged += 1;
+    }
+    merged
+}
+
+// Returns two blocks which can be merged, along with the pairs of corresponding values of the
+// second and first ones.
+fn find_identical_blocks(
+    ssa: &SSAStorage,
+) -> Option<(NodeIndex, NodeIndex, Vec<(NodeIndex, NodeIndex)>)> {
+    let idom = ssa.immediate_dominators();
+    let (entry, exit) = (ssa.entry_node(), ssa.exit_node());
+    let mut blocks = ssa
+        .blocks()
+        .into_iter()
+        .filter(|&b| Some(b) != entry && Some(b) != exit && idom.contains_key(&b))
+        .filter(|&b| ssa.phis_in(b).is_empty() && !ssa.succs_of(b).is_empty())
+        .collect::<Vec<_>>();
+    blocks.sort_by_key(|&b| ssa.starting_address(b));
+
+    for (i, &keep) in blocks.iter().enumerate() {
+        for &drop in &blocks[i + 1..] {
+            if let Some(values) = match_blocks(ssa, &idom, keep, drop) {
+                return Some((keep, drop, values));
+            }
+        }
+    }
+    None
+}
+
+// Returns the pairs of corresponding values of `drop` and `keep` if both blocks compute the same
+// values and pass the same ones to the phis of their successors.
+fn match_blocks(
+    ssa: &SSAStorage,
+    idom: &HashMap<NodeIndex, NodeIndex>,
+    keep: NodeIndex,
+    drop: NodeIndex,
+) -> Option<Vec<(NodeIndex, NodeIndex)>> {
+    if ssa.labeled_succs(keep) != ssa.labeled_succs(drop) {
+        return None;
+    }
+    let (keep_exprs, drop_exprs) = (ssa.exprs_in(keep), ssa.exprs_in(drop));
+    if keep_exprs.len() != drop_exprs.len() {
+        return None;
+    }
+
+    let mut values = HashMap::new();
+    for (&k, &d) in keep_exprs.iter().zip(&drop_exprs) {
+        if !ssa.g[k].structural_eq(&ssa.g[d]) {
+            return None;
+        }
+        let mut keep_ops = ssa.sparse_operands_of(k);
+        let mut drop_ops = ssa.sparse_operands_of(d);
+        keep_ops.sort();
+        drop_ops.sort();
+        if keep_ops.len() != drop_ops.len() {
+            return None;
+        }
+        for (&(ki, ko), &(di, dop)) in keep_ops.iter().zip(&drop_ops) {
+            if ki != di || !same_value(ssa, &values, (keep, ko), (drop, dop)) {
+                return None;
+            }
+        }
+        values.insert(d, k);
+    }
+
+    match (ssa.selector_in(keep), ssa.selector_in(drop)) {
+        (None, None) => {}
+        (Some(k), Some(d)) if same_value(ssa, &values, (keep, k), (drop, d)) => {}
+        _ => return None,
+    }
+
+    for succ in ssa.succs_of(keep) {
+        for phi in ssa.phis_in(succ) {
+            if !phi_agrees(ssa, idom, &values, keep, drop, phi) {
+                return None;
+            }
+        }
+    }
+    Some(values.into_iter().collect())
+}
+
+// Returns `true` if the value `k` used in `keep` is the value `d` used in `drop`, i.e. if they
+// are corresponding values of the blocks, the same value defined elsewhere or equal constants.
+fn same_value(
+    ssa: &SSAStorage,
+    values: &HashMap<NodeIndex, NodeIndex>,
+    (keep, k): (NodeIndex, NodeIndex),
+    (drop, d): (NodeIndex, NodeIndex),
+) -> bool {
+    if values.get(&d) == Some(&k) {
+        true
+    } else if ssa.constant_value(k).is_some() && ssa.constant_value(d).is_some() {
+        ssa.g[k].structural_eq(&ssa.g[d])
+    } else {
+        let block = ssa.block_for(k);
+        k == d && block != Some(keep) && block != Some(drop)
+    }
+}
+
+// Returns `true` if `phi` receives the same value through `keep` and `drop`.
+//
+// If the operands of the phi are not tied to predecessors, either each block must define exactly
+// one of them, both corresponding, or there must be only one operand available in the blocks.
+fn phi_agrees(
+    ssa: &SSAStorage,
+    idom: &HashMap<NodeIndex, NodeIndex>,
+    values: &HashMap<NodeIndex, NodeIndex>,
+    keep: NodeIndex,
+    drop: NodeIndex,
+    phi: NodeIndex,
+) -> bool {
+    let tied = (
+        ssa.phi_operand_from(phi, keep),
+        ssa.phi_operand_from(phi, drop),
+    );
+    if let (Some(k), Some(d)) = tied {
+        return same_value(ssa, values, (keep, k), (drop, d));
+    }
+
+    let operands = ssa.operands_of(phi);
+    let defined_in = |block| {
+        operands
+            .iter()
+            .cloned()
+            .filter(|&op| ssa.block_for(op) == Some(block))
+            .collect::<Vec<_>>()
+    };
+    let (from_keep, from_drop) = (defined_in(keep), defined_in(drop));
+    if !from_keep.is_empty() || !from_drop.is_empty() {
+        return from_keep.len() == 1
+            && from_drop.len() == 1
+            && values.get(&from_drop[0]) == Some(&from_keep[0]);
+    }
+
+    let available = operands
+        .iter()
+        .filter(|&&op| match ssa.block_for(op) {
+            Some(def) => dominates(idom, def, keep) || dominates(idom, def, drop),
+            // Constants and values of the entry register state
+            None => true,
+        })
+        .count();
+    available == 1
+}
+
+/// Returns `true` if the block `a` dominates the block `b`, according to the immediate dominators
+/// `idom` of `SSAStorage::immediate_dominators`.
+pub fn dominates(idom: &HashMap<NodeIndex, NodeIndex>, a: NodeIndex, mut b: NodeIndex) -> bool {
+    loop {
+        if a == b {
+            return true;
+        }
+        match idom.get(&b) {
+            Some(&d) if d != b => b = d,
+            _ => return false,
+        }
+    }
+}
+
+// Returns the address of the last micro-op of `block`, or its starting address if it is empty.
+fn end_of(ssa: &SSAStorage, block: NodeIndex) -> MAddress {
+    let start = ssa.starting_address(block).unwrap_or_default();
+    ssa.exprs_in(block)
+        .into_iter()
+        .filter_map(|e| ssa.address(e))
+        .fold(start, |end, addr| if addr > end { addr } else { end })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use crate::middle::ssa::verifier;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    // The true edge of the first block goes straight to the join block, which is critical.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CRITICAL_EDGE_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.critical(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown1 = %1 == #x0;
+        JMP IF %3 0x001008.0000 ELSE 0x001004.0000
+    bb_0x001004.0000(sz 0x4):
+        [@0x001004.0001] %4: $Unknown64 = %1 + #x1;
+        JMP 0x001008.0000
+    bb_0x001008.0000(sz 0x1):
+        %5: $Unknown64 = Phi(%1 from 0x001000.0000, %4 from 0x001004.0000);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %5;
+        $mem = %2;
+}
+";
+
+    // Both epilogues pop the same frame and return, one of them being reached through 0x1004.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const TWO_EPILOGUES_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.epilogues(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsp;
+        %3: $Mem = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %4: $Unknown1 = %1 == #x0;
+        JMP IF %4 0x001010.0000 ELSE 0x001004.0000
+    bb_0x001004.0000(sz 0x4):
+        [@0x001004.0001] %5: $Unknown64 = %1 - #x1;
+        JMP 0x001008.0000
+    bb_0x001008.0000(sz 0x4):
+        [@0x001008.0001] %6: $Unknown64 = %2 + #x8;
+        RETURN
+    bb_0x001010.0000(sz 0x4):
+        [@0x001010.0001] %7: $Unknown64 = %2 + #x8;
+        RETURN
+    exit-node:
+        %8: $Unknown64 = Phi(%6, %7);
+        %9: $Unknown64 = Phi(%5, %1);
+    final-register-state:
+        $rsp = %8;
+        $rdi = %9;
+        $mem = %3;
+}
+";
+
+    fn block_at(ssa: &SSAStorage, addr: u64) -> NodeIndex {
+        ssa.blocks()
+            .into_iter()
+            .find(|&b| ssa.starting_address(b) == Some(MAddress::new(addr, 0)))
+            .unwrap()
+    }
+
+    #[test]
+    fn merge_identical_epilogues() {
+        // `rdi` is returned as is from the second epilogue only.
+        let il = TWO_EPILOGUES_TXT
+            .replace("        %9: $Unknown64 = Phi(%5, %1);\n", "")
+            .replace("$rdi = %9", "$rdi = %1");
+        let mut ssa = ir_reader::parse_il(&il, REGISTER_FILE.clone());
+        let (head, body, epilogue) = (
+            block_at(&ssa, 0x1000),
+            block_at(&ssa, 0x1004),
+            block_at(&ssa, 0x1008),
+        );
+        let exit = ssa.exit_node().unwrap();
+        let phi = ssa.phis_in(exit)[0];
+        let blocks = ssa.blocks().len();
+
+        assert_eq!(merge_identical_blocks(&mut ssa), 1);
+        assert_eq!(ssa.blocks().len(), blocks - 1);
+        assert!(ssa
+            .blocks()
+            .into_iter()
+            .all(|b| ssa.starting_address(b) != Some(MAddress::new(0x1010, 0))));
+        assert_eq!(ssa.labeled_succs(head), vec![(body, 0), (epilogue, 1)]);
+        let mut preds = ssa.preds_of(epilogue);
+        preds.sort();
+        let mut expected = vec![head, body];
+        expected.sort();
+        assert_eq!(preds, expected);
+        assert_eq!(ssa.operands_of(phi), ssa.exprs_in(epilogue));
+        assert!(verifier::verify(&ssa).is_ok());
+
+        assert_eq!(merge_identical_blocks(&mut ssa), 0);
+    }
+
+    #[test]
+    fn keep_epilogues_apart_for_phi() {
+        // `rdi` differs depending on the epilogue control comes from.
+        let mut ssa = ir_reader::parse_il(TWO_EPILOGUES_TXT, REGISTER_FILE.clone());
+        let blocks = ssa.blocks().len();
+
+        assert_eq!(merge_identical_blocks(&mut ssa), 0);
+        assert_eq!(ssa.blocks().len(), blocks);
+    }
+
+    #[test]
+    fn split_critical_edge() {
+        let mut ssa = ir_reader::parse_il(CRITICAL_EDGE_TXT, REGISTER_FILE.clone());
+        let (head, body, join) = (
+            block_at(&ssa, 0x1000),
+            block_at(&ssa, 0x1004),
+            block_at(&ssa, 0x1008),
+        );
+        let phi = ssa.phis_in(join)[0];
+        let from_head = ssa.phi_operand_from(phi, head);
+        assert!(from_head.is_some());
+        let blocks = ssa.blocks().len();
+
+        let inserted = split_critical_edges(&mut ssa);
+        assert_eq!(inserted.len(), 1);
+        let split = inserted[0];
+        assert_eq!(ssa.blocks().len(), blocks + 1);
+        assert!(ssa.exprs_in(split).is_empty());
+        assert!(ssa.phis_in(split).is_empty());
+
+        assert_eq!(ssa.labeled_succs(head), vec![(body, 0), (split, 1)]);
+        assert_eq!(ssa.labeled_succs(split), vec![(join, 2)]);
+        assert_eq!(ssa.labeled_succs(body), vec![(join, 2)]);
+        let mut preds = ssa.preds_of(join);
+        preds.sort();
+        let mut expected = vec![body, split];
+        expected.sort();
+        assert_eq!(preds, expected);
+        // The phi now receives the value of the head through the inserted block.
+        assert_eq!(ssa.phi_operand_from(phi, split), from_head);
+        assert_eq!(ssa.phi_operand_from(phi, head), None);
+        assert!(verifier::verify(&ssa).is_ok());
+
+        // No critical edge is left.
+        assert!(split_critical_edges(&mut ssa).is_empty());
+    }
+}
@@ -8,7 +8,7 @@
 //! Graph visualization traits and functions to emit dot code.
 
 use std::cmp::Eq;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Debug;
 use std::hash::Hash;
 
@@ -111,6 +111,12 @@ pub trait GraphDot {
         Some(0)
     }
 
+    /// Nodes of a cluster with the same node_subcluster return value will be
+    /// put in the same graphviz-cluster, nested in the one of their cluster.
+    fn node_subcluster(&self, _: &Self::NodeIndex) -> Option<u64> {
+        None
+    }
+
     fn node_skip(&self, _: &Self::NodeIndex) -> bool {
         false
     }
@@ -130,34 +136,75 @@ pub fn emit_dot<T: GraphDot>(g: &T) -> String {
     result.push_str(&*g.configure());
 
     // Node configurations
-    {
-        let nodes = g.nodes();
-        let mut clustermap = HashMap::<T::NodeIndex, Vec<T::NodeIndex>>::new();
-
-        for i in &nodes {
-            let block = g.node_cluster(i).unwrap_or_else(|| {
-                radeco_err!("Block not found");
-                0
-            });
-            clustermap
-                .entry(T::node_index_new(block))
-                .or_insert_with(Vec::new)
-                .push(i.clone());
+    for (k, v) in &clusters(g) {
+        result.push_str(&*format!("subgraph cluster_{} {{\n", k.to_index()));
+        result.push_str("style=filled;\n");
+        result.push_str("fillcolor=gray;\n");
+        result.push_str("rankdir=TB;\n");
+        for node in v.iter() {
+            result.push_str(&*g.node_attrs(node).bake());
         }
+        result.push_str("}\n");
+    }
+
+    push_edges(g, &mut result);
+    result
+}
 
-        for (k, v) in &clustermap {
-            result.push_str(&*format!("subgraph cluster_{} {{\n", k.to_index()));
-            result.push_str("style=filled;\n");
-            result.push_str("fillcolor=gray;\n");
-            result.push_str("rankdir=TB;\n");
-            for node in v.iter() {
+/// Like `emit_dot`, but the nodes of each cluster are further grouped by
+/// `node_subcluster`, e.g. by the instruction they were lifted from.
+pub fn emit_dot_by_instruction<T: GraphDot>(g: &T) -> String {
+    let mut result = String::new();
+    result.push_str(&*g.configure());
+
+    // Node configurations
+    for (k, v) in &clusters(g) {
+        let cluster = k.to_index();
+        result.push_str(&*format!("subgraph cluster_{} {{\n", cluster));
+        result.push_str("style=filled;\n");
+        result.push_str("fillcolor=gray;\n");
+        result.push_str("rankdir=TB;\n");
+        let mut subclusters = BTreeMap::<u64, Vec<&T::NodeIndex>>::new();
+        for node in v.iter() {
+            match g.node_subcluster(node) {
+                Some(sub) => subclusters.entry(sub).or_insert_with(Vec::new).push(node),
+                None => result.push_str(&*g.node_attrs(node).bake()),
+            }
+        }
+        for (sub, nodes) in &subclusters {
+            result.push_str(&*format!("subgraph cluster_{}_{:x} {{\n", cluster, sub));
+            result.push_str(&*format!("label=\"0x{:x}\";\n", sub));
+            result.push_str("fillcolor=lightgray;\n");
+            for node in nodes {
                 result.push_str(&*g.node_attrs(node).bake());
             }
             result.push_str("}\n");
         }
+        result.push_str("}\n");
     }
 
-    // Connect nodes by edges.
+    push_edges(g, &mut result);
+    result
+}
+
+// Groups the nodes of `g` by their `node_cluster`.
+fn clusters<T: GraphDot>(g: &T) -> HashMap<T::NodeIndex, Vec<T::NodeIndex>> {
+    let mut clustermap = HashMap::<T::NodeIndex, Vec<T::NodeIndex>>::new();
+    for i in &g.nodes() {
+        let block = g.node_cluster(i).unwrap_or_else(|| {
+            radeco_err!("Block not found");
+            0
+        });
+        clustermap
+            .entry(T::node_index_new(block))
+            .or_insert_with(Vec::new)
+            .push(i.clone());
+    }
+    clustermap
+}
+
+// Connects nodes by edges, and closes the graph.
+fn push_edges<T: GraphDot>(g: &T, result: &mut String) {
     for edge_i in g.edges() {
         if g.edge_skip(&edge_i) {
             continue;
@@ -166,5 +213,4 @@ pub fn emit_dot<T: GraphDot>(g: &T) -> String {
     }
 
     result.push_str("\n}\n");
-    result
 }
@@ -15,7 +15,7 @@ use std::fmt;
 pub type Address = u64;
 
 /// Used to describe the width of an operand/operation in a SSA Node.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum WidthSpec {
     /// Takes whatever width the other operation takes
     Adaptive,
@@ -48,9 +48,18 @@ impl From<u16> for WidthSpec {
     }
 }
 
-#[derive(Clone, Default, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
-// New address struct
+#[derive(
+    Clone, Default, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+/// Address of a micro-op: the address of the instruction it comes from, and its index among the
+/// micro-ops of that instruction. Addresses are ordered by instruction address, then by offset.
+///
+/// Blocks which do not stand for code, such as the exit node, get synthetic addresses. These
+/// never compare equal to the address of an instruction, and are ordered after all of them.
 pub struct MAddress {
+    // Declared first, so that synthetic addresses are ordered after the real ones.
+    #[serde(default)]
+    synthetic: bool,
     pub address: u64,
     pub offset: u64,
 }
@@ -58,6 +67,7 @@ pub struct MAddress {
 impl MAddress {
     pub fn new(address: u64, offset: u64) -> MAddress {
         MAddress {
+            synthetic: false,
             address: address,
             offset: offset,
         }
@@ -65,10 +75,59 @@ impl MAddress {
 
     pub fn invalid_address() -> MAddress {
         MAddress {
+            synthetic: true,
             address: u64::max_value(),
             offset: u64::max_value(),
         }
     }
+
+    /// Address of the exit node, and of the other dynamic actions.
+    pub fn exit() -> MAddress {
+        MAddress {
+            synthetic: true,
+            address: u64::max_value(),
+            offset: 0,
+        }
+    }
+
+    /// Address of the `n`th block standing for code which was not explored. These count down
+    /// from the address of the exit node.
+    pub fn unexplored(n: u64) -> MAddress {
+        MAddress {
+            synthetic: true,
+            address: u64::max_value().saturating_sub(n.saturating_add(1)),
+            offset: 0,
+        }
+    }
+
+    /// Returns `true` if this address does not belong to an instruction.
+    pub fn is_synthetic(&self) -> bool {
+        self.synthetic
+    }
+
+    /// Returns the address of the next micro-op of the same instruction, or `None` if the offset
+    /// would overflow.
+    pub fn checked_next(&self) -> Option<MAddress> {
+        self.offset.checked_add(1).map(|offset| MAddress {
+            offset: offset,
+            ..*self
+        })
+    }
+
+    /// Returns the address of the next micro-op of the same instruction.
+    ///
+    /// Panics if the offset overflows.
+    pub fn next(&self) -> MAddress {
+        self.checked_next()
+            .unwrap_or_else(|| panic!("Offset overflow at {}", self))
+    }
+
+    /// Moves to the next micro-op of the same instruction.
+    ///
+    /// Panics if the offset overflows.
+    pub fn inc_offset(&mut self) {
+        *self = self.next();
+    }
 }
 
 impl fmt::UpperHex for MAddress {
@@ -85,10 +144,7 @@ impl fmt::Display for MAddress {
 
 impl From<u64> for MAddress {
     fn from(other: u64) -> MAddress {
-        MAddress {
-            address: other,
-            offset: 0,
-        }
+        MAddress::new(other, 0)
     }
 }
 
@@ -100,21 +156,37 @@ pub enum MArity {
     Zero,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum MOpcode {
     OpAdd,
     OpAnd,
+    // Assumption that the predicate, second operand, holds for the value of the first operand, as
+    // proven by an analysis. It has no runtime semantics, and its result is that predicate.
+    OpAssume,
     OpCJmp,
     OpCall,
     OpConst(u64),
     OpCustom(String),
     OpDiv,
     OpEq,
+    // Floating point arithmetic and comparison
+    OpFAdd,
+    OpFDiv,
+    OpFEq,
+    OpFLt,
+    OpFMul,
+    OpFSub,
+    // Floating point to integer conversion
+    OpFToI,
     OpGt,
     // If - Then - Else
     OpITE,
     OpIf,
+    // Jump to a computed target
+    OpIndirectJump,
     OpInvalid,
+    // Integer to floating point conversion
+    OpIToF,
     OpJmp,
     OpLoad,
     OpLsl,
@@ -164,7 +236,10 @@ impl MOpcode {
             | MOpcode::OpAnd
             | MOpcode::OpOr
             | MOpcode::OpXor
-            | MOpcode::OpEq => true,
+            | MOpcode::OpEq
+            | MOpcode::OpFAdd
+            | MOpcode::OpFMul
+            | MOpcode::OpFEq => true,
             _ => false,
         }
     }
@@ -175,6 +250,7 @@ impl MOpcode {
             | MOpcode::OpJmp
             | MOpcode::OpCJmp
             | MOpcode::OpCall
+            | MOpcode::OpIndirectJump
             | MOpcode::OpITE => true,
             _ => false,
         }
@@ -195,16 +271,26 @@ impl MOpcode {
         match *self {
             MOpcode::OpAdd => (Cow::from("OpAdd"), MArity::Binary),
             MOpcode::OpAnd => (Cow::from("OpAnd"), MArity::Binary),
+            MOpcode::OpAssume => (Cow::from("OpAssume"), MArity::Binary),
             MOpcode::OpCJmp => (Cow::from("OpJmpIf"), MArity::Binary),
             MOpcode::OpCall => (Cow::from("OpCall"), MArity::Unary),
             MOpcode::OpConst(c) => (Cow::from(format!("OpCost({})", c)), MArity::Zero),
             MOpcode::OpCustom(ref s) => (Cow::from(format!("OpCustom({})", s)), MArity::Zero),
             MOpcode::OpDiv => (Cow::from("OpDiv"), MArity::Binary),
             MOpcode::OpEq => (Cow::from("OpEq"), MArity::Binary),
+            MOpcode::OpFAdd => (Cow::from("OpFAdd"), MArity::Binary),
+            MOpcode::OpFDiv => (Cow::from("OpFDiv"), MArity::Binary),
+            MOpcode::OpFEq => (Cow::from("OpFEq"), MArity::Binary),
+            MOpcode::OpFLt => (Cow::from("OpFLt"), MArity::Binary),
+            MOpcode::OpFMul => (Cow::from("OpFMul"), MArity::Binary),
+            MOpcode::OpFSub => (Cow::from("OpFSub"), MArity::Binary),
+            MOpcode::OpFToI => (Cow::from("OpFToI"), MArity::Unary),
             MOpcode::OpGt => (Cow::from("OpGt"), MArity::Binary),
             MOpcode::OpITE => (Cow::from("OpITE"), MArity::Ternary),
             MOpcode::OpIf => (Cow::from("OpIf"), MArity::Unary),
+            MOpcode::OpIndirectJump => (Cow::from("OpIndirectJump"), MArity::Unary),
             MOpcode::OpInvalid => (Cow::from("OpInvalid"), MArity::Zero),
+            MOpcode::OpIToF => (Cow::from("OpIToF"), MArity::Unary),
             MOpcode::OpJmp => (Cow::from("OpJmp"), MArity::Unary),
             MOpcode::OpLoad => (Cow::from("OpLoad"), MArity::Binary),
             MOpcode::OpLsl => (Cow::from("OpLsl"), MArity::Binary),
@@ -260,6 +346,16 @@ impl MOpcode {
             MOpcode::OpSub => 28,
             MOpcode::OpXor => 29,
             MOpcode::OpZeroExt(_) => 30,
+            MOpcode::OpFAdd => 31,
+            MOpcode::OpFDiv => 32,
+            MOpcode::OpFEq => 33,
+            MOpcode::OpFLt => 34,
+            MOpcode::OpFMul => 35,
+            MOpcode::OpFSub => 36,
+            MOpcode::OpFToI => 37,
+            MOpcode::OpIToF => 38,
+            MOpcode::OpIndirectJump => 39,
+            MOpcode::OpAssume => 40,
         }
     }
 
@@ -304,3 +400,65 @@ impl fmt::Display for MOpcode {
         write!(f, "{}", self.to_string())
     }
 }
+
+/// Interprets the low `width` bits of the constant `value` as a signed or an unsigned integer.
+///
+/// Constants are stored as raw `u64`s, thus this must be used wherever their sign matters, e.g.
+/// for the operand of an `OpSignExt`. Widths of 64 bits or more take the whole `value`.
+pub fn interpret_const(value: u64, width: u16, signed: bool) -> i128 {
+    if width == 0 {
+        return 0;
+    }
+    let (value, width) = if width < 64 {
+        (value & ((1 << width) - 1), width)
+    } else {
+        (value, 64)
+    };
+    if signed && (value >> (width - 1)) & 1 == 1 {
+        i128::from(value) - (1 << width)
+    } else {
+        i128::from(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{interpret_const, MAddress};
+
+    #[test]
+    fn interpret_const_at_width() {
+        assert_eq!(interpret_const(0xff, 8, false), 255);
+        assert_eq!(interpret_const(0xff, 8, true), -1);
+        assert_eq!(interpret_const(0x7f, 8, true), 127);
+        assert_eq!(interpret_const(0x1ff, 8, false), 255);
+        assert_eq!(
+            interpret_const(u64::max_value(), 64, false),
+            0xffff_ffff_ffff_ffff
+        );
+        assert_eq!(interpret_const(u64::max_value(), 64, true), -1);
+        assert_eq!(interpret_const(0x8000_0000, 32, true), -0x8000_0000);
+    }
+
+    #[test]
+    fn address_ordering() {
+        assert!(MAddress::new(0x1000, 0xffff) < MAddress::new(0x1001, 0));
+        assert!(MAddress::new(0x1000, 1) < MAddress::new(0x1000, 2));
+        assert!(MAddress::new(u64::max_value(), u64::max_value()) < MAddress::unexplored(0));
+        assert!(MAddress::unexplored(0) < MAddress::exit());
+
+        let top = MAddress::new(u64::max_value(), 0);
+        assert!(!top.is_synthetic());
+        assert!(MAddress::exit().is_synthetic());
+        assert_ne!(top, MAddress::exit());
+        assert_ne!(MAddress::new(0xffff_ffff, 0), MAddress::exit());
+    }
+
+    #[test]
+    fn offset_overflow() {
+        let mut addr = MAddress::new(0x1000, u64::max_value() - 1);
+        addr.inc_offset();
+        assert_eq!(addr, MAddress::new(0x1000, u64::max_value()));
+        assert_eq!(addr.checked_next(), None);
+        assert!(::std::panic::catch_unwind(|| addr.next()).is_err());
+    }
+}
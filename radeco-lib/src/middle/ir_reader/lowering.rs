@@ -5,7 +5,7 @@ use crate::middle::ir;
 use crate::middle::ir::MOpcode as IrOpcode;
 use crate::middle::regfile::RegisterId;
 use crate::middle::ssa::cfg_traits::{CFGMod, CFG};
-use crate::middle::ssa::ssa_traits::{SSAMod, ValueInfo, SSA};
+use crate::middle::ssa::ssa_traits::{SSAMod, SsaError, ValueInfo, SSA};
 use crate::middle::ssa::ssastorage::SSAStorage;
 
 use std::collections::HashMap;
@@ -24,7 +24,7 @@ pub fn lower_simpleast<'a>(ssa: &'a mut SSAStorage, sfn: sast::Function) -> Resu
 #[derive(Debug)]
 pub enum LoweringError {
     /// If an operation on the [`SSAStorage`] fails
-    SsaError,
+    SsaError(SsaError),
     /// If the AST was invalid somehow
     InvalidAst(String),
 }
@@ -44,7 +44,7 @@ struct LowerSsa<'a> {
     blocks: HashMap<ir::MAddress, SSABlock>,
     values: HashMap<sast::ValueRef, SSAValue>,
     fw_ref_values: HashMap<sast::ValueRef, SSAValue>,
-    phi_operands: Vec<(SSAValue, Vec<sast::Operand>)>,
+    phi_operands: Vec<(SSAValue, Vec<(sast::Operand, Option<sast::PhiPred>)>)>,
 }
 
 impl<'a> LowerSsa<'a> {
@@ -87,6 +87,12 @@ impl<'a> LowerSsa<'a> {
             }
         }
 
+        // A function without basic blocks (e.g. an import stub) goes straight to the exit.
+        if first {
+            self.ssa
+                .insert_control_edge(self.entry_node, self.exit_node, UNCOND_EDGE);
+        }
+
         if let Some(sen) = sfn.exit_node {
             self.lower_exit_node(sen)?;
         }
@@ -94,9 +100,18 @@ impl<'a> LowerSsa<'a> {
         self.lower_final_reg_state(sfn.final_reg_state)?;
 
         for (phi, sops) in mem::replace(&mut self.phi_operands, Vec::new()) {
-            for sop in sops.into_iter().rev() {
+            for (sop, spred) in sops {
                 let op = self.lower_operand(sop)?;
-                self.ssa.phi_use(phi, op);
+                match spred {
+                    None => self.ssa.phi_use(phi, op),
+                    Some(sast::PhiPred::Entry) => self.ssa.phi_use_from(phi, self.entry_node, op),
+                    Some(sast::PhiPred::Block(at)) => {
+                        let pred = *self.blocks.get(&at).ok_or_else(|| {
+                            LoweringError::InvalidAst(format!("no block at {}", at))
+                        })?;
+                        self.ssa.phi_use_from(phi, pred, op);
+                    }
+                }
             }
         }
 
@@ -115,7 +130,10 @@ impl<'a> LowerSsa<'a> {
         &mut self,
         sregstate: Vec<(sast::NewValue, sast::PhysReg)>,
     ) -> Result<()> {
-        let regstate = self.ssa.registers_in(self.entry_node)?;
+        let regstate = self
+            .ssa
+            .registers_in(self.entry_node)
+            .ok_or(SsaError::NotABlock)?;
         for (sast::NewValue(vr, ty), sreg) in sregstate {
             let regid = self.index_of_reg(&sreg)?;
             let val = self.ssa.insert_comment(lower_valueinfo(ty), sreg.0)?;
@@ -129,7 +147,10 @@ impl<'a> LowerSsa<'a> {
         &mut self,
         sregstate: Vec<(sast::PhysReg, sast::Operand)>,
     ) -> Result<()> {
-        let regstate = self.ssa.registers_in(self.exit_node)?;
+        let regstate = self
+            .ssa
+            .registers_in(self.exit_node)
+            .ok_or(SsaError::NotABlock)?;
         for (sreg, sop) in sregstate {
             let regid = self.index_of_reg(&sreg)?;
             let op = self.lower_operand(sop)?;
@@ -180,7 +201,10 @@ impl<'a> LowerSsa<'a> {
     }
 
     fn lower_exit_node(&mut self, sen: sast::ExitNode) -> Result<()> {
-        let node_addr = self.ssa.starting_address(self.exit_node)?;
+        let node_addr = self
+            .ssa
+            .starting_address(self.exit_node)
+            .ok_or(SsaError::NotABlock)?;
         for sop in sen.ops {
             let (res, opt_op_addr) = self.lower_operation(sop)?;
             let op_addr = opt_op_addr.unwrap_or(node_addr);
@@ -207,7 +231,11 @@ impl<'a> LowerSsa<'a> {
             }
 
             sast::Operation::Assign(opt_addr, sast::NewValue(vr, ty), sexpr) => {
-                let vi = lower_valueinfo(ty);
+                // An indirect jump has no result, its `$Unknown0` is not the legacy memory type.
+                let vi = match sexpr {
+                    sast::Expr::IndirectJump(_) => scalar!(0),
+                    _ => lower_valueinfo(ty),
+                };
                 let (opcode, sops) = match sexpr {
                     sast::Expr::Value(sop0) => (IrOpcode::OpMov, vec![sop0]),
                     sast::Expr::Infix(sop0, sopcode, sop1) => {
@@ -219,6 +247,9 @@ impl<'a> LowerSsa<'a> {
                         (IrOpcode::OpStore, vec![sop0, sop1, sop2])
                     }
                     sast::Expr::Resize(rst, ws, sop0) => (lower_resize_op(rst, ws), vec![sop0]),
+                    sast::Expr::Float(sfop, sops) => (lower_float_op(sfop), sops),
+                    sast::Expr::IndirectJump(sop0) => (IrOpcode::OpIndirectJump, vec![sop0]),
+                    sast::Expr::Assume(sop0, sop1) => (IrOpcode::OpAssume, vec![sop0, sop1]),
                 };
                 let res = self.ssa.insert_op(opcode, vi, None)?;
                 for (i, sop) in sops.into_iter().enumerate() {
@@ -344,6 +375,19 @@ fn lower_prefix_op(spop: sast::PrefixOp) -> IrOpcode {
     }
 }
 
+fn lower_float_op(sfop: sast::FloatOp) -> IrOpcode {
+    match sfop {
+        sast::FloatOp::Add => IrOpcode::OpFAdd,
+        sast::FloatOp::Sub => IrOpcode::OpFSub,
+        sast::FloatOp::Mul => IrOpcode::OpFMul,
+        sast::FloatOp::Div => IrOpcode::OpFDiv,
+        sast::FloatOp::Eq => IrOpcode::OpFEq,
+        sast::FloatOp::Lt => IrOpcode::OpFLt,
+        sast::FloatOp::ToInt => IrOpcode::OpFToI,
+        sast::FloatOp::FromInt => IrOpcode::OpIToF,
+    }
+}
+
 fn lower_resize_op(srst: sast::ResizeType, sws: sast::WidthSpec) -> IrOpcode {
     match srst {
         sast::ResizeType::Narrow => IrOpcode::OpNarrow(sws.0),
@@ -355,24 +399,26 @@ fn lower_resize_op(srst: sast::ResizeType, sws: sast::WidthSpec) -> IrOpcode {
 fn lower_valueinfo(sty: sast::Type) -> ValueInfo {
     let ws = ir::WidthSpec::Known((sty.0).0);
     match sty.1 {
+        // Memory used to be written as a scalar without width, `$Unknown0`.
+        sast::RefSpec::Memory => ValueInfo::new_memory(),
+        sast::RefSpec::Scalar if (sty.0).0 == 0 => ValueInfo::new_memory(),
         sast::RefSpec::Scalar => ValueInfo::new_scalar(ws),
         sast::RefSpec::Reference => ValueInfo::new_reference(ws),
         sast::RefSpec::Unknown => ValueInfo::new_unresolved(ws),
+        sast::RefSpec::Float => ValueInfo::new_float(ws),
     }
 }
 
-/// [`SSAStorage`][SSAStorage] methods return `Option`,
-/// so we convert `None`s into [`SsaError`][LoweringError::SsaError]
-impl From<::std::option::NoneError> for LoweringError {
-    fn from(_: ::std::option::NoneError) -> Self {
-        LoweringError::SsaError
+impl From<SsaError> for LoweringError {
+    fn from(e: SsaError) -> Self {
+        LoweringError::SsaError(e)
     }
 }
 
 impl error::Error for LoweringError {
     fn description(&self) -> &str {
         match *self {
-            LoweringError::SsaError => "could not perform an `SSAStorage` operation",
+            LoweringError::SsaError(_) => "could not perform an `SSAStorage` operation",
             LoweringError::InvalidAst(_) => "invalid ast",
         }
     }
@@ -381,7 +427,9 @@ impl error::Error for LoweringError {
 impl fmt::Display for LoweringError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            LoweringError::SsaError => write!(f, "could not perform an `SSAStorage` operation"),
+            LoweringError::SsaError(ref e) => {
+                write!(f, "could not perform an `SSAStorage` operation: {}", e)
+            }
             LoweringError::InvalidAst(ref s) => write!(f, "invalid ast: {}", s),
         }
     }
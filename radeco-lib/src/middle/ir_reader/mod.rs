@@ -7,6 +7,8 @@ mod simple_ast;
 #[cfg(test)]
 mod test;
 
+use self::lowering::LoweringError;
+use crate::middle::ir_writer::IR_VERSION;
 use crate::middle::regfile::SubRegisterFile;
 use crate::middle::ssa::ssastorage::SSAStorage;
 
@@ -17,10 +19,53 @@ use std::sync::Arc;
 pub fn parse_il(il: &str, regfile: Arc<SubRegisterFile>) -> SSAStorage {
     let mut ret = SSAStorage::new();
     ret.regfile = regfile;
-    match parser::FunctionParser::new().parse(il) {
+    let il = match strip_version_header(il, IR_VERSION) {
+        Ok(il) => il,
+        Err(_e) => {
+            radeco_err!("Error reading IL: {:?}", _e);
+            return ret;
+        }
+    };
+    match parser::FunctionParser::new().parse(&strip_comments(il)) {
         Ok(sast) => lowering::lower_simpleast(&mut ret, sast)
             .unwrap_or_else(|_e| radeco_err!("Error lowering IL to SSA: {:?}", _e)),
         Err(_s) => radeco_err!("Error parsing IL: {}", _s),
     }
     ret
 }
+
+const VERSION_PREFIX: &str = "; radeco-ir v";
+
+/// Checks the `; radeco-ir v<N>` header emitted by
+/// [`ir_writer`](::middle::ir_writer) against `version` and returns the rest
+/// of `il`.
+fn strip_version_header(il: &str, version: u32) -> lowering::Result<&str> {
+    let il = il.trim_start();
+    let (header, rest) = match il.find('\n') {
+        Some(i) => (&il[..i], &il[i + 1..]),
+        None => (il, ""),
+    };
+    if !header.starts_with(VERSION_PREFIX) {
+        return Err(LoweringError::InvalidAst(
+            "missing IR version header".to_owned(),
+        ));
+    }
+    let found = header[VERSION_PREFIX.len()..].trim_end();
+    if found.parse::<u32>().ok() == Some(version) {
+        Ok(rest)
+    } else {
+        Err(LoweringError::InvalidAst(format!(
+            "unsupported IR version {} (expected {})",
+            found, version
+        )))
+    }
+}
+
+/// Removes the lines starting with `;`, such as the comments emitted by
+/// [`ir_writer`](::middle::ir_writer) before an operation.
+fn strip_comments(il: &str) -> String {
+    il.lines()
+        .filter(|l| !l.trim_start().starts_with(';'))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -40,11 +40,17 @@ pub enum Terminator {
 
 #[derive(Debug)]
 pub enum Operation {
-    Phi(NewValue, Vec<Operand>),
+    Phi(NewValue, Vec<(Operand, Option<PhiPred>)>),
     Assign(Option<ir::MAddress>, NewValue, Expr),
     Call(Option<ir::MAddress>, Vec<CallRet>, Operand, Vec<CallArg>),
 }
 
+#[derive(Debug)]
+pub enum PhiPred {
+    Entry,
+    Block(ir::MAddress),
+}
+
 #[derive(Debug)]
 pub struct CallRet {
     pub value: NewValue,
@@ -65,6 +71,9 @@ pub enum Expr {
     Load(Operand, Operand),
     Store(Operand, Operand, Operand),
     Resize(ResizeType, WidthSpec, Operand),
+    Float(FloatOp, Vec<Operand>),
+    IndirectJump(Operand),
+    Assume(Operand, Operand),
 }
 
 #[derive(Debug)]
@@ -74,6 +83,18 @@ pub enum ResizeType {
     ZeroExt,
 }
 
+#[derive(Debug)]
+pub enum FloatOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Lt,
+    ToInt,
+    FromInt,
+}
+
 #[derive(Debug)]
 pub enum Operand {
     ValueRef(ValueRef),
@@ -122,6 +143,8 @@ pub enum RefSpec {
     Scalar,
     Reference,
     Unknown,
+    Float,
+    Memory,
 }
 
 impl fmt::Debug for ValueRef {
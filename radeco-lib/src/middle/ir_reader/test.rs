@@ -1,5 +1,10 @@
+use super::lowering::LoweringError;
+use crate::middle::ir::{MAddress, MOpcode};
 use crate::middle::ir_writer;
 use crate::middle::regfile::SubRegisterFile;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::{SSAMod, SsaError, ValueInfo, SSA};
+use crate::middle::ssa::ssastorage::{NodeData, SSAStorage};
 use serde_json;
 use std::fs::File;
 use std::io::Read;
@@ -8,6 +13,7 @@ use std::sync::Arc;
 
 #[cfg_attr(rustfmt, rustfmt_skip)]
 const SSA_TXT: &str = "\
+; radeco-ir v1
 define-fun sym.foo(unknown) -> unknown {
     entry-register-state:
         %1: $Unknown64(*?) = $r15;
@@ -43,7 +49,7 @@ define-fun sym.foo(unknown) -> unknown {
         %31: $Unknown64(*?) = $es;
         %32: $Unknown64(*?) = $fs;
         %33: $Unknown64(*?) = $gs;
-        %34: $Unknown0 = $mem;
+        %34: $Mem = $mem;
     bb_0x000610.0000(sz 0x0):
         [@0x000610.0001] %35: $Unknown64(*?) = #x1 + %14;
         RETURN
@@ -161,6 +167,389 @@ fn roundtrip_loopy_main() {
     roundtrip_file("sym.main".to_owned(), "test_files/loopy_main_ssa");
 }
 
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const FLOAT_SSA_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.fadd(unknown) -> unknown {
+    entry-register-state:
+        %1: $Float64 = $xmm0l;
+        %2: $Float64 = $xmm1l;
+        %3: $Mem = $mem;
+    bb_0x001000.0000(sz 0x0):
+        [@0x001000.0001] %4: $Float64 = %1 + %2;
+        RETURN
+    exit-node:
+    final-register-state:
+        $xmm0l = %4;
+        $xmm1l = %2;
+        $mem = %3;
+}
+";
+
+#[test]
+fn roundtrip_float() {
+    roundtrip("sym.fadd".to_owned(), FLOAT_SSA_TXT);
+}
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const FLOAT_OPS_SSA_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.fops(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown1 = $cf;
+        %3: $Float64 = $xmm0l;
+        %4: $Float64 = $xmm1l;
+        %5: $Mem = $mem;
+    bb_0x001000.0000(sz 0x0):
+        [@0x001000.0001] %6: $Float64 = IToF(%1);
+        [@0x001000.0002] %7: $Float64 = FMul(%6, %4);
+        [@0x001000.0003] %8: $Float64 = FAdd(%7, %3);
+        [@0x001000.0004] %9: $Unknown1 = FLt(%8, %4);
+        [@0x001000.0005] %10: $Unknown64 = FToI(%8);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %10;
+        $cf = %9;
+        $xmm0l = %8;
+        $xmm1l = %4;
+        $mem = %5;
+}
+";
+
+#[test]
+fn roundtrip_float_ops() {
+    roundtrip("sym.fops".to_owned(), FLOAT_OPS_SSA_TXT);
+}
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const SIGNED_CONST_SSA_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.signed(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x0):
+        [@0x001000.0001] %3: $Unknown64 = SignExt64(#-x1);
+        [@0x001000.0002] %4: $Unknown64 = ZeroExt64(#xffffffffffffffff);
+        [@0x001000.0003] %5: $Unknown64 = %3 + %4;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %5;
+        $mem = %2;
+}
+";
+
+#[test]
+fn roundtrip_signed_const() {
+    roundtrip("sym.signed".to_owned(), SIGNED_CONST_SSA_TXT);
+}
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const ASSUME_SSA_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.assume(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x0):
+        [@0x001000.0001] %3: $Unknown64 = %1 + #x1;
+        [@0x001000.0002] %4: $Unknown1 = %3 > #x0;
+        [@0x001000.0003] %5: $Unknown1 = Assume(%3, %4);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %3;
+        $rdi = %1;
+        $mem = %2;
+}
+";
+
+#[test]
+fn roundtrip_assume() {
+    roundtrip("sym.assume".to_owned(), ASSUME_SSA_TXT);
+    let ssa = super::parse_il(ASSUME_SSA_TXT, REGISTER_FILE.clone());
+    let assume = ssa
+        .values()
+        .into_iter()
+        .find(|&v| ssa.opcode(v) == Some(MOpcode::OpAssume))
+        .unwrap();
+    let operands = ssa.operands_of(assume);
+    assert_eq!(ssa.opcode(operands[0]), Some(MOpcode::OpAdd));
+    assert_eq!(ssa.opcode(operands[1]), Some(MOpcode::OpGt));
+}
+
+#[test]
+fn emit_signed_const_at_width() {
+    let mut ssa = super::parse_il(SIGNED_CONST_SSA_TXT, REGISTER_FILE.clone());
+    // Make both extended constants the 8 bits `0xff`
+    for opcode in &[MOpcode::OpSignExt(64), MOpcode::OpZeroExt(64)] {
+        let ext = ssa
+            .values()
+            .into_iter()
+            .find(|&v| ssa.opcode(v).as_ref() == Some(opcode))
+            .unwrap();
+        let c = ssa.operands_of(ext)[0];
+        ssa.g[c] = NodeData::Op(MOpcode::OpConst(0xff), scalar!(8));
+    }
+
+    let mut emitted = String::new();
+    ir_writer::emit_il(&mut emitted, Some("sym.signed".to_owned()), &ssa).unwrap();
+    assert!(emitted.contains("SignExt64(#-x1);"));
+    assert!(emitted.contains("ZeroExt64(#xff);"));
+}
+
+#[test]
+fn read_legacy_memory_type() {
+    let legacy = SSA_TXT.replace("$Mem", "$Unknown0");
+    let parsed = super::parse_il(&legacy, REGISTER_FILE.clone());
+    let mem = parsed.values().into_iter().find(|&v| {
+        parsed
+            .node_data(v)
+            .map(|nd| nd.vt.is_memory())
+            .unwrap_or(false)
+    });
+    assert!(mem.is_some());
+
+    let mut emitted = String::new();
+    ir_writer::emit_il(&mut emitted, Some("sym.foo".to_owned()), &parsed).unwrap();
+    assert_eq!(SSA_TXT, emitted);
+}
+
+#[test]
+fn reject_old_ir_version() {
+    match super::strip_version_header(SSA_TXT, ir_writer::IR_VERSION + 1) {
+        Err(LoweringError::InvalidAst(msg)) => assert_eq!(
+            msg,
+            format!(
+                "unsupported IR version {} (expected {})",
+                ir_writer::IR_VERSION,
+                ir_writer::IR_VERSION + 1
+            )
+        ),
+        r => panic!("expected an InvalidAst error, got {:?}", r),
+    }
+}
+
+#[test]
+fn reject_missing_ir_version() {
+    let unversioned = SSA_TXT.splitn(2, '\n').nth(1).unwrap();
+    match super::strip_version_header(unversioned, ir_writer::IR_VERSION) {
+        Err(LoweringError::InvalidAst(msg)) => assert_eq!(msg, "missing IR version header"),
+        r => panic!("expected an InvalidAst error, got {:?}", r),
+    }
+}
+
+#[test]
+fn failed_insertion_error() {
+    let mut ssa = SSAStorage::new();
+    let err = ssa.insert_const(1, Some(0)).unwrap_err();
+    assert_eq!(err, SsaError::InvalidWidth(0));
+    assert_eq!(
+        LoweringError::from(err).to_string(),
+        "could not perform an `SSAStorage` operation: a constant cannot be 0 bits wide"
+    );
+}
+
+#[test]
+fn skip_comments() {
+    let commented = SSA_TXT.replacen(
+        "    entry-register-state:\n",
+        "    entry-register-state:\n        ; \"Hello, world\"\n",
+        1,
+    );
+    let parsed = super::parse_il(&commented, REGISTER_FILE.clone());
+    let mut emitted = String::new();
+    ir_writer::emit_il(&mut emitted, Some("sym.foo".to_owned()), &parsed).unwrap();
+    assert_eq!(SSA_TXT, emitted);
+}
+
+#[test]
+fn roundtrip_renumbered() {
+    let opts = ir_writer::EmitOptions {
+        renumber_blocks: true,
+        strip_comments: true,
+        ..Default::default()
+    };
+    let ssa_txt = ::std::fs::read_to_string("test_files/bin1_main_ssa").unwrap();
+    let parsed = super::parse_il(&ssa_txt, REGISTER_FILE.clone());
+
+    let mut first = String::new();
+    ir_writer::emit_il_with(&mut first, Some("main".to_owned()), &parsed, opts).unwrap();
+    let mut second = String::new();
+    ir_writer::emit_il_with(&mut second, Some("main".to_owned()), &parsed, opts).unwrap();
+    assert_eq!(first, second);
+    assert!(first.contains("    bb0(sz "));
+    assert!(!first.contains("bb_0x"));
+
+    let reparsed = super::parse_il(&first, REGISTER_FILE.clone());
+    let mut emitted = String::new();
+    ir_writer::emit_il_with(&mut emitted, Some("main".to_owned()), &reparsed, opts).unwrap();
+    assert_eq!(first, emitted);
+}
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const TWO_INSNS_SSA_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.two_insns(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x7):
+        [@0x001000.0001] %3: $Unknown64 = %1 + #x1;
+        [@0x001000.0002] %4: $Unknown64 = %3 * #x2;
+        [@0x001004.0001] %5: $Unknown64 = %4 - #x3;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %5;
+        $mem = %2;
+}
+";
+
+#[test]
+fn group_by_instruction() {
+    let opts = ir_writer::EmitOptions {
+        group_instructions: true,
+        ..Default::default()
+    };
+    let parsed = super::parse_il(TWO_INSNS_SSA_TXT, REGISTER_FILE.clone());
+    let mut emitted = String::new();
+    ir_writer::emit_il_with(
+        &mut emitted,
+        Some("sym.two_insns".to_owned()),
+        &parsed,
+        opts,
+    )
+    .unwrap();
+
+    let lines = emitted.lines().map(str::trim).collect::<Vec<_>>();
+    let headers = lines
+        .iter()
+        .enumerate()
+        .filter(|&(_, l)| l.starts_with("; insn @ "))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    assert_eq!(headers.len(), 2);
+    assert_eq!(lines[headers[0]], "; insn @ 0x1000");
+    assert!(lines[headers[0] + 1].starts_with("[@0x001000.0001] "));
+    assert!(lines[headers[0] + 2].starts_with("[@0x001000.0002] "));
+    assert_eq!(headers[1], headers[0] + 3);
+    assert_eq!(lines[headers[1]], "; insn @ 0x1004");
+    assert!(lines[headers[1] + 1].starts_with("[@0x001004.0001] "));
+    assert_eq!(lines[headers[1] + 2], "RETURN");
+
+    // The headers are skipped when reading the IL back.
+    let reparsed = super::parse_il(&emitted, REGISTER_FILE.clone());
+    let mut plain = String::new();
+    ir_writer::emit_il(&mut plain, Some("sym.two_insns".to_owned()), &reparsed).unwrap();
+    assert_eq!(TWO_INSNS_SSA_TXT, plain);
+}
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const CONSTS_SSA_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.consts(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x7):
+        [@0x001000.0001] %3: $Unknown64 = %1 + #x5;
+        [@0x001000.0002] %4: $Unknown64 = %3 & #x401000;
+        [@0x001004.0001] %5: $Unknown64 = %4 ^ #xffffffffffffffff;
+        [@0x001004.0002] %6: $Unknown64 = SignExt64(#-x1);
+        [@0x001004.0003] %7: $Unknown64 = %5 + %6;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %7;
+        $mem = %2;
+}
+";
+
+#[test]
+fn emit_const_representation() {
+    let opts = ir_writer::EmitOptions {
+        decimal_below: 0x1000,
+        bit_patterns: true,
+        ..Default::default()
+    };
+    let parsed = super::parse_il(CONSTS_SSA_TXT, REGISTER_FILE.clone());
+    let mut emitted = String::new();
+    ir_writer::emit_il_with(&mut emitted, Some("sym.consts".to_owned()), &parsed, opts).unwrap();
+    assert!(emitted.contains("= %1 + #5;"));
+    assert!(emitted.contains("= %3 & #x401000;"));
+    assert!(emitted.contains("= %4 ^ #~0;"));
+    assert!(emitted.contains("= SignExt64(#-1);"));
+
+    // The constants are read back the same.
+    let reparsed = super::parse_il(&emitted, REGISTER_FILE.clone());
+    let mut plain = String::new();
+    ir_writer::emit_il(&mut plain, Some("sym.consts".to_owned()), &reparsed).unwrap();
+    assert_eq!(CONSTS_SSA_TXT, plain);
+}
+
+// The operands of the phi are listed in the opposite order of the predecessors they flow from.
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const PHI_SOURCES_SSA_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.countdown(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x4):
+        %3: $Unknown64 = Phi(%4 from 0x001004.0000, %1 from entry);
+        [@0x001000.0001] %5: $Unknown1 = %3 == #x0;
+        JMP IF %5 0x001008.0000 ELSE 0x001004.0000
+    bb_0x001004.0000(sz 0x4):
+        [@0x001004.0001] %4: $Unknown64 = %3 - #x1;
+        JMP 0x001000.0000
+    bb_0x001008.0000(sz 0x1):
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %3;
+        $mem = %2;
+}
+";
+
+#[test]
+fn roundtrip_phi_sources() {
+    fn check_sources(ssa: &SSAStorage) {
+        let block_at = |addr| {
+            ssa.blocks()
+                .into_iter()
+                .find(|&b| ssa.starting_address(b) == Some(MAddress::new(addr, 0)))
+                .unwrap()
+        };
+        let (entry, head, body) = (
+            ssa.entry_node().unwrap(),
+            block_at(0x1000),
+            block_at(0x1004),
+        );
+        let phi = ssa.phis_in(head)[0];
+        let counter = ssa.exprs_in(body)[0];
+        let from_entry = ssa.phi_operand_from(phi, entry).unwrap();
+        assert_eq!(ssa.comment(from_entry), Some("rdi".to_owned()));
+        assert_eq!(ssa.phi_operand_from(phi, body), Some(counter));
+        assert_eq!(
+            ssa.phi_sources(phi),
+            vec![(Some(body), counter), (Some(entry), from_entry)]
+        );
+    }
+
+    let parsed = super::parse_il(PHI_SOURCES_SSA_TXT, REGISTER_FILE.clone());
+    check_sources(&parsed);
+    let mut emitted = String::new();
+    ir_writer::emit_il(&mut emitted, Some("sym.countdown".to_owned()), &parsed).unwrap();
+    assert_eq!(PHI_SOURCES_SSA_TXT, emitted);
+
+    let reparsed = super::parse_il(&emitted, REGISTER_FILE.clone());
+    check_sources(&reparsed);
+}
+
 fn roundtrip_file<P: AsRef<Path>>(fn_name: String, file_path: P) {
     let ssa_txt = {
         let mut ssa_txt_file = File::open(file_path).expect("Error opening file");
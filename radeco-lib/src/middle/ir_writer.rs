@@ -7,14 +7,15 @@
 //! representation is inspired from (and probably similar) LLVM IR.
 
 use crate::frontend::radeco_containers::RadecoFunction;
-use crate::middle::ir::MOpcode;
+use crate::middle::ir::{self, MAddress, MOpcode};
 use crate::middle::ssa::cfg_traits::CFG;
-use crate::middle::ssa::ssa_traits::{SSAWalk, ValueInfo, SSA};
+use crate::middle::ssa::graph_traits::Graph;
+use crate::middle::ssa::ssa_traits::{SSAExtra, SSAWalk, ValueInfo, SSA};
 use crate::middle::ssa::ssastorage::{NodeData, SSAStorage};
 use crate::middle::ssa::utils;
 use petgraph::graph::NodeIndex;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fmt::Write;
 
@@ -45,8 +46,93 @@ macro_rules! log_emit_err {
     }
 }
 
+/// Version of the textual IL format, emitted as a `; radeco-ir v<N>` header.
+/// Bump this whenever the grammar changes in a way that makes older files
+/// unreadable.
+pub const IR_VERSION: u32 = 1;
+
 pub fn emit_il<O: Write>(output: O, fn_name: Option<String>, ssa: &SSAStorage) -> fmt::Result {
-    IRWriter::new(output, ssa).emit_il(fn_name)
+    emit_il_with(output, fn_name, ssa, EmitOptions::default())
+}
+
+/// Options controlling how [`emit_il_with`] prints the IL.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EmitOptions {
+    /// Label the blocks `bb0`, `bb1`, ... in reverse postorder instead of by address, and give the
+    /// operations of every block addresses derived from its label. The output then no longer
+    /// depends on where the function is in the binary.
+    pub renumber_blocks: bool,
+    /// Do not emit the comments attached to operations.
+    pub strip_comments: bool,
+    /// Precede the operations coming from every machine instruction by a `; insn @ <address>`
+    /// header, to correlate the IL with the disassembly.
+    pub group_instructions: bool,
+    /// Precede the operations coming from every machine instruction by a `; esil: <ESIL>` comment
+    /// with the ESIL it was translated from, if it was recorded by `ssaconstructor::record_esil`.
+    pub esil: bool,
+    /// Print the constants below this value in decimal, e.g. `#5`, and the others in hex. With the
+    /// default of 0, every constant is printed in hex.
+    pub decimal_below: u64,
+    /// Print the constant with all its bits set as `#~0`. Only 64 bits constants are concerned,
+    /// since the reader gives every constant 64 bits.
+    pub bit_patterns: bool,
+}
+
+pub fn emit_il_with<O: Write>(
+    output: O,
+    fn_name: Option<String>,
+    ssa: &SSAStorage,
+    options: EmitOptions,
+) -> fmt::Result {
+    let mut writer = IRWriter::new(output, ssa);
+    if options.renumber_blocks {
+        writer.labels = Some(reverse_postorder_labels(ssa));
+    }
+    writer.strip_comments = options.strip_comments;
+    writer.group_instructions = options.group_instructions;
+    writer.esil = options.esil;
+    writer.decimal_below = options.decimal_below;
+    writer.bit_patterns = options.bit_patterns;
+    writer.emit_il(fn_name)
+}
+
+// Numbers the blocks reachable from the entry of `ssa` in reverse postorder, not counting the
+// entry itself. Successors are visited by kind of edge, so that the numbering only depends on the
+// shape of the CFG.
+fn reverse_postorder_labels(ssa: &SSAStorage) -> HashMap<NodeIndex, u64> {
+    let succs = |block: NodeIndex| {
+        let mut edges = ssa.outgoing_edges(block);
+        edges.sort_by_key(|&(_, kind)| kind);
+        edges
+            .into_iter()
+            .filter_map(|(e, _)| ssa.edge_info(e).map(|info| info.target))
+            .collect::<Vec<_>>()
+    };
+
+    let mut postorder = Vec::new();
+    if let Some(entry) = ssa.entry_node() {
+        let mut visited = HashSet::new();
+        visited.insert(entry);
+        let mut stack = vec![(entry, succs(entry), 0)];
+        while let Some((block, block_succs, next)) = stack.pop() {
+            if let Some(&succ) = block_succs.get(next) {
+                stack.push((block, block_succs, next + 1));
+                if visited.insert(succ) {
+                    stack.push((succ, succs(succ), 0));
+                }
+            } else {
+                postorder.push(block);
+            }
+        }
+    }
+
+    postorder
+        .into_iter()
+        .rev()
+        .filter(|&b| ssa.is_block(b) && Some(b) != ssa.entry_node())
+        .enumerate()
+        .map(|(i, b)| (b, i as u64))
+        .collect()
 }
 
 // TODO: expose width
@@ -72,6 +158,17 @@ struct IRWriter<'a, O: Write> {
     seen: HashMap<NodeIndex, u64>,
     ctr: u64,
     output: O,
+    // Labels of the blocks, if they are renumbered.
+    labels: Option<HashMap<NodeIndex, u64>>,
+    // Label of the block being emitted and number of operations emitted in it so far.
+    current_label: Option<(u64, u64)>,
+    strip_comments: bool,
+    group_instructions: bool,
+    esil: bool,
+    // Address of the instruction the last operation emitted in the current block comes from.
+    current_insn: Option<u64>,
+    decimal_below: u64,
+    bit_patterns: bool,
 }
 
 impl<'a, O: Write> IRWriter<'a, O> {
@@ -81,6 +178,14 @@ impl<'a, O: Write> IRWriter<'a, O> {
             seen: HashMap::new(),
             ctr: 0,
             output,
+            labels: None,
+            current_label: None,
+            strip_comments: false,
+            group_instructions: false,
+            esil: false,
+            current_insn: None,
+            decimal_below: 0,
+            bit_patterns: false,
         }
     }
 
@@ -90,19 +195,29 @@ impl<'a, O: Write> IRWriter<'a, O> {
         let exit_node = exit_node_err!(self.ssa);
         let fn_name = fn_name.as_ref().map(|s| &**s).unwrap_or("fn_apple");
 
+        writeln!(self.output, "; radeco-ir v{}", IR_VERSION)?;
         writeln!(self.output, "define-fun {}(unknown) -> unknown {{", fn_name)?;
 
         let entry_regs = registers_in_err!(self.ssa, entry_node);
         self.emit_entry_regstate(entry_regs)?;
 
-        for node in self.ssa.inorder_walk() {
+        for node in self.walk() {
             if node == entry_node {
                 continue;
             }
             match self.ssa.g[node] {
                 NodeData::Op(ref opcode, vt) => {
+                    if self.group_instructions || self.esil {
+                        self.emit_insn_header(node)?;
+                    }
+                    if let Some(comment) = self.ssa.comments(&node) {
+                        if !self.strip_comments {
+                            self.indent(2)?;
+                            writeln!(self.output, "; {}", comment)?;
+                        }
+                    }
                     self.indent(2)?;
-                    if let Some(address) = self.ssa.address(node) {
+                    if let Some(address) = self.address(node) {
                         write!(self.output, "[@{}] ", address)?;
                     }
                     match opcode {
@@ -121,8 +236,10 @@ impl<'a, O: Write> IRWriter<'a, O> {
                     self.indent(2)?;
                     self.emit_new_value(node, vt)?;
                     write!(self.output, "Phi(")?;
-                    let operands = self.ssa.operands_of(node);
-                    self.emit_operand_list(&operands)?;
+                    let sources = self.ssa.phi_sources(node);
+                    emit_list!(self.output, sources, |(pred, operand)| {
+                        self.emit_phi_operand(operand, pred)?
+                    });
                     writeln!(self.output, ");")?;
                 }
                 NodeData::BasicBlock(addr, sz) => {
@@ -132,8 +249,15 @@ impl<'a, O: Write> IRWriter<'a, O> {
                         self.emit_jump(prev_block)?;
                     }
                     last = Some(node);
+                    self.current_insn = None;
                     self.indent(1)?;
-                    writeln!(self.output, "bb_{}(sz {:#x}):", addr, sz)?;
+                    match self.label(node) {
+                        Some(label) => {
+                            self.current_label = Some((label, 0));
+                            writeln!(self.output, "bb{}(sz {:#x}):", label, sz)?;
+                        }
+                        None => writeln!(self.output, "bb_{}(sz {:#x}):", addr, sz)?,
+                    }
                 }
                 NodeData::DynamicAction => {
                     if let Some(prev_block) = last {
@@ -144,6 +268,8 @@ impl<'a, O: Write> IRWriter<'a, O> {
                         radeco_warn!("first node was DynamicAction");
                     }
                     last = Some(node);
+                    self.current_label = None;
+                    self.current_insn = None;
 
                     self.indent(1)?;
                     if self.ssa.exit_node() == Some(node) {
@@ -174,6 +300,76 @@ impl<'a, O: Write> IRWriter<'a, O> {
         Ok(())
     }
 
+    // Returns the nodes to emit, with the blocks in order of label if they are renumbered.
+    fn walk(&self) -> Vec<NodeIndex> {
+        let labels = match self.labels {
+            Some(ref labels) => labels,
+            None => return self.ssa.inorder_walk().collect(),
+        };
+
+        // `inorder_walk` returns every block followed by its contents.
+        let mut groups: Vec<Vec<NodeIndex>> = Vec::new();
+        for node in self.ssa.inorder_walk() {
+            let starts_group = match self.ssa.g[node] {
+                NodeData::BasicBlock(..) | NodeData::DynamicAction => true,
+                _ => groups.is_empty(),
+            };
+            if starts_group {
+                groups.push(Vec::new());
+            }
+            if let Some(group) = groups.last_mut() {
+                group.push(node);
+            }
+        }
+        let entry = self.ssa.entry_node();
+        groups.sort_by_key(|group| {
+            if Some(group[0]) == entry {
+                (0, 0)
+            } else {
+                labels.get(&group[0]).map_or((2, 0), |&l| (1, l))
+            }
+        });
+        groups.concat()
+    }
+
+    fn label(&self, block: NodeIndex) -> Option<u64> {
+        self.labels.as_ref()?.get(&block).cloned()
+    }
+
+    fn address(&mut self, node: NodeIndex) -> Option<MAddress> {
+        if self.labels.is_none() {
+            return self.ssa.address(node);
+        }
+        let current = self.current_label.as_mut()?;
+        current.1 += 1;
+        Some(MAddress::new(current.0, current.1))
+    }
+
+    // Emits a `; insn @ <address>` header and/or the ESIL of the instruction if `node` does not
+    // come from the same instruction as the previous operation of the block. The original address
+    // is used even if the blocks are renumbered.
+    fn emit_insn_header(&mut self, node: NodeIndex) -> fmt::Result {
+        let insn = match self.ssa.address(node) {
+            Some(addr) => addr.address,
+            None => return Ok(()),
+        };
+        if self.current_insn != Some(insn) {
+            self.current_insn = Some(insn);
+            if self.group_instructions {
+                self.indent(2)?;
+                writeln!(self.output, "; insn @ {:#x}", insn)?;
+            }
+            match self.ssa.esil(&node) {
+                Some(ref esil) if self.esil => {
+                    self.indent(2)?;
+                    writeln!(self.output, "; esil: {}", esil)?;
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
     fn emit_entry_regstate(&mut self, entry_regstate: NodeIndex) -> fmt::Result {
         self.indent(1)?;
         writeln!(self.output, "entry-register-state:")?;
@@ -250,7 +446,10 @@ impl<'a, O: Write> IRWriter<'a, O> {
             }
             OpSignExt(wd) => {
                 write!(self.output, "SignExt{}(", wd)?;
-                self.emit_opt_operand(operands.get(0).cloned())?;
+                match operands.get(0) {
+                    Some(&operand) => self.emit_signed_operand(operand)?,
+                    None => log_emit_err!(self, "not enough operands")?,
+                }
                 write!(self.output, ")")?;
                 Ok(())
             }
@@ -260,6 +459,28 @@ impl<'a, O: Write> IRWriter<'a, O> {
                 write!(self.output, ")")?;
                 Ok(())
             }
+            OpFAdd => self.emit_float_op("FAdd", operands),
+            OpFSub => self.emit_float_op("FSub", operands),
+            OpFMul => self.emit_float_op("FMul", operands),
+            OpFDiv => self.emit_float_op("FDiv", operands),
+            OpFEq => self.emit_float_op("FEq", operands),
+            OpFLt => self.emit_float_op("FLt", operands),
+            OpFToI => self.emit_float_op("FToI", operands),
+            OpIToF => self.emit_float_op("IToF", operands),
+            OpIndirectJump => {
+                write!(self.output, "IndirectJump(")?;
+                self.emit_opt_operand(operands.get(0).cloned())?;
+                write!(self.output, ")")?;
+                Ok(())
+            }
+            OpAssume => {
+                write!(self.output, "Assume(")?;
+                self.emit_opt_operand(operands.get(0).cloned())?;
+                write!(self.output, ", ")?;
+                self.emit_opt_operand(operands.get(1).cloned())?;
+                write!(self.output, ")")?;
+                Ok(())
+            }
             _ => {
                 radeco_warn!("unknown opcode: {:?}", opcode);
                 write!(self.output, "{}(", opcode)?;
@@ -277,6 +498,13 @@ impl<'a, O: Write> IRWriter<'a, O> {
         Ok(())
     }
 
+    fn emit_float_op(&mut self, op: &str, operands: &[NodeIndex]) -> fmt::Result {
+        write!(self.output, "{}(", op)?;
+        self.emit_operand_list(operands)?;
+        write!(self.output, ")")?;
+        Ok(())
+    }
+
     fn emit_opt_operand(&mut self, opt_operand: Option<NodeIndex>) -> fmt::Result {
         match opt_operand {
             Some(operand) => self.emit_operand(operand),
@@ -289,6 +517,21 @@ impl<'a, O: Write> IRWriter<'a, O> {
         Ok(())
     }
 
+    // Emits a phi operand, followed by the predecessor it flows from if it is known.
+    fn emit_phi_operand(&mut self, operand: NodeIndex, pred: Option<NodeIndex>) -> fmt::Result {
+        self.emit_operand(operand)?;
+        match pred {
+            Some(pred) if Some(pred) == self.ssa.entry_node() => {
+                write!(self.output, " from entry")
+            }
+            Some(pred) if self.ssa.is_block(pred) => {
+                write!(self.output, " from ")?;
+                self.emit_jump_tgt(pred)
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn emit_call(&mut self, call_node: NodeIndex) -> fmt::Result {
         if let Some(call_info) = utils::call_info(call_node, self.ssa) {
             let ret_regs = utils::call_rets(call_node, self.ssa);
@@ -377,7 +620,10 @@ impl<'a, O: Write> IRWriter<'a, O> {
 
     fn emit_jump_tgt(&mut self, tgt: NodeIndex) -> fmt::Result {
         match self.ssa.g[tgt] {
-            NodeData::BasicBlock(addr, _) => write!(self.output, "{}", addr),
+            NodeData::BasicBlock(addr, _) => match self.label(tgt) {
+                Some(label) => write!(self.output, "bb{}", label),
+                None => write!(self.output, "{}", addr),
+            },
             NodeData::DynamicAction => write!(self.output, "{}", "dynamic_action"),
             _ => log_emit_err!(
                 self,
@@ -390,7 +636,7 @@ impl<'a, O: Write> IRWriter<'a, O> {
 
     fn emit_operand(&mut self, operand: NodeIndex) -> fmt::Result {
         match self.ssa.g[operand] {
-            NodeData::Op(MOpcode::OpConst(c), _) => write!(self.output, "#x{:x}", c),
+            NodeData::Op(MOpcode::OpConst(c), vt) => self.emit_const(c, vt),
             _ => {
                 let idx = self.value(operand);
                 write!(self.output, "%{}", idx)
@@ -398,6 +644,36 @@ impl<'a, O: Write> IRWriter<'a, O> {
         }
     }
 
+    // Emits `operand` in a context where it is signed, i.e. a negative constant is printed as
+    // `#-x<magnitude>` at its width.
+    fn emit_signed_operand(&mut self, operand: NodeIndex) -> fmt::Result {
+        match self.ssa.g[operand] {
+            NodeData::Op(MOpcode::OpConst(c), vt) => {
+                let width = vt.width().get_width().unwrap_or(64);
+                match ir::interpret_const(c, width, true) {
+                    v if v < 0 && ((-v) as u64) < self.decimal_below => {
+                        write!(self.output, "#-{}", -v)
+                    }
+                    v if v < 0 => write!(self.output, "#-x{:x}", -v),
+                    _ => self.emit_const(c, vt),
+                }
+            }
+            _ => self.emit_operand(operand),
+        }
+    }
+
+    // Emits the constant `c`, in decimal if it is small enough.
+    fn emit_const(&mut self, c: u64, vt: ValueInfo) -> fmt::Result {
+        let width = vt.width().get_width().unwrap_or(64);
+        if self.bit_patterns && width >= 64 && c == u64::max_value() {
+            write!(self.output, "#~0")
+        } else if c < self.decimal_below {
+            write!(self.output, "#{}", c)
+        } else {
+            write!(self.output, "#x{:x}", c)
+        }
+    }
+
     fn emit_new_value(&mut self, node: NodeIndex, vt: ValueInfo) -> fmt::Result {
         let idx = self.value(node);
         write!(self.output, "%{}: ", idx)?;
@@ -418,7 +694,13 @@ impl<'a, O: Write> IRWriter<'a, O> {
     }
 
     fn emit_valueinfo(&mut self, vt: ValueInfo) -> fmt::Result {
+        if vt.is_memory() {
+            return write!(self.output, "$Mem");
+        }
         let w = vt.width().get_width().unwrap_or(64);
+        if vt.is_float() {
+            return write!(self.output, "$Float{}", w);
+        }
         let is_reference = if vt.is_reference() {
             "(*)"
         } else if vt.is_scalar() {
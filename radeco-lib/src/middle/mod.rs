@@ -15,6 +15,8 @@ pub mod ssa {
     pub mod ssa_traits;
     pub mod error;
     pub mod memoryssa;
+    pub mod ssa_builder;
+    pub mod ssadiff;
     pub mod ssadot;
     pub mod ssastorage;
     pub mod utils;
@@ -22,6 +24,7 @@ pub mod ssa {
     pub mod verifier;
 }
 
+pub mod cfg_cleanup;
 #[macro_use]
 pub mod dot;
 pub mod ir;
@@ -14,7 +14,7 @@ use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::u64;
 
 use crate::middle::ir::{self, MAddress, MOpcode};
-use crate::middle::ssa::graph_traits::{ConditionInfo, EdgeInfo, Graph};
+use crate::middle::ssa::graph_traits::{EdgeInfo, Graph};
 use crate::middle::ssa::ssa_traits::{SSAExtra, SSAMod, ValueInfo};
 use crate::r2api::structs::LOpInfo;
 
@@ -45,7 +45,7 @@ where
     regfile: &'a SubRegisterFile,
     sealed_blocks: HashSet<T::ActionRef>,
     ssa: &'a mut T,
-    unexplored_addr: u64,
+    unexplored_count: u64,
 }
 
 impl<'a, T> PhiPlacer<'a, T>
@@ -70,11 +70,16 @@ where
             regfile: regfile,
             sealed_blocks: HashSet::new(),
             ssa: ssa,
-            unexplored_addr: u64::max_value() - 1,
+            unexplored_count: 0,
             variable_types: Vec::new(),
         }
     }
 
+    /// The SSA being constructed.
+    pub fn ssa(&self) -> &T {
+        self.ssa
+    }
+
     /// Add a new variable that the phiplacer should know of.
     /// This information is required to place phi-s. Note that the
     /// phis are generated only for variables defined in this list.
@@ -154,6 +159,22 @@ where
         v
     }
 
+    // Returns the definition of `variable` read at `address` like `read_variable` would, following
+    // the single predecessor of sealed blocks, or `None` where it would have to add a phi.
+    fn variable_def(&self, variable: VarId, address: MAddress) -> Option<T::ValueRef> {
+        if let Some(value) = self.current_def_in_block(variable, address) {
+            return Some(*value);
+        }
+        let block = self.block_of(address)?;
+        if !self.sealed_blocks.contains(&block) {
+            return None;
+        }
+        match self.ssa.preds_of(block).as_slice() {
+            [pred] => self.variable_def(variable, self.addr_of(pred)),
+            _ => None,
+        }
+    }
+
     fn read_variable_recursive(&mut self, variable: VarId, address: &mut MAddress) -> T::ValueRef {
         radeco_trace!("Entering read_variable_recursive, variable: {:?}", variable);
         let block = self.block_of(*address).unwrap_or_else(|| {
@@ -232,9 +253,10 @@ where
     ) -> T::ActionRef {
         let seen = current_addr.map_or(false, |cur| {
             match cur.cmp(&at) {
-                Ordering::Less | Ordering::Equal => false,
-                // Check if `at` is before the first block.
-                Ordering::Greater => self.block_of(at).is_some(),
+                Ordering::Less => false,
+                // Check if `at` is before the first block. A jump to itself, e.g. `jmp $`, splits
+                // the block it is in as well.
+                Ordering::Equal | Ordering::Greater => self.block_of(at).is_some(),
             }
         });
 
@@ -281,53 +303,32 @@ where
             // phi) and
             //   this is used to provide the def for this use.
 
-            // Copy all the outgoing CF edges.
-            let invalid_edge = self
-                .ssa
-                .invalid_edge()
-                .expect("Invalid Edge is not defined");
-            let conditional_branches =
-                if let Some(branches) = self.ssa.conditional_edges(upper_block) {
-                    branches
-                } else {
-                    ConditionInfo::new(invalid_edge, invalid_edge)
-                };
-            let outgoing = [
-                conditional_branches.false_side,
-                conditional_branches.true_side,
+            // Move all the outgoing CF edges, including the side of a conditional whose other
+            // side is not added yet.
+            for (edge, i) in self.ssa.outgoing_edges(upper_block) {
+                let target = self
+                    .ssa
+                    .edge_info(edge)
+                    .unwrap_or_else(|| {
+                        radeco_err!("Less-endpoints edge");
+                        EdgeInfo::new(
+                            self.ssa.invalid_action().unwrap(),
+                            self.ssa.invalid_action().unwrap(),
+                        )
+                    })
+                    .target;
+                // An edge to `at` itself, e.g. from `jmp $`, becomes a self loop of the lower
+                // block.
+                radeco_trace!(
+                    "ADD BLOCK: phip_add_edge|{:?} --{}--> {:?}",
+                    lower_block,
+                    i,
+                    target
+                );
+                self.ssa.insert_control_edge(lower_block, target, i);
+                self.ssa.remove_control_edge(edge);
                 self.ssa
-                    .unconditional_edge(upper_block)
-                    .unwrap_or(invalid_edge),
-            ];
-            for (i, edge) in outgoing.iter().enumerate() {
-                if *edge
-                    != self
-                        .ssa
-                        .invalid_edge()
-                        .expect("Invalid Edge is not defined")
-                {
-                    let target = self
-                        .ssa
-                        .edge_info(*edge)
-                        .unwrap_or_else(|| {
-                            radeco_err!("Less-endpoints edge");
-                            EdgeInfo::new(
-                                self.ssa.invalid_action().unwrap(),
-                                self.ssa.invalid_action().unwrap(),
-                            )
-                        })
-                        .target;
-                    if lower_block != target {
-                        radeco_trace!(
-                            "ADD BLOCK: phip_add_edge|{:?} --{}--> {:?}",
-                            lower_block,
-                            i,
-                            target
-                        );
-                        self.ssa.insert_control_edge(lower_block, target, i as u8);
-                        self.ssa.remove_control_edge(*edge);
-                    }
-                }
+                    .redirect_phi_sources(target, upper_block, lower_block);
             }
 
             radeco_trace!(
@@ -346,9 +347,11 @@ where
                 if addr < at {
                     continue;
                 }
+                // The map is not ordered, so the nodes of the other blocks are skipped rather
+                // than ending the loop.
                 if let Some(block) = self.block_of(addr) {
                     if block != lower_block {
-                        break;
+                        continue;
                     }
                 }
                 // Now, this node index belongs to the lower part of the split block.
@@ -412,29 +415,53 @@ where
         current_addr: &mut MAddress,
         edge_type: u8,
     ) {
-        let source_block = self.block_of(*current_addr).unwrap_or_else(|| {
+        self.add_unexplored_succ(*current_addr, edge_type);
+
+        // Add a dummy ITE to mark the selector.
+        let op_node = self.add_op(
+            &MOpcode::OpITE,
+            current_addr,
+            ValueInfo::new_scalar(ir::WidthSpec::Known(1)),
+        );
+
+        self.op_use(&op_node, 0, selector);
+    }
+
+    /// Same as `add_indirect_cf`, but records the jump as an `OpIndirectJump` of `target` in the
+    /// current block rather than as its selector.
+    pub fn add_indirect_jump(
+        &mut self,
+        target: &T::ValueRef,
+        current_addr: &mut MAddress,
+        edge_type: u8,
+    ) {
+        self.add_unexplored_succ(*current_addr, edge_type);
+
+        let op_node = self.add_op(
+            &MOpcode::OpIndirectJump,
+            current_addr,
+            ValueInfo::new_scalar(ir::WidthSpec::Known(0)),
+        );
+        self.op_use(&op_node, 0, target);
+    }
+
+    // Adds an edge from the block at `current_addr` to a new block standing for the code which
+    // has not been explored yet.
+    fn add_unexplored_succ(&mut self, current_addr: MAddress, edge_type: u8) {
+        let source_block = self.block_of(current_addr).unwrap_or_else(|| {
             radeco_err!("Block not found @ {:?}", current_addr);
             self.ssa.invalid_action().unwrap()
         });
-        let unexplored_addr = MAddress::new(self.unexplored_addr, 0);
+        let unexplored_addr = MAddress::unexplored(self.unexplored_count);
 
-        // Decrement the unexplored addr so that we have a new unexplored block for next use
-        self.unexplored_addr = self.unexplored_addr - 1;
+        // Count the unexplored block so that we have a new unexplored block for next use
+        self.unexplored_count += 1;
 
         let unexplored_block = self.new_block(unexplored_addr);
 
         self.blocks.insert(unexplored_addr, unexplored_block);
         self.ssa
             .insert_control_edge(source_block, unexplored_block, edge_type);
-
-        // Add a dummy ITE to mark the selector.
-        let op_node = self.add_op(
-            &MOpcode::OpITE,
-            current_addr,
-            ValueInfo::new_scalar(ir::WidthSpec::Known(1)),
-        );
-
-        self.op_use(&op_node, 0, selector);
     }
 
     pub fn add_return(&mut self, current_addr: MAddress, edge_type: u8) {
@@ -486,12 +513,35 @@ where
         }
     }
 
+    // Checks if the block at `addr` jumps to itself unconditionally, and nowhere else.
+    pub fn is_self_loop(&self, addr: MAddress) -> bool {
+        self.block_of(addr).map_or(false, |block| {
+            self.ssa.labeled_succs(block) == vec![(block, UNCOND_EDGE)]
+        })
+    }
+
+    // Adds an unconditional edge to the exit node if there are no edges for the current block.
+    pub fn maybe_add_return(&mut self, current_addr: MAddress) {
+        let source_block = self.block_of(current_addr).unwrap_or_else(|| {
+            radeco_err!("Block not found @ {:?}", current_addr);
+            self.ssa.invalid_action().unwrap()
+        });
+        if self.ssa.outgoing_edges(source_block).is_empty() {
+            let exit_node = exit_node_err!(self.ssa);
+            self.ssa
+                .insert_control_edge(source_block, exit_node, UNCOND_EDGE);
+        }
+    }
+
     pub fn seal_block(&mut self, block: T::ActionRef) {
         let block_addr = self.addr_of(&block);
-        let keys = self.incomplete_phis[&block_addr]
+        // Complete the phis in the order of their variables, the phis created on the way then
+        // come in the same order from one run to the next.
+        let mut keys = self.incomplete_phis[&block_addr]
             .keys()
             .cloned()
             .collect::<Vec<_>>();
+        keys.sort();
         let invalid_value = self
             .ssa
             .invalid_value()
@@ -538,7 +588,7 @@ where
             radeco_trace!("phip_add_phi_operands|cur:{}|pred:{}", _baddr, p_addr);
             let datasource = self.read_variable(&mut p_addr, variable);
             radeco_trace!("datasource: {:?}", datasource);
-            self.ssa.phi_use(phi, datasource);
+            self.ssa.phi_use_from(phi, pred, datasource);
             radeco_trace!("done with phi_use, phi: {:?}, ds: {:?}", phi, datasource);
             if self.ssa.registers(phi).is_empty() {
                 self.propagate_reginfo(&phi);
@@ -642,12 +692,16 @@ where
         same
     }
 
+    // Adds the exit node, which is the single exit of the function. It is left unsealed until
+    // `finish`, so that the register state read here is made of phis which get one operand per
+    // return path once all the blocks are linked to it (see `gather_exits`). The phis merging a
+    // single value are removed when the exit is sealed.
     pub fn add_dynamic(&mut self) -> T::ActionRef {
-        let action = self.ssa.insert_dynamic().unwrap_or_else(|| {
-            radeco_err!("Cannot insert new actions");
+        let action = self.ssa.insert_dynamic().unwrap_or_else(|_e| {
+            radeco_err!("Cannot insert new actions: {}", _e);
             self.ssa.invalid_action().unwrap()
         });
-        let dyn_addr = MAddress::new(u64::MAX, 0);
+        let dyn_addr = MAddress::exit();
         self.blocks.insert(dyn_addr, action);
         self.incomplete_phis.insert(dyn_addr, HashMap::new());
         self.sync_register_state(action);
@@ -680,12 +734,12 @@ where
     // instruction has to go into.
 
     fn add_phi(&mut self, address: &mut MAddress, vt: ValueInfo) -> T::ValueRef {
-        let i = self.ssa.insert_phi(vt).unwrap_or_else(|| {
-            ("Cannot insert new phi nodes");
+        let i = self.ssa.insert_phi(vt).unwrap_or_else(|_e| {
+            radeco_err!("Cannot insert new phi nodes: {}", _e);
             self.ssa.invalid_value().unwrap()
         });
         self.index_to_addr.insert(i, *address);
-        address.offset += 1;
+        address.inc_offset();
         i
     }
 
@@ -699,8 +753,8 @@ where
         vt_option: Option<ValueInfo>,
     ) -> T::ValueRef {
         if vt_option.is_none() {
-            return self.ssa.insert_const(value, None).unwrap_or_else(|| {
-                radeco_err!("Cannot insert new constants");
+            return self.ssa.insert_const(value, None).unwrap_or_else(|_e| {
+                radeco_err!("Cannot insert new constants: {}", _e);
                 self.ssa.invalid_value().unwrap()
             });
         }
@@ -708,10 +762,13 @@ where
         let width = vt.width().get_width().unwrap_or(64);
         if width < 64 {
             let val: u64 = value & (1 << (width) - 1);
-            let const_node = self.ssa.insert_const(val, Some(width)).unwrap_or_else(|| {
-                radeco_err!("Cannot insert new constants");
-                self.ssa.invalid_value().unwrap()
-            });
+            let const_node = self
+                .ssa
+                .insert_const(val, Some(width))
+                .unwrap_or_else(|_e| {
+                    radeco_err!("Cannot insert new constants: {}", _e);
+                    self.ssa.invalid_value().unwrap()
+                });
             let opcode = MOpcode::OpNarrow(width as u16);
             let narrow_node = self.add_op(&opcode, address, vt);
             self.op_use(&narrow_node, 0, &const_node);
@@ -720,8 +777,8 @@ where
             let const_node = self
                 .ssa
                 .insert_const(value, Some(width))
-                .unwrap_or_else(|| {
-                    radeco_err!("Cannot insert new constants");
+                .unwrap_or_else(|_e| {
+                    radeco_err!("Cannot insert new constants: {}", _e);
                     self.ssa.invalid_value().unwrap()
                 });
             const_node
@@ -729,8 +786,8 @@ where
     }
 
     pub fn add_undefined(&mut self, address: MAddress, vt: ValueInfo) -> T::ValueRef {
-        let i = self.ssa.insert_undefined(vt).unwrap_or_else(|| {
-            radeco_err!("Cannot insert new undefined nodes");
+        let i = self.ssa.insert_undefined(vt).unwrap_or_else(|_e| {
+            radeco_err!("Cannot insert new undefined nodes: {}", _e);
             self.ssa.invalid_value().unwrap()
         });
         self.index_to_addr.insert(i, address);
@@ -738,10 +795,13 @@ where
     }
 
     pub fn add_comment(&mut self, address: MAddress, vt: ValueInfo, msg: String) -> T::ValueRef {
-        let i = self.ssa.insert_comment(vt, msg.clone()).unwrap_or_else(|| {
-            radeco_err!("Cannot insert new comments");
-            self.ssa.invalid_value().unwrap()
-        });
+        let i = self
+            .ssa
+            .insert_comment(vt, msg.clone())
+            .unwrap_or_else(|_e| {
+                radeco_err!("Cannot insert new comments: {}", _e);
+                self.ssa.invalid_value().unwrap()
+            });
 
         // Add register information into comment;
         for id in 0..self.regfile.whole_names.len() {
@@ -765,12 +825,15 @@ where
     // Something like the previous verified_add_op.
 
     pub fn add_op(&mut self, op: &MOpcode, address: &mut MAddress, vt: ValueInfo) -> T::ValueRef {
-        let i = self.ssa.insert_op(op.clone(), vt, None).unwrap_or_else(|| {
-            radeco_err!("Cannot insert new values");
-            self.ssa.invalid_value().unwrap()
-        });
+        let i = self
+            .ssa
+            .insert_op(op.clone(), vt, None)
+            .unwrap_or_else(|_e| {
+                radeco_err!("Cannot insert new values: {}", _e);
+                self.ssa.invalid_value().unwrap()
+            });
         self.index_to_addr.insert(i, *address);
-        address.offset += 1;
+        address.inc_offset();
         i
     }
 
@@ -778,6 +841,18 @@ where
         self.index_to_addr.insert(*node, address);
     }
 
+    /// Returns the value of the whole register `var` at `address`, if it is already defined.
+    /// Unlike `read_register`, this never adds a node to the SSA.
+    pub fn register_def(&self, address: MAddress, var: &str) -> Option<T::ValueRef> {
+        let info = self.regfile.get_subregister(var)?;
+        let value = self.variable_def(info.base, address)?;
+        if info.shift == 0 && info.width == u64::from(self.operand_width(&value)) {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
     pub fn read_register(&mut self, address: &mut MAddress, var: &str) -> T::ValueRef {
         radeco_trace!("phip_read_reg|{}", var);
 
@@ -822,6 +897,23 @@ where
         value
     }
 
+    /// Same as `write_register`, but `value` itself becomes the value of the register rather than
+    /// a copy of it, when it is as wide as the whole register.
+    pub fn bind_register(&mut self, address: &mut MAddress, var: &str, value: T::ValueRef) {
+        let info = match self.regfile.get_subregister(var) {
+            Some(reg) => reg,
+            None => return self.write_register(address, var, value),
+        };
+        let id = info.base;
+        let width = self.variable_types[id as usize].width().get_width();
+        if width != Some(info.width as u16) || width != Some(self.operand_width(&value)) {
+            return self.write_register(address, var, value);
+        }
+        radeco_trace!("phip_bind_reg|{}<-{:?}", var, value);
+        self.write_variable(*address, id, value);
+        self.ssa.set_register(value, var.to_owned());
+    }
+
     pub fn write_register(&mut self, address: &mut MAddress, var: &str, mut value: T::ValueRef) {
         radeco_trace!("phip_write_reg|{}<-{:?}", var, value);
 
@@ -918,6 +1010,46 @@ where
         self.ssa.op_use(*op, index, *arg)
     }
 
+    /// Returns `true` if `value` is loaded from the address `addr`.
+    pub fn is_load_from(&self, value: &T::ValueRef, addr: &T::ValueRef) -> bool {
+        self.ssa.opcode(*value) == Some(MOpcode::OpLoad)
+            && self.ssa.operands_of(*value).get(1) == Some(addr)
+    }
+
+    /// Records that `value` refers to each of the addresses in `xrefs`.
+    pub fn add_xrefs(&mut self, value: &T::ValueRef, xrefs: &[u64]) {
+        for &addr in xrefs {
+            self.ssa.add_xref(value, addr);
+        }
+    }
+
+    /// Records on every expression the addresses referred to by its instruction, according to
+    /// `xrefs`, which it uses as a constant operand.
+    pub fn add_constant_xrefs(&mut self, xrefs: &HashMap<u64, Vec<u64>>) {
+        for value in self.ssa.values() {
+            if !self.ssa.is_expr(value) {
+                continue;
+            }
+            let refs = match self.ssa.address(value).and_then(|a| xrefs.get(&a.address)) {
+                Some(refs) => refs,
+                None => continue,
+            };
+            let recorded = self.ssa.xrefs_of(&value);
+            let used = self
+                .ssa
+                .operands_of(value)
+                .into_iter()
+                .filter_map(|op| self.ssa.constant_value(op))
+                .filter(|c| refs.contains(c) && !recorded.contains(c))
+                .collect::<Vec<_>>();
+            self.add_xrefs(&value, &used);
+        }
+    }
+
+    pub fn set_segment(&mut self, value: &T::ValueRef, segment: String) {
+        self.ssa.set_segment(value, segment);
+    }
+
     pub fn operand_width(&self, node: &T::ValueRef) -> u16 {
         match self.ssa.node_data(*node) {
             Ok(x) => x.vt.width().get_width().unwrap_or(64),
@@ -928,12 +1060,34 @@ where
         }
     }
 
+    pub fn operand_is_float(&self, node: &T::ValueRef) -> bool {
+        self.ssa
+            .node_data(*node)
+            .map(|x| x.vt.is_float())
+            .unwrap_or(false)
+    }
+
+    pub fn operand_is_memory(&self, node: &T::ValueRef) -> bool {
+        self.ssa
+            .node_data(*node)
+            .map(|x| x.vt.is_memory())
+            .unwrap_or(false)
+    }
+
+    pub fn operand_opcode(&self, node: &T::ValueRef) -> Option<MOpcode> {
+        self.ssa.opcode(*node)
+    }
+
+    pub fn operands_of(&self, node: &T::ValueRef) -> Vec<T::ValueRef> {
+        self.ssa.operands_of(*node)
+    }
+
     fn new_block(&mut self, bb: MAddress) -> T::ActionRef {
         if let Some(b) = self.blocks.get(&bb) {
             *b
         } else {
-            let block = self.ssa.insert_block(bb).unwrap_or_else(|| {
-                radeco_err!("Cannot insert new blocks");
+            let block = self.ssa.insert_block(bb).unwrap_or_else(|_e| {
+                radeco_err!("Cannot insert new blocks: {}", _e);
                 self.ssa.invalid_action().unwrap()
             });
             self.incomplete_phis.insert(bb, HashMap::new());
@@ -941,41 +1095,52 @@ where
         }
     }
 
-    // For constant, we should narrow it rather widening another OpCode
-    pub fn narrow_const_operand(
+    // A constant operand takes the width of the other operand, rather than being cast to it. The
+    // value is truncated to a narrower width, as a narrowing would do. It is only widened if its
+    // value is the same whether it is zero or sign extended.
+    pub fn fit_const_operand(
         &mut self,
-        address: &mut MAddress,
         lhs: &mut Option<T::ValueRef>,
         rhs: &mut Option<T::ValueRef>,
     ) {
-        if lhs.is_none() || rhs.is_none() {
+        let (lhs_node, rhs_node) = match (*lhs, *rhs) {
+            (Some(lhs_node), Some(rhs_node)) => (lhs_node, rhs_node),
+            _ => return,
+        };
+        let (victim, value, width) =
+            match (self.ssa.constant(lhs_node), self.ssa.constant(rhs_node)) {
+                (Some(value), None) => (lhs, value, self.operand_width(&rhs_node)),
+                (None, Some(value)) => (rhs, value, self.operand_width(&lhs_node)),
+                _ => return,
+            };
+        let const_node = victim.expect("Constant operand cannot be `None`");
+        let const_width = self.operand_width(&const_node);
+        // A memory state has no width for the constant to take, nor has a constant without one.
+        if width == 0 || const_width == 0 {
+            return;
+        }
+        let sign_bit = 1 << (const_width.min(64) - 1);
+        if width == const_width || (width > const_width && value & sign_bit != 0) {
             return;
         }
 
-        let lhs_size = lhs.map_or(0, |i| self.operand_width(&i));
-        let rhs_size = rhs.map_or(0, |i| self.operand_width(&i));
-
-        // Narrowing will happen only if there is one constant and one normal opcode.
-        if lhs.map_or(false, |i| self.ssa.constant(i).is_some())
-            ^ rhs.map_or(false, |i| self.ssa.constant(i).is_some())
-        {
-            let (victim, victim_size) = if lhs_size < 64 {
-                (rhs, lhs_size)
-            } else {
-                (lhs, rhs_size)
-            };
-            if victim_size < 64 {
-                let victim_node = victim.unwrap_or_else(|| {
-                    radeco_err!("Wrong victim");
-                    self.ssa.invalid_value().unwrap()
-                });
-                let vtype = ValueInfo::new_unresolved(ir::WidthSpec::from(victim_size));
-                let opc = MOpcode::OpNarrow(victim_size as u16);
-                let node = self.add_op(&opc, address, vtype);
-                self.op_use(&node, 0, &victim_node);
-                *victim = Some(node);
-            }
+        let mask = if width >= 64 {
+            u64::MAX
+        } else {
+            (1 << width) - 1
+        };
+        let sized = self
+            .ssa
+            .insert_const(value & mask, Some(width))
+            .unwrap_or_else(|_e| {
+                radeco_err!("Cannot insert new constants: {}", _e);
+                self.ssa.invalid_value().unwrap()
+            });
+        // Constants are made for each operand, this one is not needed anymore.
+        if self.ssa.uses_of(const_node).is_empty() {
+            self.ssa.remove_value(const_node);
         }
+        *victim = Some(sized);
     }
 
     // Determine which block address should belong to.
@@ -1046,7 +1211,7 @@ where
     }
 
     // Visit all the blocks, find the exits of this function, and link these basic
-    // with exit_node
+    // with exit_node, so that every return path is merged by its register state.
     pub fn gather_exits(&mut self) {
         let blocks = self.ssa.blocks();
         let exit_node = exit_node_err!(self.ssa);
@@ -1057,6 +1222,38 @@ where
         }
     }
 
+    // Removes the blocks starting at `roots` which are not reachable from the entry node, along
+    // with the unreachable blocks connected to them, e.g. the other side of a conditional which
+    // jumps into them. The unreachable blocks may form loops.
+    pub fn prune_unreachable(&mut self, roots: &[MAddress]) {
+        let entry = entry_node_err!(self.ssa);
+        let exit = exit_node_err!(self.ssa);
+        let mut reachable = HashSet::new();
+        let mut stack = vec![entry];
+        while let Some(block) = stack.pop() {
+            if reachable.insert(block) {
+                stack.extend(self.ssa.succs_of(block));
+            }
+        }
+        let mut wl = roots
+            .iter()
+            .filter_map(|&addr| self.blocks.get(&addr).cloned())
+            .collect::<VecDeque<_>>();
+        while let Some(block) = wl.pop_front() {
+            if reachable.contains(&block)
+                || block == exit
+                || !self.blocks.values().any(|&b| b == block)
+            {
+                continue;
+            }
+            radeco_trace!("phip_prune_unreachable|{:?}", block);
+            wl.extend(self.ssa.succs_of(block).into_iter().filter(|&s| s != block));
+            wl.extend(self.ssa.preds_of(block).into_iter().filter(|&p| p != block));
+            self.blocks.retain(|_, b| *b != block);
+            self.ssa.remove_block(block);
+        }
+    }
+
     // Performs SSA finish operation such as assigning the blocks in the final
     // graph, sealing blocks, running basic dead code elimination etc.
     pub fn finish(&mut self, ops: &[LOpInfo]) {
@@ -24,7 +24,10 @@ use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::convert::From;
 
-#[derive(Clone, Copy, Debug, Default)]
+/// Widest whole register, in bits, a register partially overlapping another can be merged in.
+const MAX_WHOLE_WIDTH: u64 = 128;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct SubRegister {
     pub base: u64,
     pub shift: u64,
@@ -47,7 +50,7 @@ impl SubRegister {
 ///
 /// It can then translate accesses to partial registers to accesses of whole registers.
 /// Shifts and masks are added automatically.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct SubRegisterFile {
     /// `ValueType`s of whole registers ready to be added to a `PhiPlacer`.
     /// The index within `PhiPlacer` to the first register is needed
@@ -88,22 +91,46 @@ impl SubRegisterFile {
             aliases.insert(reg.role_str.clone(), reg.reg.clone());
         }
 
+        // Offsets of the "fpu" bank overlap with the other banks (e.g. st7 with zf), so move
+        // the whole bank past the end of every other register.
+        let fpu_base = reg_info
+            .reg_info
+            .iter()
+            .filter(|reg| reg.type_str != "fpu")
+            .map(|reg| reg.offset as u64 + reg.size as u64)
+            .max()
+            .unwrap_or(0);
+
+        // Registers packing several flags (e.g. `eflags` or `cpsr`) are left out, so that every
+        // flag which is also described on its own becomes a 1-bit whole register.
+        let flag_words = reg_info
+            .reg_info
+            .iter()
+            .map(|reg| {
+                reg.size > 1
+                    && reg_info.reg_info.iter().any(|flag| {
+                        flag.size == 1
+                            && flag.type_str == reg.type_str
+                            && flag.offset >= reg.offset
+                            && flag.offset < reg.offset + reg.size
+                    })
+            })
+            .collect::<Vec<_>>();
+
         let mut slices = HashMap::new();
         let mut events: Vec<SubRegister> = Vec::new();
         let mut types: HashMap<String, String> = HashMap::new();
         for (i, reg) in reg_info.reg_info.iter().enumerate() {
             types.insert(reg.name.clone(), reg.type_str.clone());
-            if reg.type_str == "fpu" {
+            if flag_words[i] {
                 continue;
-            } // st7 from "fpu" overlaps with zf from "gpr" (r2 bug?)
-            if reg.name.ends_with("flags") {
-                continue;
-            } // HARDCODED x86
-            events.push(SubRegister::new(
-                i as u64,
-                reg.offset as u64,
-                reg.size as u64,
-            ));
+            }
+            let offset = if reg.type_str == "fpu" {
+                fpu_base + reg.offset as u64
+            } else {
+                reg.offset as u64
+            };
+            events.push(SubRegister::new(i as u64, offset, reg.size as u64));
         }
 
         events.sort_by(|a, b| {
@@ -121,18 +148,46 @@ impl SubRegisterFile {
         for &ev in &events {
             let name = &reg_info.reg_info[ev.base as usize].name;
             let cur_until = current.shift + current.width;
-            if ev.shift >= cur_until {
+            let ev_until = ev.width + ev.shift;
+            let mut new_whole = ev.shift >= cur_until;
+            if !new_whole && ev_until > cur_until {
+                // `ev` partially overlaps the current whole register without being contained in
+                // it. The whole register is widened to cover both, so that a write to either of
+                // them is seen by the reads of the other.
+                let width = (ev_until - current.shift).next_power_of_two();
+                if width <= MAX_WHOLE_WIDTH {
+                    radeco_warn!(
+                        "{} partially overlaps {}, widening it to {} bits",
+                        name,
+                        names.last().map_or("", |n| n.as_str()),
+                        width
+                    );
+                    current.width = width;
+                    if let Some(vi) = whole.last_mut() {
+                        *vi = ValueInfo::new(*vi.value_type(), ir::WidthSpec::from(width as u16));
+                    }
+                } else {
+                    radeco_warn!(
+                        "{} partially overlaps {}, making it a separate whole register",
+                        name,
+                        names.last().map_or("", |n| n.as_str())
+                    );
+                    new_whole = true;
+                }
+            }
+
+            if new_whole {
                 current = ev;
 
                 radeco_trace!("regfile_mappings|{} -> {}", whole.len(), &name);
 
-                whole.push(ValueInfo::new_unresolved(ir::WidthSpec::from(
-                    current.width as u16,
-                )));
+                let width = ir::WidthSpec::from(current.width as u16);
+                if is_float_register(name, &reg_info.reg_info[ev.base as usize].type_str) {
+                    whole.push(ValueInfo::new_float(width));
+                } else {
+                    whole.push(ValueInfo::new_unresolved(width));
+                }
                 names.push(name.clone());
-            } else {
-                let ev_until = ev.width + ev.shift;
-                assert!(ev_until <= cur_until);
             }
 
             let subreg =
@@ -150,6 +205,12 @@ impl SubRegisterFile {
         }
     }
 
+    /// Width in bits of the program counter, which is taken as the word size of the platform.
+    pub fn pc_width(&self) -> Option<u64> {
+        let pc = self.alias_info.get("PC")?;
+        self.get_subregister(pc).map(|r| r.width)
+    }
+
     // API for Sub Reigster.
     pub fn get_subregister(&self, name: &str) -> Option<SubRegister> {
         self.named_registers.get(name).cloned()
@@ -178,16 +239,18 @@ impl SubRegisterFile {
         self.whole_names.get(id.to_usize()).map(|s| &**s)
     }
 
+    /// Width in bits of the whole register `id`, which may be wider than the register it is named
+    /// after if other registers partially overlap it.
     pub fn get_width(&self, id: RegisterId) -> Option<u64> {
-        if let Some(name) = self.get_name(id) {
-            if let Some(subreg) = self.named_registers.get(name) {
-                Some(subreg.width)
-            } else {
-                None
-            }
-        } else {
-            None
-        }
+        self.whole_registers
+            .get(id.to_usize())
+            .and_then(|vi| vi.width().get_width())
+            .map(u64::from)
+    }
+
+    /// Returns `true` if the register `name` is a segment register, such as `fs` on x86.
+    pub fn is_segment(&self, name: &str) -> bool {
+        self.type_info.get(name).map_or(false, |t| t == "seg")
     }
 
     // Get information by other way.
@@ -287,6 +350,13 @@ impl RegisterId {
     }
 }
 
+// HARDCODED x86: only the FPU stack and the SSE registers of the "fpu" bank hold floating point
+// values, the rest are control and status words.
+fn is_float_register(name: &str, type_str: &str) -> bool {
+    type_str == "fpu"
+        && (name.starts_with("st") || name.starts_with("xmm") || name.starts_with("ymm"))
+}
+
 // TODO: if r2 ever starts keeping track of preserved registers, use that instead of this
 /// For a given named calling convention, return the set of registers it
 /// preserves across calls (are callee-saved).
@@ -319,3 +389,49 @@ fn callconv_name_to_preserved_list(cc_name: &str) -> &'static [&'static str] {
         _ => &[],
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json;
+
+    // `a1` partially overlaps `a0`, which is widened to contain it, while `wx` overlaps `w` by too
+    // much to be merged with it.
+    const OVERLAPPING_PROFILE: &str = r#"{
+        "alias_info": [{"role": 0, "role_str": "PC", "reg": "pc"}],
+        "reg_info": [
+            {"type": 0, "type_str": "gpr", "name": "pc", "size": 32, "offset": 0},
+            {"type": 0, "type_str": "gpr", "name": "a0", "size": 32, "offset": 32},
+            {"type": 0, "type_str": "gpr", "name": "a0h", "size": 16, "offset": 48},
+            {"type": 0, "type_str": "gpr", "name": "a1", "size": 32, "offset": 48},
+            {"type": 0, "type_str": "gpr", "name": "b", "size": 32, "offset": 128},
+            {"type": 0, "type_str": "gpr", "name": "w", "size": 128, "offset": 256},
+            {"type": 0, "type_str": "gpr", "name": "wx", "size": 128, "offset": 320}
+        ]
+    }"#;
+
+    #[test]
+    fn partially_overlapping_registers() {
+        let reg_info: LRegInfo = serde_json::from_str(OVERLAPPING_PROFILE).unwrap();
+        let regfile = SubRegisterFile::new(&reg_info);
+        assert_eq!(regfile.whole_names, vec!["pc", "a0", "b", "w", "wx"]);
+
+        let a0 = regfile.register_id_by_name("a0").unwrap();
+        assert_eq!(regfile.get_width(a0), Some(64));
+        for &(name, shift, width) in &[("a0", 0, 32), ("a0h", 16, 16), ("a1", 16, 32)] {
+            let subreg = regfile.get_subregister(name).unwrap();
+            assert_eq!(subreg.base, a0.to_u8() as u64, "{}", name);
+            assert_eq!((subreg.shift, subreg.width), (shift, width), "{}", name);
+        }
+
+        let (w, wx) = (
+            regfile.register_id_by_name("w").unwrap(),
+            regfile.register_id_by_name("wx").unwrap(),
+        );
+        assert_ne!(w, wx);
+        assert_eq!(regfile.get_width(w), Some(128));
+        assert_eq!(regfile.get_width(wx), Some(128));
+        assert_eq!(regfile.get_subregister("wx").unwrap().shift, 0);
+        assert_eq!(regfile.pc_width(), Some(32));
+    }
+}
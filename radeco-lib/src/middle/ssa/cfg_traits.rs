@@ -41,6 +41,7 @@ use std::fmt::Debug;
 use std::hash::Hash;
 
 use super::graph_traits::{ConditionInfo, Graph};
+use super::ssa_traits::SsaError;
 use crate::middle::ir::MAddress;
 
 /// Provides __accessors__ to the underlying storage
@@ -60,19 +61,52 @@ pub trait CFG: Graph {
     /// Reference to entry block of the CFG
     fn entry_node(&self) -> Option<Self::ActionRef>;
 
-    /// Reference to exit block of the CFG
+    /// Reference to exit block of the CFG.
+    ///
+    /// The exit is the only dynamic action of the CFG: it has no successors and, unlike the
+    /// blocks, no address (its `starting_address` is the synthetic `MAddress::exit()`). Analyses
+    /// should identify it by comparing with this node rather than with its address.
     fn exit_node(&self) -> Option<Self::ActionRef>;
 
     /// Reference to immediate predecessors of block
     fn preds_of(&self, node: Self::ActionRef) -> Vec<Self::ActionRef>;
 
-    /// Reference to immediate successors of block
+    /// Reference to immediate successors of block, empty for the exit node
     fn succs_of(&self, node: Self::ActionRef) -> Vec<Self::ActionRef>;
 
-    /// Reference to the next block in the natural flow of the CFG
+    /// Immediate successors of block, each with the kind of the edge leading to it: 0 for the
+    /// false branch, 1 for the true branch and 2 for an unconditional edge.
+    fn labeled_succs(&self, node: Self::ActionRef) -> Vec<(Self::ActionRef, u8)> {
+        let mut succs = Vec::new();
+        if let Some(cond) = self.conditional_blocks(node) {
+            succs.push((cond.false_side, 0));
+            succs.push((cond.true_side, 1));
+        }
+        if let Some(next) = self.unconditional_block(node) {
+            succs.push((next, 2));
+        }
+        succs
+    }
+
+    /// Immediate predecessors of block, each with the kind of the edge leading from it, as in
+    /// `labeled_succs`.
+    fn labeled_preds(&self, node: Self::ActionRef) -> Vec<(Self::ActionRef, u8)> {
+        let mut preds = Vec::new();
+        for pred in self.preds_of(node) {
+            for (succ, label) in self.labeled_succs(pred) {
+                if succ == node && !preds.contains(&(pred, label)) {
+                    preds.push((pred, label));
+                }
+            }
+        }
+        preds
+    }
+
+    /// Reference to the next block in the natural flow of the CFG, `None` for the exit node
     fn unconditional_block(&self, i: Self::ActionRef) -> Option<Self::ActionRef>;
 
-    /// Reference to the conditional blocks in the natural flow of the CFG
+    /// Reference to the conditional blocks in the natural flow of the CFG, `None` for the exit
+    /// node
     fn conditional_blocks(&self, i: Self::ActionRef) -> Option<ConditionInfo<Self::ActionRef>>;
 
     /// Reference that represents and Invalid block
@@ -91,7 +125,7 @@ pub trait CFG: Graph {
     /// Reference to all the incoming edges to a block
     fn incoming_edges(&self, i: Self::ActionRef) -> Vec<(Self::CFEdgeRef, u8)>;
 
-    /// Reference to all the outgoing edges from a block
+    /// Reference to all the outgoing edges from a block, empty for the exit node
     fn outgoing_edges(&self, i: Self::ActionRef) -> Vec<(Self::CFEdgeRef, u8)>;
 
     /// Starting address of a basic block or dynamic
@@ -115,12 +149,13 @@ pub trait CFGMod: CFG {
     fn set_exit_node(&mut self, exit: Self::ActionRef);
 
     /// Insert a new basic block
-    fn insert_block(&mut self, info: Self::BBInfo) -> Option<Self::ActionRef>;
+    fn insert_block(&mut self, info: Self::BBInfo) -> Result<Self::ActionRef, SsaError>;
 
     /// Insert a new exit
-    fn insert_dynamic(&mut self) -> Option<Self::ActionRef>;
+    fn insert_dynamic(&mut self) -> Result<Self::ActionRef, SsaError>;
 
-    /// Insert a control edge between to basic blocks
+    /// Insert a control edge between to basic blocks. No edge may leave the exit node, `None` is
+    /// returned if `source` is the exit.
     fn insert_control_edge(
         &mut self,
         source: Self::ActionRef,
@@ -21,10 +21,14 @@ use petgraph::EdgeDirection;
 use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
 
-use r2api::structs::LVarInfo;
+use r2api::structs::{LVarInfo, LVarRef};
 
 use super::ssa_traits::NodeType;
 use super::ssa_traits::{SSAMod, SSAWalk, SSA};
+use super::ssastorage::{SSAStorage, Walker};
+use crate::analysis::analyzer::{AnalyzerInfo, AnalyzerKind};
+use crate::frontend::radeco_containers::{BindingType, RadecoFunction};
+use crate::middle::dot::{self, DotAttrBlock, GraphDot};
 use crate::middle::ir::MOpcode;
 
 // NOTE: Until now, this file is only used to implement raw Memory SSA, in
@@ -64,8 +68,43 @@ pub enum MemOpcode {
     Undefined,
 }
 
+/// Memory a load or store may access, according to its address.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MemoryLocation {
+    /// Address based on the BP/SP registers.
+    Local,
+    /// Address of a global variable.
+    Global,
+    /// Address derived from both local and global addresses, it may alias every variable.
+    Everything,
+    /// Address nothing is known about, e.g. a pointer argument. It may alias every variable but
+    /// the locals, unless the address of a local escapes the function.
+    Unknown,
+}
+
+/// How the locals a stack access may alias are told.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LocalAliasing {
+    /// A stack access may alias every local.
+    AllLocals,
+    /// A stack access aliases the slots it overlaps, from its offset and width, and every local
+    /// if these are not known. An access straddling several slots, e.g. an unaligned one, aliases
+    /// all of them.
+    Overlapped,
+}
+
 // TODO: Now the edge weight in MemorySSA graph is all zero, it may help
 // if the operands are in order.
+const NAME: &str = "memory_ssa";
+const REQUIRES: &[AnalyzerKind] = &[];
+
+pub const INFO: AnalyzerInfo = AnalyzerInfo {
+    name: NAME,
+    kind: AnalyzerKind::MemorySSA,
+    requires: REQUIRES,
+    uses_policy: false,
+};
+
 type VarId = usize;
 type ArgOrd = u16;
 
@@ -85,6 +124,8 @@ where
     pub variables: Vec<VariableType>,
     /// May-alias Set for every LOAD/STORE.
     pub may_aliases: HashMap<T::ValueRef, HashSet<VarId>>,
+    /// Memory accessed by every LOAD/STORE.
+    pub locations: HashMap<T::ValueRef, MemoryLocation>,
     /// Nodes which could be ragared as a local variable address.
     pub local_nodes: HashSet<T::ValueRef>,
     /// Nodes which could be regared as a global variable address.
@@ -92,11 +133,16 @@ where
     // TODO: Above two members(local_nodes & global_nodes) may be combined with
     // each other and expand to indicated every special variable's address separately
     // after VSA finish.
+    /// Whether a local variable address is stored to memory or passed to a call.
+    pub local_escapes: bool,
+    local_aliasing: LocalAliasing,
     /// Different variables' Phi nodes for every basic block.
     pub phi_nodes: HashMap<NodeIndex, VarId>,
     // phi_nodes is used to map every phi node to its variable, which will be useful
     // in future work.
     sealed_blocks: HashSet<T::ActionRef>,
+    // Blocks reachable from the entry, the others are ignored.
+    reachable: HashSet<T::ActionRef>,
     current_def: Vec<HashMap<T::ActionRef, NodeIndex>>,
     incomplete_phis: Vec<HashMap<T::ActionRef, NodeIndex>>,
     // Same usage in phiplacement.rs
@@ -116,9 +162,13 @@ where
             associated_nodes: HashMap::new(),
             variables: Vec::new(),
             may_aliases: HashMap::new(),
+            locations: HashMap::new(),
             local_nodes: HashSet::new(),
             global_nodes: HashSet::new(),
+            local_escapes: false,
+            local_aliasing: LocalAliasing::AllLocals,
             sealed_blocks: HashSet::new(),
+            reachable: HashSet::new(),
             phi_nodes: HashMap::new(),
             current_def: Vec::new(),
             incomplete_phis: Vec::new(),
@@ -126,8 +176,15 @@ where
         }
     }
 
-    /// Run to generate MemorySSA.
+    /// Run to generate MemorySSA. Only the blocks reachable from the entry are considered, and
+    /// the MemorySSA of a function which accesses no memory is left empty.
     pub fn run(&mut self) {
+        self.gather_reachable();
+        if !self.accesses_memory() {
+            radeco_trace!("MemorrySSA|No memory access, nothing to do");
+            return;
+        }
+
         self.gather_may_aliases();
         radeco_trace!("MemorrySSA|May_alias Set: {:?}", self.may_aliases);
         // Gather may_alias sets.
@@ -138,6 +195,125 @@ where
         radeco_trace!("MemorrySSA|generate done!");
     }
 
+    /// Sets how the locals a stack access may alias are told, every local by default. Must be
+    /// called before `run`.
+    pub fn set_local_aliasing(&mut self, aliasing: LocalAliasing) {
+        self.local_aliasing = aliasing;
+    }
+
+    /// Returns a textual dump of the variables and of the Memory SSA nodes. Every node is
+    /// listed with the variables it defines or uses, and the nodes defining them for it, e.g.
+    /// `m4 = VDef @0x001004.0002 {v0, v1} -> m1`.
+    pub fn dump(&self) -> String {
+        let mut result = String::from("variables:\n");
+        for (i, var) in self.variables.iter().enumerate() {
+            result.push_str(&format!("    v{} = {}\n", i, self.variable_label(var)));
+        }
+        result.push_str("nodes:\n");
+        let mut nodes = self.g.node_indices().collect::<Vec<_>>();
+        nodes.sort();
+        for node in nodes {
+            result.push_str(&format!("    {}\n", self.node_label(&node)));
+        }
+        result
+    }
+
+    /// Returns the memory accessed by the load or store `access`.
+    pub fn location_of(&self, access: T::ValueRef) -> Option<MemoryLocation> {
+        self.locations.get(&access).cloned()
+    }
+
+    /// Returns the store which last defines every variable `load` may read, if there is a single
+    /// one. Forwarding its value still requires both to access the same address.
+    pub fn clobbering_store(&self, load: T::ValueRef) -> Option<T::ValueRef> {
+        let vuse = self.mem_node_of(load)?;
+        let defs = self.get_operands(&vuse);
+        let def = *defs.first()?;
+        if defs.iter().all(|&d| d == def) && self.g[def] == MemOpcode::VDef {
+            self.associated_nodes.get(&def).cloned()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the blocks of the stores, phis and initial memory state defining the variables
+    /// `load` may read, or `None` if one of these definitions is unknown.
+    pub fn reaching_blocks(&self, load: T::ValueRef) -> Option<Vec<T::ActionRef>> {
+        let vuse = self.mem_node_of(load)?;
+        self.get_operands(&vuse)
+            .iter()
+            .map(|def| self.associated_blocks.get(def).cloned())
+            .collect()
+    }
+
+    fn mem_node_of(&self, access: T::ValueRef) -> Option<NodeIndex> {
+        self.associated_nodes
+            .iter()
+            .find(|&(_, &node)| node == access)
+            .map(|(&mem_node, _)| mem_node)
+    }
+
+    /// Returns the Memory SSA graph in dot format.
+    pub fn dump_dot(&self) -> String {
+        dot::emit_dot(self)
+    }
+
+    fn variable_label(&self, var: &VariableType) -> String {
+        match *var {
+            VariableType::Local(ref info) => {
+                let name = info.name.as_ref().map_or("?", |n| n.as_str());
+                let reference = info
+                    .reference
+                    .as_ref()
+                    .and_then(|r| r.base.as_ref().map(|b| (b, r.offset.unwrap_or(0))));
+                match reference {
+                    Some((base, offset)) if offset < 0 => {
+                        format!("local {} [{} - {:#x}]", name, base, -offset)
+                    }
+                    Some((base, offset)) => format!("local {} [{} + {:#x}]", name, base, offset),
+                    None => format!("local {}", name),
+                }
+            }
+            VariableType::Global(addr) => format!("global {:#x}", addr),
+            VariableType::Extra(node) => format!("extra {:?}", node),
+        }
+    }
+
+    fn node_label(&self, mem_node: &NodeIndex) -> String {
+        let mut label = format!("m{} = {:?}", mem_node.index(), self.g[*mem_node]);
+        if let Some(&node) = self.associated_nodes.get(mem_node) {
+            match self.ssa.address(node) {
+                Some(addr) => label.push_str(&format!(" @{}", addr)),
+                None => label.push_str(&format!(" {:?}", node)),
+            }
+        }
+
+        let mut vars: Vec<VarId> = match self.g[*mem_node] {
+            MemOpcode::Phi => self.phi_nodes.get(mem_node).into_iter().cloned().collect(),
+            _ => self
+                .associated_nodes
+                .get(mem_node)
+                .and_then(|node| self.may_aliases.get(node))
+                .map_or_else(Vec::new, |set| set.iter().cloned().collect::<Vec<_>>()),
+        };
+        if !vars.is_empty() {
+            vars.sort();
+            let vars = vars.iter().map(|v| format!("v{}", v)).collect::<Vec<_>>();
+            label.push_str(&format!(" {{{}}}", vars.join(", ")));
+        }
+
+        let mut defs = self.get_operands(mem_node);
+        if !defs.is_empty() {
+            defs.sort();
+            let defs = defs
+                .iter()
+                .map(|d| format!("m{}", d.index()))
+                .collect::<Vec<_>>();
+            label.push_str(&format!(" -> {}", defs.join(", ")));
+        }
+        label
+    }
+
     // Gather variables in following rules:
     //      local: from r2api to get local;
     //      global:from r2api to get datafers;
@@ -233,6 +409,35 @@ where
     // OR: It's only used for raw Memory SSA to gather may_alias set, after raw
     // MemorySSA, the later may_alias set analysis should be done by VSA.
 
+    fn gather_reachable(&mut self) {
+        let entry_node = entry_node_err!(self.ssa);
+        let mut stack = vec![entry_node];
+        while let Some(block) = stack.pop() {
+            if self.reachable.insert(block) {
+                stack.extend(self.ssa.succs_of(block));
+            }
+        }
+    }
+
+    // Whether a reachable block loads from or stores to memory.
+    fn accesses_memory(&self) -> bool {
+        self.ssa
+            .inorder_walk()
+            .any(|expr| match self.ssa.opcode(expr) {
+                Some(MOpcode::OpLoad) | Some(MOpcode::OpStore) => true,
+                _ => false,
+            })
+    }
+
+    // Predecessors of `block` the definitions may come from.
+    fn reachable_preds(&self, block: &T::ActionRef) -> Vec<T::ActionRef> {
+        self.ssa
+            .preds_of(*block)
+            .into_iter()
+            .filter(|pred| self.reachable.contains(pred))
+            .collect()
+    }
+
     // Initialize the nodes which could be regard as variables' addresses.
     //      For locals, it should be based on the BP/SP registers;      -> CommentNode
     //      For globals, it should be equal to the globals' addresses;  -> OpConse
@@ -305,48 +510,150 @@ where
         }
     }
 
+    // A local variable address escapes if it is stored to memory or passed to a call, an
+    // unknown address may then point to a local variable.
+    fn check_escape(&mut self, opc: MOpcode, args: &[T::ValueRef]) {
+        let escaping = match opc {
+            MOpcode::OpStore => args.get(2).map_or(false, |v| self.local_nodes.contains(v)),
+            MOpcode::OpCall => args.iter().any(|a| self.local_nodes.contains(a)),
+            _ => false,
+        };
+        if escaping {
+            radeco_trace!("MemorrySSA|Local variable address escapes: {:?}", args);
+            self.local_escapes = true;
+        }
+    }
+
     // This function calculate the may_alias, using a simple rule:
     //      if mem is a local address, then the may_alias set will include all
     // local variables;
     //      if mem is a global address, the the may_alias set will include all
     // global variables;
-    //      Otherwise, it will include all the variables.
+    //      if mem is both, it will include all the variables;
+    //      Otherwise, nothing is known about mem, and it will include all the
+    // variables but the locals, as long as no local address escapes.
     //
     // NOTE: This function is only used in raw MemorySSA, and may be replaces by VSA.
     fn calculate_may_alias(&mut self, idx: &T::ValueRef, mem: &T::ValueRef) {
-        let involve_local = self.local_nodes.contains(mem);
-        let involve_global = self.global_nodes.contains(mem);
+        let location = match (
+            self.local_nodes.contains(mem),
+            self.global_nodes.contains(mem),
+        ) {
+            (true, false) => MemoryLocation::Local,
+            (false, true) => MemoryLocation::Global,
+            (true, true) => MemoryLocation::Everything,
+            (false, false) => MemoryLocation::Unknown,
+        };
+        let slots = match (location, self.local_aliasing) {
+            (MemoryLocation::Local, LocalAliasing::Overlapped) => self.overlapped_slots(idx, mem),
+            _ => None,
+        };
+        if slots.as_ref().map_or(false, |s| s.len() > 1) {
+            radeco_trace!("MemorrySSA|{:?} straddles the slots {:?}", idx, slots);
+        }
         let mut may_alias = HashSet::new();
 
-        let involve_all = !(involve_local ^ involve_global);
-        // if involve_local and involve_global is both true or both false,
-        // it should involve_all.
-
         for i in 0..self.variables.len() {
-            match self.variables[i] {
-                VariableType::Extra(_) => {
-                    if involve_all {
-                        may_alias.insert(i);
-                    }
-                }
+            let alias = match (&self.variables[i], location) {
+                (_, MemoryLocation::Everything) => true,
                 // TODO: Add function to check the call statement could reach mem node
                 // or not.
-                VariableType::Local(_) => {
-                    if involve_local || involve_all {
-                        may_alias.insert(i);
-                    }
-                }
-
-                VariableType::Global(_) => {
-                    if involve_global || involve_all {
-                        may_alias.insert(i);
-                    }
+                (&VariableType::Extra(_), MemoryLocation::Unknown) => true,
+                (&VariableType::Local(_), MemoryLocation::Local) => {
+                    slots.as_ref().map_or(true, |s| s.contains(&i))
                 }
+                (&VariableType::Local(_), MemoryLocation::Unknown) => self.local_escapes,
+                (&VariableType::Global(_), MemoryLocation::Global) => true,
+                (&VariableType::Global(_), MemoryLocation::Unknown) => true,
+                _ => false,
+            };
+            if alias {
+                may_alias.insert(i);
             }
         }
 
         radeco_trace!("MemorrySSA|New may_alias set {:?} for {:?}", may_alias, idx);
         self.may_aliases.entry(*idx).or_insert(may_alias);
+        self.locations.entry(*idx).or_insert(location);
+    }
+
+    // Returns the locals overlapped by the bytes the access `idx` to `mem` reads or writes, if
+    // `mem` is at a known offset from a BP/SP register. A slot spans up to the next one based on
+    // the same register.
+    fn overlapped_slots(&self, idx: &T::ValueRef, mem: &T::ValueRef) -> Option<HashSet<VarId>> {
+        let (base, start) = self.stack_offset(*mem)?;
+        let value = match self.ssa.opcode(*idx)? {
+            MOpcode::OpLoad => *idx,
+            MOpcode::OpStore => *self.ssa.operands_of(*idx).get(2)?,
+            _ => return None,
+        };
+        let bits = self.ssa.node_data(value).ok()?.vt.width().get_width()?;
+        let end = start + (i64::from(bits) + 7) / 8;
+
+        let slots = self
+            .variables
+            .iter()
+            .enumerate()
+            .filter_map(|(i, var)| match *var {
+                VariableType::Local(ref info) => {
+                    let reference = info.reference.as_ref()?;
+                    match (reference.base.as_ref(), reference.offset) {
+                        (Some(b), Some(offset)) if *b == base => Some((offset, i)),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let overlapped = slots
+            .iter()
+            .filter(|&&(offset, _)| {
+                let slot_end = slots
+                    .iter()
+                    .map(|&(o, _)| o)
+                    .filter(|&o| o > offset)
+                    .min()
+                    .unwrap_or(i64::max_value());
+                start < slot_end && end > offset
+            })
+            .map(|&(_, i)| i)
+            .collect::<HashSet<_>>();
+        if overlapped.is_empty() {
+            None
+        } else {
+            Some(overlapped)
+        }
+    }
+
+    // Returns the BP/SP register `node` is based on, and the offset of `node` from it.
+    fn stack_offset(&self, node: T::ValueRef) -> Option<(String, i64)> {
+        if let Some(reg) = self.ssa.comment(node) {
+            if !self.local_nodes.contains(&node) {
+                return None;
+            }
+            // Comment may be in format as: reg@address.offset
+            return Some((reg.split('@').next()?.to_owned(), 0));
+        }
+        let operands = self.ssa.operands_of(node);
+        let constant = |i: usize| operands.get(i).and_then(|&o| self.ssa.constant_value(o));
+        match self.ssa.opcode(node)? {
+            MOpcode::OpMov => self.stack_offset(*operands.get(0)?),
+            MOpcode::OpAdd => {
+                let (base, c) = match (constant(0), constant(1)) {
+                    (None, Some(c)) => (operands[0], c),
+                    (Some(c), None) => (operands[1], c),
+                    _ => return None,
+                };
+                let (reg, offset) = self.stack_offset(base)?;
+                Some((reg, offset.wrapping_add(c as i64)))
+            }
+            MOpcode::OpSub => {
+                let c = constant(1)?;
+                let (reg, offset) = self.stack_offset(operands[0])?;
+                Some((reg, offset.wrapping_sub(c as i64)))
+            }
+            _ => None,
+        }
     }
 
     // This function gather may_alias set for every LOAD/STORE,
@@ -367,25 +674,34 @@ where
             if let Ok(ndata) = self.ssa.node_data(expr) {
                 let operands = self.ssa.operands_of(expr);
                 match ndata.nt {
-                    NodeType::Op(opc) => {
-                        match opc {
-                            MOpcode::OpLoad | MOpcode::OpStore => {
-                                // TODO: Token::EEq will cause OpStore uses the first
-                                // argument as memory address, although it's impossible
-                                // to happen in normal situation.
-                                self.calculate_may_alias(&expr, &operands[1]);
-                            }
-
-                            _ => {
-                                self.propagate_nodes_type(&expr, &operands);
-                            }
-                        }
-                    }
-                    NodeType::Phi => self.propagate_nodes_type(&expr, &operands),
+                    NodeType::Op(MOpcode::OpLoad) | NodeType::Op(MOpcode::OpStore) => {}
+                    NodeType::Op(_) | NodeType::Phi => self.propagate_nodes_type(&expr, &operands),
                     _ => {}
                 }
             }
         }
+
+        // Whether a local address escapes must be known before computing the may_alias set
+        // of any access from an unknown address.
+        for expr in self.ssa.inorder_walk() {
+            if let Some(opc) = self.ssa.opcode(expr) {
+                let operands = self.ssa.operands_of(expr);
+                self.check_escape(opc, &operands);
+            }
+        }
+
+        for expr in self.ssa.inorder_walk() {
+            match self.ssa.opcode(expr) {
+                Some(MOpcode::OpLoad) | Some(MOpcode::OpStore) => {
+                    // TODO: Token::EEq will cause OpStore uses the first
+                    // argument as memory address, although it's impossible
+                    // to happen in normal situation.
+                    let operands = self.ssa.operands_of(expr);
+                    self.calculate_may_alias(&expr, &operands[1]);
+                }
+                _ => {}
+            }
+        }
     }
 
     // Above code are used in raw MemorySSA to gather may_alias set.
@@ -534,7 +850,7 @@ where
         );
         radeco_trace!(
             "MemorrySSA|Pred Block information {:?}",
-            self.ssa.preds_of(block.clone())
+            self.reachable_preds(block)
         );
         let mut val: NodeIndex;
 
@@ -542,7 +858,7 @@ where
             val = self.add_phi(var, block);
             self.incomplete_phis[var].insert(block.clone(), val);
         } else {
-            let preds = self.ssa.preds_of(block.clone());
+            let preds = self.reachable_preds(block);
             if preds.len() == 1 {
                 val = self.read_variable(var, &preds[0]);
             } else {
@@ -563,7 +879,7 @@ where
                 radeco_err!("Phi not found!");
                 vec![self.ssa.invalid_action().unwrap().clone()]
             },
-            |block| self.ssa.preds_of(block.clone()),
+            |block| self.reachable_preds(block),
         );
         for pred in &preds {
             let target = self.read_variable(var, pred);
@@ -710,9 +1026,413 @@ where
 
         // Seal blocks
         for block in self.ssa.blocks() {
-            self.seal_block(&block);
+            if self.reachable.contains(&block) {
+                self.seal_block(&block);
+            }
         }
 
         radeco_trace!("MemorrySSA|Memory SSA Graph: {:?}", self.g);
     }
 }
+
+/// Generates the Memory SSA of `rfn`, with its data references, the constant addresses it accesses,
+/// its locals and its calls as variables, and records the store each of its loads reads from. A
+/// stack access only aliases the locals it overlaps.
+pub fn record_clobbering_stores(rfn: &mut RadecoFunction) {
+    let stores = {
+        let ssa = rfn.ssa();
+        let mut globals = rfn.datarefs().clone();
+        let mut calls = Vec::new();
+        for expr in ssa.inorder_walk() {
+            match ssa.opcode(expr) {
+                Some(MOpcode::OpLoad) | Some(MOpcode::OpStore) => {
+                    let addr = ssa.operands_of(expr).get(1).cloned();
+                    globals.extend(addr.and_then(|a| ssa.constant_value(a)));
+                }
+                Some(MOpcode::OpCall) => calls.push(expr),
+                _ => (),
+            }
+        }
+        globals.sort();
+        globals.dedup();
+        let locals = rfn
+            .locals()
+            .into_iter()
+            .filter_map(|local| match local.btype {
+                BindingType::RegisterLocal(ref base, offset) => Some(LVarInfo {
+                    name: Some(local.name().to_owned()),
+                    kind: Some("var".to_owned()),
+                    vtype: Some(local.type_str.clone()),
+                    reference: Some(LVarRef {
+                        base: Some(base.clone()),
+                        offset: Some(offset),
+                    }),
+                }),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut mssa: MemorySSA<Walker, SSAStorage> = MemorySSA::new(ssa);
+        // A store to one local does not clobber a load of another
+        mssa.set_local_aliasing(LocalAliasing::Overlapped);
+        mssa.gather_variables(&globals, &locals, &calls);
+        mssa.run();
+        mssa.associated_nodes
+            .values()
+            .filter(|&&access| ssa.opcode(access) == Some(MOpcode::OpLoad))
+            .filter_map(|&load| mssa.clobbering_store(load).map(|store| (load, store)))
+            .collect()
+    };
+    rfn.set_clobbering_stores(stores);
+}
+
+impl<'a, I, T> GraphDot for MemorySSA<'a, I, T>
+where
+    I: Iterator<Item = T::ValueRef>,
+    T: 'a + SSA + SSAMod + SSAWalk<I>,
+{
+    type NodeIndex = NodeIndex;
+    type EdgeIndex = EdgeIndex;
+
+    fn node_index_new(i: usize) -> Self::NodeIndex {
+        NodeIndex::new(i)
+    }
+
+    fn edge_index_new(i: usize) -> Self::EdgeIndex {
+        EdgeIndex::new(i)
+    }
+
+    fn configure(&self) -> String {
+        "digraph mssa {\nsplines=\"true\";\n".to_owned()
+    }
+
+    fn node_count(&self) -> usize {
+        self.g.node_count()
+    }
+
+    fn edge_count(&self) -> usize {
+        self.g.edge_count()
+    }
+
+    fn nodes(&self) -> Vec<Self::NodeIndex> {
+        self.g.node_indices().collect()
+    }
+
+    fn edges(&self) -> Vec<Self::EdgeIndex> {
+        self.g.edge_indices().collect()
+    }
+
+    fn node_attrs(&self, i: &Self::NodeIndex) -> DotAttrBlock {
+        let label = format!("{:?}", self.node_label(i));
+        DotAttrBlock::Hybrid(format!("n{}", i.index()), vec![("label".to_owned(), label)])
+    }
+
+    // Phi nodes read their definitions along the incoming control flow edges, draw these
+    // differently from the definitions read by loads and stores.
+    fn edge_attrs(&self, i: &Self::EdgeIndex) -> DotAttrBlock {
+        let (src, dst) = (self.edge_source(i), self.edge_target(i));
+        let style = if self.is_phi(&src) { "dashed" } else { "solid" };
+        DotAttrBlock::Hybrid(
+            format!("n{} -> n{}", src.index(), dst.index()),
+            vec![("style".to_owned(), style.to_owned())],
+        )
+    }
+
+    fn edge_source(&self, i: &Self::EdgeIndex) -> Self::NodeIndex {
+        self.g.edge_endpoints(*i).map_or(NodeIndex::end(), |e| e.0)
+    }
+
+    fn edge_target(&self, i: &Self::EdgeIndex) -> Self::NodeIndex {
+        self.g.edge_endpoints(*i).map_or(NodeIndex::end(), |e| e.1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::frontend::radeco_containers::VarBinding;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use crate::middle::ssa::ssa_builder::SSABuilder;
+    use crate::middle::ssa::ssastorage::SSAStorage;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const STACK_SLOTS_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.slots(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rbp;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %3: $Unknown64 = %1 - #x8;
+        [@0x001000.0002] %4: $Mem = Store(%2, %3, #x1);
+        [@0x001004.0001] %5: $Unknown64 = %1 - #xc;
+        [@0x001004.0002] %6: $Mem = Store(%4, %5, #x2);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rbp = %1;
+        $mem = %6;
+}
+";
+
+    const STACK_SLOTS_LOCALS: &str = r#"[
+        {"name": "local_8h", "kind": "var", "type": "int", "ref": {"base": "rbp", "offset": -8}},
+        {"name": "local_ch", "kind": "var", "type": "int", "ref": {"base": "rbp", "offset": -12}}
+    ]"#;
+
+    #[test]
+    fn dump_stack_slots() {
+        let ssa = ir_reader::parse_il(STACK_SLOTS_TXT, REGISTER_FILE.clone());
+        let locals: Vec<LVarInfo> = serde_json::from_str(STACK_SLOTS_LOCALS).unwrap();
+        let mut mssa = MemorySSA::new(&ssa);
+        mssa.gather_variables(&Vec::new(), &locals, &Vec::new());
+        mssa.run();
+
+        let dump = mssa.dump();
+        assert!(dump.contains("    v0 = local local_8h [rbp - 0x8]\n"));
+        assert!(dump.contains("    v1 = local local_ch [rbp - 0xc]\n"));
+        assert!(dump.contains("    m0 = MemoryAccess\n"));
+
+        // Both stores may alias both slots, the second one reads the definition of the first.
+        let defs = dump
+            .lines()
+            .filter(|l| l.contains(" = VDef "))
+            .collect::<Vec<_>>();
+        assert_eq!(defs.len(), 2);
+        assert!(defs[0].ends_with(" = VDef @0x001000.0002 {v0, v1} -> m0"));
+        let first = defs[0].trim().split(' ').next().unwrap();
+        assert!(defs[1].ends_with(&format!(" = VDef @0x001004.0002 {{v0, v1}} -> {}", first)));
+
+        let dot = mssa.dump_dot();
+        assert!(dot.starts_with("digraph mssa {"));
+        assert!(dot.contains(&format!("n{} -> n0", &first[1..])));
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const UNKNOWN_LOAD_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.unknown(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rbp;
+        %2: $Unknown64 = $rdi;
+        %3: $Mem = $mem;
+    bb_0x001000.0000(sz 0xc):
+        [@0x001000.0001] %4: $Unknown64 = %1 - #x8;
+        [@0x001000.0002] %5: $Mem = Store(%3, %4, #x1);
+        [@0x001004.0001] %6: $Unknown64 = Load(%5, %2);
+        [@0x001008.0001] %7: $Unknown64 = Load(%5, %4);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rbp = %1;
+        $rdi = %6;
+        $mem = %5;
+}
+";
+
+    #[test]
+    fn store_survives_unknown_load() {
+        let ssa = ir_reader::parse_il(UNKNOWN_LOAD_TXT, REGISTER_FILE.clone());
+        let locals: Vec<LVarInfo> = serde_json::from_str(STACK_SLOTS_LOCALS).unwrap();
+        let globals = vec![0x2000];
+        let mut mssa = MemorySSA::new(&ssa);
+        mssa.gather_variables(&globals, &locals, &Vec::new());
+        mssa.run();
+        assert!(!mssa.local_escapes);
+
+        let mut accesses = ssa
+            .inorder_walk()
+            .filter(|&n| match ssa.opcode(n) {
+                Some(MOpcode::OpLoad) | Some(MOpcode::OpStore) => true,
+                _ => false,
+            })
+            .collect::<Vec<_>>();
+        accesses.sort_by_key(|&n| ssa.address(n));
+        let (store, unknown, slot) = (accesses[0], accesses[1], accesses[2]);
+        assert_eq!(mssa.location_of(store), Some(MemoryLocation::Local));
+        assert_eq!(mssa.location_of(unknown), Some(MemoryLocation::Unknown));
+        assert_eq!(mssa.location_of(slot), Some(MemoryLocation::Local));
+
+        // The unknown load may read the global, but not the stack slots.
+        assert_eq!(mssa.may_aliases[&unknown].len(), 1);
+        assert!(mssa.may_aliases[&unknown].contains(&0));
+        assert_eq!(mssa.clobbering_store(slot), Some(store));
+    }
+
+    #[test]
+    fn memory_free_function() {
+        // rax = rdi + 1
+        let mut b = SSABuilder::new(REGISTER_FILE.clone());
+        let rdi = b.register("rdi");
+        let mem = b.register("mem");
+        let bb = b.block(0x1000, 0x4);
+        let one = b.constant(1);
+        let sum = b.op(bb, MOpcode::OpAdd, 64, &[rdi, one]);
+        b.ret(bb)
+            .exit_register("rax", sum)
+            .exit_register("mem", mem);
+        let ssa = b.finish();
+
+        let locals: Vec<LVarInfo> = serde_json::from_str(STACK_SLOTS_LOCALS).unwrap();
+        let mut mssa = MemorySSA::new(&ssa);
+        mssa.gather_variables(&vec![0x2000], &locals, &Vec::new());
+        mssa.run();
+        assert_eq!(mssa.g.node_count(), 0);
+        assert!(mssa.may_aliases.is_empty());
+        assert!(mssa.associated_blocks.is_empty());
+    }
+
+    // A store to a stack slot, then a load from it, in a block which may also be entered from a
+    // block nothing jumps to, storing to the same slot.
+    fn build_unreachable_store() -> (SSAStorage, [NodeIndex; 3]) {
+        let mut b = SSABuilder::new(REGISTER_FILE.clone());
+        let rbp = b.register("rbp");
+        let mem = b.register("mem");
+        let head = b.block(0x1000, 0x8);
+        let orphan = b.block(0x1008, 0x4);
+        let join = b.block(0x100C, 0x4);
+
+        let eight = b.constant(8);
+        let slot = b.op(head, MOpcode::OpSub, 64, &[rbp, eight]);
+        let one = b.constant(1);
+        let store = b.op(head, MOpcode::OpStore, 0, &[mem, slot, one]);
+        let two = b.constant(2);
+        let dead_store = b.op(orphan, MOpcode::OpStore, 0, &[mem, slot, two]);
+        let load = b.op(join, MOpcode::OpLoad, 64, &[store, slot]);
+        b.jump(head, join)
+            .jump(orphan, join)
+            .ret(join)
+            .exit_register("rax", load)
+            .exit_register("mem", store);
+        (b.finish(), [store, dead_store, load])
+    }
+
+    #[test]
+    fn unreachable_store_ignored() {
+        let (ssa, [store, dead_store, load]) = build_unreachable_store();
+        let locals: Vec<LVarInfo> = serde_json::from_str(STACK_SLOTS_LOCALS).unwrap();
+        let mut mssa = MemorySSA::new(&ssa);
+        mssa.gather_variables(&Vec::new(), &locals, &Vec::new());
+        mssa.run();
+
+        assert_eq!(mssa.location_of(dead_store), None);
+        assert!(mssa.associated_nodes.values().all(|&n| n != dead_store));
+        // The load is only reached by the store before it, without a phi merging in the other.
+        assert_eq!(mssa.clobbering_store(load), Some(store));
+        assert!(mssa.phi_nodes.is_empty());
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const UNALIGNED_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.unaligned(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rbp;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %3: $Unknown64 = %1 - #x4;
+        [@0x001000.0002] %4: $Unknown32 = Load(%2, %3);
+        [@0x001004.0001] %5: $Unknown64 = %1 - #x2;
+        [@0x001004.0002] %6: $Unknown16 = Load(%2, %5);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rbp = %1;
+        $mem = %2;
+}
+";
+
+    const SHORT_SLOTS_LOCALS: &str = r#"[
+        {"name": "local_4h", "kind": "var", "type": "short", "ref": {"base": "rbp", "offset": -4}},
+        {"name": "local_2h", "kind": "var", "type": "short", "ref": {"base": "rbp", "offset": -2}}
+    ]"#;
+
+    #[test]
+    fn straddling_access_aliases_both_slots() {
+        let ssa = ir_reader::parse_il(UNALIGNED_TXT, REGISTER_FILE.clone());
+        let locals: Vec<LVarInfo> = serde_json::from_str(SHORT_SLOTS_LOCALS).unwrap();
+        let mut loads = ssa
+            .inorder_walk()
+            .filter(|&n| ssa.opcode(n) == Some(MOpcode::OpLoad))
+            .collect::<Vec<_>>();
+        loads.sort_by_key(|&n| ssa.address(n));
+        let (straddling, aligned) = (loads[0], loads[1]);
+
+        let mut mssa = MemorySSA::new(&ssa);
+        mssa.set_local_aliasing(LocalAliasing::Overlapped);
+        mssa.gather_variables(&Vec::new(), &locals, &Vec::new());
+        mssa.run();
+        // The 4 bytes at rbp - 4 span both 2 bytes slots, those at rbp - 2 only the second one.
+        let both = [0, 1].iter().cloned().collect::<HashSet<_>>();
+        assert_eq!(mssa.may_aliases[&straddling], both);
+        assert_eq!(
+            mssa.may_aliases[&aligned],
+            [1].iter().cloned().collect::<HashSet<_>>()
+        );
+
+        let mut mssa = MemorySSA::new(&ssa);
+        mssa.gather_variables(&Vec::new(), &locals, &Vec::new());
+        mssa.run();
+        assert_eq!(mssa.may_aliases[&aligned], both);
+    }
+
+    // `%7` reads the local stored by `%4`, past the store to the other local.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const LOCAL_LOAD_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.local_load(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rbp;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0xc):
+        [@0x001000.0001] %3: $Unknown64 = %1 - #x8;
+        [@0x001000.0002] %4: $Mem = Store(%2, %3, #x1);
+        [@0x001004.0001] %5: $Unknown64 = %1 - #x10;
+        [@0x001004.0002] %6: $Mem = Store(%4, %5, #x2);
+        [@0x001008.0001] %7: $Unknown64 = Load(%6, %3);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %7;
+        $rbp = %1;
+        $mem = %6;
+}
+";
+
+    #[test]
+    fn record_local_clobbering_stores() {
+        let mut rfn = RadecoFunction::new();
+        *rfn.ssa_mut() = ir_reader::parse_il(LOCAL_LOAD_TXT, REGISTER_FILE.clone());
+        for &(name, offset) in &[("local_8h", -8), ("local_10h", -16)] {
+            let btype = BindingType::RegisterLocal("rbp".to_owned(), offset);
+            let name = Some(name.to_owned());
+            let local = VarBinding::new(btype, "int".to_owned(), name, NodeIndex::end(), None);
+            rfn.bindings_mut().push(local);
+        }
+        let (load, first_store) = {
+            let ssa = rfn.ssa();
+            let find = |opcode| {
+                ssa.inorder_walk()
+                    .filter(|&n| ssa.opcode(n).as_ref() == Some(&opcode))
+                    .min_by_key(|&n| ssa.address(n))
+                    .unwrap()
+            };
+            (find(MOpcode::OpLoad), find(MOpcode::OpStore))
+        };
+
+        record_clobbering_stores(&mut rfn);
+        assert_eq!(rfn.clobbering_store(load), Some(first_store));
+    }
+}
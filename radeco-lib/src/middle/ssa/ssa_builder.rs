@@ -0,0 +1,231 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Builder of `SSAStorage`s.
+//!
+//! Functions are usually constructed from the ESIL of their instructions, or read from the
+//! textual IR. `SSABuilder` instead builds one block by block, one value at a time, e.g. to set
+//! up the input of a pass in a test:
+//!
+//! ```rust ignore
+//! let mut b = SSABuilder::new(regfile);
+//! let rdi = b.register("rdi");
+//! let bb = b.block(0x1000, 0x4);
+//! let zero = b.constant(0);
+//! let cond = b.op(bb, MOpcode::OpEq, 1, &[rdi, zero]);
+//! let (then_bb, else_bb) = (b.block(0x1004, 0x4), b.block(0x1008, 0x4));
+//! b.branch(bb, cond, then_bb, else_bb).ret(then_bb).ret(else_bb);
+//! let ssa = b.finish();
+//! ```
+
+use crate::middle::ir::{MAddress, MOpcode, WidthSpec};
+use crate::middle::regfile::SubRegisterFile;
+use crate::middle::ssa::cfg_traits::{CFGMod, CFG};
+use crate::middle::ssa::ssa_traits::{SSAMod, ValueInfo, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+type SSABlock = <SSAStorage as CFG>::ActionRef;
+type SSAValue = <SSAStorage as SSA>::ValueRef;
+
+const FALSE_EDGE: u8 = 0;
+const TRUE_EDGE: u8 = 1;
+const UNCOND_EDGE: u8 = 2;
+
+/// Builds a `SSAStorage` out of blocks, values and control edges.
+#[derive(Debug)]
+pub struct SSABuilder {
+    ssa: SSAStorage,
+    entry: SSABlock,
+    exit: SSABlock,
+    entered: bool,
+    registers: HashMap<String, SSAValue>,
+    // Number of values in each block, which gives the offset of the next one.
+    values_in: HashMap<SSABlock, u64>,
+}
+
+impl SSABuilder {
+    /// Starts a function with the registers of `regfile`.
+    pub fn new(regfile: Arc<SubRegisterFile>) -> SSABuilder {
+        let mut ssa = SSAStorage::new();
+        ssa.regfile = regfile;
+        let entry = ssa
+            .insert_block(MAddress::new(0, 0))
+            .expect("Cannot insert new blocks");
+        ssa.set_entry_node(entry);
+        let exit = ssa.insert_dynamic().expect("Cannot insert new blocks");
+        ssa.set_exit_node(exit);
+        SSABuilder {
+            ssa,
+            entry,
+            exit,
+            entered: false,
+            registers: HashMap::new(),
+            values_in: HashMap::new(),
+        }
+    }
+
+    /// Adds a basic block of `size` bytes at `addr`. The function is entered at the first one.
+    pub fn block(&mut self, addr: u64, size: u64) -> SSABlock {
+        let block = self
+            .ssa
+            .insert_block(MAddress::new(addr, 0))
+            .expect("Cannot insert new blocks");
+        self.ssa.set_block_size(block, size);
+        if !self.entered {
+            self.ssa.insert_control_edge(self.entry, block, UNCOND_EDGE);
+            self.entered = true;
+        }
+        block
+    }
+
+    /// Value of the register `name`, or of memory for `mem`, at the entry of the function.
+    pub fn register(&mut self, name: &str) -> SSAValue {
+        if let Some(&value) = self.registers.get(name) {
+            return value;
+        }
+        let (id, vi) = self.register_info(name);
+        let value = self
+            .ssa
+            .insert_comment(vi, name.to_owned())
+            .expect("Cannot insert new values");
+        let state = self
+            .ssa
+            .registers_in(self.entry)
+            .expect("No register state");
+        self.ssa.op_use(state, id, value);
+        self.registers.insert(name.to_owned(), value);
+        value
+    }
+
+    /// 64 bits wide constant `value`.
+    pub fn constant(&mut self, value: u64) -> SSAValue {
+        self.ssa
+            .insert_const(value, None)
+            .expect("Cannot insert new constants")
+    }
+
+    /// `width` bits wide constant `value`.
+    pub fn sized_constant(&mut self, value: u64, width: u16) -> SSAValue {
+        self.ssa
+            .insert_const(value, Some(width))
+            .expect("Cannot insert new constants")
+    }
+
+    /// Adds to `block` the `width` bits wide result of `opcode` on `operands`. A `width` of 0
+    /// stands for a memory state.
+    pub fn op(
+        &mut self,
+        block: SSABlock,
+        opcode: MOpcode,
+        width: u16,
+        operands: &[SSAValue],
+    ) -> SSAValue {
+        let op = self
+            .ssa
+            .insert_op(opcode, value_info(width), None)
+            .expect("Cannot insert new values");
+        for (i, &operand) in operands.iter().enumerate() {
+            self.ssa.op_use(op, i as u8, operand);
+        }
+        let addr = self.next_address(block);
+        self.ssa.insert_into_block(op, block, addr);
+        op
+    }
+
+    /// Adds to `block` a `width` bits wide phi, which is given the value of each of `sources`
+    /// when the block is entered from its predecessor.
+    pub fn phi(
+        &mut self,
+        block: SSABlock,
+        width: u16,
+        sources: &[(SSABlock, SSAValue)],
+    ) -> SSAValue {
+        let phi = self
+            .ssa
+            .insert_phi(value_info(width))
+            .expect("Cannot insert new values");
+        let addr = self.ssa.starting_address(block).unwrap_or_default();
+        self.ssa.insert_into_block(phi, block, addr);
+        for &(pred, value) in sources {
+            self.ssa.phi_use_from(phi, pred, value);
+        }
+        phi
+    }
+
+    /// Jumps from `from` to `to`.
+    pub fn jump(&mut self, from: SSABlock, to: SSABlock) -> &mut SSABuilder {
+        self.ssa.insert_control_edge(from, to, UNCOND_EDGE);
+        self
+    }
+
+    /// Jumps from `from` to `if_true` if `selector` is set, to `if_false` otherwise.
+    pub fn branch(
+        &mut self,
+        from: SSABlock,
+        selector: SSAValue,
+        if_true: SSABlock,
+        if_false: SSABlock,
+    ) -> &mut SSABuilder {
+        self.ssa.set_selector(selector, from);
+        self.ssa.insert_control_edge(from, if_true, TRUE_EDGE);
+        self.ssa.insert_control_edge(from, if_false, FALSE_EDGE);
+        self
+    }
+
+    /// Returns from the function at the end of `from`.
+    pub fn ret(&mut self, from: SSABlock) -> &mut SSABuilder {
+        self.ssa.insert_control_edge(from, self.exit, UNCOND_EDGE);
+        self
+    }
+
+    /// Leaves `value` in the register `name`, or in memory for `mem`, when the function returns.
+    pub fn exit_register(&mut self, name: &str, value: SSAValue) -> &mut SSABuilder {
+        let (id, _) = self.register_info(name);
+        let state = self.ssa.registers_in(self.exit).expect("No register state");
+        self.ssa.op_use(state, id, value);
+        self
+    }
+
+    /// Returns the function built so far.
+    pub fn finish(mut self) -> SSAStorage {
+        if !self.entered {
+            self.ssa
+                .insert_control_edge(self.entry, self.exit, UNCOND_EDGE);
+        }
+        self.ssa
+    }
+
+    fn register_info(&self, name: &str) -> (u8, ValueInfo) {
+        let regfile = &self.ssa.regfile;
+        if name == "mem" {
+            return (regfile.mem_id().to_u8(), ValueInfo::new_memory());
+        }
+        let id = regfile
+            .register_id_by_name(name)
+            .unwrap_or_else(|| panic!("No register {}", name));
+        let width = regfile.get_width(id).unwrap_or(64);
+        (id.to_u8(), value_info(width as u16))
+    }
+
+    fn next_address(&mut self, block: SSABlock) -> MAddress {
+        let start = self.ssa.starting_address(block).unwrap_or_default();
+        let count = self.values_in.entry(block).or_insert(0);
+        *count += 1;
+        MAddress::new(start.address, *count)
+    }
+}
+
+fn value_info(width: u16) -> ValueInfo {
+    if width == 0 {
+        ValueInfo::new_memory()
+    } else {
+        ValueInfo::new_unresolved(WidthSpec::Known(width))
+    }
+}
@@ -21,6 +21,7 @@
 //! The associated type `SSA::ValueRef` is used by the methods to refer to
 //! nodes.
 
+use std::error;
 use std::fmt;
 use std::fmt::Debug;
 use std::hash::Hash;
@@ -61,21 +62,26 @@ macro_rules! registers_in_err {
     };
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 /// Defines the high level `type` of value for a node. It is only used to differentiate between
-/// references and non-reference (scalar) types.
+/// references, non-reference (scalar) and floating point types.
 pub enum ValueType {
     /// Pointer or reference to code or memory
     Reference,
     /// Not a pointer type
     Scalar,
+    /// Floating point or SIMD (FPU/SSE) value
+    Float,
+    /// State of the whole memory, e.g. the `mem` pseudo-variable or the result of a store. It has
+    /// no meaningful width.
+    Memory,
     /// Not (yet) resolved to be a reference or a constant
     Unresolved,
     /// Invalid/Unconsistent
     Invalid,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 /// Information for a `ValueRef`
 pub struct ValueInfo {
     /// `Type` of value
@@ -132,6 +138,14 @@ impl ValueInfo {
         ValueInfo::new(ValueType::Reference, width)
     }
 
+    pub fn new_float(width: ir::WidthSpec) -> ValueInfo {
+        ValueInfo::new(ValueType::Float, width)
+    }
+
+    pub fn new_memory() -> ValueInfo {
+        ValueInfo::new(ValueType::Memory, ir::WidthSpec::Known(0))
+    }
+
     pub fn set_value_type(&mut self, vt: ValueType) {
         self.vty = vt;
     }
@@ -159,6 +173,14 @@ impl ValueInfo {
     pub fn is_reference(&self) -> bool {
         self.vty == ValueType::Reference
     }
+
+    pub fn is_float(&self) -> bool {
+        self.vty == ValueType::Float
+    }
+
+    pub fn is_memory(&self) -> bool {
+        self.vty == ValueType::Memory
+    }
 }
 
 pub struct BBInfo;
@@ -197,6 +219,29 @@ pub struct NodeData {
     pub nt: NodeType,
 }
 
+/// Error of an operation on a SSA.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SsaError {
+    /// The graph cannot index any more nodes.
+    NodeLimit,
+    /// A constant was given a width it cannot have.
+    InvalidWidth(u16),
+    /// The node is not a basic block.
+    NotABlock,
+}
+
+impl error::Error for SsaError {}
+
+impl fmt::Display for SsaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SsaError::NodeLimit => write!(f, "the graph cannot hold any more nodes"),
+            SsaError::InvalidWidth(w) => write!(f, "a constant cannot be {} bits wide", w),
+            SsaError::NotABlock => write!(f, "the node is not a basic block"),
+        }
+    }
+}
+
 /// Trait for the SSA Form implementation.
 // This trait ensures that any other ssa form will be compatible with our
 // implementations provided
@@ -226,7 +271,7 @@ pub trait SSA: CFG {
     /// Get all value nodes in the whole graph.
     fn values(&self) -> Vec<Self::ValueRef>;
 
-    /// Get expr/phi node address
+    /// Get expr/phi node address. Actions, including the exit node, have none.
     fn address(&self, ni: Self::ValueRef) -> Option<ir::MAddress>;
 
     /// Get all the NodeIndex of all operations/expressions in the BasicBlock with index 'i'.
@@ -253,6 +298,14 @@ pub trait SSA: CFG {
     /// Get the operands for the operation with NodeIndex 'i' as tuples.
     fn sparse_operands_of(&self, i: Self::ValueRef) -> Vec<(u8, Self::ValueRef)>;
 
+    /// Get the operands of the phi node 'i', along with the predecessor of its block each one
+    /// flows from, if known. An operand flowing from several predecessors is listed once for each
+    /// of them, by ascending starting address; the operands themselves are in positional order.
+    fn phi_sources(&self, i: Self::ValueRef) -> Vec<(Option<Self::ActionRef>, Self::ValueRef)>;
+
+    /// Get the operand of the phi node 'i' flowing from the predecessor 'pred', if known.
+    fn phi_operand_from(&self, i: Self::ValueRef, pred: Self::ActionRef) -> Option<Self::ValueRef>;
+
     /// Get the NodeIndex of the BasicBlock to which node with index 'i' belongs to.
     fn block_for(&self, i: Self::ValueRef) -> Option<Self::ActionRef>;
 
@@ -292,19 +345,19 @@ pub trait SSAMod: SSA + CFGMod {
         opc: ir::MOpcode,
         vt: ValueInfo,
         addr: Option<u64>,
-    ) -> Option<Self::ValueRef>;
+    ) -> Result<Self::ValueRef, SsaError>;
 
-    /// Add a new constant node.
-    fn insert_const(&mut self, value: u64, size: Option<u16>) -> Option<Self::ValueRef>;
+    /// Add a new constant node, `size` bits wide, 64 by default.
+    fn insert_const(&mut self, value: u64, size: Option<u16>) -> Result<Self::ValueRef, SsaError>;
 
     /// Add a new phi node.
-    fn insert_phi(&mut self, vt: ValueInfo) -> Option<Self::ValueRef>;
+    fn insert_phi(&mut self, vt: ValueInfo) -> Result<Self::ValueRef, SsaError>;
 
     /// Add a new undefined node
-    fn insert_undefined(&mut self, vt: ValueInfo) -> Option<Self::ValueRef>;
+    fn insert_undefined(&mut self, vt: ValueInfo) -> Result<Self::ValueRef, SsaError>;
 
     /// Add a new comment node
-    fn insert_comment(&mut self, vt: ValueInfo, msg: String) -> Option<Self::ValueRef>;
+    fn insert_comment(&mut self, vt: ValueInfo, msg: String) -> Result<Self::ValueRef, SsaError>;
 
     /// Associate a node with index n with a block
     fn insert_into_block(&mut self, node: Self::ValueRef, block: Self::ActionRef, _: ir::MAddress);
@@ -312,6 +365,18 @@ pub trait SSAMod: SSA + CFGMod {
     /// Add a data source to a phi node.
     fn phi_use(&mut self, phi: Self::ValueRef, node: Self::ValueRef);
 
+    /// Add a data source to a phi node, flowing from the predecessor `pred` of its block.
+    fn phi_use_from(&mut self, phi: Self::ValueRef, pred: Self::ActionRef, node: Self::ValueRef);
+
+    /// Make the operands of the phi nodes of `block` flowing from `old_pred` flow from `new_pred`
+    /// instead, e.g. after an edge has been split.
+    fn redirect_phi_sources(
+        &mut self,
+        block: Self::ActionRef,
+        old_pred: Self::ActionRef,
+        new_pred: Self::ActionRef,
+    );
+
     /// Remove a data source from a phi node.
     fn phi_unuse(&mut self, phi: Self::ValueRef, node: Self::ValueRef);
 
@@ -321,7 +386,11 @@ pub trait SSAMod: SSA + CFGMod {
     /// Remove a data source from a expr node.
     fn op_unuse(&mut self, op: Self::ValueRef, operand: Self::ValueRef);
 
-    /// Replace one node by another within one basic block.
+    /// Make every use of `node`, as an operand, a phi argument, a register of a register state
+    /// or a selector, use `replacement` instead. `node` is left in place without any use.
+    fn replace_uses(&mut self, node: Self::ValueRef, replacement: Self::ValueRef);
+
+    /// Replace one node by another, i.e. `replace_uses` followed by the removal of `node`.
     fn replace_value(&mut self, node: Self::ValueRef, replacement: Self::ValueRef);
 
     /// Remove a node without replacement
@@ -344,6 +413,12 @@ pub trait SSAExtra: SSA {
     fn set_color(&mut self, _: &Self::ValueRef, _: u8) {}
     fn set_comment(&mut self, _: &Self::ValueRef, _: String) {}
     fn add_flag(&mut self, _: &Self::ValueRef, _: String) {}
+    /// Records that the value refers to the address `addr`, e.g. the global it is loaded from.
+    fn add_xref(&mut self, _: &Self::ValueRef, _: u64) {}
+    /// Records the ESIL of the instruction the value was translated from.
+    fn set_esil(&mut self, _: &Self::ValueRef, _: String) {}
+    /// Records the segment register the address of a load or store is based on.
+    fn set_segment(&mut self, _: &Self::ValueRef, _: String) {}
     fn is_marked(&self, _: &Self::ValueRef) -> bool {
         false
     }
@@ -363,6 +438,19 @@ pub trait SSAExtra: SSA {
     fn flags(&self, _: &Self::ValueRef) -> Option<String> {
         None
     }
+
+    fn esil(&self, _: &Self::ValueRef) -> Option<String> {
+        None
+    }
+
+    fn segment_of(&self, _: &Self::ValueRef) -> Option<String> {
+        None
+    }
+
+    /// Addresses the value refers to, in the order they were recorded.
+    fn xrefs_of(&self, _: &Self::ValueRef) -> Vec<u64> {
+        Vec::new()
+    }
 }
 
 pub trait SSAWalk<I: Iterator<Item = <Self as SSA>::ValueRef>>: SSA {
@@ -0,0 +1,204 @@
+// Copyright (c) 2018, The Radare Project. All rights reserved.
+// See the COPYING file at the top-level directory of this distribution.
+// Licensed under the BSD 3-Clause License:
+// <http://opensource.org/licenses/BSD-3-Clause>
+// This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Structural diff of two `SSAStorage`s.
+//!
+//! Two functions are compared through what they compute: the value of every register when they
+//! return, and the selector of every conditional block. Each of these is described as the
+//! expression tree it is computed by, so that functions which only differ by their node indices,
+//! by the sharing of common subexpressions or by their dead values compare equal. The operands of
+//! commutative operations are compared in any order, and phis are only compared by the block they
+//! are placed in.
+
+use petgraph::graph::NodeIndex;
+
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::{NodeType, SSA};
+use crate::middle::ssa::ssastorage::SSAStorage;
+use crate::middle::ssa::utils;
+
+use std::collections::{BTreeMap, HashMap};
+
+/// A value computed differently by two functions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SsaDiff {
+    /// What the value is: `$rax` for the register `rax` when the function returns, or
+    /// `selector@` followed by the address of a block for its selector.
+    pub root: String,
+    /// The value in the first function, `None` if it has none.
+    pub left: Option<NodeIndex>,
+    /// The value in the second function, `None` if it has none.
+    pub right: Option<NodeIndex>,
+    /// The expressions computing `left` and `right`, empty for a missing value.
+    pub left_expr: String,
+    pub right_expr: String,
+}
+
+/// Returns the values computed differently by `a` and `b`, ordered by root.
+pub fn diff(a: &SSAStorage, b: &SSAStorage) -> Vec<SsaDiff> {
+    let mut left_roots = Exprs::new(a).roots();
+    let mut right_roots = Exprs::new(b).roots();
+    let mut roots = left_roots.keys().cloned().collect::<Vec<_>>();
+    roots.extend(right_roots.keys().cloned());
+    roots.sort();
+    roots.dedup();
+
+    roots
+        .into_iter()
+        .filter_map(|root| {
+            let (left, left_expr) = left_roots.remove(&root).unwrap_or_default();
+            let (right, right_expr) = right_roots.remove(&root).unwrap_or_default();
+            if left.is_some() && left_expr == right_expr {
+                return None;
+            }
+            Some(SsaDiff {
+                root,
+                left,
+                right,
+                left_expr,
+                right_expr,
+            })
+        })
+        .collect()
+}
+
+// Expressions of the values of a function, memoized.
+struct Exprs<'a> {
+    ssa: &'a SSAStorage,
+    memo: HashMap<NodeIndex, String>,
+}
+
+impl<'a> Exprs<'a> {
+    fn new(ssa: &'a SSAStorage) -> Exprs<'a> {
+        Exprs {
+            ssa,
+            memo: HashMap::new(),
+        }
+    }
+
+    // Values compared, with their expressions, by root.
+    fn roots(mut self) -> BTreeMap<String, (Option<NodeIndex>, String)> {
+        let ssa = self.ssa;
+        let mut roots = BTreeMap::new();
+        if let Some(exit_state) = ssa.exit_node().and_then(|n| ssa.registers_in(n)) {
+            for (id, &(value, _)) in utils::register_state_info(exit_state, ssa).iter() {
+                let name = ssa.regfile.get_name(id).unwrap_or("mem");
+                let root = format!("${}", name);
+                roots.insert(root, (Some(value), self.expr(value)));
+            }
+        }
+        for block in ssa.blocks() {
+            let selector = match ssa.selector_in(block) {
+                Some(selector) => selector,
+                None => continue,
+            };
+            let addr = ssa.starting_address(block).unwrap_or_default();
+            let root = format!("selector@{}", addr);
+            roots.insert(root, (Some(selector), self.expr(selector)));
+        }
+        roots
+    }
+
+    fn expr(&mut self, node: NodeIndex) -> String {
+        if let Some(expr) = self.memo.get(&node) {
+            return expr.clone();
+        }
+        let ssa = self.ssa;
+        let expr = if let Some(value) = ssa.constant_value(node) {
+            format!("#x{:x}", value)
+        } else {
+            match ssa.node_data(node).map(|nd| nd.nt) {
+                Ok(NodeType::Op(opcode)) => {
+                    let mut operands = ssa
+                        .operands_of(node)
+                        .into_iter()
+                        .map(|operand| self.expr(operand))
+                        .collect::<Vec<_>>();
+                    if opcode.is_commutative() {
+                        operands.sort();
+                    }
+                    let width = ssa
+                        .node_data(node)
+                        .ok()
+                        .and_then(|nd| nd.vt.width().get_width())
+                        .unwrap_or(0);
+                    format!("{:?}:{}({})", opcode, width, operands.join(", "))
+                }
+                Ok(NodeType::Phi) => {
+                    let block = ssa.block_for(node);
+                    let addr = block.and_then(|b| ssa.starting_address(b));
+                    format!("phi@{}", addr.unwrap_or_default())
+                }
+                Ok(NodeType::Comment(comment)) => comment,
+                Ok(NodeType::Undefined) => "undefined".to_owned(),
+                Err(_) => "invalid".to_owned(),
+            }
+        };
+        self.memo.insert(node, expr.clone());
+        expr
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    // rax = rdi + rsi, computed twice.
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const SHARED_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.sum(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Mem = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %4: $Unknown64 = %1 + %2;
+        [@0x001004.0001] %5: $Unknown64 = %2 + %1;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %4;
+        $rdx = %5;
+        $mem = %3;
+}
+";
+
+    #[test]
+    fn diff_ignores_sharing() {
+        let ssa = ir_reader::parse_il(SHARED_TXT, REGISTER_FILE.clone());
+        let shared = ir_reader::parse_il(
+            &SHARED_TXT.replace("$rdx = %5", "$rdx = %4"),
+            REGISTER_FILE.clone(),
+        );
+        assert!(diff(&ssa, &shared).is_empty());
+
+        let sub = ir_reader::parse_il(
+            &SHARED_TXT.replace("%2 + %1", "%2 - %1"),
+            REGISTER_FILE.clone(),
+        );
+        let diffs = diff(&ssa, &sub);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].root, "$rdx");
+        assert!(diffs[0].left.is_some() && diffs[0].right.is_some());
+        assert_ne!(diffs[0].left_expr, diffs[0].right_expr);
+    }
+}
@@ -65,6 +65,10 @@ impl GraphDot for SSAStorage {
         }
     }
 
+    fn node_subcluster(&self, i: &Self::NodeIndex) -> Option<u64> {
+        self.address(*i).map(|addr| addr.address)
+    }
+
     fn edge_source(&self, i: &Self::EdgeIndex) -> Self::NodeIndex {
         let edge_opt = &self.g.edge_references().find(|x| x.id() == *i);
         if edge_opt.is_none() {
@@ -99,7 +103,7 @@ impl GraphDot for SSAStorage {
         };
         let edge = edge_opt.unwrap();
         match *edge.weight() {
-            EdgeData::ContainedInBB(_) | EdgeData::RegisterState => true,
+            EdgeData::ContainedInBB(_) | EdgeData::RegisterState | EdgeData::PhiSource(_) => true,
             EdgeData::Data(_) => {
                 // Skip data edges from registers to registerstates.
                 if let NodeData::RegisterState = self.g[edge.source()] {
@@ -160,7 +164,7 @@ impl GraphDot for SSAStorage {
             EdgeData::Selector => vec![("color".to_string(), "purple".to_string())],
             EdgeData::ReplacedBy => vec![("color".to_string(), "brown".to_string())],
             EdgeData::RegisterInfo => vec![("color".to_string(), "yellow".to_string())],
-            EdgeData::RegisterState => unreachable!(),
+            EdgeData::RegisterState | EdgeData::PhiSource(_) => unreachable!(),
         };
 
         DotAttrBlock::Hybrid(prefix, attr)
@@ -183,7 +187,14 @@ impl GraphDot for SSAStorage {
                         addr.as_ref().unwrap()
                     ))
                 }
-                r.push_str(&format!("\"[i{}] {:?}\"", w, opc));
+                let ty = if vi.is_memory() {
+                    "mem".to_owned()
+                } else if vi.is_float() {
+                    format!("f{}", w)
+                } else {
+                    format!("i{}", w)
+                };
+                r.push_str(&format!("\"[{}] {:?}\"", ty, opc));
                 if addr.is_some() {
                     r.push_str(">");
                 }
@@ -279,3 +290,93 @@ impl GraphDot for SSAStorage {
         DotAttrBlock::Hybrid(prefix, attr)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::dot;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    // add rax, rdi; sub rax, rsi
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const TWO_INSTS_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.two_insts(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rax;
+        %2: $Unknown64 = $rdi;
+        %3: $Unknown64 = $rsi;
+        %4: $Mem = $mem;
+    bb_0x001000.0000(sz 0x6):
+        [@0x001000.0001] %5: $Unknown64 = %1 + %2;
+        [@0x001000.0002] %6: $Unknown1 = %5 == #x0;
+        [@0x001003.0001] %7: $Unknown64 = %5 - %3;
+        [@0x001003.0002] %8: $Unknown1 = %7 == #x0;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %7;
+        $mem = %4;
+}
+";
+
+    // Innermost cluster each node is drawn in.
+    fn clusters_of(dot: &str) -> HashMap<String, String> {
+        let mut stack = Vec::new();
+        let mut clusters = HashMap::new();
+        for line in dot.lines() {
+            if line.starts_with("subgraph ") {
+                stack.push(line.split_whitespace().nth(1).unwrap().to_owned());
+            } else if line == "}" {
+                stack.pop();
+            } else if line.starts_with('n') && !line.contains("->") {
+                let node = line
+                    .split_whitespace()
+                    .next()
+                    .unwrap()
+                    .trim_end_matches(';');
+                if let Some(cluster) = stack.last() {
+                    clusters.insert(node.to_owned(), cluster.clone());
+                }
+            }
+        }
+        clusters
+    }
+
+    #[test]
+    fn nodes_clustered_by_instruction() {
+        let ssa = ir_reader::parse_il(TWO_INSTS_TXT, REGISTER_FILE.clone());
+        let clusters = clusters_of(&dot::emit_dot_by_instruction(&ssa));
+        let mut by_address = HashMap::<u64, Vec<String>>::new();
+        for node in ssa.blocks().into_iter().flat_map(|b| ssa.exprs_in(b)) {
+            if let Some(addr) = ssa.address(node) {
+                let cluster = clusters[&format!("n{}", node.index())].clone();
+                by_address
+                    .entry(addr.address)
+                    .or_insert_with(Vec::new)
+                    .push(cluster);
+            }
+        }
+        assert_eq!(by_address.len(), 2);
+        for (addr, clusters) in &by_address {
+            assert_eq!(clusters.len(), 2);
+            assert!(clusters[0].ends_with(&format!("_{:x}", addr)));
+            assert_eq!(clusters[0], clusters[1]);
+        }
+        assert_ne!(by_address[&0x1000][0], by_address[&0x1003][0]);
+    }
+}
@@ -13,29 +13,37 @@ use petgraph::graph::{EdgeIndex, NodeIndex};
 use petgraph::stable_graph::StableDiGraph;
 use petgraph::visit::{EdgeRef, IntoEdgeReferences};
 use petgraph::EdgeDirection;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::cmp::{Ordering, PartialEq, PartialOrd};
 use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::fmt::{self, Debug};
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
-use std::{default, u64};
+use std::{default, mem, u64};
 
 use super::cfg_traits::{CFGMod, CFG};
 use super::graph_traits::{ConditionInfo, EdgeInfo, Graph};
 use super::ssa_traits::NodeData as TNodeData;
 use super::ssa_traits::NodeType as TNodeType;
-use super::ssa_traits::{SSAExtra, SSAMod, SSAWalk, ValueInfo, SSA};
+use super::ssa_traits::{SSAExtra, SSAMod, SSAWalk, SsaError, ValueInfo, SSA};
 
 #[cfg(feature = "trace_log")]
 use utils::logger;
 
 /// Structure that represents data that maybe associated with an node in the
 /// SSA
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AdditionalData {
     comments: Option<String>,
     flag: Option<String>,
     mark: bool,
     color: Option<u8>,
+    #[serde(default)]
+    xrefs: Vec<u64>,
+    #[serde(default)]
+    esil: Option<String>,
+    #[serde(default)]
+    segment: Option<String>,
 }
 
 impl AdditionalData {
@@ -45,6 +53,9 @@ impl AdditionalData {
             flag: None,
             mark: false,
             color: None,
+            xrefs: Vec::new(),
+            esil: None,
+            segment: None,
         }
     }
 }
@@ -56,6 +67,9 @@ impl default::Default for AdditionalData {
             flag: None,
             mark: false,
             color: None,
+            xrefs: Vec::new(),
+            esil: None,
+            segment: None,
         }
     }
 }
@@ -69,7 +83,7 @@ pub type AssociatedData = HashMap<NodeIndex, AdditionalData>;
 /// `RegisterState` is neither.
 /// Value nodes have a `ValueInfo` that can be extracted with
 /// `SSA::get_node_data`
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum NodeData {
     /// Represents on operation.
     Op(MOpcode, ValueInfo),
@@ -121,6 +135,42 @@ impl NodeData {
             _ => None,
         }
     }
+
+    /// Compares the kind, opcode and `ValueInfo` of two nodes, as a basis for value numbering.
+    /// Addresses of blocks and the registers phis are placed for are not compared.
+    pub fn structural_eq(&self, other: &NodeData) -> bool {
+        match (self, other) {
+            (&NodeData::Op(ref a, ref avi), &NodeData::Op(ref b, ref bvi)) => a == b && avi == bvi,
+            (&NodeData::Phi(ref avi, _), &NodeData::Phi(ref bvi, _)) => avi == bvi,
+            (&NodeData::Comment(ref avi, ref a), &NodeData::Comment(ref bvi, ref b)) => {
+                avi == bvi && a == b
+            }
+            (&NodeData::Undefined(ref avi), &NodeData::Undefined(ref bvi)) => avi == bvi,
+            (&NodeData::BasicBlock(_, asz), &NodeData::BasicBlock(_, bsz)) => asz == bsz,
+            _ => mem::discriminant(self) == mem::discriminant(other),
+        }
+    }
+
+    /// Hashes what `structural_eq` compares, so that structurally equal nodes hash equal.
+    pub fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match *self {
+            NodeData::Op(ref op, ref vi) => {
+                op.hash(state);
+                vi.hash(state);
+            }
+            NodeData::Phi(ref vi, _) | NodeData::Undefined(ref vi) => vi.hash(state),
+            NodeData::Comment(ref vi, ref s) => {
+                vi.hash(state);
+                s.hash(state);
+            }
+            NodeData::BasicBlock(_, sz) => sz.hash(state),
+            NodeData::Removed
+            | NodeData::Unreachable
+            | NodeData::DynamicAction
+            | NodeData::RegisterState => {}
+        }
+    }
 }
 
 // Implement display helper for NodeData to make it a little nicer to read prefix notation.
@@ -138,7 +188,7 @@ impl fmt::Display for NodeData {
 }
 
 /// Edge type for the SSAStorage-internal petgraph.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum EdgeData {
     /// Edge from action to action. Represents control flow. The number is
     /// used to distinguish true branch, false branch, etc.
@@ -150,6 +200,9 @@ pub enum EdgeData {
     Data(u8),
     /// Edge from value to BasicBlock.
     ContainedInBB(MAddress),
+    /// Edge from phi to action. Ties the argument of the phi with the same
+    /// index to the predecessor of its block it flows from.
+    PhiSource(u8),
     /// Edge from value or RegisterState to comment. Represent register infor-
     /// mation for every value.
     RegisterInfo,
@@ -209,6 +262,243 @@ impl SSAStorage {
             })
             .collect::<HashMap<_, _>>()
     }
+
+    /// Returns the actions adjacent to `node` through control edges in `direction`, with the
+    /// labels of these edges, sorted by label.
+    fn gather_labeled_adjacences(
+        &self,
+        node: NodeIndex,
+        direction: EdgeDirection,
+    ) -> Vec<(NodeIndex, u8)> {
+        let mut adjacent = Vec::new();
+        let mut walk = self.g.neighbors_directed(node, direction).detach();
+        while let Some((edge, othernode)) = walk.next(&self.g) {
+            if let Some(&EdgeData::Control(i)) = self.g.edge_weight(edge) {
+                adjacent.push((othernode, i));
+            }
+        }
+        adjacent.sort_by(|a, b| a.1.cmp(&b.1));
+        adjacent
+    }
+
+    /// Returns the actions reachable from the entry node in reverse postorder.
+    fn reverse_postorder(&self) -> Vec<NodeIndex> {
+        let mut postorder = Vec::new();
+        let entry = match self.entry_node() {
+            Some(entry) => entry,
+            None => return postorder,
+        };
+        let mut visited = HashSet::new();
+        let mut stack = vec![(entry, self.succs_of(entry))];
+        visited.insert(entry);
+        while let Some((node, mut succs)) = stack.pop() {
+            if let Some(next) = succs.pop() {
+                stack.push((node, succs));
+                if visited.insert(next) {
+                    let next_succs = self.succs_of(next);
+                    stack.push((next, next_succs));
+                }
+            } else {
+                postorder.push(node);
+            }
+        }
+        postorder.reverse();
+        postorder
+    }
+
+    /// Computes the immediate dominator of every action reachable from the
+    /// entry node, using the Cooper-Harvey-Kennedy iterative algorithm.
+    ///
+    /// The entry node is its own immediate dominator.
+    pub fn immediate_dominators(&self) -> HashMap<NodeIndex, NodeIndex> {
+        let rpo = self.reverse_postorder();
+        let order = rpo
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| (n, i))
+            .collect::<HashMap<_, _>>();
+        let mut idom: Vec<Option<usize>> = vec![None; rpo.len()];
+        if rpo.is_empty() {
+            return HashMap::new();
+        }
+        idom[0] = Some(0);
+
+        let intersect = |idom: &Vec<Option<usize>>, mut f1: usize, mut f2: usize| {
+            while f1 != f2 {
+                while f1 > f2 {
+                    f1 = idom[f1].expect("Unprocessed node in dominator tree");
+                }
+                while f2 > f1 {
+                    f2 = idom[f2].expect("Unprocessed node in dominator tree");
+                }
+            }
+            f1
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, &node) in rpo.iter().enumerate().skip(1) {
+                let mut new_idom = None;
+                for pred in self.preds_of(node) {
+                    let p = match order.get(&pred) {
+                        Some(&p) if idom[p].is_some() => p,
+                        _ => continue,
+                    };
+                    new_idom = Some(match new_idom {
+                        None => p,
+                        Some(cur) => intersect(&idom, p, cur),
+                    });
+                }
+                if new_idom.is_some() && idom[i] != new_idom {
+                    idom[i] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        idom.iter()
+            .enumerate()
+            .filter_map(|(i, d)| d.map(|d| (rpo[i], rpo[d])))
+            .collect()
+    }
+
+    /// Computes the dominance frontier of every action reachable from the
+    /// entry node.
+    ///
+    /// Follows the Cooper-Harvey-Kennedy formulation: for every join point,
+    /// walk up the dominator tree from each predecessor until the immediate
+    /// dominator of the join point is reached.
+    pub fn dominance_frontier(&self) -> HashMap<NodeIndex, Vec<NodeIndex>> {
+        let idom = self.immediate_dominators();
+        let mut frontier = idom
+            .keys()
+            .map(|&n| (n, Vec::new()))
+            .collect::<HashMap<NodeIndex, Vec<NodeIndex>>>();
+        for (&node, &node_idom) in &idom {
+            let preds = self
+                .preds_of(node)
+                .into_iter()
+                .filter(|p| idom.contains_key(p))
+                .collect::<HashSet<_>>();
+            if preds.len() < 2 {
+                continue;
+            }
+            for pred in preds {
+                let mut runner = pred;
+                while runner != node_idom {
+                    let df = frontier.entry(runner).or_insert_with(Vec::new);
+                    if !df.contains(&node) {
+                        df.push(node);
+                    }
+                    if idom[&runner] == runner {
+                        break;
+                    }
+                    runner = idom[&runner];
+                }
+            }
+        }
+        frontier
+    }
+
+    /// Returns `true` if `block` is a loop header, i.e. the target of a back edge coming from a
+    /// block it dominates. To test several blocks, compute `loop_headers` once instead.
+    pub fn is_loop_header(&self, block: NodeIndex) -> bool {
+        self.loop_headers().contains(&block)
+    }
+
+    /// Returns the lowest and highest addresses of the nodes contained in `block`, or `None` if
+    /// it contains none.
+    pub fn block_address_range(&self, block: NodeIndex) -> Option<(MAddress, MAddress)> {
+        let addrs = self
+            .exprs_in(block)
+            .into_iter()
+            .filter_map(|expr| self.address(expr))
+            .collect::<Vec<_>>();
+        Some((*addrs.iter().min()?, *addrs.iter().max()?))
+    }
+
+    /// Returns the values in dataflow order, where every value comes after its operands. The
+    /// operands of phis are not waited for, which breaks the cycles of loops. This is the order in
+    /// which forward dataflow analyses and constant folding evaluate the values.
+    pub fn iter_values_dataflow(&self) -> impl Iterator<Item = NodeIndex> {
+        let mut order = Vec::new();
+        let mut visited = HashSet::new();
+        for value in self.values() {
+            if !visited.insert(value) {
+                continue;
+            }
+            let mut stack = vec![(value, self.dataflow_operands(value))];
+            while let Some((node, mut operands)) = stack.pop() {
+                if let Some(next) = operands.pop() {
+                    stack.push((node, operands));
+                    if visited.insert(next) {
+                        let next_operands = self.dataflow_operands(next);
+                        stack.push((next, next_operands));
+                    }
+                } else {
+                    order.push(node);
+                }
+            }
+        }
+        order.into_iter()
+    }
+
+    // Operands `value` is evaluated after, last one first.
+    fn dataflow_operands(&self, value: NodeIndex) -> Vec<NodeIndex> {
+        if self.is_phi(value) {
+            return Vec::new();
+        }
+        let mut operands = self.operands_of(value);
+        operands.reverse();
+        operands
+    }
+
+    /// Returns the loop headers of the control flow graph, see `is_loop_header`.
+    pub fn loop_headers(&self) -> HashSet<NodeIndex> {
+        let idom = self.immediate_dominators();
+        let dominates = |a: NodeIndex, mut b: NodeIndex| loop {
+            if a == b {
+                return true;
+            }
+            match idom.get(&b) {
+                Some(&d) if d != b => b = d,
+                _ => return false,
+            }
+        };
+        idom.keys()
+            .cloned()
+            .filter(|&block| {
+                self.preds_of(block)
+                    .into_iter()
+                    .any(|pred| idom.contains_key(&pred) && dominates(block, pred))
+            })
+            .collect()
+    }
+
+    /// Returns the index after the last one used by the arguments of `phi`.
+    fn next_phi_index(&self, phi: NodeIndex) -> u8 {
+        self.g
+            .edges_directed(phi, EdgeDirection::Outgoing)
+            .filter_map(|edge| match *edge.weight() {
+                EdgeData::Data(i) | EdgeData::PhiSource(i) => Some(i + 1),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the edges tying the argument `index` of `phi` to predecessors.
+    fn phi_source_edges(&self, phi: NodeIndex, index: u8) -> Vec<EdgeIndex> {
+        self.g
+            .edges_directed(phi, EdgeDirection::Outgoing)
+            .filter(|edge| match *edge.weight() {
+                EdgeData::PhiSource(i) => i == index,
+                _ => false,
+            })
+            .map(|edge| edge.id())
+            .collect()
+    }
 }
 
 /// //////////////////////////////////////////////////////////////////////////
@@ -265,34 +555,7 @@ impl Graph for SSAStorage {
         // Before replace, we need to copy over the edges.
         assert!(self.constant(i).is_none());
 
-        let mut walk = self
-            .g
-            .neighbors_directed(i, EdgeDirection::Incoming)
-            .detach();
-        while let Some((edge, othernode)) = walk.next(&self.g) {
-            if let Some(&EdgeData::Data(d)) = self.g.edge_weight(edge) {
-                if othernode == j {
-                    // Avoid recursion use
-                    self.g.remove_edge(edge);
-                    continue;
-                }
-                match self.g.node_weight(othernode) {
-                    Some(&NodeData::Op(_, _)) | Some(&NodeData::RegisterState) => {
-                        self.op_use(othernode, d, j);
-                    }
-                    Some(&NodeData::Phi(_, _)) => {
-                        self.phi_use(othernode, j);
-                    }
-                    _ => {}
-                }
-            } else if let Some(&EdgeData::Selector) = self.g.edge_weight(edge) {
-                let bb = self
-                    .block_for(i)
-                    .expect("Value node does'n belong to any block");
-                self.set_selector(j, bb);
-            }
-            self.g.remove_edge(edge); // TODO: Need suggestion. Remove edges? if not, there might be an operation to get successor on the preds(i) which would result in access to this deleted node
-        }
+        self.replace_uses(i, j);
 
         let mut walk = self
             .g
@@ -348,7 +611,8 @@ impl Graph for SSAStorage {
             if edge != self.invalid_edge().expect("Invalid Edge is not defined") {
                 flag = match (self.g.edge_weight(edge), e) {
                     (Some(&EdgeData::Control(i)), EdgeData::Control(j))
-                    | (Some(&EdgeData::Data(i)), EdgeData::Data(j)) => {
+                    | (Some(&EdgeData::Data(i)), EdgeData::Data(j))
+                    | (Some(&EdgeData::PhiSource(i)), EdgeData::PhiSource(j)) => {
                         if i == j {
                             exist_edge = edge;
                             true
@@ -501,6 +765,14 @@ impl CFG for SSAStorage {
         self.gather_adjacences(exi, EdgeDirection::Outgoing, false)
     }
 
+    fn labeled_succs(&self, exi: Self::ActionRef) -> Vec<(Self::ActionRef, u8)> {
+        self.gather_labeled_adjacences(exi, EdgeDirection::Outgoing)
+    }
+
+    fn labeled_preds(&self, exi: Self::ActionRef) -> Vec<(Self::ActionRef, u8)> {
+        self.gather_labeled_adjacences(exi, EdgeDirection::Incoming)
+    }
+
     fn unconditional_block(&self, i: Self::ActionRef) -> Option<Self::ActionRef> {
         let mut walk = self
             .g
@@ -608,7 +880,7 @@ impl CFG for SSAStorage {
         if let Some(&NodeData::BasicBlock(ref addr, _)) = self.g.node_weight(si) {
             Some(*addr)
         } else if let Some(&NodeData::DynamicAction) = self.g.node_weight(si) {
-            Some(MAddress::new(u64::MAX, 0))
+            Some(MAddress::exit())
         } else {
             None
         }
@@ -638,29 +910,29 @@ impl CFGMod for SSAStorage {
         self.exit_node = ei;
     }
 
-    fn insert_block(&mut self, info: Self::BBInfo) -> Option<Self::ActionRef> {
+    fn insert_block(&mut self, info: Self::BBInfo) -> Result<Self::ActionRef, SsaError> {
         // XXX: TODO, Add the correct second argument to BasicBlock
         let bb = self
             .insert_node(NodeData::BasicBlock(info, 0))
-            .expect("Cannot insert new nodes");
+            .ok_or(SsaError::NodeLimit)?;
         let rs = self
             .insert_node(NodeData::RegisterState)
-            .expect("Cannot insert new nodes");
+            .ok_or(SsaError::NodeLimit)?;
         self.insert_edge(bb, rs, EdgeData::RegisterState);
         self.insert_edge(rs, bb, EdgeData::ContainedInBB(info));
-        Some(bb)
+        Ok(bb)
     }
 
-    fn insert_dynamic(&mut self) -> Option<Self::ActionRef> {
+    fn insert_dynamic(&mut self) -> Result<Self::ActionRef, SsaError> {
         let a = self
             .insert_node(NodeData::DynamicAction)
-            .expect("Cannot insert new nodes");
+            .ok_or(SsaError::NodeLimit)?;
         let rs = self
             .insert_node(NodeData::RegisterState)
-            .expect("Cannot insert new nodes");
+            .ok_or(SsaError::NodeLimit)?;
         self.insert_edge(a, rs, EdgeData::RegisterState);
         self.insert_edge(rs, a, EdgeData::ContainedInBB(MAddress::invalid_address()));
-        Some(a)
+        Ok(a)
     }
 
     fn insert_control_edge(
@@ -669,7 +941,10 @@ impl CFGMod for SSAStorage {
         target: Self::ActionRef,
         index: u8,
     ) -> Option<Self::CFEdgeRef> {
-        // XXX: TODO
+        if Some(source) == self.exit_node() {
+            radeco_err!("Control edge leaving the exit node {:?}", source);
+            return None;
+        }
         self.insert_edge(source, target, EdgeData::Control(index))
     }
 
@@ -790,6 +1065,9 @@ impl SSA for SSAStorage {
     }
 
     fn address(&self, ni: Self::ValueRef) -> Option<MAddress> {
+        if self.is_action(ni) {
+            return None;
+        }
         for edge in self.g.edges(ni) {
             if let EdgeData::ContainedInBB(addr) = *edge.weight() {
                 return Some(addr);
@@ -912,6 +1190,50 @@ impl SSA for SSAStorage {
         args
     }
 
+    fn phi_sources(&self, phi: Self::ValueRef) -> Vec<(Option<Self::ActionRef>, Self::ValueRef)> {
+        let mut preds = HashMap::<u8, Vec<NodeIndex>>::new();
+        for edge in self.g.edges_directed(phi, EdgeDirection::Outgoing) {
+            if let EdgeData::PhiSource(index) = *edge.weight() {
+                preds
+                    .entry(index)
+                    .or_insert_with(Vec::new)
+                    .push(edge.target());
+            }
+        }
+        let mut operands = self.sparse_operands_of(phi);
+        operands.sort_by_key(|&(index, _)| index);
+
+        let mut sources = Vec::new();
+        for (index, operand) in operands {
+            match preds.remove(&index) {
+                Some(mut from) => {
+                    from.sort_by_key(|&pred| self.starting_address(pred));
+                    sources.extend(from.into_iter().map(|pred| (Some(pred), operand)));
+                }
+                None => sources.push((None, operand)),
+            }
+        }
+        sources
+    }
+
+    fn phi_operand_from(
+        &self,
+        phi: Self::ValueRef,
+        pred: Self::ActionRef,
+    ) -> Option<Self::ValueRef> {
+        let index = self
+            .g
+            .edges_directed(phi, EdgeDirection::Outgoing)
+            .find_map(|edge| match *edge.weight() {
+                EdgeData::PhiSource(i) if edge.target() == pred => Some(i),
+                _ => None,
+            })?;
+        self.sparse_operands_of(phi)
+            .into_iter()
+            .find(|&(i, _)| i == index)
+            .map(|(_, operand)| operand)
+    }
+
     fn block_for(&self, i: Self::ValueRef) -> Option<Self::ActionRef> {
         let mut walk = self
             .g
@@ -1047,29 +1369,38 @@ impl SSAMod for SSAStorage {
         self.insert_edge(block, node, EdgeData::Selector);
     }
 
-    fn insert_op(&mut self, opc: MOpcode, vt: ValueInfo, _: Option<u64>) -> Option<Self::ValueRef> {
-        Some(
-            self.insert_node(NodeData::Op(opc, vt))
-                .expect("Cannot insert new nodes!"),
-        )
+    fn insert_op(
+        &mut self,
+        opc: MOpcode,
+        vt: ValueInfo,
+        _: Option<u64>,
+    ) -> Result<Self::ValueRef, SsaError> {
+        self.insert_node(NodeData::Op(opc, vt))
+            .ok_or(SsaError::NodeLimit)
     }
 
-    fn insert_const(&mut self, value: u64, size: Option<u16>) -> Option<Self::ValueRef> {
-        let data = NodeData::Op(MOpcode::OpConst(value), scalar!(size.unwrap_or(64)));
-        let id = self.insert_node(data).expect("Cannot insert new nodes");
-        Some(id)
+    fn insert_const(&mut self, value: u64, size: Option<u16>) -> Result<Self::ValueRef, SsaError> {
+        let width = size.unwrap_or(64);
+        if width == 0 {
+            return Err(SsaError::InvalidWidth(width));
+        }
+        let data = NodeData::Op(MOpcode::OpConst(value), scalar!(width));
+        self.insert_node(data).ok_or(SsaError::NodeLimit)
     }
 
-    fn insert_phi(&mut self, vt: ValueInfo) -> Option<Self::ValueRef> {
+    fn insert_phi(&mut self, vt: ValueInfo) -> Result<Self::ValueRef, SsaError> {
         self.insert_node(NodeData::Phi(vt, "".to_owned()))
+            .ok_or(SsaError::NodeLimit)
     }
 
-    fn insert_undefined(&mut self, vt: ValueInfo) -> Option<Self::ValueRef> {
+    fn insert_undefined(&mut self, vt: ValueInfo) -> Result<Self::ValueRef, SsaError> {
         self.insert_node(NodeData::Undefined(vt))
+            .ok_or(SsaError::NodeLimit)
     }
 
-    fn insert_comment(&mut self, vt: ValueInfo, msg: String) -> Option<Self::ValueRef> {
+    fn insert_comment(&mut self, vt: ValueInfo, msg: String) -> Result<Self::ValueRef, SsaError> {
         self.insert_node(NodeData::Comment(vt, msg))
+            .ok_or(SsaError::NodeLimit)
     }
 
     fn insert_into_block(&mut self, node: Self::ValueRef, block: Self::ActionRef, at: MAddress) {
@@ -1077,11 +1408,76 @@ impl SSAMod for SSAStorage {
     }
 
     fn phi_use(&mut self, phi: Self::ValueRef, node: Self::ValueRef) {
-        self.insert_edge(phi, node, EdgeData::Data(0));
+        if !self.operands_of(phi).contains(&node) {
+            let index = self.next_phi_index(phi);
+            self.insert_edge(phi, node, EdgeData::Data(index));
+        }
+    }
+
+    fn phi_use_from(&mut self, phi: Self::ValueRef, pred: Self::ActionRef, node: Self::ValueRef) {
+        let used = self
+            .sparse_operands_of(phi)
+            .into_iter()
+            .find(|&(_, operand)| operand == node);
+        let index = match used {
+            Some((index, _)) => index,
+            None => {
+                let index = self.next_phi_index(phi);
+                self.insert_edge(phi, node, EdgeData::Data(index));
+                index
+            }
+        };
+        // A predecessor passes a single value to the phi.
+        let old_sources = self
+            .g
+            .edges_directed(phi, EdgeDirection::Outgoing)
+            .filter(|edge| match *edge.weight() {
+                EdgeData::PhiSource(i) => edge.target() == pred && i != index,
+                _ => false,
+            })
+            .map(|edge| edge.id())
+            .collect::<Vec<_>>();
+        for edge in old_sources {
+            self.g.remove_edge(edge);
+        }
+        self.insert_edge(phi, pred, EdgeData::PhiSource(index));
+    }
+
+    fn redirect_phi_sources(
+        &mut self,
+        block: Self::ActionRef,
+        old_pred: Self::ActionRef,
+        new_pred: Self::ActionRef,
+    ) {
+        for phi in self.phis_in(block) {
+            let sources = self
+                .g
+                .edges_directed(phi, EdgeDirection::Outgoing)
+                .filter_map(|edge| match *edge.weight() {
+                    EdgeData::PhiSource(i) if edge.target() == old_pred => Some((edge.id(), i)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            for (edge, index) in sources {
+                self.g.remove_edge(edge);
+                self.insert_edge(phi, new_pred, EdgeData::PhiSource(index));
+            }
+        }
     }
 
     fn phi_unuse(&mut self, phi: Self::ValueRef, node: Self::ValueRef) {
+        let indices = self
+            .sparse_operands_of(phi)
+            .into_iter()
+            .filter(|&(_, operand)| operand == node)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
         self.remove_edges_between(phi, node);
+        for index in indices {
+            for edge in self.phi_source_edges(phi, index) {
+                self.g.remove_edge(edge);
+            }
+        }
     }
 
     fn op_use(&mut self, node: Self::ValueRef, index: u8, argument: Self::ValueRef) {
@@ -1097,6 +1493,48 @@ impl SSAMod for SSAStorage {
         self.remove_edges_between(op, operand);
     }
 
+    fn replace_uses(&mut self, node: Self::ValueRef, replacement: Self::ValueRef) {
+        let mut walk = self
+            .g
+            .neighbors_directed(node, EdgeDirection::Incoming)
+            .detach();
+        while let Some((edge, othernode)) = walk.next(&self.g) {
+            match self.g.edge_weight(edge) {
+                // Avoid making `replacement` use itself
+                Some(&EdgeData::Data(_)) if othernode == replacement => {}
+                Some(&EdgeData::Data(d)) => match self.g.node_weight(othernode) {
+                    Some(&NodeData::Op(_, _)) | Some(&NodeData::RegisterState) => {
+                        self.op_use(othernode, d, replacement);
+                    }
+                    Some(&NodeData::Phi(_, _)) => {
+                        // Keep the position of the argument, and the predecessors it flows from,
+                        // unless the phi already uses `replacement`.
+                        let used = self
+                            .sparse_operands_of(othernode)
+                            .into_iter()
+                            .find(|&(_, operand)| operand == replacement);
+                        if let Some((index, _)) = used {
+                            for source in self.phi_source_edges(othernode, d) {
+                                self.g[source] = EdgeData::PhiSource(index);
+                            }
+                        } else {
+                            self.insert_edge(othernode, replacement, EdgeData::Data(d));
+                        }
+                    }
+                    _ => {}
+                },
+                Some(&EdgeData::Selector) => {
+                    let bb = self
+                        .block_for(node)
+                        .expect("Value node does'n belong to any block");
+                    self.set_selector(replacement, bb);
+                }
+                _ => continue,
+            }
+            self.g.remove_edge(edge);
+        }
+    }
+
     fn replace_value(&mut self, node: Self::ValueRef, replacement: Self::ValueRef) {
         //self.replace_node(node, replacement);
         // if let Some(adata) = self.assoc_data.remove(&node) {
@@ -1193,6 +1631,32 @@ impl SSAExtra for SSAStorage {
         data.flag = Some(f);
     }
 
+    fn add_xref(&mut self, i: &Self::ValueRef, addr: u64) {
+        let data = self
+            .assoc_data
+            .entry(*i)
+            .or_insert_with(AdditionalData::new);
+        if !data.xrefs.contains(&addr) {
+            data.xrefs.push(addr);
+        }
+    }
+
+    fn set_esil(&mut self, i: &Self::ValueRef, esil: String) {
+        let data = self
+            .assoc_data
+            .entry(*i)
+            .or_insert_with(AdditionalData::new);
+        data.esil = Some(esil);
+    }
+
+    fn set_segment(&mut self, i: &Self::ValueRef, segment: String) {
+        let data = self
+            .assoc_data
+            .entry(*i)
+            .or_insert_with(AdditionalData::new);
+        data.segment = Some(segment);
+    }
+
     fn is_marked(&self, i: &Self::ValueRef) -> bool {
         self.assoc_data
             .get(i)
@@ -1222,6 +1686,120 @@ impl SSAExtra for SSAStorage {
     fn flags(&self, i: &Self::ValueRef) -> Option<String> {
         self.assoc_data.get(i).and_then(|data| data.flag.clone())
     }
+
+    fn esil(&self, i: &Self::ValueRef) -> Option<String> {
+        self.assoc_data.get(i).and_then(|data| data.esil.clone())
+    }
+
+    fn segment_of(&self, i: &Self::ValueRef) -> Option<String> {
+        self.assoc_data.get(i).and_then(|data| data.segment.clone())
+    }
+
+    fn xrefs_of(&self, i: &Self::ValueRef) -> Vec<u64> {
+        self.assoc_data
+            .get(i)
+            .map(|data| data.xrefs.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// ////////////////////////////////////////////////////////////////////////////
+/// / Serialization of SSAStorage.
+/// ////////////////////////////////////////////////////////////////////////////
+
+/// Serialized form of `SSAStorage`.
+///
+/// Nodes are renumbered densely in index order, so the result doesn't depend
+/// on the holes petgraph leaves behind in a `StableGraph` after removals.
+/// `replaced_map` only refers to nodes that no longer exist and is dropped.
+#[derive(Serialize, Deserialize)]
+struct SerializedSSA {
+    nodes: Vec<NodeData>,
+    edges: Vec<(usize, usize, EdgeData)>,
+    entry_node: Option<usize>,
+    exit_node: Option<usize>,
+    assoc_data: Vec<(usize, AdditionalData)>,
+    regfile: SubRegisterFile,
+}
+
+impl<'a> From<&'a SSAStorage> for SerializedSSA {
+    fn from(ssa: &'a SSAStorage) -> SerializedSSA {
+        let index_map = ssa
+            .g
+            .node_indices()
+            .enumerate()
+            .map(|(i, n)| (n, i))
+            .collect::<HashMap<_, _>>();
+        let nodes = ssa.g.node_indices().map(|n| ssa.g[n].clone()).collect();
+        let edges = ssa
+            .g
+            .edge_references()
+            .map(|e| (index_map[&e.source()], index_map[&e.target()], *e.weight()))
+            .collect();
+        let mut assoc_data = ssa
+            .assoc_data
+            .iter()
+            .filter_map(|(n, d)| index_map.get(n).map(|&i| (i, d.clone())))
+            .collect::<Vec<_>>();
+        assoc_data.sort_by_key(|&(i, _)| i);
+        SerializedSSA {
+            nodes,
+            edges,
+            entry_node: index_map.get(&ssa.entry_node).cloned(),
+            exit_node: index_map.get(&ssa.exit_node).cloned(),
+            assoc_data,
+            regfile: (*ssa.regfile).clone(),
+        }
+    }
+}
+
+impl From<SerializedSSA> for SSAStorage {
+    fn from(sssa: SerializedSSA) -> SSAStorage {
+        let mut ssa = SSAStorage::new();
+        let nodes = sssa
+            .nodes
+            .into_iter()
+            .map(|data| ssa.g.add_node(data))
+            .collect::<Vec<_>>();
+        for (source, target, data) in sssa.edges {
+            ssa.g.add_edge(nodes[source], nodes[target], data);
+        }
+        ssa.entry_node = sssa.entry_node.map_or(NodeIndex::end(), |i| nodes[i]);
+        ssa.exit_node = sssa.exit_node.map_or(NodeIndex::end(), |i| nodes[i]);
+        ssa.assoc_data = sssa
+            .assoc_data
+            .into_iter()
+            .map(|(i, d)| (nodes[i], d))
+            .collect();
+        ssa.regfile = Arc::new(sssa.regfile);
+        ssa
+    }
+}
+
+impl Serialize for SSAStorage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedSSA::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SSAStorage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<SSAStorage, D::Error> {
+        let sssa = SerializedSSA::deserialize(deserializer)?;
+        if sssa
+            .edges
+            .iter()
+            .any(|&(s, t, _)| s >= sssa.nodes.len() || t >= sssa.nodes.len())
+            || sssa
+                .entry_node
+                .iter()
+                .chain(sssa.exit_node.iter())
+                .chain(sssa.assoc_data.iter().map(|(i, _)| i))
+                .any(|&i| i >= sssa.nodes.len())
+        {
+            return Err(de::Error::custom("node index out of range"));
+        }
+        Ok(SSAStorage::from(sssa))
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -1391,3 +1969,365 @@ impl DoubleEndedIterator for Walker {
         self.nodes.pop_back()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ssa::verifier;
+    use crate::middle::{ir_reader, ir_writer};
+    use serde_json;
+    use std::fs;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    fn structural_hash_of(nd: &NodeData) -> u64 {
+        let mut hasher = ::std::collections::hash_map::DefaultHasher::new();
+        nd.structural_hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn structural_eq_ignores_identity() {
+        let add = |width: u16| {
+            let mut ssa = SSAStorage::new();
+            let block = ssa.insert_block(MAddress::new(0x1000, 0)).unwrap();
+            let node = ssa.insert_op(MOpcode::OpAdd, scalar!(width), None).unwrap();
+            ssa.insert_into_block(node, block, MAddress::new(0x1000 + width as u64, 1));
+            ssa.g[node].clone()
+        };
+        let (a, b, narrow) = (add(64), add(64), add(32));
+
+        assert!(a.structural_eq(&b));
+        assert_eq!(structural_hash_of(&a), structural_hash_of(&b));
+        assert!(!a.structural_eq(&narrow));
+        assert_ne!(structural_hash_of(&a), structural_hash_of(&narrow));
+
+        let blocks = [MAddress::new(0x1000, 0), MAddress::new(0x2000, 0)]
+            .iter()
+            .map(|&addr| NodeData::BasicBlock(addr, 0x10))
+            .collect::<Vec<_>>();
+        assert!(blocks[0].structural_eq(&blocks[1]));
+        assert_eq!(
+            structural_hash_of(&blocks[0]),
+            structural_hash_of(&blocks[1])
+        );
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const THREE_USES_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.uses(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x4):
+        [@0x001000.0001] %3: $Unknown64 = %1 + #x1;
+        [@0x001000.0002] %4: $Unknown1 = %1 == #x0;
+        JMP IF %4 0x001008.0000 ELSE 0x001004.0000
+    bb_0x001004.0000(sz 0x4):
+        [@0x001004.0001] %5: $Unknown64 = %3 - %1;
+        JMP 0x001008.0000
+    bb_0x001008.0000(sz 0x1):
+        %6: $Unknown64 = Phi(%3, %5);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %6;
+        $rax = %3;
+        $mem = %2;
+}
+";
+
+    #[test]
+    fn replace_uses_of_value() {
+        let mut ssa = ir_reader::parse_il(THREE_USES_TXT, REGISTER_FILE.clone());
+        let find = |ssa: &SSAStorage, opcode: &MOpcode| {
+            ssa.values()
+                .into_iter()
+                .find(|&v| ssa.opcode(v).as_ref() == Some(opcode))
+                .unwrap()
+        };
+        let (old, sub) = (find(&ssa, &MOpcode::OpAdd), find(&ssa, &MOpcode::OpSub));
+        let new = ssa.operands_of(old)[0];
+        let phi = ssa.values().into_iter().find(|&v| ssa.is_phi(v)).unwrap();
+        let exit_state = ssa.registers_in(ssa.exit_node().unwrap()).unwrap();
+        let mut uses = ssa.uses_of(old);
+        uses.sort();
+        let mut expected = vec![sub, phi, exit_state];
+        expected.sort();
+        assert_eq!(uses, expected);
+
+        ssa.replace_uses(old, new);
+        assert!(ssa.uses_of(old).is_empty());
+        assert!(ssa.node_data(old).is_ok());
+        assert_eq!(ssa.operands_of(sub), vec![new, new]);
+        assert!(ssa.operands_of(phi).contains(&new));
+        assert!(ssa.operands_of(exit_state).contains(&new));
+        for &user in &expected {
+            assert!(ssa.uses_of(new).contains(&user));
+        }
+
+        ssa.replace_value(old, new);
+        assert!(ssa.node_data(old).is_err());
+        assert!(verifier::verify(&ssa).is_ok());
+    }
+
+    #[test]
+    fn exit_node_has_no_successors() {
+        let mut ssa = ir_reader::parse_il(THREE_USES_TXT, REGISTER_FILE.clone());
+        let exit = ssa.exit_node().unwrap();
+        let entry = ssa.entry_node().unwrap();
+        assert!(ssa.is_action(exit) && !ssa.is_block(exit));
+        assert!(!ssa.blocks().contains(&exit));
+        assert_eq!(ssa.preds_of(exit).len(), 1);
+
+        assert!(ssa.succs_of(exit).is_empty());
+        assert!(ssa.labeled_succs(exit).is_empty());
+        assert!(ssa.outgoing_edges(exit).is_empty());
+        assert!(ssa.unconditional_block(exit).is_none());
+        assert!(ssa.conditional_blocks(exit).is_none());
+        assert_eq!(ssa.address(exit), None);
+
+        let edges = ssa.edges_count();
+        assert!(ssa.insert_control_edge(exit, entry, 2).is_none());
+        assert_eq!(ssa.edges_count(), edges);
+        assert!(ssa.succs_of(exit).is_empty());
+        assert!(verifier::verify(&ssa).is_ok());
+    }
+
+    // rax = (rdi + rsi) * rdx
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const CHAIN_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.chain(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Unknown64 = $rsi;
+        %3: $Unknown64 = $rdx;
+        %4: $Mem = $mem;
+    bb_0x001000.0000(sz 0x8):
+        [@0x001000.0001] %5: $Unknown64 = %1 + %2;
+        [@0x001004.0001] %6: $Unknown64 = %5 * %3;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rax = %6;
+        $mem = %4;
+}
+";
+
+    #[test]
+    fn values_in_dataflow_order() {
+        let ssa = ir_reader::parse_il(CHAIN_TXT, REGISTER_FILE.clone());
+        let order = ssa.iter_values_dataflow().collect::<Vec<_>>();
+        let position = |opcode: &MOpcode| {
+            order
+                .iter()
+                .position(|&v| ssa.opcode(v).as_ref() == Some(opcode))
+                .unwrap()
+        };
+        assert!(position(&MOpcode::OpAdd) < position(&MOpcode::OpMul));
+
+        // Every value comes once, after its operands unless it is a phi.
+        let three_uses = ir_reader::parse_il(THREE_USES_TXT, REGISTER_FILE.clone());
+        for ssa in &[ssa, three_uses] {
+            let order = ssa.iter_values_dataflow().collect::<Vec<_>>();
+            assert_eq!(order.len(), ssa.values().len());
+            let position = order
+                .iter()
+                .enumerate()
+                .map(|(i, &v)| (v, i))
+                .collect::<HashMap<_, _>>();
+            for (i, &value) in order.iter().enumerate() {
+                if !ssa.is_phi(value) {
+                    assert!(ssa.operands_of(value).iter().all(|o| position[o] < i));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dominance_frontier_if_merge() {
+        // a -> (b | c) -> d
+        let mut ssa = SSAStorage::new();
+        let a = ssa.insert_block(MAddress::new(0x0, 0)).unwrap();
+        let b = ssa.insert_block(MAddress::new(0x10, 0)).unwrap();
+        let c = ssa.insert_block(MAddress::new(0x20, 0)).unwrap();
+        let d = ssa.insert_block(MAddress::new(0x30, 0)).unwrap();
+        ssa.set_entry_node(a);
+        ssa.insert_control_edge(a, b, 1);
+        ssa.insert_control_edge(a, c, 0);
+        ssa.insert_control_edge(b, d, 2);
+        ssa.insert_control_edge(c, d, 2);
+
+        let idom = ssa.immediate_dominators();
+        assert_eq!(idom[&a], a);
+        assert_eq!(idom[&b], a);
+        assert_eq!(idom[&c], a);
+        assert_eq!(idom[&d], a);
+
+        let df = ssa.dominance_frontier();
+        assert!(df[&a].is_empty());
+        assert_eq!(df[&b], vec![d]);
+        assert_eq!(df[&c], vec![d]);
+        assert!(df[&d].is_empty());
+    }
+
+    #[test]
+    fn labeled_if_edges() {
+        // a -> (b | c) -> d
+        let mut ssa = SSAStorage::new();
+        let a = ssa.insert_block(MAddress::new(0x0, 0)).unwrap();
+        let b = ssa.insert_block(MAddress::new(0x10, 0)).unwrap();
+        let c = ssa.insert_block(MAddress::new(0x20, 0)).unwrap();
+        let d = ssa.insert_block(MAddress::new(0x30, 0)).unwrap();
+        ssa.set_entry_node(a);
+        ssa.insert_control_edge(a, b, 1);
+        ssa.insert_control_edge(a, c, 0);
+        ssa.insert_control_edge(b, d, 2);
+        ssa.insert_control_edge(c, d, 2);
+
+        assert_eq!(ssa.labeled_succs(a), vec![(c, 0), (b, 1)]);
+        assert_eq!(ssa.labeled_succs(b), vec![(d, 2)]);
+        assert!(ssa.labeled_succs(d).is_empty());
+        assert_eq!(ssa.labeled_preds(b), vec![(a, 1)]);
+        assert_eq!(ssa.labeled_preds(c), vec![(a, 0)]);
+        let mut preds = ssa.labeled_preds(d);
+        preds.sort();
+        assert_eq!(preds, vec![(b, 2), (c, 2)]);
+
+        // The labels agree with the conditional successors.
+        let cond = ssa.conditional_blocks(a).unwrap();
+        assert_eq!((cond.true_side, cond.false_side), (b, c));
+    }
+
+    #[test]
+    fn dominance_frontier_loop() {
+        // a -> b -> c -> b, c -> d
+        let mut ssa = SSAStorage::new();
+        let a = ssa.insert_block(MAddress::new(0x0, 0)).unwrap();
+        let b = ssa.insert_block(MAddress::new(0x10, 0)).unwrap();
+        let c = ssa.insert_block(MAddress::new(0x20, 0)).unwrap();
+        let d = ssa.insert_block(MAddress::new(0x30, 0)).unwrap();
+        ssa.set_entry_node(a);
+        ssa.insert_control_edge(a, b, 2);
+        ssa.insert_control_edge(b, c, 2);
+        ssa.insert_control_edge(c, b, 1);
+        ssa.insert_control_edge(c, d, 0);
+
+        let df = ssa.dominance_frontier();
+        assert!(df[&a].is_empty());
+        assert_eq!(df[&b], vec![b]);
+        assert_eq!(df[&c], vec![b]);
+        assert!(df[&d].is_empty());
+    }
+
+    #[test]
+    fn loop_header_of_while() {
+        // a -> b -> c -> b, b -> d
+        let mut ssa = SSAStorage::new();
+        let a = ssa.insert_block(MAddress::new(0x0, 0)).unwrap();
+        let b = ssa.insert_block(MAddress::new(0x10, 0)).unwrap();
+        let c = ssa.insert_block(MAddress::new(0x20, 0)).unwrap();
+        let d = ssa.insert_block(MAddress::new(0x30, 0)).unwrap();
+        ssa.set_entry_node(a);
+        ssa.insert_control_edge(a, b, 2);
+        ssa.insert_control_edge(b, c, 1);
+        ssa.insert_control_edge(b, d, 0);
+        ssa.insert_control_edge(c, b, 2);
+
+        assert!(ssa.is_loop_header(b));
+        assert!(!ssa.is_loop_header(a));
+        assert!(!ssa.is_loop_header(c));
+        assert!(!ssa.is_loop_header(d));
+
+        // There is no loop left once the back edge is removed.
+        let back_edge = ssa.find_edges_between(c, b)[0];
+        ssa.remove_control_edge(back_edge);
+        assert!(!ssa.is_loop_header(b));
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const TWO_INSNS_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.two_insns(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x7):
+        [@0x001000.0001] %3: $Unknown64 = %1 + #x1;
+        [@0x001000.0002] %4: $Unknown64 = %3 * #x2;
+        [@0x001004.0001] %5: $Unknown64 = %4 - #x3;
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %5;
+        $mem = %2;
+}
+";
+
+    #[test]
+    fn block_address_range_spans_instructions() {
+        let ssa = ir_reader::parse_il(TWO_INSNS_TXT, REGISTER_FILE.clone());
+        let block = ssa
+            .blocks()
+            .into_iter()
+            .find(|&b| ssa.starting_address(b) == Some(MAddress::new(0x1000, 0)))
+            .unwrap();
+        assert_eq!(
+            ssa.block_address_range(block),
+            Some((MAddress::new(0x1000, 1), MAddress::new(0x1004, 1)))
+        );
+        // Neither the entry nor the exit contains any node.
+        assert_eq!(ssa.block_address_range(ssa.exit_node().unwrap()), None);
+    }
+
+    #[test]
+    fn serde_roundtrip() {
+        let il = fs::read_to_string("test_files/loopy_main_ssa").unwrap();
+        let mut ssa = ir_reader::parse_il(&il, REGISTER_FILE.clone());
+        // Leave a hole in the node indices to check that they are renumbered
+        let dummy = ssa.insert_const(0, None).unwrap();
+        ssa.remove_value(dummy);
+
+        let json = serde_json::to_string(&ssa).unwrap();
+        let deserialized: SSAStorage = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(ssa.nodes_count(), deserialized.nodes_count());
+        assert_eq!(ssa.edges_count(), deserialized.edges_count());
+        assert_eq!(ssa.regfile.whole_names, deserialized.regfile.whole_names);
+
+        let mut expected = String::new();
+        ir_writer::emit_il(&mut expected, Some("sym.main".to_owned()), &ssa).unwrap();
+        let mut actual = String::new();
+        ir_writer::emit_il(&mut actual, Some("sym.main".to_owned()), &deserialized).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn serde_rejects_dangling_edges() {
+        let json = r#"{
+            "nodes": ["DynamicAction"],
+            "edges": [[0, 1, "RegisterState"]],
+            "entry_node": null,
+            "exit_node": 0,
+            "assoc_data": [],
+            "regfile": {
+                "whole_registers": [],
+                "whole_names": [],
+                "named_registers": {},
+                "alias_info": {},
+                "type_info": {}
+            }
+        }"#;
+        assert!(serde_json::from_str::<SSAStorage>(json).is_err());
+    }
+}
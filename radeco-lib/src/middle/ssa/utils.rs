@@ -1,10 +1,15 @@
 //! A few utility functions for working with an [`SSAStorage`].
 
+use crate::middle::ir::MOpcode;
 use crate::middle::regfile::{RegisterId, RegisterMap};
-use crate::middle::ssa::ssa_traits::ValueInfo;
+use crate::middle::ssa::cfg_traits::CFG;
+use crate::middle::ssa::ssa_traits::{ValueInfo, SSA};
 use crate::middle::ssa::ssastorage::{EdgeData, SSAStorage};
 
 use petgraph::prelude::*;
+use std::collections::HashSet;
+
+const UNCOND_EDGE: u8 = 2;
 
 /// Structured information about a call.
 pub struct CallInfo {
@@ -63,3 +68,99 @@ pub fn register_state_info(
     }
     ret
 }
+
+/// Returns the blocks whose only successor is themselves, through an unconditional edge, e.g. the
+/// block of a `jmp $`. These loop forever without doing anything else.
+pub fn self_loops(ssa: &SSAStorage) -> Vec<NodeIndex> {
+    ssa.blocks()
+        .into_iter()
+        .filter(|&block| ssa.labeled_succs(block) == vec![(block, UNCOND_EDGE)])
+        .collect()
+}
+
+/// Returns the actions reachable from the entry node from which the exit node is not reachable.
+/// Control reaching one of them is stuck in an infinite loop and never leaves the function.
+pub fn without_exit(ssa: &SSAStorage) -> HashSet<NodeIndex> {
+    let mut reaches_exit = HashSet::new();
+    let mut wl = ssa.exit_node().into_iter().collect::<Vec<_>>();
+    reaches_exit.extend(wl.iter().cloned());
+    while let Some(action) = wl.pop() {
+        for pred in ssa.preds_of(action) {
+            if reaches_exit.insert(pred) {
+                wl.push(pred);
+            }
+        }
+    }
+
+    let mut stuck = HashSet::new();
+    let mut visited = HashSet::new();
+    let mut wl = ssa.entry_node().into_iter().collect::<Vec<_>>();
+    visited.extend(wl.iter().cloned());
+    while let Some(action) = wl.pop() {
+        if !reaches_exit.contains(&action) {
+            stuck.insert(action);
+        }
+        for succ in ssa.succs_of(action) {
+            if visited.insert(succ) {
+                wl.push(succ);
+            }
+        }
+    }
+    stuck
+}
+
+/// Returns `true` if the function restores the stack frame of its caller on every path to its
+/// exit, e.g. through `leave; ret`: the frame pointer is reloaded from the slot its value points
+/// to, and the stack pointer is moved right past that slot.
+pub fn restores_frame(ssa: &SSAStorage) -> bool {
+    let regfile = &ssa.regfile;
+    let (sp, bp) = match (
+        regfile.register_id_by_alias("SP"),
+        regfile.register_id_by_alias("BP"),
+    ) {
+        (Some(sp), Some(bp)) => (sp, bp),
+        _ => return false,
+    };
+    let slot = match regfile.get_width(bp) {
+        Some(width) => width / 8,
+        None => return false,
+    };
+    let exit_state = match ssa.exit_node().and_then(|n| ssa.registers_in(n)) {
+        Some(exit_state) => register_state_info(exit_state, ssa),
+        None => return false,
+    };
+    match (exit_state.get(sp), exit_state.get(bp)) {
+        (Some(&(sp_value, _)), Some(&(bp_value, _))) => {
+            is_frame_restore(ssa, sp_value, bp_value, slot)
+        }
+        _ => false,
+    }
+}
+
+// Returns `true` if `sp` and `bp` are the stack and frame pointers right after popping a frame
+// with a saved frame pointer of `slot` bytes, looking through the phis merging such values.
+fn is_frame_restore(ssa: &SSAStorage, sp: NodeIndex, bp: NodeIndex, slot: u64) -> bool {
+    if ssa.is_phi(sp) && ssa.is_phi(bp) {
+        let block = match ssa.block_for(sp) {
+            Some(block) if ssa.block_for(bp) == Some(block) => block,
+            _ => return false,
+        };
+        return ssa.preds_of(block).into_iter().all(|pred| {
+            match (
+                ssa.phi_operand_from(sp, pred),
+                ssa.phi_operand_from(bp, pred),
+            ) {
+                (Some(sp), Some(bp)) => is_frame_restore(ssa, sp, bp, slot),
+                _ => false,
+            }
+        });
+    }
+    let frame = match (ssa.opcode(bp), ssa.operands_of(bp).get(1)) {
+        (Some(MOpcode::OpLoad), Some(&frame)) => frame,
+        _ => return false,
+    };
+    let operands = ssa.operands_of(sp);
+    ssa.opcode(sp) == Some(MOpcode::OpAdd)
+        && operands.contains(&frame)
+        && operands.iter().any(|&o| ssa.constant(o) == Some(slot))
+}
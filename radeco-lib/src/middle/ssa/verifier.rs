@@ -218,46 +218,78 @@ impl Verify for SSAStorage {
                         check!(op_len == n, SSAErr::WrongNumOperands(*exi, n, op_len));
                     }
 
+                    // The width of the result of some opcodes is fixed, whatever their operands.
+                    match opcode {
+                        MOpcode::OpNarrow(w0) | MOpcode::OpZeroExt(w0) | MOpcode::OpSignExt(w0) => {
+                            check!(w == w0, SSAErr::IncompatibleWidth(*exi, w0, w));
+                        }
+                        MOpcode::OpEq
+                        | MOpcode::OpGt
+                        | MOpcode::OpLt
+                        | MOpcode::OpFEq
+                        | MOpcode::OpFLt => {
+                            check!(w == 1, SSAErr::IncompatibleWidth(*exi, 1, w));
+                        }
+                        // A store only produces a new memory state.
+                        MOpcode::OpStore => {
+                            check!(w == 0, SSAErr::IncompatibleWidth(*exi, 0, w));
+                        }
+                        // An assumption is its predicate, which holds.
+                        MOpcode::OpAssume => {
+                            check!(w == 1, SSAErr::IncompatibleWidth(*exi, 1, w));
+                        }
+                        _ => {}
+                    }
+
                     // TODO: We do not consider OpStore and OpLoad's width now.
                     operands.retain(&opfilter);
 
                     if n == 0 || operands.len() == 0 {
                         return Ok(());
                     }
+                    let width_of = |x: NodeIndex| -> u16 {
+                        self.node_data(x)
+                            .map(|vi| vi.vt.width().get_width().unwrap_or(64))
+                            .unwrap()
+                    };
                     match opcode {
                         MOpcode::OpNarrow(w0) => {
-                            let opw = self
-                                .node_data(operands[0])
-                                .map(|vi| vi.vt.width().get_width().unwrap_or(64))
-                                .unwrap();
+                            let opw = width_of(operands[0]);
                             check!(opw > w0, SSAErr::IncompatibleWidth(*exi, opw, w0));
-                            check!(w == w0, SSAErr::IncompatibleWidth(*exi, w, w0));
                         }
                         MOpcode::OpZeroExt(w0) | MOpcode::OpSignExt(w0) => {
-                            let opw = self
-                                .node_data(operands[0])
-                                .map(|vi| vi.vt.width().get_width().unwrap_or(64))
-                                .unwrap();
+                            let opw = width_of(operands[0]);
                             check!(opw < w0, SSAErr::IncompatibleWidth(*exi, opw, w0));
-                            check!(w == w0, SSAErr::IncompatibleWidth(*exi, w, w0));
                         }
-                        MOpcode::OpEq | MOpcode::OpGt | MOpcode::OpLt => {
-                            check!(w == 1, SSAErr::IncompatibleWidth(*exi, 1, w));
+                        MOpcode::OpEq
+                        | MOpcode::OpGt
+                        | MOpcode::OpLt
+                        | MOpcode::OpFEq
+                        | MOpcode::OpFLt => {
+                            // Both sides of a comparison must have the same width.
+                            let w0 = width_of(operands[0]);
+                            for op in operands.iter() {
+                                let w1 = width_of(*op);
+                                check!(w0 == w1, SSAErr::IncompatibleWidth(*exi, w0, w1));
+                            }
+                        }
+                        // The value assumed on may have any width, not the predicate.
+                        MOpcode::OpAssume => {
+                            let pred = self.operands_of(*exi)[1];
+                            let w0 = width_of(pred);
+                            check!(w0 == 1, SSAErr::IncompatibleWidth(*exi, 1, w0));
                         }
                         // TODO: Width of OpStore and OpLoad now is not certain.
-                        MOpcode::OpCall | MOpcode::OpStore | MOpcode::OpLoad => {}
+                        MOpcode::OpCall
+                        | MOpcode::OpIndirectJump
+                        | MOpcode::OpStore
+                        | MOpcode::OpLoad => {}
                         _ => {
                             // All operands to an expr must have the same width.
-                            let w0 = self
-                                .node_data(operands[0])
-                                .map(|vi| vi.vt.width().get_width().unwrap_or(64))
-                                .unwrap();
+                            let w0 = width_of(operands[0]);
                             check!(w0 == w, SSAErr::IncompatibleWidth(*exi, w, w0));
                             for op in operands.iter() {
-                                let w1 = self
-                                    .node_data(*op)
-                                    .map(|vi| vi.vt.width().get_width().unwrap_or(64))
-                                    .unwrap();
+                                let w1 = width_of(*op);
                                 check!(w == w1, SSAErr::IncompatibleWidth(*exi, w, w1));
                             }
                         }
@@ -389,3 +421,81 @@ where
     ssa.verify_SCC(&register, &mut timestamp, &mut DFN, &mut LOW, &mut stack)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::middle::ir_reader;
+    use crate::middle::regfile::SubRegisterFile;
+    use serde_json;
+    use std::sync::Arc;
+
+    const REGISTER_PROFILE: &'static str = "test_files/x86_register_profile.json";
+
+    lazy_static! {
+        static ref REGISTER_FILE: Arc<SubRegisterFile> = {
+            let s = ::std::fs::read_to_string(REGISTER_PROFILE).unwrap();
+            let reg_profile = serde_json::from_str(&*s).unwrap();
+            Arc::new(SubRegisterFile::new(&reg_profile))
+        };
+    }
+
+    #[cfg_attr(rustfmt, rustfmt_skip)]
+    const WIDTHS_TXT: &str = "\
+; radeco-ir v1
+define-fun sym.widths(unknown) -> unknown {
+    entry-register-state:
+        %1: $Unknown64 = $rdi;
+        %2: $Mem = $mem;
+    bb_0x001000.0000(sz 0x0):
+        [@0x001000.0001] %3: $Unknown32 = Narrow32(%1);
+        [@0x001000.0002] %4: $Unknown64 = ZeroExt64(%3);
+        [@0x001000.0003] %5: $Unknown1 = %4 == %1;
+        [@0x001000.0004] %6: $Unknown64 = %4 + %1;
+        [@0x001000.0005] %7: $Mem = Store(%2, %6, %1);
+        RETURN
+    exit-node:
+    final-register-state:
+        $rdi = %6;
+        $zf = %5;
+        $mem = %7;
+}
+";
+
+    // Verifies `WIDTHS_TXT` with `from` replaced by `to`, returning the expected and found widths
+    // of the first width mismatch.
+    fn width_error(from: &str, to: &str) -> (u16, u16) {
+        assert!(WIDTHS_TXT.contains(from));
+        let ssa = ir_reader::parse_il(&WIDTHS_TXT.replace(from, to), REGISTER_FILE.clone());
+        match verify(&ssa) {
+            Err(SSAErr::IncompatibleWidth(_, expected, found)) => (expected, found),
+            r => panic!("expected a width mismatch, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn verify_opcode_widths() {
+        let ssa = ir_reader::parse_il(WIDTHS_TXT, REGISTER_FILE.clone());
+        assert!(verify(&ssa).is_ok());
+
+        // Casts produce the width they are named after.
+        assert_eq!(
+            width_error("$Unknown32 = Narrow32", "$Unknown16 = Narrow32"),
+            (32, 16)
+        );
+        assert_eq!(
+            width_error("$Unknown64 = ZeroExt64", "$Unknown32 = ZeroExt64"),
+            (64, 32)
+        );
+        // Comparisons produce a single bit, from operands of the same width.
+        assert_eq!(
+            width_error("$Unknown1 = %4 == %1", "$Unknown64 = %4 == %1"),
+            (1, 64)
+        );
+        assert_eq!(width_error("%4 == %1", "%3 == %1"), (32, 64));
+        // Stores only produce a memory state.
+        assert_eq!(width_error("$Mem = Store", "$Unknown64 = Store"), (0, 64));
+        // Arithmetic operands have the width of the result.
+        assert_eq!(width_error("%4 + %1", "%3 + %1"), (64, 32));
+    }
+}
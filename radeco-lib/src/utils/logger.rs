@@ -16,8 +16,89 @@
 //! `env_logger::init()`
 //!
 //! Check minidec/main.rs for an example of the same
+//!
+//! Messages may be tagged with a category, e.g.
+//! `radeco_trace!(target: "sccp", "{:?} --> {}", node, val)`. The category is used as the
+//! `log` target, and messages of a category can be silenced or enabled independently with
+//! [`set_filters`](fn.set_filters.html), e.g. `set_filters("ssaconstruct=trace,sccp=warn")`.
+//! Untagged messages, and messages of categories without a filter, are always let through to
+//! the logger.
 
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::sync::RwLock;
+
+/// Most verbose kind of message let through for a category.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Trace,
+}
+
+impl LogLevel {
+    fn from_name(name: &str) -> Option<LogLevel> {
+        match name {
+            "off" => Some(LogLevel::Off),
+            "error" => Some(LogLevel::Error),
+            "warn" => Some(LogLevel::Warn),
+            "trace" => Some(LogLevel::Trace),
+            _ => None,
+        }
+    }
+}
+
+lazy_static! {
+    static ref FILTERS: RwLock<HashMap<String, LogLevel>> = RwLock::new(HashMap::new());
+}
+
+/// Parses a comma separated list of `<category>=<level>`, where `<level>` is one of `off`,
+/// `error`, `warn` or `trace`.
+pub fn parse_filters(spec: &str) -> Result<Vec<(String, LogLevel)>, String> {
+    spec.split(',')
+        .filter(|f| !f.trim().is_empty())
+        .map(|f| {
+            let mut parts = f.splitn(2, '=');
+            let category = parts.next().unwrap_or("").trim();
+            let level = parts.next().map(str::trim);
+            match level.and_then(LogLevel::from_name) {
+                Some(level) if !category.is_empty() => Ok((category.to_owned(), level)),
+                _ => Err(format!(
+                    "Invalid log filter {}, expected <category>=<off|error|warn|trace>",
+                    f
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Sets the most verbose kind of message let through for `category`.
+pub fn set_filter(category: &str, level: LogLevel) {
+    FILTERS.write().unwrap().insert(category.to_owned(), level);
+}
+
+/// Sets the filters of every category in `spec`, see [`parse_filters`](fn.parse_filters.html).
+/// No filter is changed if `spec` is invalid.
+pub fn set_filters(spec: &str) -> Result<(), String> {
+    for (category, level) in parse_filters(spec)? {
+        set_filter(&category, level);
+    }
+    Ok(())
+}
+
+/// Removes the filters of all categories.
+pub fn clear_filters() {
+    FILTERS.write().unwrap().clear();
+}
+
+/// Returns whether a message of `category` at `level` is let through.
+pub fn enabled(category: &str, level: LogLevel) -> bool {
+    match FILTERS.read().unwrap().get(category) {
+        Some(&filter) => level != LogLevel::Off && level <= filter,
+        None => true,
+    }
+}
 
 pub enum Event<'a, T: 'a + Debug> {
     /// external -> internal
@@ -59,6 +140,14 @@ impl<'a, T: Debug> ToString for Event<'a, T> {
 
 #[macro_export]
 macro_rules! radeco_trace {
+    (target: $cat:expr, $($arg:tt)+) => ({
+        if cfg!(feature = "trace_log")
+            && $crate::utils::logger::enabled($cat, $crate::utils::logger::LogLevel::Trace)
+        {
+            #[cfg(feature="trace_log")]
+            debug!(target: $cat, $($arg)+);
+        }
+    });
     ($t: expr) => ({
         if cfg!(feature = "trace_log") {
             #[cfg(feature="trace_log")]
@@ -75,6 +164,14 @@ macro_rules! radeco_trace {
 
 #[macro_export]
 macro_rules! radeco_warn {
+    (target: $cat:expr, $($arg:tt)+) => ({
+        if cfg!(feature = "trace_log")
+            && $crate::utils::logger::enabled($cat, $crate::utils::logger::LogLevel::Warn)
+        {
+            #[cfg(feature="trace_log")]
+            warn!(target: $cat, $($arg)+);
+        }
+    });
     ($t: expr) => ({
         if cfg!(feature = "trace_log") {
             #[cfg(feature="trace_log")]
@@ -91,6 +188,14 @@ macro_rules! radeco_warn {
 
 #[macro_export]
 macro_rules! radeco_err {
+    (target: $cat:expr, $($arg:tt)+) => ({
+        if cfg!(feature = "trace_log")
+            && $crate::utils::logger::enabled($cat, $crate::utils::logger::LogLevel::Error)
+        {
+            #[cfg(feature="trace_log")]
+            error!(target: $cat, $($arg)+);
+        }
+    });
     ($t: expr) => ({
         if cfg!(feature = "trace_log") {
             #[cfg(feature="trace_log")]
@@ -104,3 +209,34 @@ macro_rules! radeco_err {
         }
     });
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn category_filter() {
+        assert!(parse_filters("ssaconstruct=loud").is_err());
+        assert!(parse_filters("=trace").is_err());
+        assert!(parse_filters("sccp").is_err());
+
+        set_filters("ssaconstruct=trace,sccp=warn").unwrap();
+        assert!(enabled("ssaconstruct", LogLevel::Trace));
+        assert!(!enabled("sccp", LogLevel::Trace));
+        assert!(enabled("sccp", LogLevel::Warn));
+        assert!(enabled("sccp", LogLevel::Error));
+        assert!(enabled("dce", LogLevel::Trace));
+
+        assert!(set_filters("sccp=off,dce=trace,bad").is_err());
+        assert!(enabled("sccp", LogLevel::Error));
+
+        set_filters("sccp=off").unwrap();
+        assert!(!enabled("sccp", LogLevel::Error));
+        assert!(enabled("ssaconstruct", LogLevel::Trace));
+        radeco_trace!(target: "sccp", "{} --> {}", 1, 2);
+        radeco_err!(target: "sccp", "suppressed");
+
+        clear_filters();
+        assert!(enabled("sccp", LogLevel::Trace));
+    }
+}
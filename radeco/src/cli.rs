@@ -1,13 +1,53 @@
 use clap::{App, Arg};
+use radeco_lib::analysis::engine::Snapshots;
+use radeco_lib::analysis::signatures::SignatureDb;
+use radeco_lib::utils::logger::{self, LogLevel};
+use std::path::PathBuf;
 use std::process;
 
 use super::MAX_ITERATIONS;
+use crate::core::{self, AddrRange, LoadOptions};
 
-pub fn parse_args() -> (Option<String>, Option<String>, bool, bool, bool, u32) {
+/// Options of the command line.
+pub struct Args {
+    /// Binary to load, the first one if several were passed.
+    pub bin: Option<String>,
+    /// Binaries to decompile each into its own directory.
+    pub targets: Vec<String>,
+    pub command: Option<String>,
+    pub is_append: bool,
+    pub is_batch: bool,
+    pub no_highlight: bool,
+    pub max_it: u32,
+    pub range: Option<AddrRange>,
+    /// Name of the function to process, and the depth of its callees to process too.
+    pub function: Option<(String, usize)>,
+    pub snapshots: Option<Snapshots>,
+    pub log_filters: Vec<(String, LogLevel)>,
+    pub callgraph_only: bool,
+    pub vars_json: bool,
+    pub signatures: Option<SignatureDb>,
+    pub load: LoadOptions,
+}
+
+pub fn parse_args() -> Args {
     let vs = env!("VERSION_STR");
     let matches = App::new("radeco")
         .version(vs)
-        .arg(Arg::with_name("BIN").help("Binary to load").required(false))
+        .arg(
+            Arg::with_name("BIN")
+                .help("Binaries to load, each is decompiled into <BIN>_out in batch mode")
+                .required(false)
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("targets")
+                .help("Decompile the binaries listed in <FILE>, one per line, in batch mode")
+                .long("targets")
+                .value_name("FILE")
+                .required(false)
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("command")
                 .help("Run a custom command in batch mode")
@@ -24,27 +64,135 @@ pub fn parse_args() -> (Option<String>, Option<String>, bool, bool, bool, u32) {
                 .required(false)
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("max-insns")
+                .help("Skip the functions of more than <N> instructions")
+                .long("max-insns")
+                .value_name("N")
+                .required(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("range")
+                .help("Only process functions starting in <start>-<end> in batch mode")
+                .short("r")
+                .long("range")
+                .required(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("function")
+                .help("Only process the function <NAME> in batch mode")
+                .short("f")
+                .long("function")
+                .value_name("NAME")
+                .required(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("depth")
+                .help("Also process the callees of --function up to <N> calls away")
+                .long("depth")
+                .value_name("N")
+                .required(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("snapshots")
+                .help("Write the IR of every function after each pass to <DIR>")
+                .long("snapshots")
+                .value_name("DIR")
+                .required(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("signatures")
+                .help("Type the calls to the functions of the signature database <FILE>")
+                .long("signatures")
+                .value_name("FILE")
+                .required(false)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("log")
+                .help("Log verbosity per category, e.g. ssaconstruct=trace,sccp=warn")
+                .long("log")
+                .value_name("SPEC")
+                .required(false)
+                .takes_value(true),
+        )
+        .arg(Arg::from_usage(
+            "--snapshot-dot 'Also write a dot graph with every snapshot'",
+        ))
         .arg(Arg::from_usage(
             "-a --append 'Append separator to the end of every output.'",
         ))
         .arg(Arg::from_usage("-b --batch 'Decompile the whole binary'"))
+        .arg(Arg::from_usage(
+            "--callgraph-only 'Write the call graph to callgraph.dot without analyzing functions'",
+        ))
         .arg(Arg::from_usage(
             "-l --no-highlight 'Disable syntax highlight on output'",
         ))
+        .arg(Arg::from_usage(
+            "--esil 'Write the IR of every function with the ESIL of each instruction'",
+        ))
+        .arg(Arg::from_usage(
+            "--vars-json 'Also write the variables of every function as JSON next to its pseudocode'",
+        ))
         .get_matches();
     let is_append = matches.is_present("append");
     let is_batch = matches.is_present("batch");
     let no_highlight = matches.is_present("no-highlight");
-    let bin = matches.value_of("BIN").map(|s| s.to_string());
+    let mut bins = matches
+        .values_of("BIN")
+        .map(|v| v.map(str::to_string).collect::<Vec<_>>())
+        .unwrap_or_default();
+    if let Some(path) = matches.value_of("targets") {
+        match core::read_targets(path) {
+            Ok(targets) => bins.extend(targets),
+            Err(err) => {
+                eprintln!("Failed to read {}: {}", path, err);
+                process::exit(1);
+            }
+        }
+    }
+    let vars_json = matches.is_present("vars-json");
+    // Several binaries are each decompiled into their own directory, as is a single one whose
+    // variables are written along its pseudocode.
+    let targets = if bins.len() > 1 || matches.is_present("targets") || vars_json {
+        bins.clone()
+    } else {
+        Vec::new()
+    };
+    let bin = bins.into_iter().next();
     let command = matches.value_of("command").map(|s| s.to_string());
+    let callgraph_only = matches.is_present("callgraph-only");
+    let esil = matches.is_present("esil");
 
     if is_batch && bin.is_none() {
         eprintln!("Pass a binary for batch mode");
-        process::exit(0);
+        process::exit(1);
+    }
+    if callgraph_only && bin.is_none() {
+        eprintln!("Pass a binary for --callgraph-only");
+        process::exit(1);
+    }
+    if vars_json && !is_batch {
+        eprintln!("Passed --vars-json in interactive mode");
+        process::exit(1);
+    }
+    if !targets.is_empty() && !is_batch {
+        eprintln!("Passed several binaries in interactive mode");
+        process::exit(1);
+    }
+    if !targets.is_empty() && (command.is_some() || callgraph_only) {
+        eprintln!("Passed a command or --callgraph-only with several binaries");
+        process::exit(1);
     }
     if command.is_some() && !is_batch {
         eprintln!("Passed a command in interactive mode");
-        process::exit(0);
+        process::exit(1);
     }
     let max_it = match matches.value_of("max-iterations") {
         Some(s) => {
@@ -53,7 +201,7 @@ pub fn parse_args() -> (Option<String>, Option<String>, bool, bool, bool, u32) {
                 Ok(max_it) => max_it,
                 Err(_) => {
                     eprintln!("max-iterations must be a deciamal number");
-                    process::exit(0);
+                    process::exit(1);
                 }
             }
         }
@@ -62,8 +210,139 @@ pub fn parse_args() -> (Option<String>, Option<String>, bool, bool, bool, u32) {
 
     if max_it == 0 {
         eprintln!("max-iterations can't be zero");
-        process::exit(0);
+        process::exit(1);
+    }
+
+    let max_insns = match matches.value_of("max-insns") {
+        Some(s) => match usize::from_str_radix(s.trim(), 10) {
+            Ok(max_insns) => Some(max_insns),
+            Err(_) => {
+                eprintln!("max-insns must be a decimal number");
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let range = match matches.value_of("range").map(parse_range) {
+        Some(Ok(range)) => Some(range),
+        Some(Err(err)) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+        None => None,
+    };
+    if range.is_some() && !is_batch {
+        eprintln!("Passed a range in interactive mode");
+        process::exit(1);
+    }
+
+    let depth = match matches.value_of("depth") {
+        Some(s) => match usize::from_str_radix(s.trim(), 10) {
+            Ok(depth) => depth,
+            Err(_) => {
+                eprintln!("depth must be a decimal number");
+                process::exit(1);
+            }
+        },
+        None => 0,
+    };
+    let function = matches
+        .value_of("function")
+        .map(|name| (name.to_string(), depth));
+    if function.is_none() && matches.is_present("depth") {
+        eprintln!("Passed --depth without --function");
+        process::exit(1);
     }
+    if function.is_some() && !is_batch {
+        eprintln!("Passed a function in interactive mode");
+        process::exit(1);
+    }
+
+    let snapshot_dot = matches.is_present("snapshot-dot");
+    let snapshots = matches.value_of("snapshots").map(|dir| Snapshots {
+        dir: PathBuf::from(dir),
+        dot: snapshot_dot,
+    });
+    if snapshot_dot && snapshots.is_none() {
+        eprintln!("Passed --snapshot-dot without --snapshots");
+        process::exit(1);
+    }
+
+    let signatures = match matches.value_of("signatures") {
+        Some(path) => match SignatureDb::load(path) {
+            Ok(db) => Some(db),
+            Err(err) => {
+                eprintln!("Failed to read {}: {}", path, err);
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
 
-    (bin, command, is_append, is_batch, no_highlight, max_it)
+    let log_filters = match matches.value_of("log").map(logger::parse_filters) {
+        Some(Ok(filters)) => filters,
+        Some(Err(err)) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+        None => Vec::new(),
+    };
+
+    Args {
+        bin,
+        targets,
+        command,
+        is_append,
+        is_batch,
+        no_highlight,
+        max_it,
+        range,
+        function,
+        snapshots,
+        log_filters,
+        callgraph_only,
+        vars_json,
+        signatures,
+        load: LoadOptions { esil, max_insns },
+    }
+}
+
+fn parse_addr(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let res = if s.starts_with("0x") || s.starts_with("0X") {
+        u64::from_str_radix(&s[2..], 16)
+    } else {
+        u64::from_str_radix(s, 10)
+    };
+    res.map_err(|_| format!("Invalid address {}", s))
+}
+
+/// Parses an address range of the form `<start>-<end>`, where both addresses
+/// are either hexadecimal (`0x` prefixed) or decimal. `end` is exclusive.
+fn parse_range(s: &str) -> Result<AddrRange, String> {
+    let mut bounds = s.splitn(2, '-');
+    let start = parse_addr(bounds.next().unwrap_or(""))?;
+    let end = match bounds.next() {
+        Some(end) => parse_addr(end)?,
+        None => return Err(format!("Invalid range {}, expected <start>-<end>", s)),
+    };
+    if start >= end {
+        return Err(format!("Empty range {}", s));
+    }
+    Ok((start, end))
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_range;
+
+    #[test]
+    fn range_parse() {
+        assert_eq!(parse_range("0x400000-0x401000"), Ok((0x400000, 0x401000)));
+        assert_eq!(parse_range("16-0x20"), Ok((16, 32)));
+        assert!(parse_range("0x401000-0x400000").is_err());
+        assert!(parse_range("0x400000").is_err());
+        assert!(parse_range("0x40zz00-0x401000").is_err());
+    }
 }
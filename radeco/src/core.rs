@@ -1,23 +1,60 @@
 use base64;
 use r2pipe::{R2Pipe, R2};
-use radeco_lib::analysis::engine::{Engine, RadecoEngine};
+use radeco_lib::analysis::analyzer::{self, AnalyzerInfo, AnalyzerKind};
+use radeco_lib::analysis::engine::{self, Engine, RadecoEngine, Snapshots};
+use radeco_lib::analysis::signatures::SignatureDb;
 use radeco_lib::backend::lang_c::c_cfg::ctrl_flow_struct;
 use radeco_lib::backend::lang_c::c_cfg::CCFGVerifier;
 use radeco_lib::backend::lang_c::c_cfg_builder;
+use radeco_lib::backend::variables;
 use radeco_lib::frontend::radeco_containers::*;
+use radeco_lib::frontend::ssaconstructor::ConstructError;
 use radeco_lib::middle::dot;
 use radeco_lib::middle::ir_writer;
 use radeco_lib::middle::ssa::ssastorage::SSAStorage;
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::panic;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
 use std::rc::Rc;
 use std::str;
 
+/// Address range `[start, end)` used to restrict which functions are processed.
+pub type AddrRange = (u64, u64);
+
 thread_local!(
     pub static PROJ: RefCell<Option<RadecoProject>> = RefCell::new(None);
+    pub static SNAPSHOTS: RefCell<Option<Snapshots>> = RefCell::new(None);
+    pub static VARS_JSON: RefCell<bool> = RefCell::new(false);
+    pub static SIGNATURES: RefCell<Option<SignatureDb>> = RefCell::new(None);
 );
 
+/// How a project is loaded, as asked for on the command line.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoadOptions {
+    /// Record the ESIL of the instructions, to write it along with the IR.
+    pub esil: bool,
+    /// Skip the functions of more than this many instructions.
+    pub max_insns: Option<usize>,
+}
+
+/// Creates an engine which also writes the snapshots and applies the signature database
+/// requested on the command line, if any.
+pub fn new_engine(max_it: u32) -> RadecoEngine {
+    let engine = RadecoEngine::new(max_it);
+    let engine = match SNAPSHOTS.with(|s| s.borrow().clone()) {
+        Some(snapshots) => engine.snapshots(snapshots),
+        None => engine,
+    };
+    match SIGNATURES.with(|s| s.borrow().clone()) {
+        Some(db) => engine.signatures(db),
+        None => engine,
+    }
+}
+
 pub fn fn_list(proj: &RadecoProject) -> Vec<String> {
     proj.iter()
         .map(|i| i.module)
@@ -72,34 +109,138 @@ pub fn fn_rename(old_f: &str, new_f: &str, proj: &mut RadecoProject) {
 }
 
 pub fn analyze(rfn: &mut RadecoFunction, max_it: u32) {
-    let engine = RadecoEngine::new(max_it);
+    let engine = new_engine(max_it);
     engine.run_func(rfn);
 }
 
-pub fn analyze_all_functions<'a>(proj: &'a mut RadecoProject, max_it: u32) {
+/// Parses a comma separated list of function analyzers, e.g. `sccp,cse`.
+pub fn parse_pipeline(s: &str) -> Result<Vec<AnalyzerKind>, String> {
+    s.split(',')
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            analyzer::all_func_analyzers()
+                .into_iter()
+                .find(|&kind| <&AnalyzerInfo>::from(kind).name == name)
+                .ok_or_else(|| format!("Unknown analyzer {}", name))
+        })
+        .collect()
+}
+
+/// Runs each of `pipeline_a` and `pipeline_b` on `rfn`, which is left as it is, and lists the
+/// values they compute differently.
+pub fn compare_pipelines(
+    rfn: &RadecoFunction,
+    pipeline_a: &[AnalyzerKind],
+    pipeline_b: &[AnalyzerKind],
+) -> String {
+    let diffs = engine::compare_pipelines(rfn.ssa(), pipeline_a, pipeline_b);
+    if diffs.is_empty() {
+        return "No difference".to_owned();
+    }
+    diffs
+        .iter()
+        .map(|d| format!("{}\n  - {}\n  + {}", d.root, d.left_expr, d.right_expr))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns true if `rfn` starts in `range`, or if there is no `range`.
+pub fn in_range(rfn: &RadecoFunction, range: Option<AddrRange>) -> bool {
+    range.map_or(true, |(start, end)| start <= rfn.offset && rfn.offset < end)
+}
+
+/// Returns true if `rfn` starts at one of the `selected` offsets, or if there is no selection.
+pub fn is_selected(rfn: &RadecoFunction, selected: Option<&[u64]>) -> bool {
+    selected.map_or(true, |offsets| offsets.contains(&rfn.offset))
+}
+
+/// Returns the offsets of the function `name` and of its callees up to `depth` calls away in the
+/// call graph, or `None` if there is no such function.
+pub fn select_functions(proj: &RadecoProject, name: &str, depth: usize) -> Option<Vec<u64>> {
+    proj.iter().map(|i| i.module).find_map(|rmod| {
+        let rfn = rmod.functions.values().find(|rfn| rfn.name == name)?;
+        Some(rmod.callees_within(rfn.offset, depth))
+    })
+}
+
+pub fn analyze_all_functions<'a>(
+    proj: &'a mut RadecoProject,
+    max_it: u32,
+    range: Option<AddrRange>,
+    selected: Option<&[u64]>,
+) {
     let rfns = proj
         .iter_mut()
         .map(|i| i.module)
-        .flat_map(|rmod| rmod.functions.values_mut());
+        .flat_map(|rmod| rmod.functions.values_mut())
+        .filter(|rfn| in_range(rfn, range) && is_selected(rfn, selected));
     for rfn in rfns {
         analyze(rfn, max_it);
     }
 }
 
-pub fn emit_ir(rfn: &RadecoFunction) -> String {
+/// Writes the IR of `rfn`, with the ESIL of each instruction if `esil` is set. The ESIL is only
+/// there if it was recorded, see `LoadOptions::esil`.
+pub fn emit_ir(rfn: &RadecoFunction, esil: bool) -> String {
     eprintln!("  [*] Writing out IR");
+    let opts = ir_writer::EmitOptions {
+        esil,
+        ..Default::default()
+    };
     let mut res = String::new();
-    ir_writer::emit_il(&mut res, Some(rfn.name.to_string()), rfn.ssa()).unwrap();
+    ir_writer::emit_il_with(&mut res, Some(rfn.name.to_string()), rfn.ssa(), opts).unwrap();
     res
 }
 
+/// Emits the IR of every function starting in `range` and among the `selected` ones. The
+/// functions are sorted by address, and a `; function <name> @ <addr>` line follows the version
+/// header of each one, so that the output is the same from one run to the next.
+pub fn emit_all_ir(
+    proj: &RadecoProject,
+    range: Option<AddrRange>,
+    selected: Option<&[u64]>,
+    esil: bool,
+) -> String {
+    let mut rfns = proj
+        .iter()
+        .map(|i| i.module)
+        .flat_map(|rmod| rmod.functions.values())
+        .filter(|rfn| in_range(rfn, range) && is_selected(rfn, selected))
+        .collect::<Vec<_>>();
+    rfns.sort_by(|a, b| (a.offset, &a.name).cmp(&(b.offset, &b.name)));
+    let header = format!("; radeco-ir v{}\n", ir_writer::IR_VERSION);
+    rfns.into_iter()
+        .map(|rfn| {
+            let ir = emit_ir(rfn, esil);
+            format!(
+                "{}; function {} @ {:#x}\n{}",
+                header,
+                rfn.name,
+                rfn.offset,
+                ir.strip_prefix(&header).unwrap_or(&ir)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 pub fn emit_dot(ssa: &SSAStorage) -> String {
     dot::emit_dot(ssa)
 }
 
-pub fn decompile_all_functions<'a>(proj: &'a RadecoProject) -> String {
+pub fn decompile_all_functions<'a>(
+    proj: &'a RadecoProject,
+    range: Option<AddrRange>,
+    selected: Option<&[u64]>,
+) -> String {
     let mut decompiled_funcs = Vec::new();
-    let funcs = fn_list(&proj);
+    let funcs: Vec<String> = proj
+        .iter()
+        .map(|i| i.module)
+        .flat_map(|rmod| rmod.functions.values())
+        .filter(|rfn| in_range(rfn, range) && is_selected(rfn, selected))
+        .map(|rfn| rfn.name.to_string())
+        .collect();
     for f in &funcs {
         match decompile(f, &proj) {
             Ok(res) => {
@@ -129,6 +270,15 @@ fn decompile_priv(
     func_name_map: &HashMap<u64, String>,
     strings: &HashMap<u64, String>,
 ) -> Result<String, String> {
+    // Nothing can be recovered from a function whose instructions have no ESIL, or which was
+    // skipped for its size.
+    match rfn.construct_error() {
+        Some(err @ &ConstructError::NoEsil) | Some(err @ &ConstructError::TooLarge { .. }) => {
+            return Err(format!("{}: {}", rfn.name, err));
+        }
+        _ => {}
+    }
+
     let c_cfg_result =
         panic::catch_unwind(|| c_cfg_builder::recover_c_cfg(rfn, func_name_map, strings));
 
@@ -156,36 +306,239 @@ fn decompile_priv(
     }
 }
 
-pub fn load_proj_by_path(path: &str, max_it: u32) -> RadecoProject {
-    let mut p = ProjectLoader::new().path(path).load();
+// Returns a `ProjectLoader` recording the ESIL of the functions and skipping the large ones if
+// it was asked for.
+fn project_loader<'a>(opts: LoadOptions) -> ProjectLoader<'a> {
+    let mut loader = ProjectLoader::new();
+    if opts.esil {
+        loader = loader.record_esil();
+    }
+    match opts.max_insns {
+        Some(max) => loader.max_insns(max),
+        None => loader,
+    }
+}
+
+pub fn load_proj_by_path(
+    path: &str,
+    max_it: u32,
+    opts: LoadOptions,
+) -> Result<RadecoProject, String> {
+    let mut p = project_loader(opts)
+        .path(path)
+        .try_load()
+        .map_err(|e| format!("{}: {}", path, e))?;
     let regfile = p.regfile().clone();
     for xy in p.iter_mut() {
-        let engine = RadecoEngine::new(max_it);
+        let engine = new_engine(max_it);
         engine.run_module(xy.module, &*regfile.clone());
     }
-    p
+    Ok(p)
+}
+
+/// Loads the call graph of the binary at `path` from the cross-references of r2 and the direct
+/// calls found in the disassembly, without constructing the SSA of any function.
+pub fn load_callgraph_by_path(path: &str) -> Result<RadecoProject, String> {
+    let mloader = ModuleLoader::default().build_callgraph();
+    ProjectLoader::new()
+        .path(path)
+        .module_loader(mloader)
+        .try_load()
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Emits the call graph of `rmod` in Graphviz dot, with its nodes labelled by function name.
+pub fn emit_callgraph_dot(rmod: &RadecoModule) -> String {
+    let names = func_names(rmod);
+    let mut dot = String::from("digraph callgraph {\n");
+    for n in rmod.callgraph.node_indices() {
+        let addr = rmod.callgraph[n];
+        let name = names.get(&addr).cloned().unwrap_or(format!("0x{:x}", addr));
+        dot.push_str(&format!("    n{} [label=\"{}\"];\n", n.index(), name));
+    }
+    for e in rmod.callgraph.edge_indices() {
+        if let Some((src, dst)) = rmod.callgraph.edge_endpoints(e) {
+            dot.push_str(&format!("    n{} -> n{};\n", src.index(), dst.index()));
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Outcome of `decompile_targets`.
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    /// Number of targets loaded.
+    pub targets: usize,
+    /// Number of functions analyzed.
+    pub functions: usize,
+    /// Functions which were skipped for their size.
+    pub skipped: Vec<String>,
+    /// Targets which could not be loaded and functions which could not be decompiled.
+    pub failures: Vec<String>,
 }
 
-pub fn load_proj_tcp(url: &str, max_it: u32) -> Result<RadecoProject, &'static str> {
+impl fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} targets, {} functions analyzed, {} skipped, {} failures",
+            self.targets,
+            self.functions,
+            self.skipped.len(),
+            self.failures.len()
+        )?;
+        for skipped in &self.skipped {
+            writeln!(f, "  skipped {}", skipped)?;
+        }
+        for failure in &self.failures {
+            writeln!(f, "  {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the paths listed in the file at `path`, one per line. Blank lines and lines starting
+/// with `#` are skipped.
+pub fn read_targets(path: &str) -> io::Result<Vec<String>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_owned)
+        .collect())
+}
+
+/// Analyzes and decompiles each of `targets`, loaded by `load`, into its own `<name>_out`
+/// directory in `out_dir`, which holds a `<function>.c` file per function, along with its
+/// variables in `<function>.json` if they were asked for. `range` and `function` restrict the
+/// functions processed in every target, as in batch mode.
+pub fn decompile_targets<F>(
+    targets: &[String],
+    out_dir: &Path,
+    max_it: u32,
+    range: Option<AddrRange>,
+    function: Option<&(String, usize)>,
+    load: F,
+) -> BatchSummary
+where
+    F: Fn(&str, u32) -> Result<RadecoProject, String>,
+{
+    let mut summary = BatchSummary::default();
+    for target in targets {
+        eprintln!("[*] Processing {}", target);
+        let mut proj = match panic::catch_unwind(AssertUnwindSafe(|| load(target, max_it))) {
+            Ok(Ok(proj)) => proj,
+            Ok(Err(err)) => {
+                summary.failures.push(err);
+                continue;
+            }
+            Err(_) => {
+                summary.failures.push(format!("{}: failed to load", target));
+                continue;
+            }
+        };
+        summary.targets += 1;
+
+        let selected = match function {
+            Some(&(ref name, depth)) => match select_functions(&proj, name, depth) {
+                Some(offsets) => Some(offsets),
+                None => {
+                    summary
+                        .failures
+                        .push(format!("{}: {} is not found.", target, name));
+                    continue;
+                }
+            },
+            None => None,
+        };
+        let selected = selected.as_ref().map(|offsets| &offsets[..]);
+        analyze_all_functions(&mut proj, max_it, range, selected);
+
+        let name = Path::new(target)
+            .file_name()
+            .map_or_else(|| target.clone(), |n| n.to_string_lossy().into_owned());
+        let dir = out_dir.join(format!("{}_out", name));
+        if let Err(err) = fs::create_dir_all(&dir) {
+            summary
+                .failures
+                .push(format!("{}: {}: {}", target, dir.display(), err));
+            continue;
+        }
+        let vars_json = VARS_JSON.with(|v| *v.borrow());
+        for rmod in proj.iter().map(|i| i.module) {
+            let func_name_map = func_names(rmod);
+            let strings = strings(rmod);
+            let rfns = rmod
+                .functions
+                .values()
+                .filter(|rfn| in_range(rfn, range) && is_selected(rfn, selected));
+            for rfn in rfns {
+                if let Some(err @ &ConstructError::TooLarge { .. }) = rfn.construct_error() {
+                    summary
+                        .skipped
+                        .push(format!("{}: {}: {}", target, rfn.name, err));
+                    continue;
+                }
+                summary.functions += 1;
+                let res = decompile_priv(rfn, &func_name_map, &strings).and_then(|code| {
+                    let file = dir.join(format!("{}.c", file_name(&rfn.name)));
+                    fs::write(&file, code).map_err(|e| format!("{}: {}", file.display(), e))
+                });
+                if let Err(err) = res {
+                    summary.failures.push(format!("{}: {}", target, err));
+                }
+                if vars_json {
+                    let file = dir.join(format!("{}.json", file_name(&rfn.name)));
+                    if let Err(err) = fs::write(&file, variables::emit_json(rfn)) {
+                        summary
+                            .failures
+                            .push(format!("{}: {}: {}", target, file.display(), err));
+                    }
+                }
+            }
+        }
+    }
+    summary
+}
+
+// Replaces the characters of the function `name` which are not safe in a file name.
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '_' | '-' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+pub fn load_proj_tcp(url: &str, max_it: u32, opts: LoadOptions) -> Result<RadecoProject, String> {
     let r2p = R2Pipe::tcp(url)?;
-    Ok(load_project_by_r2pipe(r2p, max_it))
+    load_project_by_r2pipe(r2p, max_it, opts)
 }
 
-pub fn load_proj_http(url: &str, max_it: u32) -> Result<RadecoProject, &'static str> {
+pub fn load_proj_http(url: &str, max_it: u32, opts: LoadOptions) -> Result<RadecoProject, String> {
     let r2p = R2Pipe::http(url)?;
-    Ok(load_project_by_r2pipe(r2p, max_it))
+    load_project_by_r2pipe(r2p, max_it, opts)
 }
 
-pub fn load_project_by_r2pipe(r2p: R2Pipe, max_it: u32) -> RadecoProject {
+pub fn load_project_by_r2pipe(
+    r2p: R2Pipe,
+    max_it: u32,
+    opts: LoadOptions,
+) -> Result<RadecoProject, String> {
     let r2 = R2::from(r2p);
     let r2w = Rc::new(RefCell::new(r2));
-    let mut p = ProjectLoader::new().source(Rc::new(r2w)).load();
+    let mut p = project_loader(opts)
+        .source(Rc::new(r2w))
+        .try_load()
+        .map_err(|e| e.to_string())?;
     let regfile = p.regfile().clone();
     for xy in p.iter_mut() {
-        let engine = RadecoEngine::new(max_it);
+        let engine = new_engine(max_it);
         engine.run_module(xy.module, &*regfile.clone());
     }
-    p
+    Ok(p)
 }
 
 pub fn func_names(rmod: &RadecoModule) -> HashMap<u64, String> {
@@ -211,3 +564,124 @@ pub fn strings(rmod: &RadecoModule) -> HashMap<u64, String> {
         })
         .collect()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn batch_over_two_targets() {
+        use radeco_lib::frontend::radeco_source::FileSource;
+
+        let out_dir = std::env::temp_dir().join("radeco_batch_over_two_targets");
+        let _ = fs::remove_dir_all(&out_dir);
+        let targets = vec!["corpus/first".to_owned(), "corpus/second".to_owned()];
+        let load = |_: &str, _| {
+            let source = FileSource::open("../radeco-lib/test_files/bin1_filesource/bin1");
+            ProjectLoader::new()
+                .source(Rc::new(source))
+                .try_load()
+                .map_err(|e| e.to_string())
+        };
+        let main = ("main".to_owned(), 0);
+        let summary = decompile_targets(&targets, &out_dir, 10, None, Some(&main), load);
+
+        assert_eq!(summary.targets, 2);
+        assert_eq!(summary.functions, 2);
+        for name in &["first_out", "second_out"] {
+            let dir = out_dir.join(name);
+            assert!(dir.is_dir(), "{} was not produced", dir.display());
+        }
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn skip_functions_above_max_insns() {
+        use radeco_lib::frontend::radeco_source::FileSource;
+
+        let loader = || {
+            let source = FileSource::open("../radeco-lib/test_files/bin1_filesource/bin1");
+            ProjectLoader::new().source(Rc::new(source))
+        };
+        let proj = loader().try_load().unwrap();
+        let sizes = proj
+            .iter()
+            .flat_map(|i| i.module.functions.values())
+            .map(|rfn| (rfn.name.to_string(), rfn.instructions().len()))
+            .collect::<Vec<_>>();
+        let (largest, insns) = sizes
+            .iter()
+            .max_by_key(|&&(_, insns)| insns)
+            .unwrap()
+            .clone();
+        let max = insns - 1;
+
+        let out_dir = std::env::temp_dir().join("radeco_skip_functions_above_max_insns");
+        let _ = fs::remove_dir_all(&out_dir);
+        let targets = vec!["corpus/bin1".to_owned()];
+        let load = |_: &str, _| {
+            loader()
+                .max_insns(max)
+                .try_load()
+                .map_err(|e| e.to_string())
+        };
+        let summary = decompile_targets(&targets, &out_dir, 10, None, None, load);
+
+        let above = sizes.iter().filter(|&&(_, insns)| insns > max).count();
+        assert_eq!(summary.skipped.len(), above);
+        assert!(summary.skipped.iter().any(|s| s.contains(&largest)));
+        assert_eq!(summary.functions, sizes.len() - above);
+        assert!(summary.functions > 0);
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn combined_ir_is_reproducible() {
+        use radeco_lib::frontend::radeco_source::FileSource;
+
+        let emit = || {
+            let source = FileSource::open("../radeco-lib/test_files/bin1_filesource/bin1");
+            let proj = ProjectLoader::new()
+                .source(Rc::new(source))
+                .try_load()
+                .unwrap();
+            emit_all_ir(&proj, None, None, false)
+        };
+        let ir = emit();
+        assert_eq!(ir, emit());
+
+        let offsets = ir
+            .lines()
+            .filter(|line| line.starts_with("; function "))
+            .map(|line| u64::from_str_radix(line.rsplit("0x").next().unwrap(), 16).unwrap())
+            .collect::<Vec<_>>();
+        assert!(!offsets.is_empty());
+        assert!(offsets.windows(2).all(|w| w[0] <= w[1]));
+        // Every function starts with the version header, followed by its name.
+        let lines = ir.lines().collect::<Vec<_>>();
+        for (i, line) in lines.iter().enumerate() {
+            if line.starts_with("; function ") {
+                assert_eq!(lines[i - 1], "; radeco-ir v1");
+            }
+        }
+    }
+
+    #[test]
+    fn only_in_range_functions() {
+        let funcs = [0x3fff00, 0x400000, 0x400800, 0x401000]
+            .iter()
+            .map(|&offset| {
+                let mut rfn = RadecoFunction::default();
+                rfn.offset = offset;
+                rfn
+            })
+            .collect::<Vec<_>>();
+        let selected = funcs
+            .iter()
+            .filter(|rfn| in_range(rfn, Some((0x400000, 0x401000))))
+            .map(|rfn| rfn.offset)
+            .collect::<Vec<_>>();
+        assert_eq!(selected, vec![0x400000, 0x400800]);
+        assert!(funcs.iter().all(|rfn| in_range(rfn, None)));
+    }
+}
@@ -1,6 +1,8 @@
 #![feature(box_patterns)]
 #[cfg(feature = "trace_log")]
 extern crate env_logger;
+#[cfg(feature = "trace_log")]
+extern crate log;
 
 extern crate base64;
 extern crate r2api;
@@ -17,6 +19,7 @@ mod cli;
 mod core;
 mod highlighting;
 
+use radeco_lib::utils::logger::{self, LogLevel};
 use rustyline::completion::{Completer, FilenameCompleter};
 use rustyline::error::ReadlineError;
 use rustyline::highlight::Highlighter;
@@ -24,6 +27,7 @@ use rustyline::hint::Hinter;
 use rustyline::validate::Validator;
 use rustyline::{CompletionType, Config, Context, EditMode, Editor, Helper};
 use std::fs;
+use std::path::Path;
 use std::process;
 
 mod scheme {
@@ -57,6 +61,9 @@ const FILE_SP: char = '\\';
 // Default max number of iterations of the engine.
 pub const MAX_ITERATIONS: u32 = 100;
 
+// File written by --callgraph-only.
+const CALLGRAPH_DOT: &'static str = "callgraph.dot";
+
 #[derive(Default)]
 struct Completes {
     file_completer: FilenameCompleter,
@@ -87,6 +94,7 @@ impl Completer for Completes {
             command::IR,
             command::DECOMPILE,
             command::FUNC_RENAME,
+            command::PIPELINE_DIFF,
             command::QUIT,
         ];
 
@@ -155,10 +163,87 @@ impl Completer for Completes {
 
 const SEP: &'static str = "END";
 
-fn main() {
+/// Applies the category filters of `--log`. When built with `trace_log`, the categories are also
+/// let through `env_logger` at the requested level, on top of `RUST_LOG`.
+fn init_logger(filters: &[(String, LogLevel)]) {
+    for &(ref category, level) in filters {
+        logger::set_filter(category, level);
+    }
     #[cfg(feature = "trace_log")]
-    env_logger::init();
-    let (arg, cmd_opt, is_append_mode, is_batch_mode, no_highlight, max_it) = cli::parse_args();
+    {
+        use log::LevelFilter;
+        let mut builder = env_logger::Builder::from_default_env();
+        for &(ref category, level) in filters {
+            let level = match level {
+                LogLevel::Off => LevelFilter::Off,
+                LogLevel::Error => LevelFilter::Error,
+                LogLevel::Warn => LevelFilter::Warn,
+                LogLevel::Trace => LevelFilter::Trace,
+            };
+            builder.filter_module(category, level);
+        }
+        builder.init();
+    }
+}
+
+fn main() {
+    let cli::Args {
+        bin: arg,
+        targets,
+        command: cmd_opt,
+        is_append: is_append_mode,
+        is_batch: is_batch_mode,
+        no_highlight,
+        max_it,
+        range,
+        function,
+        snapshots,
+        log_filters,
+        callgraph_only,
+        vars_json,
+        signatures,
+        load: opts,
+    } = cli::parse_args();
+    init_logger(&log_filters);
+    if callgraph_only {
+        let path = arg.unwrap();
+        if !is_file(&path) {
+            eprintln!("Invalid argument {}", path);
+            process::exit(1);
+        }
+        let proj = match core::load_callgraph_by_path(&path) {
+            Ok(proj) => proj,
+            Err(err) => {
+                eprintln!("{}", err);
+                process::exit(1);
+            }
+        };
+        let dot = proj
+            .iter()
+            .next()
+            .map(|xy| core::emit_callgraph_dot(xy.module))
+            .unwrap_or_default();
+        if let Err(err) = fs::write(CALLGRAPH_DOT, dot) {
+            eprintln!("Failed to write {}: {}", CALLGRAPH_DOT, err);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+    core::SNAPSHOTS.with(|s| *s.borrow_mut() = snapshots);
+    core::SIGNATURES.with(|s| *s.borrow_mut() = signatures);
+    core::VARS_JSON.with(|v| *v.borrow_mut() = vars_json);
+    if !targets.is_empty() {
+        let summary = core::decompile_targets(
+            &targets,
+            Path::new("."),
+            max_it,
+            range,
+            function.as_ref(),
+            |path, max_it| core::load_proj_by_path(path, max_it, opts),
+        );
+        eprint!("{}", summary);
+        process::exit(0);
+    }
     let config = Config::builder()
         .auto_add_history(true)
         .history_ignore_space(true)
@@ -171,17 +256,17 @@ fn main() {
         use r2pipe::R2Pipe;
         let proj_result = match arg {
             Some(ref s) if scheme::is_http(s) => {
-                core::load_proj_http(&s[scheme::HTTP.len()..], max_it)
+                core::load_proj_http(&s[scheme::HTTP.len()..], max_it, opts)
                     .map_err(|e| Some(e.to_string()))
             }
             Some(ref s) if scheme::is_tcp(s) => {
-                core::load_proj_tcp(&s[scheme::TCP.len()..], max_it)
+                core::load_proj_tcp(&s[scheme::TCP.len()..], max_it, opts)
                     .map_err(|e| Some(e.to_string()))
             }
-            Some(ref s) if is_file(s) => Ok(core::load_proj_by_path(s, max_it)),
+            Some(ref s) if is_file(s) => core::load_proj_by_path(s, max_it, opts).map_err(Some),
             Some(s) => Err(Some(format!("Invalid argument {}", s))),
             None => match R2Pipe::open() {
-                Ok(r2p) => Ok(core::load_project_by_r2pipe(r2p, max_it)),
+                Ok(r2p) => core::load_project_by_r2pipe(r2p, max_it, opts).map_err(Some),
                 Err(_) => Err(None),
             },
         };
@@ -201,13 +286,28 @@ fn main() {
             // If a command is specified by the user run it,
             // otherwise decompile all functions.
             if let Some(command) = cmd_opt {
-                cmd(command, no_highlight, max_it);
+                cmd(command, no_highlight, max_it, opts);
             } else {
                 let mut proj_ = proj_opt.borrow_mut();
                 let proj = proj_.as_mut().unwrap();
 
-                core::analyze_all_functions(proj, max_it);
-                let decompiled = core::decompile_all_functions(proj);
+                let selected = match function {
+                    Some((ref name, depth)) => match core::select_functions(proj, name, depth) {
+                        Some(offsets) => Some(offsets),
+                        None => {
+                            eprintln!("{} is not found.", name);
+                            process::exit(1);
+                        }
+                    },
+                    None => None,
+                };
+                let selected = selected.as_ref().map(|offsets| &offsets[..]);
+                core::analyze_all_functions(proj, max_it, range, selected);
+                if opts.esil {
+                    println!("{}", core::emit_all_ir(proj, range, selected, opts.esil));
+                    process::exit(0);
+                }
+                let decompiled = core::decompile_all_functions(proj, range, selected);
                 if no_highlight {
                     println!("{}", decompiled);
                 } else {
@@ -223,7 +323,7 @@ fn main() {
         let readline = rl.readline(PROMPT);
         match readline {
             Ok(line) => {
-                cmd(line, !no_highlight, max_it);
+                cmd(line, !no_highlight, max_it, opts);
                 if is_append_mode {
                     println!("{}", SEP);
                 }
@@ -248,6 +348,7 @@ mod command {
     pub const IR: &'static str = "ir";
     pub const DECOMPILE: &'static str = "decompile";
     pub const FUNC_RENAME: &'static str = "fn_rn";
+    pub const PIPELINE_DIFF: &'static str = "pipeline_diff";
     pub const QUIT: &'static str = "quit";
 
     pub fn help() {
@@ -289,19 +390,24 @@ mod command {
             format!("{} <old_name> <new_name>", FUNC_RENAME),
             width = width
         );
+        println!(
+            "{:width$}    Compare the IR of <func> after two orderings of analyzers, e.g. sccp,cse",
+            format!("{} <func> <a> <b>", PIPELINE_DIFF),
+            width = width
+        );
         println!("{:width$}    Quit interactive prompt", QUIT, width = width);
     }
 
     /// Returns true if `cmd` requires a function as parameter.
     pub fn requires_func(cmd: &str) -> bool {
         match cmd {
-            ANALYZE | DOT | IR | DECOMPILE | FUNC_RENAME => true,
+            ANALYZE | DOT | IR | DECOMPILE | FUNC_RENAME | PIPELINE_DIFF => true,
             _ => false,
         }
     }
 }
 
-fn cmd(line: String, highlight: bool, max_it: u32) {
+fn cmd(line: String, highlight: bool, max_it: u32, opts: core::LoadOptions) {
     if line.is_empty() {
         return;
     }
@@ -310,6 +416,7 @@ fn cmd(line: String, highlight: bool, max_it: u32) {
     let op1 = terms.next();
     let op2 = terms.next();
     let op3 = terms.next();
+    let op4 = terms.next();
 
     core::PROJ.with(|proj_opt| {
         match (op1, op2, op3) {
@@ -319,7 +426,10 @@ fn cmd(line: String, highlight: bool, max_it: u32) {
             }
             (Some(command::LOAD), Some(path), _) => {
                 if is_file(path) {
-                    *proj_opt.borrow_mut() = Some(core::load_proj_by_path(path, max_it));
+                    match core::load_proj_by_path(path, max_it, opts) {
+                        Ok(p) => *proj_opt.borrow_mut() = Some(p),
+                        Err(err) => println!("{}", err),
+                    }
                     return;
                 } else {
                     println!("{} is not found.", path);
@@ -328,11 +438,11 @@ fn cmd(line: String, highlight: bool, max_it: u32) {
             }
             (Some(command::CONNECT), Some(url), _) => {
                 let p_opt = if scheme::is_http(&url) {
-                    core::load_proj_http(&url[scheme::HTTP.len()..], max_it)
+                    core::load_proj_http(&url[scheme::HTTP.len()..], max_it, opts)
                 } else if scheme::is_tcp(&url) {
-                    core::load_proj_tcp(&url[scheme::TCP.len()..], max_it)
+                    core::load_proj_tcp(&url[scheme::TCP.len()..], max_it, opts)
                 } else {
-                    Err("Invalid url")
+                    Err("Invalid url".to_owned())
                 };
                 match p_opt {
                     Ok(p) => *proj_opt.borrow_mut() = Some(p),
@@ -353,7 +463,7 @@ fn cmd(line: String, highlight: bool, max_it: u32) {
         let proj = proj_.as_mut().unwrap();
         match (op1, op2, op3) {
             (Some(command::ANALYZE), Some("*"), _) => {
-                core::analyze_all_functions(proj, max_it);
+                core::analyze_all_functions(proj, max_it, None, None);
             }
             (Some(command::FNLIST), _, _) => {
                 let funcs = core::fn_list(&proj);
@@ -377,13 +487,13 @@ fn cmd(line: String, highlight: bool, max_it: u32) {
             }
             (Some(command::IR), Some(f), _) => {
                 if let Some(rfn) = core::get_function(f, &proj) {
-                    println!("{}", core::emit_ir(rfn));
+                    println!("{}", core::emit_ir(rfn, opts.esil));
                 } else {
                     println!("{} is not found", f);
                 }
             }
             (Some(command::DECOMPILE), Some("*"), _) => {
-                let decompiled = core::decompile_all_functions(&proj);
+                let decompiled = core::decompile_all_functions(&proj, None, None);
                 if highlight {
                     highlighting::print_highlighted(&decompiled);
                 } else {
@@ -403,6 +513,18 @@ fn cmd(line: String, highlight: bool, max_it: u32) {
             (Some(command::FUNC_RENAME), Some(old_f), Some(new_f)) => {
                 core::fn_rename(old_f, new_f, proj);
             }
+            (Some(command::PIPELINE_DIFF), Some(f), Some(a)) => {
+                // An omitted <b> runs no analyzer.
+                let b = op4.unwrap_or("");
+                let pipelines = (core::parse_pipeline(a), core::parse_pipeline(b));
+                match (core::get_function(f, &proj), pipelines) {
+                    (Some(rfn), (Ok(a), Ok(b))) => {
+                        println!("{}", core::compare_pipelines(rfn, &a, &b));
+                    }
+                    (None, _) => println!("{} is not found", f),
+                    (_, (Err(err), _)) | (_, (_, Err(err))) => println!("{}", err),
+                }
+            }
             _ => {
                 println!(
                     "Invalid command {} {}",